@@ -1,12 +1,85 @@
+pub use sea_orm_migration::MigrationStatus;
 pub use sea_orm_migration::prelude::*;
 
 mod m20250405_000001_create_table;
+mod m20250412_000001_add_post_is_locked;
+mod m20250413_000001_add_tag_is_protected;
+mod m20250414_000001_create_post_report;
+mod m20250415_000001_create_notification;
+mod m20250416_000001_add_user_preferences;
+mod m20250417_000001_add_post_original_filename;
+mod m20250418_000001_add_post_virus_scanned_at;
+mod m20250419_000001_add_post_version;
+mod m20250420_000001_add_updated_at_and_indexes;
+mod m20250421_000001_add_post_views;
+mod m20250422_000001_create_popular_post;
+mod m20250423_000001_create_follower;
+mod m20250424_000001_create_integration;
+mod m20250425_000001_add_post_source_unique_index;
+mod m20250426_000001_add_post_translation;
+mod m20250427_000001_create_notification_setting;
+mod m20250428_000001_add_user_display_preferences;
+mod m20250429_000001_add_user_last_login_and_disabled;
+mod m20250430_000001_add_user_created_at;
+mod m20250501_000001_add_session_user_id;
+mod m20250502_000001_create_audit_log;
+mod m20250503_000001_create_idempotency_key;
+mod m20250504_000001_create_change;
+mod m20250505_000001_add_post_thumbnail_variants;
+mod m20250506_000001_add_post_stream_playlist;
+mod m20250507_000001_add_post_attachment;
+mod m20250508_000001_create_post_attachment;
+mod m20250509_000001_add_user_avatar;
+mod m20250510_000001_add_post_stored_size;
+mod m20250511_000001_add_post_schedule;
+mod m20250512_000001_add_post_is_draft;
+mod m20250513_000001_create_takedown_request;
+mod m20250514_000001_add_user_rss_token;
+mod m20250515_000001_hash_user_rss_token;
+mod m20250516_000001_add_pool_created_at;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20250405_000001_create_table::Migration)]
+        vec![
+            Box::new(m20250405_000001_create_table::Migration),
+            Box::new(m20250412_000001_add_post_is_locked::Migration),
+            Box::new(m20250413_000001_add_tag_is_protected::Migration),
+            Box::new(m20250414_000001_create_post_report::Migration),
+            Box::new(m20250415_000001_create_notification::Migration),
+            Box::new(m20250416_000001_add_user_preferences::Migration),
+            Box::new(m20250417_000001_add_post_original_filename::Migration),
+            Box::new(m20250418_000001_add_post_virus_scanned_at::Migration),
+            Box::new(m20250419_000001_add_post_version::Migration),
+            Box::new(m20250420_000001_add_updated_at_and_indexes::Migration),
+            Box::new(m20250421_000001_add_post_views::Migration),
+            Box::new(m20250422_000001_create_popular_post::Migration),
+            Box::new(m20250423_000001_create_follower::Migration),
+            Box::new(m20250424_000001_create_integration::Migration),
+            Box::new(m20250425_000001_add_post_source_unique_index::Migration),
+            Box::new(m20250426_000001_add_post_translation::Migration),
+            Box::new(m20250427_000001_create_notification_setting::Migration),
+            Box::new(m20250428_000001_add_user_display_preferences::Migration),
+            Box::new(m20250429_000001_add_user_last_login_and_disabled::Migration),
+            Box::new(m20250430_000001_add_user_created_at::Migration),
+            Box::new(m20250501_000001_add_session_user_id::Migration),
+            Box::new(m20250502_000001_create_audit_log::Migration),
+            Box::new(m20250503_000001_create_idempotency_key::Migration),
+            Box::new(m20250504_000001_create_change::Migration),
+            Box::new(m20250505_000001_add_post_thumbnail_variants::Migration),
+            Box::new(m20250506_000001_add_post_stream_playlist::Migration),
+            Box::new(m20250507_000001_add_post_attachment::Migration),
+            Box::new(m20250508_000001_create_post_attachment::Migration),
+            Box::new(m20250509_000001_add_user_avatar::Migration),
+            Box::new(m20250510_000001_add_post_stored_size::Migration),
+            Box::new(m20250511_000001_add_post_schedule::Migration),
+            Box::new(m20250512_000001_add_post_is_draft::Migration),
+            Box::new(m20250513_000001_create_takedown_request::Migration),
+            Box::new(m20250514_000001_add_user_rss_token::Migration),
+            Box::new(m20250515_000001_hash_user_rss_token::Migration),
+            Box::new(m20250516_000001_add_pool_created_at::Migration),
+        ]
     }
 }
@@ -0,0 +1,83 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SameyPostReport::Table)
+                    .if_not_exists()
+                    .col(pk_auto(SameyPostReport::Id))
+                    .col(integer(SameyPostReport::PostId))
+                    .col(integer(SameyPostReport::ReporterId))
+                    .col(text(SameyPostReport::Reason))
+                    .col(date_time(SameyPostReport::CreatedAt))
+                    .col(integer_null(SameyPostReport::ResolvedById))
+                    .col(date_time_null(SameyPostReport::ResolvedAt))
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk-samey_post_report-samey_post-post_id")
+                            .from(SameyPostReport::Table, SameyPostReport::PostId)
+                            .to(SameyPost::Table, SameyPost::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk-samey_post_report-samey_user-reporter_id")
+                            .from(SameyPostReport::Table, SameyPostReport::ReporterId)
+                            .to(SameyUser::Table, SameyUser::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk-samey_post_report-samey_user-resolved_by_id")
+                            .from(SameyPostReport::Table, SameyPostReport::ResolvedById)
+                            .to(SameyUser::Table, SameyUser::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SameyPostReport::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SameyUser {
+    #[sea_orm(iden = "samey_user")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum SameyPost {
+    #[sea_orm(iden = "samey_post")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum SameyPostReport {
+    #[sea_orm(iden = "samey_post_report")]
+    Table,
+    Id,
+    PostId,
+    ReporterId,
+    Reason,
+    CreatedAt,
+    ResolvedById,
+    ResolvedAt,
+}
@@ -0,0 +1,96 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SameyNotification::Table)
+                    .if_not_exists()
+                    .col(pk_auto(SameyNotification::Id))
+                    .col(integer(SameyNotification::UserId))
+                    .col(integer(SameyNotification::ActorId))
+                    .col(integer(SameyNotification::PostId))
+                    .col(enumeration(
+                        SameyNotification::Kind,
+                        NotificationKind::Enum,
+                        [NotificationKind::Mention],
+                    ))
+                    .col(boolean(SameyNotification::IsRead).default(false))
+                    .col(date_time(SameyNotification::CreatedAt))
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk-samey_notification-samey_user-user_id")
+                            .from(SameyNotification::Table, SameyNotification::UserId)
+                            .to(SameyUser::Table, SameyUser::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk-samey_notification-samey_user-actor_id")
+                            .from(SameyNotification::Table, SameyNotification::ActorId)
+                            .to(SameyUser::Table, SameyUser::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk-samey_notification-samey_post-post_id")
+                            .from(SameyNotification::Table, SameyNotification::PostId)
+                            .to(SameyPost::Table, SameyPost::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SameyNotification::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SameyUser {
+    #[sea_orm(iden = "samey_user")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum SameyPost {
+    #[sea_orm(iden = "samey_post")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum SameyNotification {
+    #[sea_orm(iden = "samey_notification")]
+    Table,
+    Id,
+    UserId,
+    ActorId,
+    PostId,
+    Kind,
+    IsRead,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+#[sea_orm(enum_name = "notification_kind")]
+pub enum NotificationKind {
+    #[sea_orm(iden = "notification_kind")]
+    Enum,
+    #[sea_orm(iden = "mention")]
+    Mention,
+}
@@ -0,0 +1,165 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SameyPost::Table)
+                    .add_column(date_time_null(SameyPost::UpdatedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SameyPool::Table)
+                    .add_column(date_time_null(SameyPool::UpdatedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SameyTag::Table)
+                    .add_column(date_time_null(SameyTag::UpdatedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        // Existing posts were last known to change when they were uploaded
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(SameyPost::Table)
+                    .value(SameyPost::UpdatedAt, Expr::col(SameyPost::UploadedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-samey_tag_post-tag_id")
+                    .table(SameyTagPost::Table)
+                    .col(SameyTagPost::TagId)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-samey_post-parent_id")
+                    .table(SameyPost::Table)
+                    .col(SameyPost::ParentId)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-samey_post-rating")
+                    .table(SameyPost::Table)
+                    .col(SameyPost::Rating)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-samey_post-uploaded_at")
+                    .table(SameyPost::Table)
+                    .col(SameyPost::UploadedAt)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for (name, table) in [
+            ("idx-samey_post-uploaded_at", SameyPost::Table.into_iden()),
+            ("idx-samey_post-rating", SameyPost::Table.into_iden()),
+            ("idx-samey_post-parent_id", SameyPost::Table.into_iden()),
+            ("idx-samey_tag_post-tag_id", SameyTagPost::Table.into_iden()),
+        ] {
+            manager
+                .drop_index(Index::drop().name(name).table(table).to_owned())
+                .await?;
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SameyTag::Table)
+                    .drop_column(SameyTag::UpdatedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SameyPool::Table)
+                    .drop_column(SameyPool::UpdatedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SameyPost::Table)
+                    .drop_column(SameyPost::UpdatedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SameyPost {
+    #[sea_orm(iden = "samey_post")]
+    Table,
+    ParentId,
+    Rating,
+    UploadedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum SameyPool {
+    #[sea_orm(iden = "samey_pool")]
+    Table,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum SameyTag {
+    #[sea_orm(iden = "samey_tag")]
+    Table,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum SameyTagPost {
+    #[sea_orm(iden = "samey_tag_post")]
+    Table,
+    TagId,
+}
@@ -0,0 +1,116 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SameyPost::Table)
+                    .add_column(integer(SameyPost::ViewCount).default(0))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-samey_post-view_count")
+                    .table(SameyPost::Table)
+                    .col(SameyPost::ViewCount)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(SameyPostView::Table)
+                    .if_not_exists()
+                    .col(pk_auto(SameyPostView::Id))
+                    .col(integer(SameyPostView::PostId))
+                    .col(date(SameyPostView::Day))
+                    .col(integer(SameyPostView::Views))
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk-samey_post_view-samey_post-post_id")
+                            .from(SameyPostView::Table, SameyPostView::PostId)
+                            .to(SameyPost::Table, SameyPost::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-samey_post_view-post_id-day")
+                    .table(SameyPostView::Table)
+                    .col(SameyPostView::PostId)
+                    .col(SameyPostView::Day)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-samey_post_view-day")
+                    .table(SameyPostView::Table)
+                    .col(SameyPostView::Day)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SameyPostView::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-samey_post-view_count")
+                    .table(SameyPost::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SameyPost::Table)
+                    .drop_column(SameyPost::ViewCount)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SameyPost {
+    #[sea_orm(iden = "samey_post")]
+    Table,
+    Id,
+    ViewCount,
+}
+
+#[derive(DeriveIden)]
+enum SameyPostView {
+    #[sea_orm(iden = "samey_post_view")]
+    Table,
+    Id,
+    PostId,
+    Day,
+    Views,
+}
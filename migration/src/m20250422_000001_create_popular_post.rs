@@ -0,0 +1,79 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SameyPopularPost::Table)
+                    .if_not_exists()
+                    .col(pk_auto(SameyPopularPost::Id))
+                    .col(string_len(SameyPopularPost::Period, 8))
+                    .col(integer(SameyPopularPost::PostId))
+                    .col(integer(SameyPopularPost::Views))
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk-samey_popular_post-samey_post-post_id")
+                            .from(SameyPopularPost::Table, SameyPopularPost::PostId)
+                            .to(SameyPost::Table, SameyPost::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-samey_popular_post-period-post_id")
+                    .table(SameyPopularPost::Table)
+                    .col(SameyPopularPost::Period)
+                    .col(SameyPopularPost::PostId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-samey_popular_post-period-views")
+                    .table(SameyPopularPost::Table)
+                    .col(SameyPopularPost::Period)
+                    .col(SameyPopularPost::Views)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SameyPopularPost::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SameyPost {
+    #[sea_orm(iden = "samey_post")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum SameyPopularPost {
+    #[sea_orm(iden = "samey_popular_post")]
+    Table,
+    Id,
+    Period,
+    PostId,
+    Views,
+}
@@ -0,0 +1,42 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SameyFollower::Table)
+                    .if_not_exists()
+                    .col(pk_auto(SameyFollower::Id))
+                    .col(string_uniq(SameyFollower::ActorId))
+                    .col(string(SameyFollower::Inbox))
+                    .col(date_time(SameyFollower::CreatedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SameyFollower::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SameyFollower {
+    #[sea_orm(iden = "samey_follower")]
+    Table,
+    Id,
+    ActorId,
+    Inbox,
+    CreatedAt,
+}
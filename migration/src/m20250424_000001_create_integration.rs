@@ -0,0 +1,129 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SameyIntegration::Table)
+                    .if_not_exists()
+                    .col(pk_auto(SameyIntegration::Id))
+                    .col(string_len(SameyIntegration::Kind, 16))
+                    .col(string(SameyIntegration::Name))
+                    .col(string(SameyIntegration::Url))
+                    .col(string_null(SameyIntegration::AccessToken))
+                    .col(string(SameyIntegration::TagFilter))
+                    .col(text(SameyIntegration::MessageTemplate))
+                    .col(date_time_null(SameyIntegration::RateLimitedUntil))
+                    .col(date_time(SameyIntegration::CreatedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(SameyCrosspost::Table)
+                    .if_not_exists()
+                    .col(pk_auto(SameyCrosspost::Id))
+                    .col(integer(SameyCrosspost::IntegrationId))
+                    .col(integer(SameyCrosspost::PostId))
+                    .col(integer(SameyCrosspost::Attempts).default(0))
+                    .col(date_time(SameyCrosspost::RunAt))
+                    .col(text_null(SameyCrosspost::LastError))
+                    .col(date_time_null(SameyCrosspost::DeliveredAt))
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk-samey_crosspost-samey_integration-integration_id")
+                            .from(SameyCrosspost::Table, SameyCrosspost::IntegrationId)
+                            .to(SameyIntegration::Table, SameyIntegration::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk-samey_crosspost-samey_post-post_id")
+                            .from(SameyCrosspost::Table, SameyCrosspost::PostId)
+                            .to(SameyPost::Table, SameyPost::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-samey_crosspost-integration_id-post_id")
+                    .table(SameyCrosspost::Table)
+                    .col(SameyCrosspost::IntegrationId)
+                    .col(SameyCrosspost::PostId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-samey_crosspost-delivered_at-run_at")
+                    .table(SameyCrosspost::Table)
+                    .col(SameyCrosspost::DeliveredAt)
+                    .col(SameyCrosspost::RunAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SameyCrosspost::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(SameyIntegration::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SameyPost {
+    #[sea_orm(iden = "samey_post")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum SameyIntegration {
+    #[sea_orm(iden = "samey_integration")]
+    Table,
+    Id,
+    Kind,
+    Name,
+    Url,
+    AccessToken,
+    TagFilter,
+    MessageTemplate,
+    RateLimitedUntil,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum SameyCrosspost {
+    #[sea_orm(iden = "samey_crosspost")]
+    Table,
+    Id,
+    IntegrationId,
+    PostId,
+    Attempts,
+    RunAt,
+    LastError,
+    DeliveredAt,
+}
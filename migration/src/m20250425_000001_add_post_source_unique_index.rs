@@ -0,0 +1,63 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Keep only the oldest copy of each source of a post
+        manager
+            .exec_stmt(
+                Query::delete()
+                    .from_table(SameyPostSource::Table)
+                    .and_where(
+                        Expr::col(SameyPostSource::Id).not_in_subquery(
+                            Query::select()
+                                .expr(Expr::col(SameyPostSource::Id).min())
+                                .from(SameyPostSource::Table)
+                                .group_by_columns([SameyPostSource::PostId, SameyPostSource::Url])
+                                .to_owned(),
+                        ),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-samey_post_source-post_id-url")
+                    .table(SameyPostSource::Table)
+                    .col(SameyPostSource::PostId)
+                    .col(SameyPostSource::Url)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-samey_post_source-post_id-url")
+                    .table(SameyPostSource::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SameyPostSource {
+    #[sea_orm(iden = "samey_post_source")]
+    Table,
+    Id,
+    Url,
+    PostId,
+}
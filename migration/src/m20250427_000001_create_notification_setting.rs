@@ -0,0 +1,63 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SameyNotificationSetting::Table)
+                    .if_not_exists()
+                    .col(integer(SameyNotificationSetting::UserId).primary_key())
+                    .col(string_len_null(SameyNotificationSetting::Email, 254))
+                    .col(boolean(SameyNotificationSetting::EmailOnMention).default(false))
+                    .col(boolean(SameyNotificationSetting::EmailOnPoolAdd).default(false))
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk-samey_notification_setting-samey_user-user_id")
+                            .from(
+                                SameyNotificationSetting::Table,
+                                SameyNotificationSetting::UserId,
+                            )
+                            .to(SameyUser::Table, SameyUser::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(SameyNotificationSetting::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SameyUser {
+    #[sea_orm(iden = "samey_user")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum SameyNotificationSetting {
+    #[sea_orm(iden = "samey_notification_setting")]
+    Table,
+    UserId,
+    Email,
+    EmailOnMention,
+    EmailOnPoolAdd,
+}
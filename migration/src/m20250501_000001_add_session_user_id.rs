@@ -0,0 +1,61 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite can't add a foreign key to an existing table, but sessions of a missing user never log in anyway
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SameySession::Table)
+                    .add_column(integer_null(SameySession::UserId))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-samey_session-user_id")
+                    .table(SameySession::Table)
+                    .col(SameySession::UserId)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-samey_session-user_id")
+                    .table(SameySession::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SameySession::Table)
+                    .drop_column(SameySession::UserId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SameySession {
+    #[sea_orm(iden = "samey_session")]
+    Table,
+    UserId,
+}
@@ -0,0 +1,58 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SameyAuditLog::Table)
+                    .if_not_exists()
+                    .col(pk_auto(SameyAuditLog::Id))
+                    .col(integer_null(SameyAuditLog::ActorId))
+                    .col(string_len(SameyAuditLog::Action, 64))
+                    .col(text(SameyAuditLog::Details))
+                    .col(date_time(SameyAuditLog::CreatedAt))
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk-samey_audit_log-samey_user-actor_id")
+                            .from(SameyAuditLog::Table, SameyAuditLog::ActorId)
+                            .to(SameyUser::Table, SameyUser::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SameyAuditLog::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SameyUser {
+    #[sea_orm(iden = "samey_user")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum SameyAuditLog {
+    #[sea_orm(iden = "samey_audit_log")]
+    Table,
+    Id,
+    ActorId,
+    Action,
+    Details,
+    CreatedAt,
+}
@@ -0,0 +1,84 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SameyIdempotencyKey::Table)
+                    .if_not_exists()
+                    .col(pk_auto(SameyIdempotencyKey::Id))
+                    .col(integer(SameyIdempotencyKey::UserId))
+                    .col(string_len(SameyIdempotencyKey::Key, 255))
+                    .col(integer_null(SameyIdempotencyKey::PostId))
+                    .col(date_time(SameyIdempotencyKey::CreatedAt))
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk-samey_idempotency_key-samey_user-user_id")
+                            .from(SameyIdempotencyKey::Table, SameyIdempotencyKey::UserId)
+                            .to(SameyUser::Table, SameyUser::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk-samey_idempotency_key-samey_post-post_id")
+                            .from(SameyIdempotencyKey::Table, SameyIdempotencyKey::PostId)
+                            .to(SameyPost::Table, SameyPost::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-samey_idempotency_key-user_id-key")
+                    .table(SameyIdempotencyKey::Table)
+                    .col(SameyIdempotencyKey::UserId)
+                    .col(SameyIdempotencyKey::Key)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SameyIdempotencyKey::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SameyUser {
+    #[sea_orm(iden = "samey_user")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum SameyPost {
+    #[sea_orm(iden = "samey_post")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum SameyIdempotencyKey {
+    #[sea_orm(iden = "samey_idempotency_key")]
+    Table,
+    Id,
+    UserId,
+    Key,
+    PostId,
+    CreatedAt,
+}
@@ -0,0 +1,88 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Post columns that mirrors care about, leaving out counters like `view_count`.
+const POST_COLUMNS: &str = "uploader_id, media, width, height, media_type, thumbnail, \
+    thumbnail_width, thumbnail_height, title, description, is_public, rating, parent_id, \
+    is_locked, original_filename, version, translation_language, translated_title, \
+    translated_description";
+const TAG_COLUMNS: &str = "name, normalized_name, is_protected";
+
+/// Returns the statements creating the triggers that log changes to `table` as `entity` changes.
+fn create_triggers(table: &str, entity: &str, columns: &str) -> String {
+    let insert = |action: &str, id: &str| {
+        format!(
+            "INSERT INTO samey_change (entity, entity_id, action, created_at) \
+             VALUES ('{entity}', {id}, '{action}', strftime('%Y-%m-%d %H:%M:%f', 'now'));"
+        )
+    };
+    format!(
+        "CREATE TRIGGER IF NOT EXISTS {table}_change_create AFTER INSERT ON {table} BEGIN {} END; \
+         CREATE TRIGGER IF NOT EXISTS {table}_change_update AFTER UPDATE OF {columns} ON {table} BEGIN {} END; \
+         CREATE TRIGGER IF NOT EXISTS {table}_change_delete AFTER DELETE ON {table} BEGIN {} END;",
+        insert("create", "NEW.id"),
+        insert("update", "NEW.id"),
+        insert("delete", "OLD.id"),
+    )
+}
+
+fn drop_triggers(table: &str) -> String {
+    format!(
+        "DROP TRIGGER IF EXISTS {table}_change_create; \
+         DROP TRIGGER IF EXISTS {table}_change_update; \
+         DROP TRIGGER IF EXISTS {table}_change_delete;"
+    )
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SameyChange::Table)
+                    .if_not_exists()
+                    .col(pk_auto(SameyChange::Id))
+                    .col(string_len(SameyChange::Entity, 16))
+                    .col(integer(SameyChange::EntityId))
+                    .col(string_len(SameyChange::Action, 16))
+                    .col(date_time(SameyChange::CreatedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        // Triggers also catch bulk updates and cascading deletes, which skip the entities' hooks
+        let db = manager.get_connection();
+        db.execute_unprepared(&create_triggers("samey_post", "post", POST_COLUMNS))
+            .await?;
+        db.execute_unprepared(&create_triggers("samey_tag", "tag", TAG_COLUMNS))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared(&drop_triggers("samey_post")).await?;
+        db.execute_unprepared(&drop_triggers("samey_tag")).await?;
+
+        manager
+            .drop_table(Table::drop().table(SameyChange::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SameyChange {
+    #[sea_orm(iden = "samey_change")]
+    Table,
+    Id,
+    Entity,
+    EntityId,
+    Action,
+    CreatedAt,
+}
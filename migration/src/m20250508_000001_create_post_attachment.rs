@@ -0,0 +1,70 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SameyPostAttachment::Table)
+                    .if_not_exists()
+                    .col(pk_auto(SameyPostAttachment::Id))
+                    .col(integer(SameyPostAttachment::PostId))
+                    .col(string(SameyPostAttachment::FileName))
+                    .col(string(SameyPostAttachment::OriginalFilename))
+                    .col(big_integer(SameyPostAttachment::Size))
+                    .col(date_time(SameyPostAttachment::UploadedAt))
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk-samey_post_attachment-samey_post-post_id")
+                            .from(SameyPostAttachment::Table, SameyPostAttachment::PostId)
+                            .to(SameyPost::Table, SameyPost::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-samey_post_attachment-post_id")
+                    .table(SameyPostAttachment::Table)
+                    .col(SameyPostAttachment::PostId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SameyPostAttachment::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SameyPostAttachment {
+    #[sea_orm(iden = "samey_post_attachment")]
+    Table,
+    Id,
+    PostId,
+    FileName,
+    OriginalFilename,
+    Size,
+    UploadedAt,
+}
+
+#[derive(DeriveIden)]
+enum SameyPost {
+    #[sea_orm(iden = "samey_post")]
+    Table,
+    Id,
+}
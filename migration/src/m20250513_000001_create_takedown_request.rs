@@ -0,0 +1,102 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(SameyTakedownRequest::Table)
+                    .if_not_exists()
+                    .col(pk_auto(SameyTakedownRequest::Id))
+                    .col(integer_null(SameyTakedownRequest::PostId))
+                    .col(string(SameyTakedownRequest::ClaimantName))
+                    .col(string(SameyTakedownRequest::ClaimantEmail))
+                    .col(text(SameyTakedownRequest::Reason))
+                    .col(string(SameyTakedownRequest::Status))
+                    .col(date_time(SameyTakedownRequest::CreatedAt))
+                    .col(integer_null(SameyTakedownRequest::ReviewedById))
+                    .col(date_time_null(SameyTakedownRequest::ReviewedAt))
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk-samey_takedown_request-samey_post-post_id")
+                            .from(SameyTakedownRequest::Table, SameyTakedownRequest::PostId)
+                            .to(SameyPost::Table, SameyPost::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .name("fk-samey_takedown_request-samey_user-reviewed_by_id")
+                            .from(
+                                SameyTakedownRequest::Table,
+                                SameyTakedownRequest::ReviewedById,
+                            )
+                            .to(SameyUser::Table, SameyUser::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SameyPost::Table)
+                    .add_column(date_time_null(SameyPost::TakenDownAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SameyPost::Table)
+                    .drop_column(SameyPost::TakenDownAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(SameyTakedownRequest::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum SameyUser {
+    #[sea_orm(iden = "samey_user")]
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum SameyPost {
+    #[sea_orm(iden = "samey_post")]
+    Table,
+    Id,
+    TakenDownAt,
+}
+
+#[derive(DeriveIden)]
+enum SameyTakedownRequest {
+    #[sea_orm(iden = "samey_takedown_request")]
+    Table,
+    Id,
+    PostId,
+    ClaimantName,
+    ClaimantEmail,
+    Reason,
+    Status,
+    CreatedAt,
+    ReviewedById,
+    ReviewedAt,
+}
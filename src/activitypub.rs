@@ -0,0 +1,652 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{
+        HeaderMap, Method, StatusCode, Uri,
+        header::{CONTENT_TYPE, DATE},
+    },
+    response::IntoResponse,
+};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use chrono::{SecondsFormat, Utc};
+use itertools::Itertools;
+use rsa::{
+    RsaPrivateKey, RsaPublicKey,
+    pkcs1v15::{Signature, SigningKey, VerifyingKey},
+    pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey, LineEnding},
+    signature::{SignatureEncoding, Signer, Verifier},
+};
+use samey_migration::OnConflict;
+use sea_orm::{
+    ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect,
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    AppState,
+    config::AppConfig,
+    entities::{
+        prelude::{SameyFollower, SameyPost},
+        samey_follower, samey_post,
+    },
+    error::SameyError,
+    query::{get_tags_for_post, is_post_published, published_posts_condition},
+    tags::{Rating, is_sensitive_rating},
+};
+
+const ACTIVITY_JSON: &str = "application/activity+json";
+const ACTIVITY_STREAMS_CONTEXT: &str = "https://www.w3.org/ns/activitystreams";
+const SECURITY_CONTEXT: &str = "https://w3id.org/security/v1";
+const PUBLIC_COLLECTION: &str = "https://www.w3.org/ns/activitystreams#Public";
+/// How many of the latest posts are listed in the outbox.
+const OUTBOX_POSTS: u64 = 20;
+/// How long other servers may take to reply.
+const FEDERATION_TIMEOUT: Duration = Duration::from_secs(10);
+/// Signed requests dated further than this from now are rejected, to limit replays.
+const MAX_SIGNATURE_AGE: Duration = Duration::from_secs(12 * 60 * 60);
+const RSA_KEY_BITS: usize = 2048;
+
+/// Generates a new key for signing ActivityPub requests, as PKCS#8 PEM.
+pub(crate) async fn generate_private_key() -> Result<String, SameyError> {
+    tokio::task::spawn_blocking(|| {
+        let key = RsaPrivateKey::new(&mut rsa::rand_core::OsRng, RSA_KEY_BITS)
+            .map_err(|err| SameyError::Other(format!("Unable to generate key: {}", err)))?;
+        key.to_pkcs8_pem(LineEnding::LF)
+            .map(|pem| pem.to_string())
+            .map_err(|err| SameyError::Other(format!("Unable to encode key: {}", err)))
+    })
+    .await?
+}
+
+/// ActivityPub settings of the instance, which has a single actor publishing all public posts.
+#[derive(Clone)]
+pub(crate) struct Federation {
+    base_url: String,
+    username: String,
+    application_name: String,
+    private_key: String,
+    age_confirmation: bool,
+    age_confirmation_explicit_only: bool,
+    ratings: Vec<Rating>,
+}
+
+impl Federation {
+    /// Returns the settings if ActivityPub is enabled and fully configured.
+    pub(crate) fn from_config(app_config: &AppConfig) -> Option<Self> {
+        (app_config.activitypub_enabled
+            && !app_config.base_url.is_empty()
+            && !app_config.activitypub_private_key.is_empty())
+        .then(|| Self {
+            base_url: app_config.base_url.clone(),
+            username: app_config.activitypub_username.clone(),
+            application_name: app_config.application_name.clone(),
+            private_key: app_config.activitypub_private_key.clone(),
+            age_confirmation: app_config.age_confirmation,
+            age_confirmation_explicit_only: app_config.age_confirmation_explicit_only,
+            ratings: app_config.ratings.clone(),
+        })
+    }
+
+    fn actor_id(&self) -> String {
+        format!("{}/ap/actor", self.base_url)
+    }
+
+    fn key_id(&self) -> String {
+        format!("{}#main-key", self.actor_id())
+    }
+
+    fn followers_id(&self) -> String {
+        format!("{}/ap/followers", self.base_url)
+    }
+
+    fn note_id(&self, post_id: i32) -> String {
+        format!("{}/ap/post/{}", self.base_url, post_id)
+    }
+
+    /// Posts behind the age check aren't federated, since other servers can't pass it to fetch their media.
+    fn is_federated(&self, post: &samey_post::Model) -> bool {
+        is_post_published(post)
+            && !(self.age_confirmation
+                && (!self.age_confirmation_explicit_only
+                    || is_sensitive_rating(&self.ratings, &post.rating)))
+    }
+
+    fn sign(&self, signing_string: &str) -> Result<String, SameyError> {
+        let key = RsaPrivateKey::from_pkcs8_pem(&self.private_key)
+            .map_err(|err| SameyError::Other(format!("Invalid ActivityPub key: {}", err)))?;
+        let signature = SigningKey::<Sha256>::new(key).sign(signing_string.as_bytes());
+        Ok(BASE64.encode(signature.to_bytes()))
+    }
+
+    fn public_key_pem(&self) -> Result<String, SameyError> {
+        RsaPrivateKey::from_pkcs8_pem(&self.private_key)
+            .and_then(|key| {
+                RsaPublicKey::from(&key)
+                    .to_public_key_pem(LineEnding::LF)
+                    .map_err(Into::into)
+            })
+            .map_err(|err| SameyError::Other(format!("Invalid ActivityPub key: {}", err)))
+    }
+}
+
+async fn federation(app_config: &tokio::sync::RwLock<AppConfig>) -> Result<Federation, SameyError> {
+    Federation::from_config(&*app_config.read().await).ok_or(SameyError::NotFound)
+}
+
+fn activity_json(value: Value) -> impl IntoResponse {
+    ([(CONTENT_TYPE, ACTIVITY_JSON)], Json(value))
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Documents
+
+async fn note(
+    db: &DatabaseConnection,
+    federation: &Federation,
+    post: &samey_post::Model,
+) -> Result<Value, SameyError> {
+    let tags = get_tags_for_post(post.id).all(db).await?;
+    let post_url = format!("{}/post/{}", federation.base_url, post.id);
+    let tag_url = |tag: &str| {
+        reqwest::Url::parse_with_params(&format!("{}/posts", federation.base_url), [("tags", tag)])
+            .map(|url| url.to_string())
+            .unwrap_or_default()
+    };
+
+    let mut content = String::new();
+    if let Some(title) = post.title.as_ref() {
+        content.push_str(&format!("<p>{}</p>", escape_html(title)));
+    }
+    content.push_str(&format!("<p><a href=\"{0}\">{0}</a></p>", post_url));
+    if !tags.is_empty() {
+        content.push_str(&format!(
+            "<p>{}</p>",
+            tags.iter()
+                .map(|tag| format!(
+                    "<a href=\"{}\" class=\"mention hashtag\" rel=\"tag\">#<span>{}</span></a>",
+                    escape_html(&tag_url(&tag.name)),
+                    escape_html(&tag.name)
+                ))
+                .join(" ")
+        ));
+    }
+
+    let content_warning = federation
+        .ratings
+        .iter()
+        .find(|rating| rating.code == post.rating && rating.sensitive)
+        .map(|rating| rating.label.clone());
+    Ok(json!({
+        "id": federation.note_id(post.id),
+        "type": "Note",
+        "attributedTo": federation.actor_id(),
+        "published": post.uploaded_at.and_utc().to_rfc3339_opts(SecondsFormat::Secs, true),
+        "url": post_url,
+        "to": [PUBLIC_COLLECTION],
+        "cc": [federation.followers_id()],
+        "content": content,
+        "sensitive": content_warning.is_some(),
+        "summary": content_warning,
+        "attachment": [{
+            "type": "Document",
+            "mediaType": mime_guess::from_path(&post.media).first_or_octet_stream().to_string(),
+            "url": format!("{}/files/{}", federation.base_url, post.media),
+            "name": post.title,
+            "width": post.width,
+            "height": post.height,
+        }],
+        "tag": tags.iter().map(|tag| json!({
+            "type": "Hashtag",
+            "href": tag_url(&tag.name),
+            "name": format!("#{}", tag.name),
+        })).collect::<Vec<_>>(),
+    }))
+}
+
+async fn create_activity(
+    db: &DatabaseConnection,
+    federation: &Federation,
+    post: &samey_post::Model,
+) -> Result<Value, SameyError> {
+    let note = note(db, federation, post).await?;
+    Ok(json!({
+        "id": format!("{}/activity", federation.note_id(post.id)),
+        "type": "Create",
+        "actor": federation.actor_id(),
+        "published": note["published"],
+        "to": note["to"],
+        "cc": note["cc"],
+        "object": note,
+    }))
+}
+
+// Discovery views
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct WebfingerQuery {
+    resource: String,
+}
+
+pub(crate) async fn webfinger(
+    State(AppState { app_config, .. }): State<AppState>,
+    Query(query): Query<WebfingerQuery>,
+) -> Result<impl IntoResponse, SameyError> {
+    let federation = federation(&app_config).await?;
+    let host = federation
+        .base_url
+        .parse::<Uri>()
+        .ok()
+        .and_then(|base_url| base_url.authority().map(|authority| authority.to_string()))
+        .ok_or(SameyError::NotFound)?;
+    let subject = format!("acct:{}@{}", federation.username, host);
+    if !query.resource.eq_ignore_ascii_case(&subject) && query.resource != federation.actor_id() {
+        return Err(SameyError::NotFound);
+    }
+
+    Ok((
+        [(CONTENT_TYPE, "application/jrd+json")],
+        Json(json!({
+            "subject": subject,
+            "aliases": [federation.actor_id()],
+            "links": [
+                {
+                    "rel": "self",
+                    "type": ACTIVITY_JSON,
+                    "href": federation.actor_id(),
+                },
+                {
+                    "rel": "http://webfinger.net/rel/profile-page",
+                    "type": "text/html",
+                    "href": federation.base_url,
+                },
+            ],
+        })),
+    ))
+}
+
+pub(crate) async fn actor(
+    State(AppState { app_config, .. }): State<AppState>,
+) -> Result<impl IntoResponse, SameyError> {
+    let federation = federation(&app_config).await?;
+
+    Ok(activity_json(json!({
+        "@context": [ACTIVITY_STREAMS_CONTEXT, SECURITY_CONTEXT],
+        "id": federation.actor_id(),
+        "type": "Service",
+        "preferredUsername": federation.username,
+        "name": federation.application_name,
+        "url": federation.base_url,
+        "inbox": format!("{}/ap/inbox", federation.base_url),
+        "outbox": format!("{}/ap/outbox", federation.base_url),
+        "followers": federation.followers_id(),
+        "manuallyApprovesFollowers": false,
+        "discoverable": true,
+        "icon": {
+            "type": "Image",
+            "url": format!("{}/favicon.ico", federation.base_url),
+        },
+        "publicKey": {
+            "id": federation.key_id(),
+            "owner": federation.actor_id(),
+            "publicKeyPem": federation.public_key_pem()?,
+        },
+    })))
+}
+
+pub(crate) async fn followers(
+    State(AppState { db, app_config, .. }): State<AppState>,
+) -> Result<impl IntoResponse, SameyError> {
+    let federation = federation(&app_config).await?;
+    let follower_count = SameyFollower::find().count(&db).await?;
+
+    Ok(activity_json(json!({
+        "@context": ACTIVITY_STREAMS_CONTEXT,
+        "id": federation.followers_id(),
+        "type": "OrderedCollection",
+        "totalItems": follower_count,
+    })))
+}
+
+pub(crate) async fn outbox(
+    State(AppState { db, app_config, .. }): State<AppState>,
+) -> Result<impl IntoResponse, SameyError> {
+    let federation = federation(&app_config).await?;
+    let posts = SameyPost::find()
+        .filter(published_posts_condition())
+        .order_by_desc(samey_post::Column::Id)
+        .limit(OUTBOX_POSTS)
+        .all(&db)
+        .await?;
+    let mut activities = Vec::with_capacity(posts.len());
+    for post in posts.iter().filter(|post| federation.is_federated(post)) {
+        activities.push(create_activity(&db, &federation, post).await?);
+    }
+
+    Ok(activity_json(json!({
+        "@context": ACTIVITY_STREAMS_CONTEXT,
+        "id": format!("{}/ap/outbox", federation.base_url),
+        "type": "OrderedCollection",
+        "totalItems": activities.len(),
+        "orderedItems": activities,
+    })))
+}
+
+pub(crate) async fn post_note(
+    State(AppState { db, app_config, .. }): State<AppState>,
+    Path(post_id): Path<i32>,
+) -> Result<impl IntoResponse, SameyError> {
+    let federation = federation(&app_config).await?;
+    let post = SameyPost::find_by_id(post_id)
+        .one(&db)
+        .await?
+        .filter(|post| federation.is_federated(post))
+        .ok_or(SameyError::NotFound)?;
+
+    let mut note = note(&db, &federation, &post).await?;
+    note["@context"] = ACTIVITY_STREAMS_CONTEXT.into();
+    Ok(activity_json(note))
+}
+
+// Inbox views
+
+pub(crate) async fn inbox(
+    State(AppState { db, app_config, .. }): State<AppState>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, SameyError> {
+    let federation = federation(&app_config).await?;
+    let activity: Value = serde_json::from_slice(&body)
+        .map_err(|_| SameyError::BadRequest("Invalid activity".into()))?;
+    let actor_id = activity["actor"]
+        .as_str()
+        .ok_or_else(|| SameyError::BadRequest("Missing actor".into()))?;
+    let actor = verify_signature(&federation, &method, &uri, &headers, &body, actor_id).await?;
+
+    match activity["type"].as_str() {
+        Some("Follow") if activity["object"].as_str() == Some(&federation.actor_id()) => {
+            let inbox = actor["inbox"]
+                .as_str()
+                .ok_or_else(|| SameyError::BadRequest("Missing inbox".into()))?;
+            let delivery_inbox = actor["endpoints"]["sharedInbox"].as_str().unwrap_or(inbox);
+            SameyFollower::insert(samey_follower::ActiveModel {
+                actor_id: Set(actor_id.into()),
+                inbox: Set(delivery_inbox.into()),
+                created_at: Set(Utc::now().naive_utc()),
+                ..Default::default()
+            })
+            .on_conflict(
+                OnConflict::column(samey_follower::Column::ActorId)
+                    .update_column(samey_follower::Column::Inbox)
+                    .to_owned(),
+            )
+            .exec_without_returning(&db)
+            .await?;
+
+            let accept = json!({
+                "@context": ACTIVITY_STREAMS_CONTEXT,
+                "id": format!(
+                    "{}#accepts/{:x}",
+                    federation.actor_id(),
+                    Sha256::digest(&body)
+                ),
+                "type": "Accept",
+                "actor": federation.actor_id(),
+                "object": activity,
+            });
+            let inbox = inbox.to_owned();
+            tokio::spawn(async move {
+                if let Err(err) = deliver(&federation, &inbox, &accept).await {
+                    println!("Error when accepting follow - {}", err);
+                }
+            });
+        }
+        Some("Undo") if activity["object"]["type"].as_str() == Some("Follow") => {
+            SameyFollower::delete_many()
+                .filter(samey_follower::Column::ActorId.eq(actor_id))
+                .exec(&db)
+                .await?;
+        }
+        _ => {}
+    }
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Checks the HTTP signature of an incoming request, and returns the document of the actor who signed it.
+async fn verify_signature(
+    federation: &Federation,
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    body: &[u8],
+    actor_id: &str,
+) -> Result<Value, SameyError> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    let parameters: HashMap<&str, &str> = header("signature")
+        .ok_or(SameyError::Forbidden)?
+        .split(',')
+        .filter_map(|parameter| parameter.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim().trim_matches('"')))
+        .collect();
+    let key_id = parameters.get("keyId").ok_or(SameyError::Forbidden)?;
+    let signature = parameters
+        .get("signature")
+        .and_then(|signature| BASE64.decode(signature).ok())
+        .and_then(|signature| Signature::try_from(signature.as_slice()).ok())
+        .ok_or(SameyError::Forbidden)?;
+    let signed_headers: Vec<&str> = parameters
+        .get("headers")
+        .copied()
+        .unwrap_or("date")
+        .split_whitespace()
+        .collect();
+    if !["(request-target)", "date", "digest"]
+        .iter()
+        .all(|required| signed_headers.contains(required))
+    {
+        return Err(SameyError::Forbidden);
+    }
+
+    let date = header(DATE.as_str())
+        .and_then(|date| httpdate::parse_http_date(date).ok())
+        .ok_or(SameyError::Forbidden)?;
+    let now = SystemTime::now();
+    let age = now
+        .duration_since(date)
+        .or_else(|_| date.duration_since(now))
+        .unwrap_or_default();
+    if age > MAX_SIGNATURE_AGE {
+        return Err(SameyError::Forbidden);
+    }
+    let digest = format!("SHA-256={}", BASE64.encode(Sha256::digest(body)));
+    if header("digest") != Some(digest.as_str()) {
+        return Err(SameyError::Forbidden);
+    }
+
+    let signing_string = signed_headers
+        .iter()
+        .map(|name| match *name {
+            "(request-target)" => Some(format!(
+                "(request-target): {} {}",
+                method.as_str().to_lowercase(),
+                uri.path_and_query()
+                    .map(|path| path.as_str())
+                    .unwrap_or(uri.path())
+            )),
+            name => Some(format!("{}: {}", name, header(name)?)),
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or(SameyError::Forbidden)?
+        .join("\n");
+
+    let actor = fetch(federation, key_id.split('#').next().unwrap_or(key_id)).await?;
+    if actor["id"].as_str() != Some(actor_id) || actor["publicKey"]["id"].as_str() != Some(key_id) {
+        return Err(SameyError::Forbidden);
+    }
+    let public_key = actor["publicKey"]["publicKeyPem"]
+        .as_str()
+        .and_then(|pem| RsaPublicKey::from_public_key_pem(pem).ok())
+        .ok_or(SameyError::Forbidden)?;
+    VerifyingKey::<Sha256>::new(public_key)
+        .verify(signing_string.as_bytes(), &signature)
+        .map_err(|_| SameyError::Forbidden)?;
+
+    Ok(actor)
+}
+
+// Delivery
+
+/// Returns the `Host` header value and the request target of a URL.
+fn host_and_target(url: &reqwest::Url) -> Result<(String, String), SameyError> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| SameyError::Other(format!("Invalid URL: {}", url)))?;
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_owned(),
+    };
+    let target = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_owned(),
+    };
+    Ok((host, target))
+}
+
+/// Fetches an ActivityPub document with a signed request, as required by servers with authorized fetch.
+async fn fetch(federation: &Federation, url: &str) -> Result<Value, SameyError> {
+    let url = reqwest::Url::parse(url)
+        .map_err(|_| SameyError::BadRequest(format!("Invalid URL: {}", url)))?;
+    let (host, target) = host_and_target(&url)?;
+    let date = httpdate::fmt_http_date(SystemTime::now());
+    let signature = federation.sign(&format!(
+        "(request-target): get {}\nhost: {}\ndate: {}",
+        target, host, date
+    ))?;
+
+    reqwest::Client::new()
+        .get(url)
+        .header("accept", ACTIVITY_JSON)
+        .header("date", date)
+        .header(
+            "signature",
+            format!(
+                "keyId=\"{}\",algorithm=\"rsa-sha256\",headers=\"(request-target) host date\",signature=\"{}\"",
+                federation.key_id(),
+                signature
+            ),
+        )
+        .timeout(FEDERATION_TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| SameyError::Other(format!("ActivityPub fetch failed: {}", err)))?
+        .json()
+        .await
+        .map_err(|err| SameyError::Other(format!("Invalid ActivityPub document: {}", err)))
+}
+
+/// Posts an activity to an inbox with a signed request.
+async fn deliver(federation: &Federation, inbox: &str, activity: &Value) -> Result<(), SameyError> {
+    let url = reqwest::Url::parse(inbox)
+        .map_err(|_| SameyError::Other(format!("Invalid inbox: {}", inbox)))?;
+    let (host, target) = host_and_target(&url)?;
+    let body = serde_json::to_vec(activity).map_err(|err| SameyError::Other(err.to_string()))?;
+    let date = httpdate::fmt_http_date(SystemTime::now());
+    let digest = format!("SHA-256={}", BASE64.encode(Sha256::digest(&body)));
+    let signature = federation.sign(&format!(
+        "(request-target): post {}\nhost: {}\ndate: {}\ndigest: {}",
+        target, host, date, digest
+    ))?;
+
+    reqwest::Client::new()
+        .post(url)
+        .header("content-type", ACTIVITY_JSON)
+        .header("date", date)
+        .header("digest", digest)
+        .header(
+            "signature",
+            format!(
+                "keyId=\"{}\",algorithm=\"rsa-sha256\",headers=\"(request-target) host date digest\",signature=\"{}\"",
+                federation.key_id(),
+                signature
+            ),
+        )
+        .body(body)
+        .timeout(FEDERATION_TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| SameyError::Other(format!("ActivityPub delivery failed: {}", err)))?;
+    Ok(())
+}
+
+async fn deliver_to_followers(
+    db: &DatabaseConnection,
+    federation: &Federation,
+    activity: &Value,
+) -> Result<(), SameyError> {
+    let inboxes: Vec<String> = SameyFollower::find()
+        .select_only()
+        .column(samey_follower::Column::Inbox)
+        .distinct()
+        .into_tuple()
+        .all(db)
+        .await?;
+    for inbox in inboxes {
+        if let Err(err) = deliver(federation, &inbox, activity).await {
+            println!("Error when delivering to {} - {}", inbox, err);
+        }
+    }
+    Ok(())
+}
+
+/// Announces to followers that a post was published or removed, in the background.
+///
+/// Call this after a post becomes public, stops being public, or is deleted.
+pub(crate) fn federate_post(db: DatabaseConnection, federation: Federation, post_id: i32) {
+    tokio::spawn(async move {
+        let result = async {
+            let post = SameyPost::find_by_id(post_id).one(&db).await?;
+            let activity = match post {
+                Some(post) if federation.is_federated(&post) => {
+                    let mut activity = create_activity(&db, &federation, &post).await?;
+                    activity["@context"] = ACTIVITY_STREAMS_CONTEXT.into();
+                    activity
+                }
+                _ => json!({
+                    "@context": ACTIVITY_STREAMS_CONTEXT,
+                    "id": format!("{}#delete", federation.note_id(post_id)),
+                    "type": "Delete",
+                    "actor": federation.actor_id(),
+                    "to": [PUBLIC_COLLECTION],
+                    "object": {
+                        "id": federation.note_id(post_id),
+                        "type": "Tombstone",
+                    },
+                }),
+            };
+            deliver_to_followers(&db, &federation, &activity).await
+        };
+        if let Err(err) = result.await {
+            println!("Error when federating post #{} - {}", post_id, err);
+        }
+    });
+}
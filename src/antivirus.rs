@@ -0,0 +1,68 @@
+use std::{path::Path, time::Duration};
+
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+use crate::SameyError;
+
+/// How long a scan may take before it's considered failed.
+const SCAN_TIMEOUT: Duration = Duration::from_secs(60);
+/// Size of each chunk sent to clamd, well under its default `StreamMaxLength`.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+pub(crate) enum ScanResult {
+    Clean,
+    /// Contains the name of the detected signature.
+    Infected(String),
+}
+
+/// Scans a file with clamd, listening on either a TCP address (`host:port`) or a Unix socket path.
+pub(crate) async fn scan_file(address: &str, path: &Path) -> Result<ScanResult, SameyError> {
+    let file = File::open(path).await?;
+    timeout(SCAN_TIMEOUT, async {
+        #[cfg(unix)]
+        if address.starts_with('/') {
+            let stream = tokio::net::UnixStream::connect(address).await?;
+            return instream(stream, file).await;
+        }
+        let stream = TcpStream::connect(address).await?;
+        instream(stream, file).await
+    })
+    .await
+    .map_err(|_| SameyError::Other("Virus scan timed out".into()))?
+}
+
+/// Sends a file with clamd's `INSTREAM` command, and parses its reply.
+async fn instream<S>(mut stream: S, mut file: File) -> Result<ScanResult, SameyError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream.write_all(b"zINSTREAM\0").await?;
+    let mut buffer = vec![0; CHUNK_SIZE];
+    loop {
+        let length = file.read(&mut buffer).await?;
+        stream.write_all(&(length as u32).to_be_bytes()).await?;
+        if length == 0 {
+            break;
+        }
+        stream.write_all(&buffer[..length]).await?;
+    }
+    stream.flush().await?;
+
+    let mut reply = vec![];
+    stream.read_to_end(&mut reply).await?;
+    let reply = String::from_utf8_lossy(&reply);
+    let reply = reply.trim_end_matches(['\0', '\n']);
+    let result = reply.strip_prefix("stream: ").unwrap_or(reply);
+    if result == "OK" {
+        Ok(ScanResult::Clean)
+    } else if let Some(signature) = result.strip_suffix(" FOUND") {
+        Ok(ScanResult::Infected(signature.into()))
+    } else {
+        Err(SameyError::Other(format!("Virus scan failed: {}", reply)))
+    }
+}
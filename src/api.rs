@@ -0,0 +1,1253 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    Json,
+    extract::{FromRequest, Multipart, Path, Query, Request, State},
+    http::{
+        HeaderMap, StatusCode,
+        header::{CONTENT_TYPE, LOCATION},
+    },
+    response::{IntoResponse, Response},
+};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use itertools::Itertools;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, Condition, ConnectionTrait,
+    DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, SqlErr,
+    TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+use crate::{
+    AppState,
+    activitypub::{Federation, federate_post},
+    auth::{AuthSession, User},
+    content::{
+        bump_post_version, create_uploaded_post, remove_post_files, replace_post_tags,
+        save_upload_tags, store_upload_media,
+    },
+    entities::{
+        prelude::{
+            SameyChange, SameyIdempotencyKey, SameyPool, SameyPoolPost, SameyPost, SameyPostSource,
+        },
+        samey_change, samey_idempotency_key, samey_pool, samey_pool_post, samey_post,
+        samey_post_source, samey_tag,
+    },
+    error::SameyError,
+    permissions::RequireOwnerOrAdmin,
+    query::{
+        PostOverview, PostsCursor, PostsKeysetPage, TagCount, autocomplete_tags,
+        clean_dangling_tags, filter_pools_by_user, filter_posts_by_user, get_posts_in_pool,
+        get_protected_tags, get_search_neighbours, get_tags_for_post, is_post_published,
+        search_posts, search_posts_keyset,
+    },
+    search::SearchQuery,
+    tags::{MEDIA_TYPE_PREFIX, NEGATIVE_PREFIX, RATING_PREFIX},
+    views::create_post_from_multipart,
+};
+
+const API_POSTS_PER_PAGE: u64 = 50;
+const API_POOLS_PER_PAGE: u64 = 50;
+const API_CHANGES_PER_PAGE: u64 = 500;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Samey API"),
+    paths(
+        posts,
+        post,
+        post_neighbours,
+        upload_post,
+        batch_tags,
+        batch_rating,
+        batch_delete,
+        pools,
+        pool,
+        sort_pool,
+        tags_autocomplete,
+        changes
+    ),
+    components(schemas(
+        PostsResponse,
+        PostSummary,
+        PostResponse,
+        NeighboursResponse,
+        UploadPostRequest,
+        BatchTagsRequest,
+        BatchRatingRequest,
+        BatchDeleteRequest,
+        BatchResponse,
+        BatchItemResult,
+        BatchItemStatus,
+        PoolsResponse,
+        PoolSummary,
+        PoolResponse,
+        PoolPostEntry,
+        SortPoolRequest,
+        TagSuggestion,
+        ChangesResponse,
+        Change
+    ))
+)]
+struct ApiDoc;
+
+pub(crate) async fn openapi() -> impl IntoResponse {
+    Json(ApiDoc::openapi())
+}
+
+// Posts API
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct PostsQuery {
+    /// Space-separated search tags.
+    pub(crate) tags: Option<String>,
+    /// Page number, starting from 1. Ignored when a cursor is given.
+    pub(crate) page: Option<u32>,
+    /// Only return posts newer than this ID.
+    pub(crate) before_id: Option<i32>,
+    /// Only return posts older than this ID.
+    pub(crate) after_id: Option<i32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct PostSummary {
+    id: i32,
+    media: String,
+    thumbnail: String,
+    media_type: String,
+    rating: String,
+    title: Option<String>,
+    tags: Vec<String>,
+    uploaded_at: NaiveDateTime,
+}
+
+impl From<PostOverview> for PostSummary {
+    fn from(post: PostOverview) -> Self {
+        Self {
+            id: post.id,
+            media: format!("/files/{}", post.media),
+            thumbnail: format!("/files/{}", post.thumbnail),
+            media_type: post.media_type,
+            rating: post.rating,
+            title: post.title,
+            tags: post
+                .tags
+                .map(|tags| {
+                    tags.split_ascii_whitespace()
+                        .map(String::from)
+                        .sorted()
+                        .collect()
+                })
+                .unwrap_or_default(),
+            uploaded_at: post.uploaded_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct PostsResponse {
+    posts: Vec<PostSummary>,
+    /// Only present when paginating by page number.
+    page: Option<u32>,
+    /// Only present when paginating by page number.
+    page_count: Option<u64>,
+    /// Value of `before_id` for the previous page, if any.
+    previous_id: Option<i32>,
+    /// Value of `after_id` for the next page, if any.
+    next_id: Option<i32>,
+}
+
+/// Searches posts visible to the current user.
+#[utoipa::path(
+    get,
+    path = "/api/v1/posts",
+    tag = "posts",
+    params(PostsQuery),
+    responses((status = 200, description = "Page of matching posts", body = PostsResponse))
+)]
+pub(crate) async fn posts(
+    State(AppState { db, .. }): State<AppState>,
+    auth_session: AuthSession,
+    Query(query): Query<PostsQuery>,
+) -> Result<impl IntoResponse, SameyError> {
+    Ok(Json(
+        get_posts_response(&db, auth_session.user.as_ref(), query, API_POSTS_PER_PAGE).await?,
+    ))
+}
+
+/// Searches posts visible to the user, in the same shape as the `/api/v1/posts` response.
+pub(crate) async fn get_posts_response(
+    db: &DatabaseConnection,
+    user: Option<&User>,
+    query: PostsQuery,
+    posts_per_page: u64,
+) -> Result<PostsResponse, SameyError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let search = SearchQuery::parse(query.tags.as_deref().unwrap_or_default());
+    let cursor = match (query.before_id, query.after_id) {
+        (Some(before_id), _) => PostsCursor::Before(before_id),
+        (None, Some(after_id)) => PostsCursor::After(after_id),
+        (None, None) => PostsCursor::Page(page.saturating_sub(1) as u64),
+    };
+    let (page, page_count) = match cursor {
+        PostsCursor::Page(_) => (
+            Some(page),
+            Some(
+                search_posts(&search, user)
+                    .paginate(db, posts_per_page)
+                    .num_pages()
+                    .await?,
+            ),
+        ),
+        _ => (None, None),
+    };
+    let PostsKeysetPage {
+        posts,
+        previous_id,
+        next_id,
+    } = search_posts_keyset(db, &search, user, cursor, posts_per_page).await?;
+
+    Ok(PostsResponse {
+        posts: posts.into_iter().map(PostSummary::from).collect(),
+        page,
+        page_count,
+        previous_id,
+        next_id,
+    })
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct PostResponse {
+    id: i32,
+    media: String,
+    width: i32,
+    height: i32,
+    thumbnail: String,
+    media_type: String,
+    rating: String,
+    title: Option<String>,
+    description: Option<String>,
+    is_public: bool,
+    /// When a scheduled post becomes public.
+    publish_at: Option<NaiveDateTime>,
+    /// When the post stops being public.
+    expires_at: Option<NaiveDateTime>,
+    parent_id: Option<i32>,
+    tags: Vec<String>,
+    sources: Vec<String>,
+    uploaded_at: NaiveDateTime,
+}
+
+/// Returns a single post visible to the current user.
+#[utoipa::path(
+    get,
+    path = "/api/v1/posts/{post_id}",
+    tag = "posts",
+    params(("post_id" = i32, Path, description = "Post ID")),
+    responses(
+        (status = 200, description = "Post details", body = PostResponse),
+        (status = 404, description = "Post not found")
+    )
+)]
+pub(crate) async fn post(
+    State(AppState { db, .. }): State<AppState>,
+    auth_session: AuthSession,
+    Path(post_id): Path<i32>,
+) -> Result<impl IntoResponse, SameyError> {
+    Ok(Json(
+        get_post_response(&db, auth_session.user.as_ref(), post_id).await?,
+    ))
+}
+
+/// Fetches a post visible to the user, in the same shape as the `/api/v1/posts/{post_id}` response.
+pub(crate) async fn get_post_response(
+    db: &DatabaseConnection,
+    user: Option<&User>,
+    post_id: i32,
+) -> Result<PostResponse, SameyError> {
+    let post = filter_posts_by_user(SameyPost::find_by_id(post_id), user)
+        .one(db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+
+    let tags = get_tags_for_post(post_id)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|tag| tag.name)
+        .collect();
+
+    let sources = SameyPostSource::find()
+        .filter(samey_post_source::Column::PostId.eq(post_id))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|source| source.url)
+        .collect();
+
+    Ok(PostResponse {
+        id: post.id,
+        media: format!("/files/{}", post.media),
+        width: post.width,
+        height: post.height,
+        thumbnail: format!("/files/{}", post.thumbnail),
+        media_type: post.media_type,
+        rating: post.rating,
+        title: post.title,
+        description: post.description,
+        is_public: post.is_public,
+        publish_at: post.publish_at,
+        expires_at: post.expires_at,
+        parent_id: post.parent_id,
+        tags,
+        sources,
+        uploaded_at: post.uploaded_at,
+    })
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct NeighboursQuery {
+    /// Space-separated search tags, to browse the results of a search.
+    tags: Option<String>,
+    /// Pool ID, to browse the posts of a pool instead of a search.
+    pool_id: Option<i32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct NeighboursResponse {
+    /// Post listed right before this one, if any.
+    previous_id: Option<i32>,
+    /// Post listed right after this one, if any.
+    next_id: Option<i32>,
+}
+
+/// Returns the posts listed right before and after a post, in a search or a pool.
+///
+/// Meant for browsing posts one at a time, e.g. to prefetch the next post or to bind arrow keys.
+#[utoipa::path(
+    get,
+    path = "/api/v1/posts/{post_id}/neighbours",
+    tag = "posts",
+    params(("post_id" = i32, Path, description = "Post ID"), NeighboursQuery),
+    responses(
+        (status = 200, description = "IDs of the neighbouring posts", body = NeighboursResponse),
+        (status = 404, description = "Post not found, or not in the pool")
+    )
+)]
+pub(crate) async fn post_neighbours(
+    State(AppState { db, .. }): State<AppState>,
+    auth_session: AuthSession,
+    Path(post_id): Path<i32>,
+    Query(query): Query<NeighboursQuery>,
+) -> Result<impl IntoResponse, SameyError> {
+    let user = auth_session.user.as_ref();
+    filter_posts_by_user(SameyPost::find_by_id(post_id), user)
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+
+    let (previous_id, next_id) = match query.pool_id {
+        Some(pool_id) => {
+            let pool = filter_pools_by_user(SameyPool::find_by_id(pool_id), user)
+                .one(&db)
+                .await?
+                .ok_or(SameyError::NotFound)?;
+            let posts = get_posts_in_pool(pool.id, user).all(&db).await?;
+            let index = posts
+                .iter()
+                .position(|post| post.id == post_id)
+                .ok_or(SameyError::NotFound)?;
+            (
+                index
+                    .checked_sub(1)
+                    .and_then(|index| posts.get(index))
+                    .map(|post| post.id),
+                posts.get(index + 1).map(|post| post.id),
+            )
+        }
+        None => {
+            get_search_neighbours(
+                &db,
+                &SearchQuery::parse(query.tags.as_deref().unwrap_or_default()),
+                user,
+                post_id,
+            )
+            .await?
+        }
+    };
+
+    Ok(Json(NeighboursResponse {
+        previous_id,
+        next_id,
+    }))
+}
+
+/// Header that lets clients safely retry an upload without creating duplicate posts.
+const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+/// How long a finished upload is remembered for its idempotency key.
+const IDEMPOTENCY_KEY_LIFETIME: TimeDelta = TimeDelta::hours(24);
+/// How long before an upload that never finished stops blocking retries with the same key.
+const PENDING_IDEMPOTENCY_KEY_LIFETIME: TimeDelta = TimeDelta::minutes(15);
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct UploadPostRequest {
+    /// Space-separated tags.
+    tags: String,
+    /// MIME type of the media, such as `image/png`.
+    content_type: String,
+    /// Original name of the media file.
+    file_name: Option<String>,
+    /// Base64-encoded media file.
+    media: String,
+    /// Saves the post as a draft, which stays hidden until it is published.
+    #[serde(default)]
+    draft: bool,
+}
+
+enum IdempotencyKeyClaim {
+    /// The key was already used for this post.
+    Existing(i32),
+    /// The key was claimed for a new upload, with this ID.
+    New(i32),
+}
+
+/// Claims an idempotency key for an upload, or returns the post that was already uploaded with it.
+async fn claim_idempotency_key(
+    db: &DatabaseConnection,
+    user_id: i32,
+    key: &str,
+) -> Result<IdempotencyKeyClaim, SameyError> {
+    let now = Utc::now().naive_utc();
+    SameyIdempotencyKey::delete_many()
+        .filter(
+            Condition::any()
+                .add(samey_idempotency_key::Column::CreatedAt.lt(now - IDEMPOTENCY_KEY_LIFETIME))
+                .add(
+                    Condition::all()
+                        .add(samey_idempotency_key::Column::PostId.is_null())
+                        .add(
+                            samey_idempotency_key::Column::CreatedAt
+                                .lt(now - PENDING_IDEMPOTENCY_KEY_LIFETIME),
+                        ),
+                ),
+        )
+        .exec(db)
+        .await?;
+
+    let in_progress =
+        || SameyError::Conflict("An upload with this idempotency key is still in progress".into());
+    if let Some(existing) = SameyIdempotencyKey::find()
+        .filter(samey_idempotency_key::Column::UserId.eq(user_id))
+        .filter(samey_idempotency_key::Column::Key.eq(key))
+        .one(db)
+        .await?
+    {
+        return existing
+            .post_id
+            .map(IdempotencyKeyClaim::Existing)
+            .ok_or_else(in_progress);
+    }
+
+    match (samey_idempotency_key::ActiveModel {
+        user_id: Set(user_id),
+        key: Set(key.to_owned()),
+        post_id: Set(None),
+        created_at: Set(now),
+        ..Default::default()
+    })
+    .insert(db)
+    .await
+    {
+        Ok(claim) => Ok(IdempotencyKeyClaim::New(claim.id)),
+        // Another request claimed the same key in the meantime
+        Err(err) if matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+            Err(in_progress())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Creates a post from either a multipart form or a JSON body.
+async fn create_post_from_request(
+    state: &AppState,
+    user: &User,
+    request: Request,
+) -> Result<i32, SameyError> {
+    let is_multipart = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("multipart/form-data"));
+    if is_multipart {
+        let multipart = Multipart::from_request(request, state)
+            .await
+            .map_err(|rejection| SameyError::BadRequest(rejection.body_text()))?;
+        return create_post_from_multipart(state, user, multipart)
+            .await?
+            .map_err(|invalid| invalid.errors.into());
+    }
+
+    let Json(body) = Json::<UploadPostRequest>::from_request(request, state)
+        .await
+        .map_err(|rejection| match rejection.status() {
+            StatusCode::PAYLOAD_TOO_LARGE => SameyError::PayloadTooLarge,
+            _ => SameyError::BadRequest(rejection.body_text()),
+        })?;
+    let media = BASE64
+        .decode(&body.media)
+        .map_err(|err| SameyError::BadRequest(format!("Invalid media encoding: {}", err)))?;
+    let tags = save_upload_tags(&state.db, user, &body.tags).await?;
+    let media = store_upload_media(
+        state,
+        user,
+        &body.content_type,
+        body.file_name,
+        async |file_path| Ok(tokio::fs::write(file_path, &media).await?),
+    )
+    .await?;
+    create_uploaded_post(state, user, tags, media, body.draft).await
+}
+
+/// Uploads a new post, from either a multipart form or a JSON body.
+///
+/// Multipart forms take the same `tags`, `media-file` and `draft` fields as the upload page. With an `Idempotency-Key`
+/// header, retrying a request returns the post from the first successful attempt instead of creating another.
+#[utoipa::path(
+    post,
+    path = "/api/v1/posts",
+    tag = "posts",
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Unique key to safely retry this upload with")
+    ),
+    request_body(content(
+        (UploadPostRequest = "application/json"),
+        (String = "multipart/form-data")
+    )),
+    responses(
+        (status = 201, description = "Uploaded post", body = PostResponse),
+        (status = 200, description = "Post previously uploaded with the same idempotency key", body = PostResponse),
+        (status = 400, description = "Invalid upload"),
+        (status = 403, description = "Not logged in"),
+        (status = 409, description = "An upload with the same idempotency key is in progress"),
+        (status = 413, description = "Upload is too large")
+    )
+)]
+pub(crate) async fn upload_post(
+    State(state): State<AppState>,
+    auth_session: AuthSession,
+    headers: HeaderMap,
+    request: Request,
+) -> Result<Response, SameyError> {
+    let user = auth_session.user.ok_or(SameyError::Forbidden)?;
+    let idempotency_key = headers
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .map(|key| {
+            key.to_str()
+                .ok()
+                .filter(|key| !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LENGTH)
+                .ok_or(SameyError::BadRequest("Invalid idempotency key".into()))
+        })
+        .transpose()?;
+
+    let claim_id = match idempotency_key {
+        Some(key) => match claim_idempotency_key(&state.db, user.id, key).await? {
+            IdempotencyKeyClaim::Existing(post_id) => {
+                return Ok(
+                    Json(get_post_response(&state.db, Some(&user), post_id).await?).into_response(),
+                );
+            }
+            IdempotencyKeyClaim::New(claim_id) => Some(claim_id),
+        },
+        None => None,
+    };
+
+    let result = create_post_from_request(&state, &user, request).await;
+    if let Some(claim_id) = claim_id {
+        match result {
+            Ok(post_id) => {
+                SameyIdempotencyKey::update(samey_idempotency_key::ActiveModel {
+                    id: Set(claim_id),
+                    post_id: Set(Some(post_id)),
+                    ..Default::default()
+                })
+                .exec(&state.db)
+                .await?;
+            }
+            Err(_) => {
+                // Let the client retry with the same key
+                SameyIdempotencyKey::delete_by_id(claim_id)
+                    .exec(&state.db)
+                    .await?;
+            }
+        }
+    }
+    let post_id = result?;
+
+    Ok((
+        StatusCode::CREATED,
+        [(LOCATION, format!("/post/{}", post_id))],
+        Json(get_post_response(&state.db, Some(&user), post_id).await?),
+    )
+        .into_response())
+}
+
+// Batch API
+
+/// Most posts that a single batch request can change.
+const MAX_BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct BatchTagsRequest {
+    post_ids: Vec<i32>,
+    /// Tags to add to every post.
+    #[serde(default)]
+    add: Vec<String>,
+    /// Tags to remove from every post.
+    #[serde(default)]
+    remove: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct BatchRatingRequest {
+    post_ids: Vec<i32>,
+    /// Rating code to set on every post.
+    rating: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct BatchDeleteRequest {
+    post_ids: Vec<i32>,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BatchItemStatus {
+    /// The post was changed.
+    Ok,
+    /// The post doesn't exist, or isn't visible to the user.
+    NotFound,
+    /// The user can't change the post.
+    Forbidden,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct BatchItemResult {
+    post_id: i32,
+    status: BatchItemStatus,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct BatchResponse {
+    /// One result per requested post, in request order.
+    results: Vec<BatchItemResult>,
+}
+
+/// Returns the requested post IDs without duplicates, in their original order.
+fn batch_post_ids(post_ids: Vec<i32>) -> Result<Vec<i32>, SameyError> {
+    let mut seen = HashSet::new();
+    let post_ids: Vec<i32> = post_ids.into_iter().filter(|id| seen.insert(*id)).collect();
+    if post_ids.len() > MAX_BATCH_SIZE {
+        return Err(SameyError::BadRequest(format!(
+            "At most {} posts can be changed at once",
+            MAX_BATCH_SIZE
+        )));
+    }
+    Ok(post_ids)
+}
+
+/// Loads the posts of a batch, and checks which ones the user may change.
+///
+/// With `allow_locked`, uploaders can also change their locked posts.
+async fn check_batch_posts<C: ConnectionTrait>(
+    db: &C,
+    user: &User,
+    post_ids: &[i32],
+    allow_locked: bool,
+) -> Result<Vec<(i32, BatchItemStatus, Option<samey_post::Model>)>, SameyError> {
+    let mut posts: HashMap<i32, samey_post::Model> = SameyPost::find()
+        .filter(samey_post::Column::Id.is_in(post_ids.iter().copied()))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|post| (post.id, post))
+        .collect();
+
+    Ok(post_ids
+        .iter()
+        .map(|post_id| match posts.remove(post_id) {
+            None => (*post_id, BatchItemStatus::NotFound, None),
+            Some(post) if user.is_admin => (*post_id, BatchItemStatus::Ok, Some(post)),
+            Some(post) if post.uploader_id != user.id => {
+                let status = if is_post_published(&post) {
+                    BatchItemStatus::Forbidden
+                } else {
+                    BatchItemStatus::NotFound
+                };
+                (*post_id, status, None)
+            }
+            Some(post) if post.is_locked && !allow_locked => {
+                (*post_id, BatchItemStatus::Forbidden, None)
+            }
+            Some(post) => (*post_id, BatchItemStatus::Ok, Some(post)),
+        })
+        .collect())
+}
+
+fn batch_response(
+    checked_posts: &[(i32, BatchItemStatus, Option<samey_post::Model>)],
+) -> Json<BatchResponse> {
+    Json(BatchResponse {
+        results: checked_posts
+            .iter()
+            .map(|(post_id, status, _)| BatchItemResult {
+                post_id: *post_id,
+                status: *status,
+            })
+            .collect(),
+    })
+}
+
+/// Adds and removes tags on several posts at once.
+///
+/// All changes are made in a single transaction. Posts that the user can't change are skipped.
+#[utoipa::path(
+    post,
+    path = "/api/v1/posts/batch/tags",
+    tag = "batch",
+    request_body = BatchTagsRequest,
+    responses(
+        (status = 200, description = "Result for each post", body = BatchResponse),
+        (status = 400, description = "Invalid or protected tags, or too many posts"),
+        (status = 403, description = "Not logged in")
+    )
+)]
+pub(crate) async fn batch_tags(
+    State(AppState { db, .. }): State<AppState>,
+    auth_session: AuthSession,
+    Json(body): Json<BatchTagsRequest>,
+) -> Result<impl IntoResponse, SameyError> {
+    let user = auth_session.user.ok_or(SameyError::Forbidden)?;
+    let post_ids = batch_post_ids(body.post_ids)?;
+
+    let added_tags: Vec<String> = body
+        .add
+        .iter()
+        .flat_map(|tags| tags.split_whitespace())
+        .unique_by(|tag| tag.to_lowercase())
+        .map(String::from)
+        .collect();
+    if let Some(tag) = added_tags.iter().find(|tag| {
+        tag.starts_with(NEGATIVE_PREFIX)
+            || tag.starts_with(RATING_PREFIX)
+            || tag.starts_with(MEDIA_TYPE_PREFIX)
+    }) {
+        return Err(SameyError::BadRequest(format!("Invalid tag {}", tag)));
+    }
+    let changed_tags: HashSet<String> = added_tags
+        .iter()
+        .map(|tag| tag.to_lowercase())
+        .chain(
+            body.remove
+                .iter()
+                .flat_map(|tags| tags.split_whitespace())
+                .map(str::to_lowercase),
+        )
+        .collect();
+    // Protected tags can't be added to or removed from posts by anyone other than admins
+    if !user.is_admin && !changed_tags.is_empty() {
+        if let Some(tag) = get_protected_tags()
+            .filter(samey_tag::Column::NormalizedName.is_in(changed_tags.iter().cloned()))
+            .one(&db)
+            .await?
+        {
+            return Err(SameyError::BadRequest(format!(
+                "Tag {} is protected",
+                tag.name
+            )));
+        }
+    }
+
+    let txn = db.begin().await?;
+    let checked_posts = check_batch_posts(&txn, &user, &post_ids, false).await?;
+    for (post_id, status, _) in checked_posts.iter() {
+        if *status != BatchItemStatus::Ok {
+            continue;
+        }
+        let tags = get_tags_for_post(*post_id)
+            .all(&txn)
+            .await?
+            .into_iter()
+            .filter(|tag| !changed_tags.contains(&tag.normalized_name))
+            .map(|tag| tag.name)
+            .chain(added_tags.iter().cloned());
+        replace_post_tags(&txn, *post_id, tags).await?;
+        bump_post_version(&txn, *post_id).await?;
+    }
+    txn.commit().await?;
+
+    tokio::spawn(async move {
+        if let Err(err) = clean_dangling_tags(&db).await {
+            println!("Error when cleaning dangling tags - {}", err);
+        }
+    });
+
+    Ok(batch_response(&checked_posts))
+}
+
+/// Sets the rating of several posts at once.
+///
+/// All changes are made in a single transaction. Posts that the user can't change are skipped.
+#[utoipa::path(
+    post,
+    path = "/api/v1/posts/batch/rating",
+    tag = "batch",
+    request_body = BatchRatingRequest,
+    responses(
+        (status = 200, description = "Result for each post", body = BatchResponse),
+        (status = 400, description = "Invalid rating, or too many posts"),
+        (status = 403, description = "Not logged in")
+    )
+)]
+pub(crate) async fn batch_rating(
+    State(AppState { db, app_config, .. }): State<AppState>,
+    auth_session: AuthSession,
+    Json(body): Json<BatchRatingRequest>,
+) -> Result<impl IntoResponse, SameyError> {
+    let user = auth_session.user.ok_or(SameyError::Forbidden)?;
+    let post_ids = batch_post_ids(body.post_ids)?;
+    if !app_config.read().await.is_valid_rating(&body.rating) {
+        return Err(SameyError::BadRequest("Invalid rating".into()));
+    }
+
+    let txn = db.begin().await?;
+    let checked_posts = check_batch_posts(&txn, &user, &post_ids, false).await?;
+    for (post_id, status, _) in checked_posts.iter() {
+        if *status != BatchItemStatus::Ok {
+            continue;
+        }
+        samey_post::ActiveModel {
+            id: Set(*post_id),
+            rating: Set(body.rating.clone()),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await?;
+        bump_post_version(&txn, *post_id).await?;
+    }
+    txn.commit().await?;
+
+    Ok(batch_response(&checked_posts))
+}
+
+/// Deletes several posts at once.
+///
+/// All posts are deleted in a single transaction. Posts that the user can't delete are skipped.
+#[utoipa::path(
+    post,
+    path = "/api/v1/posts/batch/delete",
+    tag = "batch",
+    request_body = BatchDeleteRequest,
+    responses(
+        (status = 200, description = "Result for each post", body = BatchResponse),
+        (status = 400, description = "Too many posts"),
+        (status = 403, description = "Not logged in")
+    )
+)]
+pub(crate) async fn batch_delete(
+    State(AppState {
+        db,
+        files_dir,
+        app_config,
+        ..
+    }): State<AppState>,
+    auth_session: AuthSession,
+    Json(body): Json<BatchDeleteRequest>,
+) -> Result<impl IntoResponse, SameyError> {
+    let user = auth_session.user.ok_or(SameyError::Forbidden)?;
+    let post_ids = batch_post_ids(body.post_ids)?;
+
+    let txn = db.begin().await?;
+    let checked_posts = check_batch_posts(&txn, &user, &post_ids, true).await?;
+    let deleted_ids: Vec<i32> = checked_posts
+        .iter()
+        .filter_map(|(_, _, post)| post.as_ref().map(|post| post.id))
+        .collect();
+    if !deleted_ids.is_empty() {
+        SameyPost::delete_many()
+            .filter(samey_post::Column::Id.is_in(deleted_ids))
+            .exec(&txn)
+            .await?;
+    }
+    txn.commit().await?;
+
+    let response = batch_response(&checked_posts);
+    let deleted_posts: Vec<samey_post::Model> = checked_posts
+        .into_iter()
+        .filter_map(|(_, _, post)| post)
+        .collect();
+    if let Some(federation) = Federation::from_config(&*app_config.read().await) {
+        for post in deleted_posts.iter().filter(|post| is_post_published(post)) {
+            federate_post(db.clone(), federation.clone(), post.id);
+        }
+    }
+
+    tokio::spawn(async move {
+        for post in deleted_posts {
+            remove_post_files(&files_dir, &post).await;
+        }
+        if let Err(err) = clean_dangling_tags(&db).await {
+            println!("Error when cleaning dangling tags - {}", err);
+        }
+    });
+
+    Ok(response)
+}
+
+// Pools API
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct PoolsQuery {
+    /// Page number, starting from 1.
+    page: Option<u32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct PoolSummary {
+    id: i32,
+    name: String,
+    is_public: bool,
+    updated_at: Option<NaiveDateTime>,
+}
+
+impl From<samey_pool::Model> for PoolSummary {
+    fn from(pool: samey_pool::Model) -> Self {
+        Self {
+            id: pool.id,
+            name: pool.name,
+            is_public: pool.is_public,
+            updated_at: pool.updated_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct PoolsResponse {
+    pools: Vec<PoolSummary>,
+    page: u32,
+    page_count: u64,
+}
+
+/// Lists pools visible to the current user.
+#[utoipa::path(
+    get,
+    path = "/api/v1/pools",
+    tag = "pools",
+    params(PoolsQuery),
+    responses((status = 200, description = "Page of pools", body = PoolsResponse))
+)]
+pub(crate) async fn pools(
+    State(AppState { db, .. }): State<AppState>,
+    auth_session: AuthSession,
+    Query(query): Query<PoolsQuery>,
+) -> Result<impl IntoResponse, SameyError> {
+    let page = query.page.unwrap_or(1).max(1);
+    let pagination = filter_pools_by_user(SameyPool::find(), auth_session.user.as_ref())
+        .order_by_asc(samey_pool::Column::Id)
+        .paginate(&db, API_POOLS_PER_PAGE);
+    let page_count = pagination.num_pages().await?;
+    let pools = pagination.fetch_page(page as u64 - 1).await?;
+
+    Ok(Json(PoolsResponse {
+        pools: pools.into_iter().map(PoolSummary::from).collect(),
+        page,
+        page_count,
+    }))
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct PoolPostEntry {
+    /// ID of the post's entry in this pool, used for sorting.
+    pool_post_id: i32,
+    post_id: i32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct PoolResponse {
+    id: i32,
+    name: String,
+    is_public: bool,
+    updated_at: Option<NaiveDateTime>,
+    /// Posts in pool order.
+    posts: Vec<PoolPostEntry>,
+}
+
+async fn get_pool_response<C: ConnectionTrait>(
+    db: &C,
+    user: Option<&User>,
+    pool: samey_pool::Model,
+) -> Result<PoolResponse, SameyError> {
+    let posts = get_posts_in_pool(pool.id, user).all(db).await?;
+
+    Ok(PoolResponse {
+        id: pool.id,
+        name: pool.name,
+        is_public: pool.is_public,
+        updated_at: pool.updated_at,
+        posts: posts
+            .into_iter()
+            .map(|post| PoolPostEntry {
+                pool_post_id: post.pool_post_id,
+                post_id: post.id,
+            })
+            .collect(),
+    })
+}
+
+/// Returns a single pool visible to the current user, with its posts in order.
+#[utoipa::path(
+    get,
+    path = "/api/v1/pools/{pool_id}",
+    tag = "pools",
+    params(("pool_id" = i32, Path, description = "Pool ID")),
+    responses(
+        (status = 200, description = "Pool details", body = PoolResponse),
+        (status = 404, description = "Pool not found")
+    )
+)]
+pub(crate) async fn pool(
+    State(AppState { db, .. }): State<AppState>,
+    auth_session: AuthSession,
+    Path(pool_id): Path<i32>,
+) -> Result<impl IntoResponse, SameyError> {
+    let pool = filter_pools_by_user(SameyPool::find_by_id(pool_id), auth_session.user.as_ref())
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+
+    Ok(Json(
+        get_pool_response(&db, auth_session.user.as_ref(), pool).await?,
+    ))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct SortPoolRequest {
+    /// Every `pool_post_id` from the pool response, in the new order.
+    pool_post_ids: Vec<i32>,
+}
+
+/// Replaces the order of all posts in a pool.
+#[utoipa::path(
+    put,
+    path = "/api/v1/pools/{pool_id}/order",
+    tag = "pools",
+    params(("pool_id" = i32, Path, description = "Pool ID")),
+    request_body = SortPoolRequest,
+    responses(
+        (status = 200, description = "Pool with its new order", body = PoolResponse),
+        (status = 400, description = "The new order doesn't list every post in the pool once"),
+        (status = 403, description = "Not allowed to edit this pool"),
+        (status = 404, description = "Pool not found")
+    )
+)]
+pub(crate) async fn sort_pool(
+    State(AppState { db, .. }): State<AppState>,
+    RequireOwnerOrAdmin(user, pool): RequireOwnerOrAdmin<SameyPool>,
+    Json(body): Json<SortPoolRequest>,
+) -> Result<impl IntoResponse, SameyError> {
+    let txn = db.begin().await?;
+    let visible_ids = get_posts_in_pool(pool.id, Some(&user))
+        .all(&txn)
+        .await?
+        .into_iter()
+        .map(|post| post.pool_post_id)
+        .collect::<HashSet<_>>();
+    let new_ids = body.pool_post_ids.iter().copied().collect::<HashSet<_>>();
+    if new_ids.len() != body.pool_post_ids.len() || new_ids != visible_ids {
+        return Err(SameyError::BadRequest(
+            "New order must list every post in the pool exactly once".into(),
+        ));
+    }
+
+    // Posts hidden from the user keep their place, and everything gets renumbered
+    let pool_posts = SameyPoolPost::find()
+        .filter(samey_pool_post::Column::PoolId.eq(pool.id))
+        .order_by_asc(samey_pool_post::Column::Position)
+        .all(&txn)
+        .await?;
+    let mut new_order = body.pool_post_ids.into_iter();
+    for (index, pool_post) in pool_posts.into_iter().enumerate() {
+        let id = if visible_ids.contains(&pool_post.id) {
+            new_order.next().unwrap_or(pool_post.id)
+        } else {
+            pool_post.id
+        };
+        SameyPoolPost::update(samey_pool_post::ActiveModel {
+            id: Set(id),
+            position: Set(index as f32 + 1.0),
+            ..Default::default()
+        })
+        .exec(&txn)
+        .await?;
+    }
+    txn.commit().await?;
+
+    Ok(Json(get_pool_response(&db, Some(&user), pool).await?))
+}
+
+// Tags API
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct TagsAutocompleteQuery {
+    /// Partial tag name to complete.
+    q: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct TagSuggestion {
+    name: String,
+    post_count: i64,
+    /// Prefix before the first `:` in the tag name, such as `artist` in `artist:someone`.
+    category: Option<String>,
+}
+
+impl From<TagCount> for TagSuggestion {
+    fn from(tag: TagCount) -> Self {
+        Self {
+            category: tag
+                .name
+                .split_once(':')
+                .map(|(category, _)| category.to_owned())
+                .filter(|category| !category.is_empty()),
+            name: tag.name,
+            post_count: tag.post_count,
+        }
+    }
+}
+
+/// Suggests tags to complete a partial tag name with, most relevant first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/tags/autocomplete",
+    tag = "tags",
+    params(TagsAutocompleteQuery),
+    responses((status = 200, description = "Matching tags", body = Vec<TagSuggestion>))
+)]
+pub(crate) async fn tags_autocomplete(
+    State(AppState { db, .. }): State<AppState>,
+    Query(query): Query<TagsAutocompleteQuery>,
+) -> Result<impl IntoResponse, SameyError> {
+    let input = query.q.trim();
+    let tags = if input.is_empty() {
+        vec![]
+    } else {
+        autocomplete_tags(&db, input).await?
+    };
+
+    Ok(Json(
+        tags.into_iter()
+            .map(TagSuggestion::from)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+// Changes API
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ChangesQuery {
+    /// Only return changes after this cursor, from `next_cursor` of a previous response.
+    since: Option<i32>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct Change {
+    /// Cursor of this change.
+    id: i32,
+    /// Either `post` or `tag`.
+    entity: String,
+    entity_id: i32,
+    /// One of `create`, `update` or `delete`.
+    action: String,
+    created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct ChangesResponse {
+    /// Changes in the order they happened.
+    changes: Vec<Change>,
+    /// Value of `since` for the next request.
+    next_cursor: i32,
+    /// Whether more changes are available right away.
+    has_more: bool,
+}
+
+/// Lists changes to posts and tags after a cursor, oldest first, for keeping mirrors in sync.
+///
+/// Posts that aren't visible to the current user are reported as deleted, so that mirrors drop posts that become
+/// private.
+#[utoipa::path(
+    get,
+    path = "/api/v1/changes",
+    tag = "changes",
+    params(ChangesQuery),
+    responses((status = 200, description = "Page of changes", body = ChangesResponse))
+)]
+pub(crate) async fn changes(
+    State(AppState { db, .. }): State<AppState>,
+    auth_session: AuthSession,
+    Query(query): Query<ChangesQuery>,
+) -> Result<impl IntoResponse, SameyError> {
+    let since = query.since.unwrap_or(0);
+    let mut changes = SameyChange::find()
+        .filter(samey_change::Column::Id.gt(since))
+        .order_by_asc(samey_change::Column::Id)
+        .limit(API_CHANGES_PER_PAGE + 1)
+        .all(&db)
+        .await?;
+    let has_more = changes.len() as u64 > API_CHANGES_PER_PAGE;
+    changes.truncate(API_CHANGES_PER_PAGE as usize);
+
+    let post_ids: HashSet<i32> = changes
+        .iter()
+        .filter(|change| change.entity == "post" && change.action != "delete")
+        .map(|change| change.entity_id)
+        .collect();
+    let visible_post_ids: HashSet<i32> = filter_posts_by_user(
+        SameyPost::find().filter(samey_post::Column::Id.is_in(post_ids)),
+        auth_session.user.as_ref(),
+    )
+    .select_only()
+    .column(samey_post::Column::Id)
+    .into_tuple::<i32>()
+    .all(&db)
+    .await?
+    .into_iter()
+    .collect();
+
+    Ok(Json(ChangesResponse {
+        next_cursor: changes.last().map(|change| change.id).unwrap_or(since),
+        has_more,
+        changes: changes
+            .into_iter()
+            .map(|change| Change {
+                action: if change.entity == "post" && !visible_post_ids.contains(&change.entity_id)
+                {
+                    "delete".into()
+                } else {
+                    change.action
+                },
+                id: change.id,
+                entity: change.entity,
+                entity_id: change.entity_id,
+                created_at: change.created_at,
+            })
+            .collect(),
+    }))
+}
@@ -0,0 +1,135 @@
+use std::path::Path;
+
+use chrono::Utc;
+use rand::Rng;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter,
+};
+
+use crate::{
+    AppState, SameyError,
+    antivirus::{ScanResult, scan_file},
+    auth::User,
+    entities::{prelude::SameyPostAttachment, samey_post_attachment},
+};
+
+/// Directory in the files directory where attachments are stored.
+///
+/// It isn't served under `/files`, so that attachments can only be downloaded by those who can see their post.
+///
+/// These are extra files added by users, unlike the source archive that some media is converted from, which is kept
+/// next to the media in the post's `attachment` column. That archive doesn't count towards the attachment limits,
+/// and can't be removed without the media.
+pub(crate) const ATTACHMENTS_DIRECTORY: &str = "attachments";
+/// Most attachments that a post may have.
+pub(crate) const MAX_POST_ATTACHMENTS: u64 = 10;
+/// Longest file name that is kept for attachments, in characters.
+const MAX_ATTACHMENT_NAME_LENGTH: usize = 200;
+
+/// Returns the directory holding the attachments of a post, relative to the files directory.
+fn post_attachments_directory(post_id: i32) -> String {
+    format!("{}/{}", ATTACHMENTS_DIRECTORY, post_id)
+}
+
+/// Cleans up a file name from an upload, so that it can be shown and sent back in a `Content-Disposition` header.
+fn sanitize_file_name(original_filename: &str) -> String {
+    let file_name = original_filename
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_control() || c == '"' { '_' } else { c })
+        .take(MAX_ATTACHMENT_NAME_LENGTH)
+        .collect::<String>();
+    match file_name.trim() {
+        "" => "attachment".into(),
+        file_name => file_name.into(),
+    }
+}
+
+/// Stores a file written by `write` as an attachment of a post, scanning it for viruses if enabled.
+///
+/// `write` returns how many bytes it wrote, and must fail once the file grows past the size limit. Nothing is left
+/// behind in the files directory if any step fails.
+pub(crate) async fn store_attachment(
+    state: &AppState,
+    user: &User,
+    post_id: i32,
+    original_filename: &str,
+    write: impl AsyncFnOnce(&Path) -> Result<u64, SameyError>,
+) -> Result<samey_post_attachment::Model, SameyError> {
+    let attachment_count = SameyPostAttachment::find()
+        .filter(samey_post_attachment::Column::PostId.eq(post_id))
+        .count(&state.db)
+        .await?;
+    if attachment_count >= MAX_POST_ATTACHMENTS {
+        return Err(SameyError::BadRequest(format!(
+            "Posts can't have more than {} attachments",
+            MAX_POST_ATTACHMENTS
+        )));
+    }
+    let clamav_address = {
+        let app_config = state.app_config.read().await;
+        app_config
+            .clamav_enabled
+            .then(|| app_config.clamav_address.clone())
+    };
+
+    let original_filename = sanitize_file_name(original_filename);
+    let mut file_name: String = rand::rng()
+        .sample_iter(rand::distr::Alphanumeric)
+        .take(16)
+        .map(char::from)
+        .collect();
+    // Keep the extension, so that the file is recognizable on disk
+    if let Some((_, extension)) = original_filename.rsplit_once('.') {
+        if !extension.is_empty()
+            && extension.len() <= 10
+            && extension.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            file_name.push('.');
+            file_name.push_str(&extension.to_lowercase());
+        }
+    }
+    let file_name = format!("{}/{}", post_attachments_directory(post_id), file_name);
+    let file_path = state.files_dir.join(&file_name);
+
+    let result = async {
+        if let Some(directory) = file_path.parent() {
+            tokio::fs::create_dir_all(directory).await?;
+        }
+        let size = write(&file_path).await?;
+        if let Some(clamav_address) = clamav_address.as_deref() {
+            if let ScanResult::Infected(signature) = scan_file(clamav_address, &file_path).await? {
+                println!(
+                    "Rejected attachment by user {} - Virus scan found {}",
+                    user.id, signature
+                );
+                return Err(SameyError::BadRequest(format!(
+                    "File rejected by virus scan: {}",
+                    signature
+                )));
+            }
+        }
+        Ok(samey_post_attachment::ActiveModel {
+            post_id: Set(post_id),
+            file_name: Set(file_name),
+            original_filename: Set(original_filename),
+            size: Set(size.try_into()?),
+            uploaded_at: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        }
+        .insert(&state.db)
+        .await?)
+    }
+    .await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&file_path).await;
+    }
+    result
+}
+
+/// Removes every attachment of a deleted post, ignoring missing files.
+pub(crate) async fn remove_attachment_files(files_dir: &Path, post_id: i32) {
+    let _ = tokio::fs::remove_dir_all(files_dir.join(post_attachments_directory(post_id))).await;
+}
@@ -0,0 +1,40 @@
+use chrono::Utc;
+use sea_orm::{ActiveValue::Set, ConnectionTrait, EntityTrait};
+
+use crate::{
+    SameyError,
+    entities::{prelude::SameyAuditLog, samey_audit_log},
+};
+
+#[derive(strum::Display, Debug)]
+pub(crate) enum AuditAction {
+    #[strum(serialize = "reset_admin")]
+    ResetAdmin,
+    #[strum(serialize = "take_down_post")]
+    TakeDownPost,
+    #[strum(serialize = "reject_takedown")]
+    RejectTakedown,
+    #[strum(serialize = "restore_post")]
+    RestorePost,
+}
+
+/// Records a sensitive action, such as restoring admin access or taking down a post.
+///
+/// The actor is `None` for actions taken from the command line.
+pub(crate) async fn record_audit_log(
+    db: &impl ConnectionTrait,
+    actor_id: Option<i32>,
+    action: AuditAction,
+    details: String,
+) -> Result<(), SameyError> {
+    SameyAuditLog::insert(samey_audit_log::ActiveModel {
+        actor_id: Set(actor_id),
+        action: Set(action.to_string()),
+        details: Set(details),
+        created_at: Set(Utc::now().naive_utc()),
+        ..Default::default()
+    })
+    .exec(db)
+    .await?;
+    Ok(())
+}
@@ -1,26 +1,57 @@
 use std::fmt::Debug;
 
 use axum_login::{AuthUser, AuthnBackend, UserId};
-use password_auth::verify_password;
+use chrono::Utc;
+use password_auth::{generate_hash, verify_password};
+use rand::Rng;
 use samey_migration::Expr;
 use sea_orm::{ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use time::OffsetDateTime;
 use tower_sessions::{ExpiredDeletion, SessionStore, session::Record, session_store};
 
 use crate::{
     SameyError,
+    config::{AuthConfig, ProcessConfigHandle},
     entities::{
         prelude::{SameySession, SameyUser},
         samey_session, samey_user,
     },
+    ldap::authenticate_ldap,
 };
 
+/// Length of the random password of users from LDAP or a reverse proxy, which is never shown to anyone.
+const EXTERNAL_USER_PASSWORD_LENGTH: usize = 64;
+
 #[derive(Debug, Clone)]
 pub(crate) struct User {
     pub(crate) id: i32,
     pub(crate) username: String,
     pub(crate) is_admin: bool,
+    pub(crate) timezone: Option<String>,
+    pub(crate) locale: Option<String>,
+    pub(crate) posts_per_page: Option<i32>,
+    pub(crate) thumbnail_density: Option<String>,
+    pub(crate) autoplay_videos: bool,
+    /// Avatar image, relative to the files directory.
+    pub(crate) avatar: Option<String>,
+}
+
+impl From<samey_user::Model> for User {
+    fn from(user: samey_user::Model) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            is_admin: user.is_admin,
+            timezone: user.timezone,
+            locale: user.locale,
+            posts_per_page: user.posts_per_page,
+            thumbnail_density: user.thumbnail_density,
+            autoplay_videos: user.autoplay_videos,
+            avatar: user.avatar,
+        }
+    }
 }
 
 impl AuthUser for User {
@@ -53,11 +84,60 @@ impl Debug for Credentials {
 #[derive(Debug, Clone)]
 pub(crate) struct Backend {
     db: DatabaseConnection,
+    process_config: ProcessConfigHandle,
 }
 
 impl Backend {
-    pub(crate) fn new(db: DatabaseConnection) -> Self {
-        Self { db }
+    pub(crate) fn new(db: DatabaseConnection, process_config: ProcessConfigHandle) -> Self {
+        Self { db, process_config }
+    }
+
+    /// Returns the local account of a user from LDAP or a reverse proxy, creating it on their first login.
+    ///
+    /// Their admin status is replaced with `is_admin` if given. Disabled users are rejected.
+    pub(crate) async fn external_user(
+        &self,
+        username: &str,
+        is_admin: Option<bool>,
+    ) -> Result<Option<User>, SameyError> {
+        let now = Utc::now().naive_utc();
+        let user = SameyUser::find()
+            .filter(samey_user::Column::Username.eq(username))
+            .one(&self.db)
+            .await?;
+        let user = match user {
+            Some(user) if user.is_disabled => return Ok(None),
+            Some(user) => {
+                let mut update = samey_user::ActiveModel {
+                    id: Set(user.id),
+                    last_login_at: Set(Some(now)),
+                    ..Default::default()
+                };
+                if let Some(is_admin) = is_admin {
+                    update.is_admin = Set(is_admin);
+                }
+                SameyUser::update(update).exec(&self.db).await?
+            }
+            None => {
+                // The password only has to be impossible to guess, since they never log in with it
+                let password: String = rand::rng()
+                    .sample_iter(rand::distr::Alphanumeric)
+                    .take(EXTERNAL_USER_PASSWORD_LENGTH)
+                    .map(char::from)
+                    .collect();
+                SameyUser::insert(samey_user::ActiveModel {
+                    username: Set(username.into()),
+                    password: Set(generate_hash(password)),
+                    is_admin: Set(is_admin.unwrap_or(false)),
+                    created_at: Set(Some(now)),
+                    last_login_at: Set(Some(now)),
+                    ..Default::default()
+                })
+                .exec_with_returning(&self.db)
+                .await?
+            }
+        };
+        Ok(Some(user.into()))
     }
 }
 
@@ -71,35 +151,90 @@ impl AuthnBackend for Backend {
         &self,
         credentials: Self::Credentials,
     ) -> Result<Option<Self::User>, Self::Error> {
+        if let AuthConfig::Ldap(ldap) = self.process_config.get().await.auth {
+            // Local passwords are still checked if the directory rejects the user or can't be reached
+            match authenticate_ldap(&ldap, &credentials.username, &credentials.password).await {
+                Ok(Some(ldap_user)) => {
+                    return self
+                        .external_user(&credentials.username, ldap_user.is_admin)
+                        .await;
+                }
+                Ok(None) => (),
+                Err(err) => println!("Error when authenticating with LDAP - {}", err),
+            }
+        }
+
         let user = SameyUser::find()
             .filter(samey_user::Column::Username.eq(credentials.username))
+            .filter(samey_user::Column::IsDisabled.eq(false))
             .one(&self.db)
             .await?;
+        let Some(user) =
+            user.filter(|user| verify_password(credentials.password, &user.password).is_ok())
+        else {
+            return Ok(None);
+        };
 
-        Ok(user.and_then(|user| {
-            verify_password(credentials.password, &user.password)
-                .ok()
-                .map(|_| User {
-                    id: user.id,
-                    username: user.username,
-                    is_admin: user.is_admin,
-                })
-        }))
+        SameyUser::update(samey_user::ActiveModel {
+            id: Set(user.id),
+            last_login_at: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        })
+        .exec(&self.db)
+        .await?;
+
+        Ok(Some(user.into()))
     }
 
     async fn get_user(&self, user_id: &UserId<Self>) -> Result<Option<Self::User>, Self::Error> {
-        let user = SameyUser::find_by_id(*user_id).one(&self.db).await?;
+        // Disabled users are logged out of their existing sessions
+        let user = SameyUser::find_by_id(*user_id)
+            .filter(samey_user::Column::IsDisabled.eq(false))
+            .one(&self.db)
+            .await?;
 
-        Ok(user.map(|user| User {
-            id: user.id,
-            username: user.username,
-            is_admin: user.is_admin,
-        }))
+        Ok(user.map(User::from))
     }
 }
 
 pub(crate) type AuthSession = axum_login::AuthSession<Backend>;
 
+/// Session key with the ID of the logged in user, copied to [`samey_session::Column::UserId`] so that all of a
+/// user's sessions can be revoked at once.
+pub(crate) const USER_ID_SESSION_KEY: &str = "user_id";
+/// Session key with a hash of the user agent that logged in.
+pub(crate) const USER_AGENT_SESSION_KEY: &str = "user_agent_hash";
+
+/// Hashes a user agent, so that sessions don't store it in plain text.
+pub(crate) fn hash_user_agent(user_agent: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(user_agent))
+}
+
+/// Hashes a personal RSS token, which is only stored as its hash.
+pub(crate) fn hash_rss_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token))
+}
+
+/// Logs a user out of all of their sessions, such as after their password changes or they get disabled.
+pub(crate) async fn revoke_user_sessions(
+    db: &DatabaseConnection,
+    user_id: i32,
+) -> Result<(), SameyError> {
+    SameySession::delete_many()
+        .filter(samey_session::Column::UserId.eq(user_id))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+fn session_user_id(record: &Record) -> Option<i32> {
+    record
+        .data
+        .get(USER_ID_SESSION_KEY)
+        .and_then(|user_id| user_id.as_i64())
+        .and_then(|user_id| user_id.try_into().ok())
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct SessionStorage {
     db: DatabaseConnection,
@@ -124,6 +259,7 @@ impl SessionStore for SessionStorage {
                     .collect(),
             )),
             expiry_date: Set(record.expiry_date.unix_timestamp()),
+            user_id: Set(session_user_id(record)),
             ..Default::default()
         })
         .exec(&self.db)
@@ -151,6 +287,7 @@ impl SessionStore for SessionStorage {
                     .collect(),
             )),
             expiry_date: Set(record.expiry_date.unix_timestamp()),
+            user_id: Set(session_user_id(record)),
             ..Default::default()
         })
         .exec(&self.db)
@@ -224,6 +361,9 @@ impl ExpiredDeletion for SessionStorage {
 
 impl From<axum_login::Error<Backend>> for SameyError {
     fn from(value: axum_login::Error<Backend>) -> Self {
-        value.into()
+        match value {
+            axum_login::Error::Session(err) => SameyError::Authentication(err.to_string()),
+            axum_login::Error::Backend(err) => err,
+        }
     }
 }
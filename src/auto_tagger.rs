@@ -0,0 +1,52 @@
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use reqwest::multipart::{Form, Part};
+
+use crate::SameyError;
+
+/// How long the tagging service may take to reply.
+const AUTO_TAGGER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Asks a DeepDanbooru/WD-tagger style service for tags for an image.
+///
+/// The image is sent as the `file` field of a multipart form, and the service must reply with a JSON object
+/// mapping tag names to their confidence between 0 and 1. Returns tags with at least `threshold` confidence,
+/// most confident first.
+pub(crate) async fn suggest_tags(
+    url: &str,
+    image_path: &Path,
+    threshold: f64,
+) -> Result<Vec<String>, SameyError> {
+    let image = tokio::fs::read(image_path).await?;
+    let file_name = image_path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let form = Form::new().part("file", Part::bytes(image).file_name(file_name));
+    let confidences: HashMap<String, f64> = reqwest::Client::new()
+        .post(url)
+        .multipart(form)
+        .timeout(AUTO_TAGGER_TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| SameyError::Other(format!("Auto-tagger request failed: {}", err)))?
+        .json()
+        .await
+        .map_err(|err| SameyError::Other(format!("Invalid auto-tagger response: {}", err)))?;
+
+    let mut tags: Vec<(String, f64)> = confidences
+        .into_iter()
+        .filter(|(_, confidence)| *confidence >= threshold)
+        // Tags are separated by whitespace
+        .map(|(tag, confidence)| {
+            (
+                tag.split_whitespace().collect::<Vec<_>>().join("_"),
+                confidence,
+            )
+        })
+        .filter(|(tag, _)| !tag.is_empty())
+        .collect();
+    tags.sort_by(|a, b| b.1.total_cmp(&a.1));
+    Ok(tags.into_iter().map(|(tag, _)| tag).collect())
+}
@@ -0,0 +1,113 @@
+use std::{sync::Arc, time::Duration};
+
+use moka::future::Cache;
+use sea_orm::DatabaseConnection;
+
+use crate::{
+    SameyError,
+    entities::samey_tag,
+    query::{
+        PostOverview, PostsCursor, PostsKeysetPage, TagCount, get_tags_for_post, get_top_tags,
+        search_posts_keyset, sort_post_overview_tags,
+    },
+    search::SearchQuery,
+};
+
+/// Upper bound on how long cached objects are reused, in case a change doesn't go through
+/// [`ObjectCache::invalidate_all`].
+const OBJECT_CACHE_DURATION: Duration = Duration::from_secs(5 * 60);
+const MAX_CACHED_POST_TAGS: u64 = 10_000;
+
+/// Frequently read objects that are expensive to query, shared by all visitors.
+///
+/// Application settings don't need to be here, since [`AppConfig`](crate::config::AppConfig) is always kept in
+/// memory.
+#[derive(Clone)]
+pub(crate) struct ObjectCache {
+    /// Public posts for the front page with sorted tags, by amount of posts.
+    recent_posts: Cache<u64, Arc<Vec<PostOverview>>>,
+    /// Most used tags, by amount of tags.
+    top_tags: Cache<u64, Arc<Vec<TagCount>>>,
+    /// Tags of each post, sorted by name.
+    post_tags: Cache<i32, Arc<Vec<samey_tag::Model>>>,
+}
+
+impl Default for ObjectCache {
+    fn default() -> Self {
+        Self {
+            recent_posts: Cache::builder()
+                .max_capacity(16)
+                .time_to_live(OBJECT_CACHE_DURATION)
+                .build(),
+            top_tags: Cache::builder()
+                .max_capacity(16)
+                .time_to_live(OBJECT_CACHE_DURATION)
+                .build(),
+            post_tags: Cache::builder()
+                .max_capacity(MAX_CACHED_POST_TAGS)
+                .time_to_live(OBJECT_CACHE_DURATION)
+                .build(),
+        }
+    }
+}
+
+impl ObjectCache {
+    /// Returns the latest public posts, without filtering for the current user.
+    pub(crate) async fn recent_posts(
+        &self,
+        db: &DatabaseConnection,
+        limit: u64,
+    ) -> Result<Arc<Vec<PostOverview>>, SameyError> {
+        self.recent_posts
+            .try_get_with(limit, async {
+                let PostsKeysetPage { posts, .. } = search_posts_keyset(
+                    db,
+                    &SearchQuery::default(),
+                    None,
+                    PostsCursor::Page(0),
+                    limit,
+                )
+                .await?;
+                Ok(Arc::new(sort_post_overview_tags(posts)))
+            })
+            .await
+            .map_err(cache_error)
+    }
+
+    pub(crate) async fn top_tags(
+        &self,
+        db: &DatabaseConnection,
+        limit: u64,
+    ) -> Result<Arc<Vec<TagCount>>, SameyError> {
+        self.top_tags
+            .try_get_with(limit, async {
+                Ok(Arc::new(get_top_tags(limit).all(db).await?))
+            })
+            .await
+            .map_err(cache_error)
+    }
+
+    pub(crate) async fn post_tags(
+        &self,
+        db: &DatabaseConnection,
+        post_id: i32,
+    ) -> Result<Arc<Vec<samey_tag::Model>>, SameyError> {
+        self.post_tags
+            .try_get_with(post_id, async {
+                Ok(Arc::new(get_tags_for_post(post_id).all(db).await?))
+            })
+            .await
+            .map_err(cache_error)
+    }
+
+    /// Drops every cached object, so that the next reads see any changes.
+    pub(crate) fn invalidate_all(&self) {
+        self.recent_posts.invalidate_all();
+        self.top_tags.invalidate_all();
+        self.post_tags.invalidate_all();
+    }
+}
+
+fn cache_error(error: Arc<SameyError>) -> SameyError {
+    Arc::try_unwrap(error).unwrap_or_else(|error| SameyError::Other(error.to_string()))
+}
@@ -0,0 +1,239 @@
+use std::time::Duration;
+
+use moka::future::Cache;
+use rand::Rng;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use strum::IntoEnumIterator;
+
+use crate::{SameyError, config::AppConfig};
+
+const HCAPTCHA_VERIFY_URL: &str = "https://api.hcaptcha.com/siteverify";
+const TURNSTILE_VERIFY_URL: &str = "https://challenges.cloudflare.com/turnstile/v0/siteverify";
+const CAPTCHA_VERIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Leading zero bits that the hash of a proof-of-work solution needs, which takes browsers a second or two.
+pub(crate) const PROOF_OF_WORK_DIFFICULTY: u32 = 16;
+const PROOF_OF_WORK_CHALLENGE_LENGTH: usize = 32;
+/// How long a proof-of-work challenge can be solved for after the form is shown.
+const PROOF_OF_WORK_CHALLENGE_DURATION: Duration = Duration::from_secs(10 * 60);
+const MAX_PROOF_OF_WORK_CHALLENGES: u64 = 100_000;
+/// How long failed logins are counted for after the last one.
+const FAILED_LOGINS_DURATION: Duration = Duration::from_secs(15 * 60);
+const MAX_FAILED_LOGINS: u64 = 100_000;
+
+/// Service that checks that a form was sent by a person.
+#[derive(strum::EnumIter, strum::Display, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CaptchaProvider {
+    #[strum(serialize = "hcaptcha")]
+    HCaptcha,
+    #[strum(serialize = "turnstile")]
+    Turnstile,
+    /// Built-in challenge that the browser solves by finding a hash, without any third-party service.
+    #[strum(serialize = "proof_of_work")]
+    ProofOfWork,
+}
+
+impl CaptchaProvider {
+    pub(crate) fn parse(provider: &str) -> Option<Self> {
+        Self::iter().find(|p| p.to_string() == provider)
+    }
+
+    /// Whether the provider is an external service, which needs a site key and a secret key.
+    pub(crate) fn needs_keys(self) -> bool {
+        self != Self::ProofOfWork
+    }
+}
+
+/// CAPTCHA fields of a form, which depend on the provider.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct CaptchaForm {
+    #[serde(rename = "h-captcha-response")]
+    hcaptcha_response: Option<String>,
+    #[serde(rename = "cf-turnstile-response")]
+    turnstile_response: Option<String>,
+    captcha_challenge: Option<String>,
+    captcha_nonce: Option<String>,
+}
+
+/// What is needed to show the CAPTCHA in a form.
+pub(crate) struct CaptchaWidget {
+    pub(crate) provider: CaptchaProvider,
+    pub(crate) site_key: String,
+    /// Proof-of-work challenge to solve, which can only be used once.
+    pub(crate) challenge: String,
+    pub(crate) difficulty: u32,
+}
+
+/// CAPTCHA state that is kept in memory, and is lost on restarts.
+#[derive(Clone)]
+pub(crate) struct CaptchaState {
+    /// Proof-of-work challenges that were shown and not yet solved.
+    challenges: Cache<String, ()>,
+    /// Failed logins by lowercase username.
+    failed_logins: Cache<String, u64>,
+}
+
+impl Default for CaptchaState {
+    fn default() -> Self {
+        Self {
+            challenges: Cache::builder()
+                .max_capacity(MAX_PROOF_OF_WORK_CHALLENGES)
+                .time_to_live(PROOF_OF_WORK_CHALLENGE_DURATION)
+                .build(),
+            failed_logins: Cache::builder()
+                .max_capacity(MAX_FAILED_LOGINS)
+                .time_to_live(FAILED_LOGINS_DURATION)
+                .build(),
+        }
+    }
+}
+
+impl CaptchaState {
+    pub(crate) async fn failed_logins(&self, username: &str) -> u64 {
+        self.failed_logins
+            .get(&username.to_lowercase())
+            .await
+            .unwrap_or(0)
+    }
+
+    pub(crate) async fn record_failed_login(&self, username: &str) {
+        let username = username.to_lowercase();
+        let failed_logins = self.failed_logins.get(&username).await.unwrap_or(0);
+        self.failed_logins.insert(username, failed_logins + 1).await;
+    }
+
+    pub(crate) async fn clear_failed_logins(&self, username: &str) {
+        self.failed_logins
+            .invalidate(&username.to_lowercase())
+            .await;
+    }
+}
+
+/// CAPTCHA settings of the instance.
+#[derive(Clone)]
+pub(crate) struct CaptchaSettings {
+    provider: CaptchaProvider,
+    site_key: String,
+    secret_key: String,
+    login_attempts: u64,
+}
+
+impl CaptchaSettings {
+    /// Returns the settings if a CAPTCHA is enabled and fully configured.
+    pub(crate) fn from_config(app_config: &AppConfig) -> Option<Self> {
+        let provider = CaptchaProvider::parse(&app_config.captcha_provider)?;
+        (!provider.needs_keys()
+            || (!app_config.captcha_site_key.is_empty()
+                && !app_config.captcha_secret_key.is_empty()))
+        .then(|| Self {
+            provider,
+            site_key: app_config.captcha_site_key.clone(),
+            secret_key: app_config.captcha_secret_key.clone(),
+            login_attempts: app_config.captcha_login_attempts,
+        })
+    }
+
+    /// Whether every login needs the CAPTCHA, even before any failed attempts.
+    pub(crate) fn required_for_every_login(&self) -> bool {
+        self.login_attempts == 0
+    }
+
+    /// Whether logging in as this username needs the CAPTCHA, after too many failed attempts.
+    pub(crate) async fn required_for_login(&self, state: &CaptchaState, username: &str) -> bool {
+        state.failed_logins(username).await >= self.login_attempts
+    }
+
+    /// Returns the CAPTCHA to show in a form, issuing a new proof-of-work challenge if needed.
+    pub(crate) async fn widget(&self, state: &CaptchaState) -> CaptchaWidget {
+        let challenge = if self.provider == CaptchaProvider::ProofOfWork {
+            let challenge: String = rand::rng()
+                .sample_iter(rand::distr::Alphanumeric)
+                .take(PROOF_OF_WORK_CHALLENGE_LENGTH)
+                .map(char::from)
+                .collect();
+            state.challenges.insert(challenge.clone(), ()).await;
+            challenge
+        } else {
+            String::new()
+        };
+        CaptchaWidget {
+            provider: self.provider,
+            site_key: self.site_key.clone(),
+            challenge,
+            difficulty: PROOF_OF_WORK_DIFFICULTY,
+        }
+    }
+
+    /// Checks the CAPTCHA of a form, returning whether it was solved.
+    ///
+    /// Errors are only returned when the provider can't be reached.
+    pub(crate) async fn verify(
+        &self,
+        state: &CaptchaState,
+        form: &CaptchaForm,
+    ) -> Result<bool, SameyError> {
+        match self.provider {
+            CaptchaProvider::HCaptcha => {
+                self.verify_with_service(HCAPTCHA_VERIFY_URL, form.hcaptcha_response.as_deref())
+                    .await
+            }
+            CaptchaProvider::Turnstile => {
+                self.verify_with_service(TURNSTILE_VERIFY_URL, form.turnstile_response.as_deref())
+                    .await
+            }
+            CaptchaProvider::ProofOfWork => {
+                let (Some(challenge), Some(nonce)) = (&form.captcha_challenge, &form.captcha_nonce)
+                else {
+                    return Ok(false);
+                };
+                // Challenges are removed once used, so that a solution can't be replayed
+                if state.challenges.remove(challenge).await.is_none() {
+                    return Ok(false);
+                }
+                Ok(is_proof_of_work_solved(challenge, nonce))
+            }
+        }
+    }
+
+    /// Sends the response token to hCaptcha or Turnstile, which share the same verification API.
+    async fn verify_with_service(
+        &self,
+        url: &str,
+        response: Option<&str>,
+    ) -> Result<bool, SameyError> {
+        #[derive(Deserialize)]
+        struct VerifyResponse {
+            success: bool,
+        }
+
+        let Some(response) = response.filter(|response| !response.is_empty()) else {
+            return Ok(false);
+        };
+        let verification: VerifyResponse = reqwest::Client::new()
+            .post(url)
+            .form(&[("secret", self.secret_key.as_str()), ("response", response)])
+            .timeout(CAPTCHA_VERIFY_TIMEOUT)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| SameyError::Other(format!("CAPTCHA verification failed: {}", err)))?
+            .json()
+            .await
+            .map_err(|err| SameyError::Other(format!("Invalid CAPTCHA response: {}", err)))?;
+        Ok(verification.success)
+    }
+}
+
+/// Whether the SHA-256 hash of `{challenge}:{nonce}` starts with enough zero bits.
+fn is_proof_of_work_solved(challenge: &str, nonce: &str) -> bool {
+    let hash = Sha256::digest(format!("{}:{}", challenge, nonce));
+    let mut zero_bits = 0;
+    for byte in hash {
+        zero_bits += byte.leading_zeros();
+        if byte != 0 {
+            break;
+        }
+    }
+    zero_bits >= PROOF_OF_WORK_DIFFICULTY
+}
@@ -0,0 +1,930 @@
+use std::{
+    collections::HashSet,
+    num::NonZero,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+use chrono::{NaiveDateTime, Utc};
+use image::ImageFormat;
+use rand::Rng;
+use samey_migration::{Expr, OnConflict};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, Condition, ConnectionTrait,
+    DatabaseConnection, EntityTrait, QueryFilter, QuerySelect, TransactionTrait,
+};
+use tokio::sync::RwLock;
+
+use crate::{
+    AppState, SameyError,
+    activitypub::{Federation, federate_post},
+    antivirus::{ScanResult, scan_file},
+    attachments::remove_attachment_files,
+    auth::User,
+    config::AppConfig,
+    crosspost::queue_crossposts,
+    disk_usage::stored_size,
+    entities::{
+        prelude::{SameyPoolPost, SameyPost, SameyTag, SameyTagPost},
+        samey_pool, samey_pool_post, samey_post, samey_tag, samey_tag_post,
+    },
+    preferences::ThumbnailDensity,
+    query::{clean_dangling_tags, get_protected_tags, is_post_published},
+    streaming::{STREAM_DIRECTORY_PREFIX, remove_stream_files, spawn_stream_generation},
+    tags::{MEDIA_TYPE_PREFIX, NEGATIVE_PREFIX, RATING_PREFIX},
+    thumbnailer::{DefaultThumbnailer, MediaDimensions, Thumbnailer},
+    ugoira::convert_ugoira,
+    video::HLS_PLAYLIST_NAME,
+};
+
+/// Maximum size of small thumbnails, matching how compact grids display them.
+const SMALL_THUMBNAIL_DIMENSION: u32 = 128;
+
+/// Thumbnail sizes stored next to the regular thumbnail, for compact grids and high-DPI screens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ThumbnailVariant {
+    /// Fits the compact thumbnail density.
+    Small,
+    /// Twice the regular size.
+    Double,
+}
+
+impl ThumbnailVariant {
+    pub(crate) const ALL: [ThumbnailVariant; 2] =
+        [ThumbnailVariant::Small, ThumbnailVariant::Double];
+
+    /// Returns the maximum size of this variant, relative to the size of regular thumbnails.
+    pub(crate) fn max_dimension(self, thumbnail_dimension: u32) -> u32 {
+        match self {
+            ThumbnailVariant::Small => SMALL_THUMBNAIL_DIMENSION.min(thumbnail_dimension),
+            ThumbnailVariant::Double => thumbnail_dimension * 2,
+        }
+    }
+
+    /// Returns the file name of this variant of a thumbnail.
+    pub(crate) fn file_name(self, thumbnail: &str) -> String {
+        match self {
+            ThumbnailVariant::Small => format!("sm-{}", thumbnail),
+            ThumbnailVariant::Double => format!("2x-{}", thumbnail),
+        }
+    }
+}
+
+/// Returns the file names of a thumbnail and all of its variants.
+pub(crate) fn thumbnail_file_names(thumbnail: &str) -> impl Iterator<Item = String> {
+    std::iter::once(thumbnail.to_owned()).chain(
+        ThumbnailVariant::ALL
+            .into_iter()
+            .map(move |variant| variant.file_name(thumbnail)),
+    )
+}
+
+/// Matches the post that a file in the files directory belongs to, given its path relative to that directory.
+///
+/// This covers the media, the thumbnail and its variants, the source archive, and the HLS stream.
+pub(crate) fn post_file_condition(file: &str) -> Condition {
+    if let Some((directory, _)) = file.split_once('/') {
+        return if directory.starts_with(STREAM_DIRECTORY_PREFIX) {
+            Condition::all().add(
+                samey_post::Column::StreamPlaylist
+                    .eq(format!("{}/{}", directory, HLS_PLAYLIST_NAME)),
+            )
+        } else {
+            // No other post files are stored in subdirectories
+            Condition::all().add(Expr::value(false))
+        };
+    }
+    let mut condition = Condition::any()
+        .add(samey_post::Column::Media.eq(file))
+        .add(samey_post::Column::Thumbnail.eq(file))
+        .add(samey_post::Column::Attachment.eq(file));
+    for variant in ThumbnailVariant::ALL {
+        if let Some(thumbnail) = file.strip_prefix(&variant.file_name("")) {
+            condition = condition.add(samey_post::Column::Thumbnail.eq(thumbnail));
+        }
+    }
+    condition
+}
+
+/// Returns the `srcset` attribute for a thumbnail, if its variants were generated.
+pub(crate) fn thumbnail_srcset(
+    thumbnail: &str,
+    thumbnail_variants: bool,
+    density: ThumbnailDensity,
+) -> Option<String> {
+    if !thumbnail_variants {
+        return None;
+    }
+    let small = ThumbnailVariant::Small.file_name(thumbnail);
+    let double = ThumbnailVariant::Double.file_name(thumbnail);
+    Some(match density {
+        // Compact grids show thumbnails at the small size, which is at most half of the double size
+        ThumbnailDensity::Compact => format!("/files/{} 1x, /files/{} 2x", small, double),
+        _ => format!("/files/{} 1x, /files/{} 2x", thumbnail, double),
+    })
+}
+
+/// Generates a thumbnail and its variants for the media at `media_path`, returning the media's dimensions.
+///
+/// The largest variant is made from the media itself, and the smaller ones are scaled down from it.
+pub(crate) async fn generate_thumbnails(
+    thumbnailer: &dyn Thumbnailer,
+    media_path: &Path,
+    is_video: bool,
+    files_dir: &Path,
+    thumbnail: &str,
+    thumbnail_dimension: u32,
+) -> Result<MediaDimensions, SameyError> {
+    let double_path = files_dir.join(ThumbnailVariant::Double.file_name(thumbnail));
+    let double_dimension = ThumbnailVariant::Double.max_dimension(thumbnail_dimension);
+    let media_dimensions = if is_video {
+        thumbnailer
+            .video(media_path, &double_path, double_dimension)
+            .await?
+    } else {
+        thumbnailer
+            .image(media_path, &double_path, double_dimension)
+            .await?
+    };
+    let small_path = files_dir.join(ThumbnailVariant::Small.file_name(thumbnail));
+    thumbnailer
+        .image(
+            &double_path,
+            &small_path,
+            ThumbnailVariant::Small.max_dimension(thumbnail_dimension),
+        )
+        .await?;
+    let thumbnail_dimensions = thumbnailer
+        .image(
+            &double_path,
+            &files_dir.join(thumbnail),
+            thumbnail_dimension,
+        )
+        .await?;
+    Ok(MediaDimensions {
+        thumbnail_width: thumbnail_dimensions.thumbnail_width,
+        thumbnail_height: thumbnail_dimensions.thumbnail_height,
+        ..media_dimensions
+    })
+}
+
+/// Removes a thumbnail and all of its variants, ignoring missing files.
+pub(crate) async fn remove_thumbnail_files(files_dir: &Path, thumbnail: &str) {
+    for file_name in thumbnail_file_names(thumbnail) {
+        let _ = tokio::fs::remove_file(files_dir.join(file_name)).await;
+    }
+}
+
+/// Removes every file of a deleted post, ignoring missing files.
+pub(crate) async fn remove_post_files(files_dir: &Path, post: &samey_post::Model) {
+    let _ = tokio::fs::remove_file(files_dir.join(&post.media)).await;
+    remove_thumbnail_files(files_dir, &post.thumbnail).await;
+    remove_stream_files(files_dir, post.stream_playlist.as_deref()).await;
+    if let Some(attachment) = &post.attachment {
+        let _ = tokio::fs::remove_file(files_dir.join(attachment)).await;
+    }
+    remove_attachment_files(files_dir, post.id).await;
+}
+
+/// Generates the thumbnails of every post again at `thumbnail_dimension`, after the size was changed in the settings.
+///
+/// Stops early if the size is changed again, so that only the latest change is applied.
+pub(crate) async fn regenerate_thumbnails(
+    db: &DatabaseConnection,
+    files_dir: &Path,
+    thumbnailer: &dyn Thumbnailer,
+    app_config: &RwLock<AppConfig>,
+    thumbnail_dimension: u32,
+) -> Result<(), SameyError> {
+    let posts: Vec<(i32, String, String, String)> = SameyPost::find()
+        .select_only()
+        .columns([
+            samey_post::Column::Id,
+            samey_post::Column::Media,
+            samey_post::Column::MediaType,
+            samey_post::Column::Thumbnail,
+        ])
+        .into_tuple()
+        .all(db)
+        .await?;
+    for (post_id, media, media_type, thumbnail) in posts {
+        if app_config.read().await.thumbnail_dimension != thumbnail_dimension {
+            break;
+        }
+        let dimensions = match generate_thumbnails(
+            thumbnailer,
+            &files_dir.join(&media),
+            media_type == "video",
+            files_dir,
+            &thumbnail,
+            thumbnail_dimension,
+        )
+        .await
+        {
+            Ok(dimensions) => dimensions,
+            Err(err) => {
+                println!(
+                    "Error when regenerating thumbnail for post {} - {}",
+                    post_id, err
+                );
+                continue;
+            }
+        };
+        SameyPost::update(samey_post::ActiveModel {
+            id: Set(post_id),
+            thumbnail_width: Set(dimensions.thumbnail_width.try_into()?),
+            thumbnail_height: Set(dimensions.thumbnail_height.try_into()?),
+            thumbnail_variants: Set(true),
+            ..Default::default()
+        })
+        .exec(db)
+        .await?;
+    }
+    Ok(())
+}
+
+pub(crate) enum Format {
+    Video(&'static str),
+    Image(ImageFormat),
+    /// A pixiv ugoira archive, which is converted into a video.
+    Ugoira,
+}
+
+impl Format {
+    pub(crate) fn media_type(&self) -> &'static str {
+        match self {
+            Format::Video(_) | Format::Ugoira => "video",
+            Format::Image(_) => "image",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = SameyError;
+
+    fn from_str(content_type: &str) -> Result<Self, Self::Err> {
+        match content_type {
+            "video/mp4" => Ok(Self::Video(".mp4")),
+            "video/webm" => Ok(Self::Video(".webm")),
+            "application/x-matroska" | "video/mastroska" => Ok(Self::Video(".mkv")),
+            "video/quicktime" => Ok(Self::Video(".mov")),
+            "application/zip" | "application/x-zip-compressed" => Ok(Self::Ugoira),
+            _ => Ok(Self::Image(
+                ImageFormat::from_mime_type(content_type).ok_or(SameyError::BadRequest(
+                    format!("Unknown content type: {}", content_type),
+                ))?,
+            )),
+        }
+    }
+}
+
+/// A media file to be stored in the files directory, with a randomly generated name.
+pub(crate) struct MediaFile {
+    pub(crate) format: Format,
+    pub(crate) file_name: String,
+    pub(crate) thumbnail_file_name: String,
+    /// Original upload that the media file is converted from, which is kept for downloading.
+    pub(crate) attachment_file_name: Option<String>,
+    thumbnailer: Arc<dyn Thumbnailer>,
+}
+
+/// A media file and its thumbnail after processing.
+pub(crate) struct StoredMedia {
+    pub(crate) media: String,
+    pub(crate) media_type: &'static str,
+    pub(crate) width: i32,
+    pub(crate) height: i32,
+    pub(crate) thumbnail: String,
+    pub(crate) thumbnail_width: i32,
+    pub(crate) thumbnail_height: i32,
+    /// Duration in seconds, only known for videos.
+    pub(crate) duration: Option<f64>,
+    /// Original upload that the media was converted from, such as an ugoira archive.
+    ///
+    /// This is part of the media rather than one of the post's attachments in [`crate::attachments`], since it's
+    /// stored and removed along with the media.
+    pub(crate) attachment: Option<String>,
+}
+
+impl MediaFile {
+    /// Fails if `thumbnailer` can't handle the file's format.
+    pub(crate) fn new(
+        format: Format,
+        thumbnailer: Arc<dyn Thumbnailer>,
+    ) -> Result<Self, SameyError> {
+        if matches!(format, Format::Video(_)) && !thumbnailer.supports_video() {
+            return Err(SameyError::BadRequest(
+                "Video uploads are not supported".into(),
+            ));
+        }
+        if matches!(format, Format::Ugoira) && !thumbnailer.supports_video() {
+            return Err(SameyError::BadRequest(
+                "Ugoira uploads are not supported".into(),
+            ));
+        }
+        let mut rng = rand::rng();
+        let name: String = (0..8)
+            .map(|_| rng.sample(rand::distr::Alphanumeric) as char)
+            .collect();
+        let (file_name, thumbnail_file_name) = match format {
+            Format::Video(video_format) => (
+                format!("{}{}", name, video_format),
+                format!("thumb-{}.{}", name, ImageFormat::Png.extensions_str()[0]),
+            ),
+            Format::Ugoira => (
+                format!("{}.webm", name),
+                format!("thumb-{}.{}", name, ImageFormat::Png.extensions_str()[0]),
+            ),
+            Format::Image(image_format) => {
+                let file_name = format!("{}.{}", name, image_format.extensions_str()[0]);
+                let thumbnail_file_name = format!("thumb-{}", file_name);
+                (file_name, thumbnail_file_name)
+            }
+        };
+        let attachment_file_name = match format {
+            Format::Ugoira => Some(format!("{}.zip", name)),
+            _ => None,
+        };
+        Ok(Self {
+            format,
+            file_name,
+            thumbnail_file_name,
+            attachment_file_name,
+            thumbnailer,
+        })
+    }
+
+    /// Returns the name that the uploaded file should be written to, before processing.
+    pub(crate) fn upload_file_name(&self) -> &str {
+        self.attachment_file_name
+            .as_deref()
+            .unwrap_or(&self.file_name)
+    }
+
+    /// Generates thumbnails for a media file that has already been written to `files_dir`, converting it first if
+    /// needed.
+    pub(crate) async fn process(
+        self,
+        files_dir: &Path,
+        thumbnail_dimension: u32,
+    ) -> Result<StoredMedia, SameyError> {
+        let file_path = files_dir.join(&self.file_name);
+        if let Format::Ugoira = self.format {
+            let archive = self.upload_file_name();
+            convert_ugoira(
+                self.thumbnailer.as_ref(),
+                &files_dir.join(archive),
+                &file_path,
+            )
+            .await?;
+        }
+        let MediaDimensions {
+            width,
+            height,
+            thumbnail_width,
+            thumbnail_height,
+            video,
+        } = generate_thumbnails(
+            self.thumbnailer.as_ref(),
+            &file_path,
+            self.format.media_type() == "video",
+            files_dir,
+            &self.thumbnail_file_name,
+            thumbnail_dimension,
+        )
+        .await?;
+
+        let dimension = |dimension: u32| -> Result<i32, SameyError> {
+            NonZero::new(dimension.try_into()?)
+                .map(NonZero::get)
+                .ok_or(SameyError::BadRequest("Media has no dimensions".into()))
+        };
+        Ok(StoredMedia {
+            media: self.file_name,
+            media_type: self.format.media_type(),
+            width: dimension(width)?,
+            height: dimension(height)?,
+            thumbnail: self.thumbnail_file_name,
+            thumbnail_width: dimension(thumbnail_width)?,
+            thumbnail_height: dimension(thumbnail_height)?,
+            duration: video.and_then(|video| video.duration),
+            attachment: self.attachment_file_name,
+        })
+    }
+}
+
+/// Media received in an upload, after being stored and processed.
+pub(crate) struct UploadedMedia {
+    media: StoredMedia,
+    original_filename: Option<String>,
+    virus_scanned_at: Option<NaiveDateTime>,
+}
+
+impl UploadedMedia {
+    /// Removes the stored files, for uploads that won't become a post.
+    pub(crate) async fn discard(self, files_dir: &Path) {
+        let _ = tokio::fs::remove_file(files_dir.join(&self.media.media)).await;
+        remove_thumbnail_files(files_dir, &self.media.thumbnail).await;
+        if let Some(attachment) = &self.media.attachment {
+            let _ = tokio::fs::remove_file(files_dir.join(attachment)).await;
+        }
+    }
+}
+
+/// Creates the tags given in an upload, returning them.
+///
+/// Search-only tags like ratings are skipped, and non-admin users can't use protected tags.
+pub(crate) async fn save_upload_tags(
+    db: &DatabaseConnection,
+    user: &User,
+    tags: &str,
+) -> Result<Vec<samey_tag::Model>, SameyError> {
+    let tags: HashSet<String> = tags
+        .split_whitespace()
+        .filter_map(|tag| {
+            if tag.starts_with(NEGATIVE_PREFIX)
+                || tag.starts_with(RATING_PREFIX)
+                || tag.starts_with(MEDIA_TYPE_PREFIX)
+            {
+                None
+            } else {
+                Some(String::from(tag))
+            }
+        })
+        .collect();
+    if tags.is_empty() {
+        return Ok(vec![]);
+    }
+    let normalized_tags: HashSet<String> = tags.iter().map(|tag| tag.to_lowercase()).collect();
+    if !user.is_admin {
+        if let Some(tag) = get_protected_tags()
+            .filter(
+                samey_tag::Column::NormalizedName.is_in(normalized_tags.iter().map(String::as_str)),
+            )
+            .one(db)
+            .await?
+        {
+            return Err(SameyError::BadRequest(format!(
+                "Tag {} is protected",
+                tag.name
+            )));
+        }
+    }
+    SameyTag::insert_many(tags.into_iter().map(|tag| samey_tag::ActiveModel {
+        normalized_name: Set(tag.to_lowercase()),
+        name: Set(tag),
+        updated_at: Set(Some(Utc::now().naive_utc())),
+        ..Default::default()
+    }))
+    .on_conflict(
+        OnConflict::column(samey_tag::Column::NormalizedName)
+            .do_nothing()
+            .to_owned(),
+    )
+    .exec_without_returning(db)
+    .await?;
+    Ok(SameyTag::find()
+        .filter(samey_tag::Column::NormalizedName.is_in(normalized_tags))
+        .all(db)
+        .await?)
+}
+
+/// Stores an uploaded file written by `write`, scanning it for viruses if enabled and generating its thumbnail.
+///
+/// Nothing is left behind in the files directory if any step fails.
+pub(crate) async fn store_upload_media(
+    state: &AppState,
+    user: &User,
+    content_type: &str,
+    original_filename: Option<String>,
+    write: impl AsyncFnOnce(&Path) -> Result<(), SameyError>,
+) -> Result<UploadedMedia, SameyError> {
+    let (clamav_address, thumbnail_dimension) = {
+        let app_config = state.app_config.read().await;
+        (
+            app_config
+                .clamav_enabled
+                .then(|| app_config.clamav_address.clone()),
+            app_config.thumbnail_dimension,
+        )
+    };
+    let base_path = state.files_dir.as_path();
+    let media_file = MediaFile::new(
+        Format::from_str(content_type)?,
+        Arc::clone(&state.thumbnailer),
+    )?;
+    let file_path = base_path.join(&media_file.file_name);
+    let upload_path = base_path.join(media_file.upload_file_name());
+    let thumbnail_file_name = media_file.thumbnail_file_name.clone();
+    let result = async {
+        write(&upload_path).await?;
+        let mut virus_scanned_at = None;
+        if let Some(clamav_address) = clamav_address.as_deref() {
+            if let ScanResult::Infected(signature) = scan_file(clamav_address, &upload_path).await?
+            {
+                println!(
+                    "Rejected upload by user {} - Virus scan found {}",
+                    user.id, signature
+                );
+                return Err(SameyError::BadRequest(format!(
+                    "File rejected by virus scan: {}",
+                    signature
+                )));
+            }
+            virus_scanned_at = Some(Utc::now().naive_utc());
+        }
+        Ok(UploadedMedia {
+            media: media_file.process(base_path, thumbnail_dimension).await?,
+            original_filename,
+            virus_scanned_at,
+        })
+    }
+    .await;
+    if result.is_err() {
+        // Don't leave partial uploads behind
+        let _ = tokio::fs::remove_file(&file_path).await;
+        let _ = tokio::fs::remove_file(&upload_path).await;
+        remove_thumbnail_files(base_path, &thumbnail_file_name).await;
+    }
+    result
+}
+
+/// Creates a post from uploaded media, returning its ID.
+///
+/// The post gets the instance's default rating and visibility, and is federated and cross-posted if public. Drafts
+/// stay private until they are published.
+pub(crate) async fn create_uploaded_post(
+    state: &AppState,
+    user: &User,
+    tags: Vec<samey_tag::Model>,
+    media: UploadedMedia,
+    is_draft: bool,
+) -> Result<i32, SameyError> {
+    let db = &state.db;
+    let media_path = state.files_dir.join(&media.media.media);
+    let is_video = media.media.media_type == "video";
+    let size = tokio::fs::metadata(&media_path)
+        .await
+        .map_or(0, |metadata| metadata.len());
+    let (default_rating, is_public, should_stream) = {
+        let app_config = state.app_config.read().await;
+        (
+            app_config.default_rating.clone(),
+            app_config.default_public && !is_draft,
+            is_video
+                && state.thumbnailer.supports_streaming()
+                && app_config.should_stream(media.media.duration, size),
+        )
+    };
+    let media_name = media.media.media.clone();
+    let thumbnail = media.media.thumbnail.clone();
+    let attachment = media.media.attachment.clone();
+    let stored_size = stored_size(
+        &state.files_dir,
+        &media_name,
+        &thumbnail,
+        attachment.as_deref(),
+        None,
+    )
+    .await?;
+    let result = async {
+        let txn = db.begin().await?;
+        let post_id = samey_post::ActiveModel {
+            uploader_id: Set(user.id),
+            media: Set(media.media.media),
+            media_type: Set(media.media.media_type.into()),
+            original_filename: Set(media.original_filename),
+            virus_scanned_at: Set(media.virus_scanned_at),
+            width: Set(media.media.width),
+            height: Set(media.media.height),
+            thumbnail: Set(media.media.thumbnail),
+            thumbnail_width: Set(media.media.thumbnail_width),
+            thumbnail_height: Set(media.media.thumbnail_height),
+            thumbnail_variants: Set(true),
+            attachment: Set(media.media.attachment),
+            stored_size: Set(Some(stored_size)),
+            title: Set(None),
+            description: Set(None),
+            is_public: Set(is_public),
+            is_draft: Set(is_draft),
+            rating: Set(default_rating),
+            uploaded_at: Set(Utc::now().naive_utc()),
+            parent_id: Set(None),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?
+        .id;
+
+        // Add tags to post
+        if !tags.is_empty() {
+            SameyTagPost::insert_many(tags.into_iter().map(|tag| samey_tag_post::ActiveModel {
+                post_id: Set(post_id),
+                tag_id: Set(tag.id),
+                ..Default::default()
+            }))
+            .exec(&txn)
+            .await?;
+        }
+        txn.commit().await?;
+        Ok::<_, SameyError>(post_id)
+    }
+    .await;
+    let post_id = match result {
+        Ok(post_id) => post_id,
+        Err(err) => {
+            // Don't leave files without a post behind
+            let _ = tokio::fs::remove_file(media_path).await;
+            remove_thumbnail_files(&state.files_dir, &thumbnail).await;
+            if let Some(attachment) = attachment {
+                let _ = tokio::fs::remove_file(state.files_dir.join(attachment)).await;
+            }
+            return Err(err);
+        }
+    };
+    if should_stream {
+        spawn_stream_generation(
+            db.clone(),
+            Arc::clone(&state.files_dir),
+            Arc::clone(&state.thumbnailer),
+            post_id,
+            media_name,
+        );
+    }
+    if is_public {
+        if let Some(federation) = Federation::from_config(&*state.app_config.read().await) {
+            federate_post(db.clone(), federation, post_id);
+        }
+        queue_crossposts(db, post_id).await?;
+    }
+
+    Ok(post_id)
+}
+
+/// Replaces the tags of a post, creating any tags that don't exist yet.
+///
+/// Only the difference between the current and the new tags is written, so unchanged tag-post entries are kept.
+/// Returns the post's new tags, sorted by name.
+pub(crate) async fn replace_post_tags(
+    db: &impl ConnectionTrait,
+    post_id: i32,
+    tags: impl IntoIterator<Item = String>,
+) -> Result<Vec<samey_tag::Model>, SameyError> {
+    let tags: Vec<String> = tags.into_iter().collect();
+    let normalized_tags: Vec<String> = tags.iter().map(|tag| tag.to_lowercase()).collect();
+
+    let mut post_tags = if tags.is_empty() {
+        vec![]
+    } else {
+        SameyTag::insert_many(tags.into_iter().map(|tag| samey_tag::ActiveModel {
+            normalized_name: Set(tag.to_lowercase()),
+            name: Set(tag),
+            updated_at: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        }))
+        .on_conflict(
+            OnConflict::column(samey_tag::Column::NormalizedName)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+        SameyTag::find()
+            .filter(samey_tag::Column::NormalizedName.is_in(normalized_tags))
+            .all(db)
+            .await?
+    };
+
+    let new_tag_ids: HashSet<i32> = post_tags.iter().map(|tag| tag.id).collect();
+    let current_tag_ids: HashSet<i32> = SameyTagPost::find()
+        .select_only()
+        .column(samey_tag_post::Column::TagId)
+        .filter(samey_tag_post::Column::PostId.eq(post_id))
+        .into_tuple::<i32>()
+        .all(db)
+        .await?
+        .into_iter()
+        .collect();
+
+    let removed_tag_ids: Vec<i32> = current_tag_ids.difference(&new_tag_ids).copied().collect();
+    if !removed_tag_ids.is_empty() {
+        SameyTagPost::delete_many()
+            .filter(samey_tag_post::Column::PostId.eq(post_id))
+            .filter(samey_tag_post::Column::TagId.is_in(removed_tag_ids))
+            .exec(db)
+            .await?;
+    }
+    let added_tag_ids: Vec<i32> = new_tag_ids.difference(&current_tag_ids).copied().collect();
+    if !added_tag_ids.is_empty() {
+        SameyTagPost::insert_many(added_tag_ids.into_iter().map(|tag_id| {
+            samey_tag_post::ActiveModel {
+                post_id: Set(post_id),
+                tag_id: Set(tag_id),
+                ..Default::default()
+            }
+        }))
+        .exec(db)
+        .await?;
+    }
+
+    post_tags.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(post_tags)
+}
+
+/// Marks a post as changed, so that edits started before this are rejected as conflicting.
+pub(crate) async fn bump_post_version(
+    db: &impl ConnectionTrait,
+    post_id: i32,
+) -> Result<(), SameyError> {
+    SameyPost::update_many()
+        .set(samey_post::ActiveModel {
+            updated_at: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        })
+        .col_expr(
+            samey_post::Column::Version,
+            Expr::col(samey_post::Column::Version).add(1),
+        )
+        .filter(samey_post::Column::Id.eq(post_id))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Creates a post from a local image or video file, returning its ID.
+///
+/// The file is copied into `files_dir`, and its format is guessed from its extension.
+/// Like uploaded posts, the new post gets the instance's default rating and visibility, and is federated and crossposted
+/// if it's public. Thumbnails are generated with [`DefaultThumbnailer`].
+///
+/// ```
+/// use samey::create_post_from_file;
+///
+/// # async fn _main() {
+/// let db = sea_orm::Database::connect("sqlite:db.sqlite3?mode=rwc").await.unwrap();
+/// let post_id = create_post_from_file(db, "files", 1, "image.png", &["tag_1", "tag_2"])
+///     .await
+///     .expect("Unable to create post");
+/// # }
+/// ```
+pub async fn create_post_from_file(
+    db: DatabaseConnection,
+    files_dir: impl AsRef<Path>,
+    uploader_id: i32,
+    path: impl AsRef<Path>,
+    tags: &[&str],
+) -> Result<i32, SameyError> {
+    let path: PathBuf = path.as_ref().to_owned();
+    let content_type = mime_guess::from_path(&path)
+        .first()
+        .ok_or(SameyError::BadRequest(format!(
+            "Unknown file type: {}",
+            path.display()
+        )))?;
+    let media_file = MediaFile::new(
+        Format::from_str(content_type.as_ref())?,
+        Arc::new(DefaultThumbnailer::new()),
+    )?;
+    let app_config = AppConfig::new(&db).await?;
+    tokio::fs::copy(
+        &path,
+        files_dir.as_ref().join(media_file.upload_file_name()),
+    )
+    .await?;
+    let media = media_file
+        .process(files_dir.as_ref(), app_config.thumbnail_dimension)
+        .await?;
+    let stored_size = stored_size(
+        files_dir.as_ref(),
+        &media.media,
+        &media.thumbnail,
+        media.attachment.as_deref(),
+        None,
+    )
+    .await?;
+
+    let txn = db.begin().await?;
+    let post = samey_post::ActiveModel {
+        uploader_id: Set(uploader_id),
+        media: Set(media.media),
+        media_type: Set(media.media_type.into()),
+        original_filename: Set(path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().into_owned())),
+        width: Set(media.width),
+        height: Set(media.height),
+        thumbnail: Set(media.thumbnail),
+        thumbnail_width: Set(media.thumbnail_width),
+        thumbnail_height: Set(media.thumbnail_height),
+        thumbnail_variants: Set(true),
+        attachment: Set(media.attachment),
+        stored_size: Set(Some(stored_size)),
+        title: Set(None),
+        description: Set(None),
+        is_public: Set(app_config.default_public),
+        rating: Set(app_config.default_rating.clone()),
+        uploaded_at: Set(Utc::now().naive_utc()),
+        parent_id: Set(None),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+    replace_post_tags(&txn, post.id, tags.iter().map(|tag| (*tag).to_owned())).await?;
+    txn.commit().await?;
+    if is_post_published(&post) {
+        if let Some(federation) = Federation::from_config(&app_config) {
+            federate_post(db.clone(), federation, post.id);
+        }
+        queue_crossposts(&db, post.id).await?;
+    }
+
+    Ok(post.id)
+}
+
+/// Replaces all tags of a post.
+///
+/// ```
+/// use samey::set_post_tags;
+///
+/// # async fn _main() {
+/// let db = sea_orm::Database::connect("sqlite:db.sqlite3?mode=rwc").await.unwrap();
+/// set_post_tags(db, 1, &["tag_1", "tag_2"]).await.expect("Unable to set tags");
+/// # }
+/// ```
+pub async fn set_post_tags(
+    db: DatabaseConnection,
+    post_id: i32,
+    tags: &[&str],
+) -> Result<(), SameyError> {
+    SameyPost::find_by_id(post_id)
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+    let txn = db.begin().await?;
+    replace_post_tags(&txn, post_id, tags.iter().map(|tag| (*tag).to_owned())).await?;
+    bump_post_version(&txn, post_id).await?;
+    txn.commit().await?;
+    clean_dangling_tags(&db).await
+}
+
+/// Creates a pool, returning its ID.
+///
+/// ```
+/// use samey::create_pool;
+///
+/// # async fn _main() {
+/// let db = sea_orm::Database::connect("sqlite:db.sqlite3?mode=rwc").await.unwrap();
+/// let pool_id = create_pool(db, 1, "My pool", true).await.expect("Unable to create pool");
+/// # }
+/// ```
+pub async fn create_pool(
+    db: DatabaseConnection,
+    uploader_id: i32,
+    name: &str,
+    is_public: bool,
+) -> Result<i32, SameyError> {
+    Ok(samey_pool::ActiveModel {
+        name: Set(name.into()),
+        uploader_id: Set(uploader_id),
+        is_public: Set(is_public),
+        ..Default::default()
+    }
+    .insert(&db)
+    .await?
+    .id)
+}
+
+/// Adds a post to the end of a pool.
+///
+/// ```
+/// use samey::add_post_to_pool;
+///
+/// # async fn _main() {
+/// let db = sea_orm::Database::connect("sqlite:db.sqlite3?mode=rwc").await.unwrap();
+/// add_post_to_pool(db, 1, 1).await.expect("Unable to add post to pool");
+/// # }
+/// ```
+pub async fn add_post_to_pool(
+    db: DatabaseConnection,
+    pool_id: i32,
+    post_id: i32,
+) -> Result<(), SameyError> {
+    let max_position: Option<f32> = SameyPoolPost::find()
+        .select_only()
+        .column_as(samey_pool_post::Column::Position.max(), "max_position")
+        .filter(samey_pool_post::Column::PoolId.eq(pool_id))
+        .into_tuple()
+        .one(&db)
+        .await?
+        .flatten();
+    SameyPoolPost::insert(samey_pool_post::ActiveModel {
+        pool_id: Set(pool_id),
+        post_id: Set(post_id),
+        position: Set(max_position.unwrap_or(0.0).floor() + 1.0),
+        ..Default::default()
+    })
+    .exec(&db)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use axum::{extract::FromRequestParts, http::request::Parts};
+use tower_sessions::Session;
+
+use crate::{
+    AppState, SameyError,
+    auth::{AuthSession, User},
+    preferences::Preferences,
+    views::AGE_CONFIRMED_SESSION_KEY,
+};
+
+/// Template data shared by every page.
+///
+/// Use it as an extractor in handlers, and add it as a `base` field in page templates.
+#[derive(Debug, Clone)]
+pub(crate) struct BaseContext {
+    pub(crate) application_name: String,
+    /// Whether to show the age check dialog to this visitor.
+    pub(crate) show_age_check: bool,
+    pub(crate) announcement: Option<String>,
+    pub(crate) logo_url: String,
+    pub(crate) user: Option<User>,
+    pub(crate) preferences: Preferences,
+}
+
+impl FromRequestParts<AppState> for BaseContext {
+    type Rejection = SameyError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let auth_session = AuthSession::from_request_parts(parts, state)
+            .await
+            .map_err(|(_, err)| SameyError::Other(err.into()))?;
+        let age_confirmed = Session::from_request_parts(parts, state)
+            .await
+            .map_err(|(_, err)| SameyError::Other(err.into()))?
+            .get::<bool>(AGE_CONFIRMED_SESSION_KEY)
+            .await
+            .map_err(|err| SameyError::Other(err.to_string()))?
+            .unwrap_or(false);
+        let preferences = Preferences::from_request_parts(parts, state).await?;
+        let app_config = state.app_config.read().await;
+        Ok(Self {
+            application_name: app_config.application_name.clone(),
+            show_age_check: app_config.age_confirmation
+                && !app_config.age_confirmation_explicit_only
+                && !age_confirmed,
+            announcement: app_config.announcement(),
+            logo_url: app_config.logo_url.clone(),
+            user: auth_session.user,
+            preferences,
+        })
+    }
+}
@@ -0,0 +1,408 @@
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use itertools::Itertools;
+use reqwest::{StatusCode, header::HeaderMap};
+use samey_migration::{Expr, OnConflict};
+use sea_orm::{
+    ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect,
+};
+use serde_json::json;
+use strum::IntoEnumIterator;
+use tokio::sync::RwLock;
+
+use crate::{
+    SameyError,
+    config::AppConfig,
+    entities::{
+        prelude::{SameyCrosspost, SameyIntegration, SameyPost},
+        samey_crosspost, samey_integration, samey_post,
+    },
+    query::{get_tags_for_post, is_post_published, search_posts_query},
+    search::SearchQuery,
+    tags::is_sensitive_rating,
+};
+
+/// How often the queue is checked for crossposts that are due.
+const CROSSPOST_INTERVAL: Duration = Duration::from_secs(15);
+/// How many crossposts are attempted on each check, so a large backlog is spread out.
+const CROSSPOST_BATCH_SIZE: u64 = 10;
+/// Crossposts that fail this many times are no longer retried until an admin asks to.
+pub(crate) const MAX_CROSSPOST_ATTEMPTS: i32 = 5;
+/// Delay before the first retry, doubled on each subsequent failure.
+const CROSSPOST_RETRY_DELAY: Duration = Duration::from_secs(60);
+/// How long to wait when a service limits us without saying for how long.
+const DEFAULT_RATE_LIMIT_DELAY: Duration = Duration::from_secs(60);
+const CROSSPOST_TIMEOUT: Duration = Duration::from_secs(10);
+pub(crate) const DEFAULT_MESSAGE_TEMPLATE: &str = "{title}\n{url}\n\n{hashtags}";
+
+/// Service that posts are crossposted to.
+#[derive(strum::EnumIter, strum::Display, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IntegrationKind {
+    #[strum(serialize = "mastodon")]
+    Mastodon,
+    #[strum(serialize = "discord")]
+    Discord,
+}
+
+impl IntegrationKind {
+    pub(crate) fn parse(kind: &str) -> Option<Self> {
+        Self::iter().find(|integration_kind| integration_kind.to_string() == kind)
+    }
+
+    /// Longest message accepted by the service; Mastodon's is the default of most instances.
+    fn max_message_length(self) -> usize {
+        match self {
+            Self::Mastodon => 500,
+            Self::Discord => 2000,
+        }
+    }
+}
+
+/// Queues a post that just became public for every integration whose tag filter it matches.
+///
+/// Posts that were already crossposted by an integration aren't queued for it again.
+pub(crate) async fn queue_crossposts(
+    db: &DatabaseConnection,
+    post_id: i32,
+) -> Result<(), SameyError> {
+    let integrations = SameyIntegration::find().all(db).await?;
+    let now = Utc::now().naive_utc();
+    for integration in integrations {
+        let matches = search_posts_query(&SearchQuery::parse(&integration.tag_filter), None)
+            .filter(samey_post::Column::Id.eq(post_id))
+            .count(db)
+            .await?
+            > 0;
+        if !matches {
+            continue;
+        }
+        SameyCrosspost::insert(samey_crosspost::ActiveModel {
+            integration_id: Set(integration.id),
+            post_id: Set(post_id),
+            run_at: Set(now),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::columns([
+                samey_crosspost::Column::IntegrationId,
+                samey_crosspost::Column::PostId,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Spawns the background task that delivers queued crossposts.
+pub(crate) fn spawn_crosspost_jobs(db: DatabaseConnection, app_config: Arc<RwLock<AppConfig>>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CROSSPOST_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = run_crossposts(&db, &app_config).await {
+                println!("Error when crossposting - {}", err);
+            }
+        }
+    });
+}
+
+enum Delivery {
+    /// The service may also have told us that no more requests are allowed for now.
+    Delivered(Option<NaiveDateTime>),
+    RateLimited(NaiveDateTime),
+    Failed(String),
+}
+
+async fn run_crossposts(
+    db: &DatabaseConnection,
+    app_config: &RwLock<AppConfig>,
+) -> Result<(), SameyError> {
+    let now = Utc::now().naive_utc();
+    let jobs = SameyCrosspost::find()
+        .find_also_related(SameyIntegration)
+        .filter(samey_crosspost::Column::DeliveredAt.is_null())
+        .filter(samey_crosspost::Column::Attempts.lt(MAX_CROSSPOST_ATTEMPTS))
+        .filter(samey_crosspost::Column::RunAt.lte(now))
+        .order_by_asc(samey_crosspost::Column::RunAt)
+        .limit(CROSSPOST_BATCH_SIZE)
+        .all(db)
+        .await?;
+    if jobs.is_empty() {
+        return Ok(());
+    }
+    let app_config = app_config.read().await;
+    let base_url = app_config.base_url.clone();
+    let ratings = app_config.ratings.clone();
+    drop(app_config);
+
+    let mut rate_limited = HashSet::new();
+    for (job, integration) in jobs {
+        let Some(mut integration) = integration else {
+            continue;
+        };
+        if rate_limited.contains(&integration.id)
+            || integration
+                .rate_limited_until
+                .is_some_and(|until| until > Utc::now().naive_utc())
+        {
+            continue;
+        }
+
+        let post = SameyPost::find_by_id(job.post_id).one(db).await?;
+        let Some(post) = post.filter(is_post_published) else {
+            // The post was hidden before it could be crossposted, so it may be queued again once public
+            SameyCrosspost::delete_by_id(job.id).exec(db).await?;
+            continue;
+        };
+
+        let delivery = if base_url.is_empty() {
+            Delivery::Failed("The base URL must be set to link to posts".into())
+        } else {
+            let message = render_message(db, &integration, &base_url, &post).await?;
+            let sensitive = is_sensitive_rating(&ratings, &post.rating);
+            deliver(&integration, job.id, &message, sensitive).await
+        };
+
+        let now = Utc::now().naive_utc();
+        match delivery {
+            Delivery::Delivered(rate_limited_until) => {
+                SameyCrosspost::update(samey_crosspost::ActiveModel {
+                    id: Set(job.id),
+                    delivered_at: Set(Some(now)),
+                    last_error: Set(None),
+                    ..Default::default()
+                })
+                .exec(db)
+                .await?;
+                if rate_limited_until.is_some() {
+                    rate_limited.insert(integration.id);
+                }
+                if rate_limited_until != integration.rate_limited_until {
+                    integration.rate_limited_until = rate_limited_until;
+                    set_rate_limited_until(db, &integration).await?;
+                }
+            }
+            Delivery::RateLimited(until) => {
+                SameyCrosspost::update(samey_crosspost::ActiveModel {
+                    id: Set(job.id),
+                    run_at: Set(until),
+                    ..Default::default()
+                })
+                .exec(db)
+                .await?;
+                rate_limited.insert(integration.id);
+                integration.rate_limited_until = Some(until);
+                set_rate_limited_until(db, &integration).await?;
+            }
+            Delivery::Failed(error) => {
+                let attempts = job.attempts + 1;
+                let delay = CROSSPOST_RETRY_DELAY * 2u32.saturating_pow(job.attempts as u32);
+                SameyCrosspost::update(samey_crosspost::ActiveModel {
+                    id: Set(job.id),
+                    attempts: Set(attempts),
+                    run_at: Set(now + delay),
+                    last_error: Set(Some(error.clone())),
+                    ..Default::default()
+                })
+                .exec(db)
+                .await?;
+                println!(
+                    "Error when crossposting post #{} to {} (attempt {} of {}) - {}",
+                    post.id, integration.name, attempts, MAX_CROSSPOST_ATTEMPTS, error
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn set_rate_limited_until(
+    db: &DatabaseConnection,
+    integration: &samey_integration::Model,
+) -> Result<(), SameyError> {
+    SameyIntegration::update(samey_integration::ActiveModel {
+        id: Set(integration.id),
+        rate_limited_until: Set(integration.rate_limited_until),
+        ..Default::default()
+    })
+    .exec(db)
+    .await?;
+    Ok(())
+}
+
+/// Lets failed crossposts of an integration be attempted again.
+pub(crate) async fn retry_failed_crossposts(
+    db: &DatabaseConnection,
+    integration_id: i32,
+) -> Result<(), SameyError> {
+    SameyCrosspost::update_many()
+        .col_expr(samey_crosspost::Column::Attempts, Expr::value(0))
+        .col_expr(
+            samey_crosspost::Column::RunAt,
+            Expr::value(Utc::now().naive_utc()),
+        )
+        .filter(samey_crosspost::Column::IntegrationId.eq(integration_id))
+        .filter(samey_crosspost::Column::DeliveredAt.is_null())
+        .filter(samey_crosspost::Column::Attempts.gte(MAX_CROSSPOST_ATTEMPTS))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Fills in the integration's message template for a post.
+///
+/// The template may contain `{title}`, `{url}`, `{id}`, `{rating}`, `{tags}` and `{hashtags}`.
+async fn render_message(
+    db: &DatabaseConnection,
+    integration: &samey_integration::Model,
+    base_url: &str,
+    post: &samey_post::Model,
+) -> Result<String, SameyError> {
+    let tags = get_tags_for_post(post.id).all(db).await?;
+    let title = post
+        .title
+        .clone()
+        .unwrap_or_else(|| format!("Post #{}", post.id));
+    let hashtags = tags
+        .iter()
+        .map(|tag| {
+            tag.name
+                .chars()
+                .map(|c| if c.is_alphanumeric() { c } else { '_' })
+                .collect::<String>()
+        })
+        .filter(|hashtag| hashtag.chars().any(|c| c != '_'))
+        .map(|hashtag| format!("#{}", hashtag))
+        .join(" ");
+    let message = integration
+        .message_template
+        .replace("{title}", &title)
+        .replace("{url}", &format!("{}/post/{}", base_url, post.id))
+        .replace("{id}", &post.id.to_string())
+        .replace("{rating}", &post.rating)
+        .replace("{tags}", &tags.iter().map(|tag| &tag.name).join(" "))
+        .replace("{hashtags}", &hashtags);
+
+    let max_length = IntegrationKind::parse(&integration.kind)
+        .map(IntegrationKind::max_message_length)
+        .unwrap_or(usize::MAX);
+    Ok(message.trim().chars().take(max_length).collect())
+}
+
+async fn deliver(
+    integration: &samey_integration::Model,
+    crosspost_id: i32,
+    message: &str,
+    sensitive: bool,
+) -> Delivery {
+    let request = match IntegrationKind::parse(&integration.kind) {
+        Some(IntegrationKind::Mastodon) => {
+            let mut status = json!({
+                "status": message,
+                "visibility": "public",
+                "sensitive": sensitive,
+            });
+            if sensitive {
+                status["spoiler_text"] = "Sensitive content".into();
+            }
+            reqwest::Client::new()
+                .post(format!(
+                    "{}/api/v1/statuses",
+                    integration.url.trim_end_matches('/')
+                ))
+                .bearer_auth(integration.access_token.as_deref().unwrap_or_default())
+                // Keeps a retry from posting twice if the previous response was lost
+                .header(
+                    "idempotency-key",
+                    format!("samey-crosspost-{}", crosspost_id),
+                )
+                .json(&status)
+        }
+        Some(IntegrationKind::Discord) => {
+            reqwest::Client::new().post(&integration.url).json(&json!({
+                "content": message,
+                "allowed_mentions": { "parse": [] },
+            }))
+        }
+        None => return Delivery::Failed(format!("Unknown integration {}", integration.kind)),
+    };
+
+    let response = match request.timeout(CROSSPOST_TIMEOUT).send().await {
+        Ok(response) => response,
+        Err(err) => return Delivery::Failed(err.to_string()),
+    };
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let until = retry_after(response.headers())
+            .or_else(|| rate_limit_reset(response.headers()))
+            .unwrap_or_else(|| Utc::now().naive_utc() + DEFAULT_RATE_LIMIT_DELAY);
+        return Delivery::RateLimited(until);
+    }
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Delivery::Failed(format!(
+            "{} {}",
+            status,
+            body.chars().take(200).collect::<String>()
+        ));
+    }
+    let exhausted = response
+        .headers()
+        .get("x-ratelimit-remaining")
+        .and_then(|remaining| remaining.to_str().ok())
+        .is_some_and(|remaining| remaining.trim() == "0");
+    Delivery::Delivered(
+        exhausted
+            .then(|| rate_limit_reset(response.headers()))
+            .flatten(),
+    )
+}
+
+/// Parses `Retry-After`, which is either a number of seconds or an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<NaiveDateTime> {
+    let value = headers.get("retry-after")?.to_str().ok()?.trim();
+    let delay = match value.parse::<f64>() {
+        Ok(seconds) => Duration::try_from_secs_f64(seconds).ok()?,
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()?
+            .duration_since(SystemTime::now())
+            .unwrap_or_default(),
+    };
+    Some(Utc::now().naive_utc() + delay)
+}
+
+/// Parses when the current rate limit window ends.
+///
+/// Discord sends `X-RateLimit-Reset-After` in seconds and `X-RateLimit-Reset` as a Unix timestamp,
+/// while Mastodon sends `X-RateLimit-Reset` as an ISO 8601 date.
+fn rate_limit_reset(headers: &HeaderMap) -> Option<NaiveDateTime> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+    };
+    if let Some(seconds) = header("x-ratelimit-reset-after").and_then(|value| value.parse().ok()) {
+        return Duration::try_from_secs_f64(seconds)
+            .ok()
+            .map(|delay| Utc::now().naive_utc() + delay);
+    }
+    let reset = header("x-ratelimit-reset")?;
+    match reset.parse::<f64>() {
+        Ok(timestamp) => DateTime::from_timestamp_millis((timestamp * 1000.0) as i64)
+            .map(|date| date.naive_utc()),
+        Err(_) => DateTime::parse_from_rfc3339(reset)
+            .ok()
+            .map(|date| date.naive_utc()),
+    }
+    .map(|until| until.max(Utc::now().naive_utc() + TimeDelta::seconds(1)))
+}
@@ -0,0 +1,312 @@
+use std::{collections::HashSet, path::Path};
+
+use chrono::{Duration, Utc};
+use image::{Rgb, RgbImage, imageops::FilterType};
+use password_auth::generate_hash;
+use rand::{Rng, seq::IndexedRandom};
+use samey_migration::OnConflict;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait,
+    PaginatorTrait, QueryFilter,
+};
+use tokio::task::spawn_blocking;
+
+use crate::{
+    SameyError,
+    config::AppConfig,
+    disk_usage::stored_size,
+    entities::{
+        prelude::{SameyPool, SameyPoolPost, SameyTag, SameyTagPost, SameyUser},
+        samey_pool, samey_pool_post, samey_post, samey_tag, samey_tag_post, samey_user,
+    },
+};
+
+const DEMO_USERNAMES: [&str; 4] = ["demo_alice", "demo_bob", "demo_carol", "demo_dave"];
+const DEMO_PASSWORD: &str = "demo";
+const DEMO_TAGS: [&str; 24] = [
+    "abstract",
+    "gradient",
+    "stripes",
+    "circles",
+    "noise",
+    "red",
+    "green",
+    "blue",
+    "yellow",
+    "purple",
+    "dark",
+    "bright",
+    "landscape",
+    "portrait",
+    "square",
+    "wallpaper",
+    "sketch",
+    "pattern",
+    "retro",
+    "minimal",
+    "warm",
+    "cool",
+    "pastel",
+    "neon",
+];
+const DEMO_POOL_PREFIX: &str = "Demo pool #";
+/// One demo pool is created for this many posts.
+const POSTS_PER_DEMO_POOL: u32 = 10;
+
+/// Procedurally generated image patterns.
+#[derive(Clone, Copy)]
+enum Pattern {
+    Gradient,
+    Stripes,
+    Circles,
+    Noise,
+}
+
+impl Pattern {
+    fn tag(self) -> &'static str {
+        match self {
+            Pattern::Gradient => "gradient",
+            Pattern::Stripes => "stripes",
+            Pattern::Circles => "circles",
+            Pattern::Noise => "noise",
+        }
+    }
+}
+
+fn random_color(rng: &mut impl Rng) -> Rgb<u8> {
+    Rgb([rng.random(), rng.random(), rng.random()])
+}
+
+fn generate_image(pattern: Pattern, width: u32, height: u32) -> RgbImage {
+    let mut rng = rand::rng();
+    let from = random_color(&mut rng);
+    let to = random_color(&mut rng);
+    let mix = |t: f32| {
+        Rgb(std::array::from_fn(|i| {
+            (from.0[i] as f32 * (1.0 - t) + to.0[i] as f32 * t) as u8
+        }))
+    };
+    match pattern {
+        Pattern::Gradient => RgbImage::from_fn(width, height, |x, y| {
+            mix((x + y) as f32 / (width + height) as f32)
+        }),
+        Pattern::Stripes => {
+            let stripe_width = rng.random_range(8..64);
+            RgbImage::from_fn(width, height, |x, _| {
+                if (x / stripe_width) % 2 == 0 {
+                    from
+                } else {
+                    to
+                }
+            })
+        }
+        Pattern::Circles => {
+            let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+            let ring_width = rng.random_range(8.0..48.0);
+            RgbImage::from_fn(width, height, |x, y| {
+                let distance = ((x as f32 - cx).powi(2) + (y as f32 - cy).powi(2)).sqrt();
+                mix((distance / ring_width).sin() * 0.5 + 0.5)
+            })
+        }
+        Pattern::Noise => RgbImage::from_fn(width, height, |_, _| mix(rng.random())),
+    }
+}
+
+fn random_file_name(rng: &mut impl Rng) -> String {
+    (0..8)
+        .map(|_| rng.sample(rand::distr::Alphanumeric) as char)
+        .collect()
+}
+
+/// Fills the database with demo users, posts, tags and pools.
+///
+/// Demo users all share the password `demo`. Post media is generated procedurally and saved to `files_dir`.
+pub async fn seed_demo(
+    db: DatabaseConnection,
+    files_dir: impl AsRef<Path>,
+    count: u32,
+) -> Result<(), SameyError> {
+    let files_dir = files_dir.as_ref();
+    tokio::fs::create_dir_all(files_dir).await?;
+
+    let password = generate_hash(DEMO_PASSWORD);
+    SameyUser::insert_many(
+        DEMO_USERNAMES
+            .into_iter()
+            .map(|username| samey_user::ActiveModel {
+                username: Set(username.into()),
+                password: Set(password.clone()),
+                is_admin: Set(false),
+                created_at: Set(Some(Utc::now().naive_utc())),
+                ..Default::default()
+            }),
+    )
+    .on_conflict(
+        OnConflict::column(samey_user::Column::Username)
+            .do_nothing()
+            .to_owned(),
+    )
+    .exec_without_returning(&db)
+    .await?;
+    let users = SameyUser::find()
+        .filter(samey_user::Column::Username.is_in(DEMO_USERNAMES))
+        .all(&db)
+        .await?;
+
+    SameyTag::insert_many(DEMO_TAGS.into_iter().map(|tag| samey_tag::ActiveModel {
+        name: Set(tag.into()),
+        normalized_name: Set(tag.into()),
+        updated_at: Set(Some(Utc::now().naive_utc())),
+        ..Default::default()
+    }))
+    .on_conflict(
+        OnConflict::column(samey_tag::Column::NormalizedName)
+            .do_nothing()
+            .to_owned(),
+    )
+    .exec_without_returning(&db)
+    .await?;
+    let tags = SameyTag::find()
+        .filter(samey_tag::Column::NormalizedName.is_in(DEMO_TAGS))
+        .all(&db)
+        .await?;
+
+    // Demo posts use the instance's own ratings, which may have been customized
+    let app_config = AppConfig::new(&db).await?;
+    let ratings: Vec<String> = app_config.rating_codes().map(String::from).collect();
+    let thumbnail_dimension = app_config.thumbnail_dimension;
+
+    let mut post_ids = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let (pattern, width, height, file_name, post_tags, rating, user_id, age) = {
+            let mut rng = rand::rng();
+            let pattern = *[
+                Pattern::Gradient,
+                Pattern::Stripes,
+                Pattern::Circles,
+                Pattern::Noise,
+            ]
+            .choose(&mut rng)
+            .expect("patterns shouldn't be empty");
+            let tag_count = rng.random_range(2..6);
+            let mut post_tags: HashSet<i32> = tags
+                .choose_multiple(&mut rng, tag_count)
+                .map(|tag| tag.id)
+                .collect();
+            post_tags.extend(
+                tags.iter()
+                    .filter(|tag| tag.name == pattern.tag())
+                    .map(|tag| tag.id),
+            );
+            let rating = ratings
+                .choose(&mut rng)
+                .expect("ratings shouldn't be empty")
+                .clone();
+            (
+                pattern,
+                rng.random_range(320..1280),
+                rng.random_range(320..1280),
+                random_file_name(&mut rng),
+                post_tags,
+                rating,
+                users.choose(&mut rng).expect("demo users should exist").id,
+                Duration::minutes(rng.random_range(0..60 * 24 * 365)),
+            )
+        };
+
+        let file_name = format!("{}.png", file_name);
+        let thumbnail_file_name = format!("thumb-{}", file_name);
+        let file_path = files_dir.join(&file_name);
+        let thumbnail_path = files_dir.join(&thumbnail_file_name);
+        let (thumbnail_width, thumbnail_height) =
+            spawn_blocking(move || -> Result<_, SameyError> {
+                let image = generate_image(pattern, width, height);
+                image.save(file_path)?;
+                let thumbnail = image::DynamicImage::from(image).resize(
+                    thumbnail_dimension,
+                    thumbnail_dimension,
+                    FilterType::CatmullRom,
+                );
+                thumbnail.save(thumbnail_path)?;
+                Ok((thumbnail.width(), thumbnail.height()))
+            })
+            .await??;
+        let stored_size =
+            stored_size(files_dir, &file_name, &thumbnail_file_name, None, None).await?;
+
+        let post_id = samey_post::ActiveModel {
+            uploader_id: Set(user_id),
+            media: Set(file_name),
+            media_type: Set("image".into()),
+            width: Set(width.try_into()?),
+            height: Set(height.try_into()?),
+            thumbnail: Set(thumbnail_file_name),
+            thumbnail_width: Set(thumbnail_width.try_into()?),
+            thumbnail_height: Set(thumbnail_height.try_into()?),
+            stored_size: Set(Some(stored_size)),
+            title: Set(None),
+            description: Set(None),
+            is_public: Set(true),
+            rating: Set(rating),
+            uploaded_at: Set((Utc::now() - age).naive_utc()),
+            parent_id: Set(None),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await?
+        .id;
+        SameyTagPost::insert_many(post_tags.into_iter().map(|tag_id| {
+            samey_tag_post::ActiveModel {
+                post_id: Set(post_id),
+                tag_id: Set(tag_id),
+                ..Default::default()
+            }
+        }))
+        .exec(&db)
+        .await?;
+        post_ids.push(post_id);
+    }
+
+    let existing_pools = SameyPool::find()
+        .filter(samey_pool::Column::Name.starts_with(DEMO_POOL_PREFIX))
+        .count(&db)
+        .await?;
+    for pool_index in 0..(count / POSTS_PER_DEMO_POOL) as u64 {
+        let (user_id, pool_posts) = {
+            let mut rng = rand::rng();
+            let pool_size = rng.random_range(2..=POSTS_PER_DEMO_POOL as usize);
+            (
+                users.choose(&mut rng).expect("demo users should exist").id,
+                post_ids
+                    .choose_multiple(&mut rng, pool_size)
+                    .copied()
+                    .collect::<Vec<_>>(),
+            )
+        };
+        let pool_id = samey_pool::ActiveModel {
+            name: Set(format!(
+                "{}{}",
+                DEMO_POOL_PREFIX,
+                existing_pools + pool_index + 1
+            )),
+            uploader_id: Set(user_id),
+            is_public: Set(true),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await?
+        .id;
+        SameyPoolPost::insert_many(pool_posts.into_iter().enumerate().map(|(index, post_id)| {
+            samey_pool_post::ActiveModel {
+                pool_id: Set(pool_id),
+                post_id: Set(post_id),
+                position: Set(index as f32 + 1.0),
+                ..Default::default()
+            }
+        }))
+        .exec(&db)
+        .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{TimeDelta, Utc};
+use samey_migration::OnConflict;
+use sea_orm::{ActiveValue::Set, DatabaseConnection, EntityTrait};
+use serde_json::json;
+use tokio::sync::RwLock;
+
+use crate::{
+    SameyError,
+    config::{AppConfig, MODERATION_DIGEST_SENT_AT_KEY, TIMESTAMP_FORMAT},
+    email::send_email,
+    entities::{prelude::SameyConfig, samey_config},
+    query::{PendingPostReport, get_pending_post_reports},
+};
+
+/// How often to check whether the next digest is due.
+const DIGEST_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const DIGEST_PERIOD: TimeDelta = TimeDelta::days(1);
+const DIGEST_WEBHOOK_TIMEOUT: Duration = Duration::from_secs(30);
+/// Discord rejects longer webhook messages.
+const DIGEST_WEBHOOK_MESSAGE_LENGTH: usize = 2000;
+/// Long report reasons are shortened to keep the digest readable.
+const DIGEST_REASON_LENGTH: usize = 200;
+
+/// Spawns the background task that sends the daily moderation digest.
+pub(crate) fn spawn_moderation_digest_job(
+    db: DatabaseConnection,
+    app_config: Arc<RwLock<AppConfig>>,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(DIGEST_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let is_due = app_config
+                .read()
+                .await
+                .moderation_digest_sent_at
+                .is_none_or(|sent_at| sent_at + DIGEST_PERIOD <= Utc::now().naive_utc());
+            if !is_due {
+                continue;
+            }
+            if let Err(err) = send_moderation_digest(&db, &app_config).await {
+                println!("Error when sending the moderation digest - {}", err);
+            }
+        }
+    });
+}
+
+/// Sends a summary of pending reports to the digest's email addresses and webhook, returning how many reports it
+/// included.
+///
+/// Nothing is sent while the moderation queue is empty. The digest counts as sent if any of its recipients got it.
+pub(crate) async fn send_moderation_digest(
+    db: &DatabaseConnection,
+    app_config: &RwLock<AppConfig>,
+) -> Result<usize, SameyError> {
+    let config = app_config.read().await.clone();
+    if config.moderation_digest_emails.is_empty() && config.moderation_digest_webhook_url.is_empty()
+    {
+        return Ok(0);
+    }
+    let reports = get_pending_post_reports().all(db).await?;
+    if reports.is_empty() {
+        return Ok(0);
+    }
+
+    let subject = format!(
+        "{} pending report{} on {}",
+        reports.len(),
+        if reports.len() == 1 { "" } else { "s" },
+        config.application_name
+    );
+    let message = format_digest(&config, &reports);
+    let mut results = Vec::new();
+    if !config.moderation_digest_emails.is_empty() {
+        results.push(
+            send_email(
+                &config,
+                &config.moderation_digest_emails,
+                &subject,
+                message.clone(),
+            )
+            .await,
+        );
+    }
+    if !config.moderation_digest_webhook_url.is_empty() {
+        results.push(send_webhook(&config.moderation_digest_webhook_url, &reports, &message).await);
+    }
+    let mut sent = false;
+    let mut last_error = None;
+    for result in results {
+        match result {
+            Ok(()) => sent = true,
+            Err(err) => {
+                println!("Error when sending the moderation digest - {}", err);
+                last_error = Some(err);
+            }
+        }
+    }
+    if !sent {
+        if let Some(err) = last_error {
+            return Err(err);
+        }
+    }
+
+    let sent_at = Utc::now().naive_utc();
+    SameyConfig::insert(samey_config::ActiveModel {
+        key: Set(MODERATION_DIGEST_SENT_AT_KEY.into()),
+        data: Set(sent_at.format(TIMESTAMP_FORMAT).to_string().into()),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::column(samey_config::Column::Key)
+            .update_column(samey_config::Column::Data)
+            .to_owned(),
+    )
+    .exec(db)
+    .await?;
+    app_config.write().await.moderation_digest_sent_at = Some(sent_at);
+
+    Ok(reports.len())
+}
+
+/// Lists pending reports grouped by post, oldest first.
+fn format_digest(config: &AppConfig, reports: &[PendingPostReport]) -> String {
+    let mut posts: Vec<(i32, Vec<&PendingPostReport>)> = Vec::new();
+    for report in reports {
+        match posts
+            .iter_mut()
+            .find(|(post_id, _)| *post_id == report.post_id)
+        {
+            Some((_, post_reports)) => post_reports.push(report),
+            None => posts.push((report.post_id, vec![report])),
+        }
+    }
+
+    let mut message = format!(
+        "{} has {} pending report{} on {} post{}.\n",
+        config.application_name,
+        reports.len(),
+        if reports.len() == 1 { "" } else { "s" },
+        posts.len(),
+        if posts.len() == 1 { "" } else { "s" },
+    );
+    for (post_id, post_reports) in posts {
+        message.push_str(&format!(
+            "\nPost #{} - {}/post/{}\n",
+            post_id, config.base_url, post_id
+        ));
+        for report in post_reports {
+            let reason: String = report
+                .reason
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .chars()
+                .take(DIGEST_REASON_LENGTH)
+                .collect();
+            message.push_str(&format!("- {}: {}\n", report.reporter, reason));
+        }
+    }
+    message.push_str(&format!(
+        "\nReview them at {}/moderation\n",
+        config.base_url
+    ));
+    message
+}
+
+/// Posts the digest as JSON, with the message as both `content` for Discord and `text` for Slack-compatible hooks.
+async fn send_webhook(
+    url: &str,
+    reports: &[PendingPostReport],
+    message: &str,
+) -> Result<(), SameyError> {
+    let content: String = message
+        .chars()
+        .take(DIGEST_WEBHOOK_MESSAGE_LENGTH)
+        .collect();
+    reqwest::Client::new()
+        .post(url)
+        .json(&json!({
+            "content": content,
+            "text": message,
+            "allowed_mentions": { "parse": [] },
+            "pending_reports": reports.iter().map(|report| json!({
+                "id": report.id,
+                "post_id": report.post_id,
+                "reporter": report.reporter,
+                "reason": report.reason,
+                "created_at": report.created_at,
+            })).collect::<Vec<_>>(),
+        }))
+        .timeout(DIGEST_WEBHOOK_TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| SameyError::Other(format!("Digest webhook failed: {}", err)))?;
+    Ok(())
+}
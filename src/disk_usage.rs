@@ -0,0 +1,219 @@
+use std::path::Path;
+
+use samey_migration::Expr;
+use sea_orm::{
+    ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, FromQueryResult, IntoIdentity,
+    QueryOrder, QuerySelect, RelationTrait,
+};
+
+use crate::{
+    SameyError,
+    content::thumbnail_file_names,
+    entities::{
+        prelude::{SameyPost, SameyTag, SameyTagPost},
+        samey_post, samey_tag, samey_tag_post, samey_user,
+    },
+};
+
+/// SQL for the bytes taken by a post in a [`SameyPost`] query, including the attachments in their own table.
+pub(crate) const POST_DISK_USAGE_SQL: &str = "(COALESCE(\"samey_post\".\"stored_size\", 0) + (SELECT COALESCE(SUM(\"samey_post_attachment\".\"size\"), 0) FROM \"samey_post_attachment\" WHERE \"samey_post_attachment\".\"post_id\" = \"samey_post\".\"id\"))";
+
+#[derive(Debug, FromQueryResult)]
+pub(crate) struct UploaderDiskUsage {
+    pub(crate) username: String,
+    pub(crate) post_count: i64,
+    pub(crate) disk_usage: i64,
+}
+
+#[derive(Debug, FromQueryResult)]
+pub(crate) struct TagDiskUsage {
+    pub(crate) name: String,
+    pub(crate) post_count: i64,
+    pub(crate) disk_usage: i64,
+}
+
+async fn file_size(path: &Path) -> u64 {
+    tokio::fs::metadata(path)
+        .await
+        .map_or(0, |metadata| metadata.len())
+}
+
+/// Adds up the size of the files directly inside of a directory, such as the segments of an HLS stream.
+async fn directory_size(path: &Path) -> Result<u64, SameyError> {
+    let mut entries = match tokio::fs::read_dir(path).await {
+        Ok(entries) => entries,
+        Err(_) => return Ok(0),
+    };
+    let mut size = 0;
+    while let Some(entry) = entries.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        if metadata.is_file() {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
+/// Returns how many bytes the files of a post take in the files directory.
+///
+/// This covers its media, thumbnails, attachment and HLS stream, but not the files in the attachments table, which
+/// keep track of their own size.
+pub(crate) async fn stored_size(
+    files_dir: &Path,
+    media: &str,
+    thumbnail: &str,
+    attachment: Option<&str>,
+    stream_playlist: Option<&str>,
+) -> Result<i64, SameyError> {
+    let mut size = file_size(&files_dir.join(media)).await;
+    for file_name in thumbnail_file_names(thumbnail) {
+        size += file_size(&files_dir.join(file_name)).await;
+    }
+    if let Some(attachment) = attachment {
+        size += file_size(&files_dir.join(attachment)).await;
+    }
+    if let Some(directory) = stream_playlist
+        .and_then(|playlist| Path::new(playlist).parent())
+        .filter(|directory| !directory.as_os_str().is_empty())
+    {
+        size += directory_size(&files_dir.join(directory)).await?;
+    }
+    Ok(size.try_into()?)
+}
+
+async fn post_stored_size(files_dir: &Path, post: &samey_post::Model) -> Result<i64, SameyError> {
+    stored_size(
+        files_dir,
+        &post.media,
+        &post.thumbnail,
+        post.attachment.as_deref(),
+        post.stream_playlist.as_deref(),
+    )
+    .await
+}
+
+/// Stores how many bytes the files of a post take, after they were added or changed.
+pub(crate) async fn update_post_stored_size(
+    db: &DatabaseConnection,
+    files_dir: &Path,
+    post_id: i32,
+) -> Result<(), SameyError> {
+    let Some(post) = SameyPost::find_by_id(post_id).one(db).await? else {
+        return Ok(());
+    };
+    let stored_size = post_stored_size(files_dir, &post).await?;
+    SameyPost::update(samey_post::ActiveModel {
+        id: Set(post_id),
+        stored_size: Set(Some(stored_size)),
+        ..Default::default()
+    })
+    .exec(db)
+    .await?;
+    Ok(())
+}
+
+/// Helper function to measure the files of every post again, such as for posts from before disk usage was tracked.
+///
+/// Returns how many posts had a different size stored.
+///
+/// ```
+/// use samey::recompute_disk_usage;
+///
+/// # async fn _main() {
+/// let db = sea_orm::Database::connect("sqlite:db.sqlite3?mode=rwc").await.unwrap();
+/// let changed = recompute_disk_usage(db, "files")
+///     .await
+///     .expect("Unable to recompute disk usage");
+/// # }
+/// ```
+pub async fn recompute_disk_usage(
+    db: DatabaseConnection,
+    files_dir: impl AsRef<Path>,
+) -> Result<u64, SameyError> {
+    let files_dir = files_dir.as_ref();
+    let mut changed = 0;
+    for post in SameyPost::find().all(&db).await? {
+        let stored_size = post_stored_size(files_dir, &post).await?;
+        if post.stored_size == Some(stored_size) {
+            continue;
+        }
+        SameyPost::update(samey_post::ActiveModel {
+            id: Set(post.id),
+            stored_size: Set(Some(stored_size)),
+            ..Default::default()
+        })
+        .exec(&db)
+        .await?;
+        changed += 1;
+    }
+    Ok(changed)
+}
+
+/// Returns the uploaders taking the most disk space.
+pub(crate) async fn get_disk_usage_by_uploader(
+    db: &DatabaseConnection,
+    limit: u64,
+) -> Result<Vec<UploaderDiskUsage>, SameyError> {
+    Ok(SameyPost::find()
+        .select_only()
+        .column(samey_user::Column::Username)
+        .column_as(samey_post::Column::Id.count(), "post_count")
+        .column_as(
+            Expr::cust(format!("SUM({})", POST_DISK_USAGE_SQL)),
+            "disk_usage",
+        )
+        .join(
+            sea_orm::JoinType::InnerJoin,
+            samey_post::Relation::SameyUser.def(),
+        )
+        .group_by(samey_user::Column::Id)
+        .order_by_desc(Expr::col("disk_usage".into_identity()))
+        .order_by_asc(samey_user::Column::Username)
+        .limit(limit)
+        .into_model::<UploaderDiskUsage>()
+        .all(db)
+        .await?)
+}
+
+/// Returns the tags whose posts take the most disk space.
+///
+/// Posts with several tags count towards each of them, so these don't add up to the total.
+pub(crate) async fn get_disk_usage_by_tag(
+    db: &DatabaseConnection,
+    limit: u64,
+) -> Result<Vec<TagDiskUsage>, SameyError> {
+    Ok(SameyTag::find()
+        .select_only()
+        .column(samey_tag::Column::Name)
+        .column_as(samey_tag_post::Column::Id.count(), "post_count")
+        .column_as(
+            Expr::cust(format!("SUM({})", POST_DISK_USAGE_SQL)),
+            "disk_usage",
+        )
+        .inner_join(SameyTagPost)
+        .join(
+            sea_orm::JoinType::InnerJoin,
+            samey_tag_post::Relation::SameyPost.def(),
+        )
+        .group_by(samey_tag::Column::Id)
+        .order_by_desc(Expr::col("disk_usage".into_identity()))
+        .order_by_asc(samey_tag::Column::Name)
+        .limit(limit)
+        .into_model::<TagDiskUsage>()
+        .all(db)
+        .await?)
+}
+
+/// Returns how many bytes all posts take, including their attachments.
+pub(crate) async fn get_total_disk_usage(db: &DatabaseConnection) -> Result<i64, SameyError> {
+    Ok(SameyPost::find()
+        .select_only()
+        .column_as(
+            Expr::cust(format!("COALESCE(SUM({}), 0)", POST_DISK_USAGE_SQL)),
+            "disk_usage",
+        )
+        .into_tuple::<i64>()
+        .one(db)
+        .await?
+        .unwrap_or_default())
+}
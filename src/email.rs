@@ -0,0 +1,43 @@
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor, message::Mailbox,
+    message::header::ContentType,
+};
+
+use crate::{SameyError, config::AppConfig};
+
+/// Checks that text is a valid email address, optionally with a name like `Samey <samey@example.com>`.
+pub(crate) fn parse_mailbox(mailbox: &str) -> Result<Mailbox, SameyError> {
+    mailbox
+        .parse()
+        .map_err(|_| SameyError::BadRequest(format!("Invalid email address \"{}\"", mailbox)))
+}
+
+/// Sends a plain text email to each recipient with the configured SMTP server.
+pub(crate) async fn send_email(
+    app_config: &AppConfig,
+    recipients: &[String],
+    subject: &str,
+    body: String,
+) -> Result<(), SameyError> {
+    if !app_config.email_enabled() {
+        return Err(SameyError::Other("Email is not configured".into()));
+    }
+    let mut message = Message::builder()
+        .from(parse_mailbox(&app_config.smtp_from)?)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN);
+    for recipient in recipients {
+        message = message.to(parse_mailbox(recipient)?);
+    }
+    let message = message
+        .body(body)
+        .map_err(|err| SameyError::Other(format!("Unable to build email: {}", err)))?;
+
+    AsyncSmtpTransport::<Tokio1Executor>::from_url(&app_config.smtp_url)
+        .map_err(|err| SameyError::Other(format!("Invalid SMTP URL: {}", err)))?
+        .build()
+        .send(message)
+        .await
+        .map_err(|err| SameyError::Other(format!("Unable to send email: {}", err)))?;
+    Ok(())
+}
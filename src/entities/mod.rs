@@ -2,12 +2,25 @@
 
 pub mod prelude;
 
+pub mod samey_audit_log;
+pub mod samey_change;
 pub mod samey_config;
+pub mod samey_crosspost;
+pub mod samey_follower;
+pub mod samey_idempotency_key;
+pub mod samey_integration;
+pub mod samey_notification;
+pub mod samey_notification_setting;
 pub mod samey_pool;
 pub mod samey_pool_post;
+pub mod samey_popular_post;
 pub mod samey_post;
+pub mod samey_post_attachment;
+pub mod samey_post_report;
 pub mod samey_post_source;
+pub mod samey_post_view;
 pub mod samey_session;
 pub mod samey_tag;
 pub mod samey_tag_post;
+pub mod samey_takedown_request;
 pub mod samey_user;
@@ -1,11 +1,24 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.8
 
+pub use super::samey_audit_log::Entity as SameyAuditLog;
+pub use super::samey_change::Entity as SameyChange;
 pub use super::samey_config::Entity as SameyConfig;
+pub use super::samey_crosspost::Entity as SameyCrosspost;
+pub use super::samey_follower::Entity as SameyFollower;
+pub use super::samey_idempotency_key::Entity as SameyIdempotencyKey;
+pub use super::samey_integration::Entity as SameyIntegration;
+pub use super::samey_notification::Entity as SameyNotification;
+pub use super::samey_notification_setting::Entity as SameyNotificationSetting;
 pub use super::samey_pool::Entity as SameyPool;
 pub use super::samey_pool_post::Entity as SameyPoolPost;
+pub use super::samey_popular_post::Entity as SameyPopularPost;
 pub use super::samey_post::Entity as SameyPost;
+pub use super::samey_post_attachment::Entity as SameyPostAttachment;
+pub use super::samey_post_report::Entity as SameyPostReport;
 pub use super::samey_post_source::Entity as SameyPostSource;
+pub use super::samey_post_view::Entity as SameyPostView;
 pub use super::samey_session::Entity as SameySession;
 pub use super::samey_tag::Entity as SameyTag;
 pub use super::samey_tag_post::Entity as SameyTagPost;
+pub use super::samey_takedown_request::Entity as SameyTakedownRequest;
 pub use super::samey_user::Entity as SameyUser;
@@ -1,6 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.8
 
-use sea_orm::entity::prelude::*;
+use sea_orm::{ActiveValue::Set, entity::prelude::*};
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "samey_pool")]
@@ -11,6 +11,8 @@ pub struct Model {
     pub name: String,
     pub uploader_id: i32,
     pub is_public: bool,
+    pub updated_at: Option<DateTime>,
+    pub created_at: Option<DateTime>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -39,4 +41,17 @@ impl Related<super::samey_user::Entity> for Entity {
     }
 }
 
-impl ActiveModelBehavior for ActiveModel {}
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let now = chrono::Utc::now().naive_utc();
+        self.updated_at = Set(Some(now));
+        if insert {
+            self.created_at = Set(Some(now));
+        }
+        Ok(self)
+    }
+}
@@ -1,6 +1,6 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.8
 
-use sea_orm::entity::prelude::*;
+use sea_orm::{ActiveValue::Set, entity::prelude::*};
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "samey_post")]
@@ -24,12 +24,36 @@ pub struct Model {
     pub rating: String,
     pub uploaded_at: DateTime,
     pub parent_id: Option<i32>,
+    pub is_locked: bool,
+    pub original_filename: Option<String>,
+    pub virus_scanned_at: Option<DateTime>,
+    pub version: i32,
+    pub updated_at: Option<DateTime>,
+    pub view_count: i32,
+    pub translation_language: Option<String>,
+    pub translated_title: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub translated_description: Option<String>,
+    pub thumbnail_variants: bool,
+    pub stream_playlist: Option<String>,
+    pub attachment: Option<String>,
+    pub stored_size: Option<i64>,
+    pub publish_at: Option<DateTime>,
+    pub expires_at: Option<DateTime>,
+    pub is_draft: bool,
+    pub taken_down_at: Option<DateTime>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::samey_crosspost::Entity")]
+    SameyCrosspost,
+    #[sea_orm(has_many = "super::samey_notification::Entity")]
+    SameyNotification,
     #[sea_orm(has_many = "super::samey_pool_post::Entity")]
     SameyPoolPost,
+    #[sea_orm(has_many = "super::samey_popular_post::Entity")]
+    SameyPopularPost,
     #[sea_orm(
         belongs_to = "Entity",
         from = "Column::ParentId",
@@ -38,10 +62,18 @@ pub enum Relation {
         on_delete = "SetNull"
     )]
     SelfRef,
+    #[sea_orm(has_many = "super::samey_post_attachment::Entity")]
+    SameyPostAttachment,
+    #[sea_orm(has_many = "super::samey_post_report::Entity")]
+    SameyPostReport,
     #[sea_orm(has_many = "super::samey_post_source::Entity")]
     SameyPostSource,
+    #[sea_orm(has_many = "super::samey_post_view::Entity")]
+    SameyPostView,
     #[sea_orm(has_many = "super::samey_tag_post::Entity")]
     SameyTagPost,
+    #[sea_orm(has_many = "super::samey_takedown_request::Entity")]
+    SameyTakedownRequest,
     #[sea_orm(
         belongs_to = "super::samey_user::Entity",
         from = "Column::UploaderId",
@@ -52,28 +84,79 @@ pub enum Relation {
     SameyUser,
 }
 
+impl Related<super::samey_crosspost::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SameyCrosspost.def()
+    }
+}
+
+impl Related<super::samey_notification::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SameyNotification.def()
+    }
+}
+
 impl Related<super::samey_pool_post::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::SameyPoolPost.def()
     }
 }
 
+impl Related<super::samey_popular_post::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SameyPopularPost.def()
+    }
+}
+
+impl Related<super::samey_post_attachment::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SameyPostAttachment.def()
+    }
+}
+
+impl Related<super::samey_post_report::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SameyPostReport.def()
+    }
+}
+
 impl Related<super::samey_post_source::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::SameyPostSource.def()
     }
 }
 
+impl Related<super::samey_post_view::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SameyPostView.def()
+    }
+}
+
 impl Related<super::samey_tag_post::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::SameyTagPost.def()
     }
 }
 
+impl Related<super::samey_takedown_request::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::SameyTakedownRequest.def()
+    }
+}
+
 impl Related<super::samey_user::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::SameyUser.def()
     }
 }
 
-impl ActiveModelBehavior for ActiveModel {}
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(mut self, _db: &C, _insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        self.updated_at = Set(Some(chrono::Utc::now().naive_utc()));
+        Ok(self)
+    }
+}
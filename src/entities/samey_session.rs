@@ -11,6 +11,7 @@ pub struct Model {
     pub session_id: String,
     pub data: Json,
     pub expiry_date: i64,
+    pub user_id: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -11,6 +11,17 @@ pub struct Model {
     pub username: String,
     pub password: String,
     pub is_admin: bool,
+    pub timezone: Option<String>,
+    pub locale: Option<String>,
+    pub posts_per_page: Option<i32>,
+    pub thumbnail_density: Option<String>,
+    pub autoplay_videos: bool,
+    pub last_login_at: Option<DateTime>,
+    pub is_disabled: bool,
+    pub created_at: Option<DateTime>,
+    pub avatar: Option<String>,
+    #[sea_orm(unique)]
+    pub rss_token_hash: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -1,8 +1,12 @@
+use std::error::Error;
+
 use askama::Template;
 use axum::{
+    extract::multipart::MultipartError,
     http::StatusCode,
     response::{Html, IntoResponse, Response},
 };
+use tower_http::timeout::TimeoutError;
 
 #[derive(askama::Template)]
 #[template(path = "pages/bad_request.html")]
@@ -10,6 +14,12 @@ struct BadRequestTemplate<'a> {
     error: &'a str,
 }
 
+#[derive(askama::Template)]
+#[template(path = "pages/conflict.html")]
+struct ConflictTemplate<'a> {
+    error: &'a str,
+}
+
 #[derive(askama::Template)]
 #[template(path = "pages/unauthorized.html")]
 struct UnauthorizedTemplate;
@@ -22,6 +32,18 @@ struct ForbiddenTemplate;
 #[template(path = "pages/not_found.html")]
 struct NotFoundTemplate;
 
+#[derive(askama::Template)]
+#[template(path = "pages/read_only.html")]
+struct ReadOnlyTemplate;
+
+#[derive(askama::Template)]
+#[template(path = "pages/request_timeout.html")]
+struct RequestTimeoutTemplate;
+
+#[derive(askama::Template)]
+#[template(path = "pages/payload_too_large.html")]
+struct PayloadTooLargeTemplate;
+
 #[derive(askama::Template)]
 #[template(path = "pages/internal_server_error.html")]
 struct InternalServerErrorTemplate;
@@ -49,7 +71,7 @@ pub enum SameyError {
     Database(#[from] sea_orm::error::DbErr),
     /// File streaming error.
     #[error("File streaming error: {0}")]
-    Multipart(#[from] axum::extract::multipart::MultipartError),
+    Multipart(axum::extract::multipart::MultipartError),
     /// Image error.
     #[error("Image error: {0}")]
     Image(#[from] image::ImageError),
@@ -65,6 +87,21 @@ pub enum SameyError {
     /// Bad request.
     #[error("Bad request: {0}")]
     BadRequest(String),
+    /// Conflicting request.
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    /// The database schema is missing migrations.
+    #[error("Database has pending migrations: {}", .0.join(", "))]
+    PendingMigrations(Vec<String>),
+    /// The request took too long, or its body was sent too slowly.
+    #[error("Request timeout")]
+    RequestTimeout,
+    /// The request body is over the size limit.
+    #[error("Payload too large")]
+    PayloadTooLarge,
+    /// Instance is in read-only mode.
+    #[error("Read-only mode")]
+    ReadOnly,
     /// Custom internal error.
     #[error("Internal error: {0}")]
     Other(String),
@@ -80,6 +117,7 @@ impl IntoResponse for SameyError {
             | SameyError::Render(_)
             | SameyError::Database(_)
             | SameyError::Image(_)
+            | SameyError::PendingMigrations(_)
             | SameyError::Other(_) => {
                 println!("Internal server error - {:?}", &self);
                 (
@@ -112,6 +150,15 @@ impl IntoResponse for SameyError {
                 ),
             )
                 .into_response(),
+            SameyError::Conflict(error) => (
+                StatusCode::CONFLICT,
+                Html(
+                    ConflictTemplate { error }
+                        .render()
+                        .expect("shouldn't fail to render ConflictTemplate"),
+                ),
+            )
+                .into_response(),
             SameyError::NotFound => (
                 StatusCode::NOT_FOUND,
                 Html(
@@ -139,6 +186,50 @@ impl IntoResponse for SameyError {
                 ),
             )
                 .into_response(),
+            SameyError::ReadOnly => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Html(
+                    ReadOnlyTemplate {}
+                        .render()
+                        .expect("shouldn't fail to render ReadOnlyTemplate"),
+                ),
+            )
+                .into_response(),
+            SameyError::RequestTimeout => (
+                StatusCode::REQUEST_TIMEOUT,
+                Html(
+                    RequestTimeoutTemplate {}
+                        .render()
+                        .expect("shouldn't fail to render RequestTimeoutTemplate"),
+                ),
+            )
+                .into_response(),
+            SameyError::PayloadTooLarge => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Html(
+                    PayloadTooLargeTemplate {}
+                        .render()
+                        .expect("shouldn't fail to render PayloadTooLargeTemplate"),
+                ),
+            )
+                .into_response(),
+        }
+    }
+}
+
+impl From<MultipartError> for SameyError {
+    fn from(error: MultipartError) -> Self {
+        if error.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            return SameyError::PayloadTooLarge;
+        }
+        // Body read timeouts are buried in the multipart stream error
+        let mut source = error.source();
+        while let Some(inner) = source {
+            if inner.is::<TimeoutError>() {
+                return SameyError::RequestTimeout;
+            }
+            source = inner.source();
         }
+        SameyError::Multipart(error)
     }
 }
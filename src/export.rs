@@ -0,0 +1,481 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use askama::Template;
+use chrono::NaiveDateTime;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder};
+
+use crate::{
+    Asset, SameyError,
+    config::AppConfig,
+    content::thumbnail_file_names,
+    entities::{
+        prelude::{SameyPool, SameyPoolPost, SameyPost, SameyPostSource, SameyTag, SameyTagPost},
+        samey_pool, samey_pool_post, samey_post, samey_tag,
+    },
+    query::published_posts_condition,
+    render::{MarkdownLinks, render_markdown_with_links},
+};
+
+/// How many posts are listed on each page of the exported post lists.
+const EXPORT_POSTS_PER_PAGE: usize = 100;
+/// Stylesheets copied from the embedded assets.
+const EXPORT_STYLESHEETS: [&str; 2] = ["water.css", "samey.css"];
+
+/// What was written by [`export_static`].
+#[derive(Debug, Default)]
+pub struct ExportReport {
+    /// How many posts were exported.
+    pub posts: usize,
+    /// How many pools were exported.
+    pub pools: usize,
+    /// How many tags were exported.
+    pub tags: usize,
+    /// Files of exported posts that couldn't be found in the files directory, sorted by name.
+    pub missing_files: Vec<String>,
+}
+
+/// Post shown in a list of thumbnails.
+#[derive(Clone)]
+struct ExportPostCard {
+    id: i32,
+    thumbnail: String,
+    rating: String,
+    media_type: String,
+    tags: String,
+}
+
+struct ExportTagLink {
+    id: i32,
+    name: String,
+    post_count: usize,
+}
+
+struct ExportPoolLink {
+    id: i32,
+    name: String,
+}
+
+#[derive(Template)]
+#[template(path = "export/posts.html")]
+struct ExportPostsTemplate<'a> {
+    application_name: &'a str,
+    root: String,
+    title: &'a str,
+    posts: &'a [ExportPostCard],
+    page: usize,
+    page_links: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "export/post.html")]
+struct ExportPostTemplate<'a> {
+    application_name: &'a str,
+    root: String,
+    post: &'a samey_post::Model,
+    description: Option<String>,
+    rating_label: String,
+    tags: Vec<&'a ExportTagLink>,
+    sources: Vec<&'a str>,
+    pools: Vec<&'a ExportPoolLink>,
+    parent_id: Option<i32>,
+    uploaded_at: String,
+}
+
+#[derive(Template)]
+#[template(path = "export/pools.html")]
+struct ExportPoolsTemplate<'a> {
+    application_name: &'a str,
+    root: String,
+    pools: &'a [ExportPoolLink],
+}
+
+#[derive(Template)]
+#[template(path = "export/tags.html")]
+struct ExportTagsTemplate<'a> {
+    application_name: &'a str,
+    root: String,
+    tags: &'a [ExportTagLink],
+}
+
+/// Links from exported post descriptions to the other exported pages.
+struct ExportLinks<'a> {
+    thumbnails: &'a HashMap<i32, String>,
+    tag_ids: &'a HashMap<String, i32>,
+}
+
+impl MarkdownLinks for ExportLinks<'_> {
+    fn post(&self, post_id: i32) -> String {
+        format!("../post/{}.html", post_id)
+    }
+
+    fn post_thumbnail(&self, post_id: i32) -> String {
+        match self.thumbnails.get(&post_id) {
+            Some(thumbnail) => format!("../files/{}", thumbnail),
+            None => String::new(),
+        }
+    }
+
+    fn tag(&self, tag: &str) -> String {
+        match self.tag_ids.get(&tag.to_lowercase()) {
+            Some(tag_id) => format!("../tag/{}.html", tag_id),
+            None => "../tags.html".into(),
+        }
+    }
+}
+
+/// Path of a page of posts, relative to the output directory.
+fn page_path(first_page: &str, subdirectory: &str, page: usize) -> String {
+    if page == 1 {
+        first_page.into()
+    } else {
+        format!("{}/{}.html", subdirectory, page)
+    }
+}
+
+async fn write_page(out_dir: &Path, path: &str, contents: String) -> Result<(), SameyError> {
+    let path = out_dir.join(path);
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(path, contents).await?;
+    Ok(())
+}
+
+/// Writes a list of posts over as many pages as needed.
+///
+/// The first page goes in `first_page`, and the others in `subdirectory`.
+async fn write_post_pages(
+    out_dir: &Path,
+    application_name: &str,
+    title: &str,
+    posts: &[ExportPostCard],
+    first_page: &str,
+    subdirectory: &str,
+) -> Result<(), SameyError> {
+    let page_count = posts.len().div_ceil(EXPORT_POSTS_PER_PAGE).max(1);
+    for page in 1..=page_count {
+        let path = page_path(first_page, subdirectory, page);
+        let root = "../".repeat(path.matches('/').count());
+        let page_links = (1..=page_count)
+            .map(|page| format!("{}{}", root, page_path(first_page, subdirectory, page)))
+            .collect();
+        let start = (page - 1) * EXPORT_POSTS_PER_PAGE;
+        let end = (start + EXPORT_POSTS_PER_PAGE).min(posts.len());
+        let contents = ExportPostsTemplate {
+            application_name,
+            root,
+            title,
+            posts: &posts[start..end],
+            page,
+            page_links,
+        }
+        .render()?;
+        write_page(out_dir, &path, contents).await?;
+    }
+    Ok(())
+}
+
+/// Copies a file into the `files` directory of the export, returning whether it existed.
+async fn copy_file(files_dir: &Path, out_dir: &Path, file_name: &str) -> Result<bool, SameyError> {
+    let source = files_dir.join(file_name);
+    if !tokio::fs::try_exists(&source).await? {
+        return Ok(false);
+    }
+    let destination = out_dir.join("files").join(file_name);
+    if let Some(parent) = destination.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::copy(source, destination).await?;
+    Ok(true)
+}
+
+/// Writes every published post, public pool and tag as static HTML pages, along with their media, so that the
+/// instance can be archived or served read-only without running the server.
+///
+/// Pages link to each other with relative paths, so the output directory can be served from anywhere or opened
+/// locally. Post attachments and HLS streams aren't included.
+///
+/// ```
+/// use samey::export_static;
+///
+/// # async fn _main() {
+/// let db = sea_orm::Database::connect("sqlite:db.sqlite3?mode=rwc").await.unwrap();
+/// let report = export_static(db, "files", "site")
+///     .await
+///     .expect("Unable to export static site");
+/// # }
+/// ```
+pub async fn export_static(
+    db: DatabaseConnection,
+    files_dir: impl AsRef<Path>,
+    out_dir: impl AsRef<Path>,
+) -> Result<ExportReport, SameyError> {
+    let files_dir = files_dir.as_ref();
+    let out_dir = out_dir.as_ref();
+    let app_config = AppConfig::new(&db).await?;
+    let application_name = app_config.application_name.as_str();
+    let mut report = ExportReport::default();
+
+    let posts = SameyPost::find()
+        .filter(published_posts_condition())
+        .order_by_desc(samey_post::Column::Id)
+        .all(&db)
+        .await?;
+    let post_ids: HashSet<i32> = posts.iter().map(|post| post.id).collect();
+    let thumbnails: HashMap<i32, String> = posts
+        .iter()
+        .map(|post| (post.id, post.thumbnail.clone()))
+        .collect();
+
+    // Everything is loaded at once and filtered here, since the exported posts may be too many to filter by in SQL
+    let mut tags_by_post: HashMap<i32, Vec<i32>> = HashMap::new();
+    let mut posts_by_tag: HashMap<i32, Vec<i32>> = HashMap::new();
+    for tag_post in SameyTagPost::find().all(&db).await? {
+        if post_ids.contains(&tag_post.post_id) {
+            tags_by_post
+                .entry(tag_post.post_id)
+                .or_default()
+                .push(tag_post.tag_id);
+            posts_by_tag
+                .entry(tag_post.tag_id)
+                .or_default()
+                .push(tag_post.post_id);
+        }
+    }
+    let tags: Vec<ExportTagLink> = SameyTag::find()
+        .order_by_asc(samey_tag::Column::Name)
+        .all(&db)
+        .await?
+        .into_iter()
+        .filter_map(|tag| {
+            let post_count = posts_by_tag.get(&tag.id)?.len();
+            Some(ExportTagLink {
+                id: tag.id,
+                name: tag.name,
+                post_count,
+            })
+        })
+        .collect();
+    let tags_by_id: HashMap<i32, &ExportTagLink> = tags.iter().map(|tag| (tag.id, tag)).collect();
+    let tag_ids: HashMap<String, i32> = tags
+        .iter()
+        .map(|tag| (tag.name.to_lowercase(), tag.id))
+        .collect();
+
+    let mut sources_by_post: HashMap<i32, Vec<String>> = HashMap::new();
+    for source in SameyPostSource::find().all(&db).await? {
+        if post_ids.contains(&source.post_id) {
+            sources_by_post
+                .entry(source.post_id)
+                .or_default()
+                .push(source.url);
+        }
+    }
+
+    let pools: Vec<ExportPoolLink> = SameyPool::find()
+        .filter(samey_pool::Column::IsPublic.eq(true))
+        .order_by_asc(samey_pool::Column::Name)
+        .all(&db)
+        .await?
+        .into_iter()
+        .map(|pool| ExportPoolLink {
+            id: pool.id,
+            name: pool.name,
+        })
+        .collect();
+    let pools_by_id: HashMap<i32, &ExportPoolLink> =
+        pools.iter().map(|pool| (pool.id, pool)).collect();
+    let mut posts_by_pool: HashMap<i32, Vec<i32>> = HashMap::new();
+    let mut pools_by_post: HashMap<i32, Vec<i32>> = HashMap::new();
+    for pool_post in SameyPoolPost::find()
+        .order_by_asc(samey_pool_post::Column::Position)
+        .all(&db)
+        .await?
+    {
+        if post_ids.contains(&pool_post.post_id) && pools_by_id.contains_key(&pool_post.pool_id) {
+            posts_by_pool
+                .entry(pool_post.pool_id)
+                .or_default()
+                .push(pool_post.post_id);
+            pools_by_post
+                .entry(pool_post.post_id)
+                .or_default()
+                .push(pool_post.pool_id);
+        }
+    }
+
+    let cards: HashMap<i32, ExportPostCard> = posts
+        .iter()
+        .map(|post| {
+            let tags = tags_by_post
+                .get(&post.id)
+                .into_iter()
+                .flatten()
+                .filter_map(|tag_id| tags_by_id.get(tag_id))
+                .map(|tag| tag.name.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            (
+                post.id,
+                ExportPostCard {
+                    id: post.id,
+                    thumbnail: post.thumbnail.clone(),
+                    rating: post.rating.clone(),
+                    media_type: post.media_type.clone(),
+                    tags,
+                },
+            )
+        })
+        .collect();
+    let cards_for = |post_ids: &[i32]| -> Vec<ExportPostCard> {
+        post_ids
+            .iter()
+            .filter_map(|post_id| cards.get(post_id))
+            .cloned()
+            .collect()
+    };
+
+    // Post pages, along with their files
+    let links = ExportLinks {
+        thumbnails: &thumbnails,
+        tag_ids: &tag_ids,
+    };
+    let mut missing_files = Vec::new();
+    for post in &posts {
+        let mut post_tags: Vec<&ExportTagLink> = tags_by_post
+            .get(&post.id)
+            .into_iter()
+            .flatten()
+            .filter_map(|tag_id| tags_by_id.get(tag_id).copied())
+            .collect();
+        post_tags.sort_by(|a, b| a.name.cmp(&b.name));
+        let contents = ExportPostTemplate {
+            application_name,
+            root: "../".into(),
+            post,
+            description: post
+                .description
+                .as_deref()
+                .map(|description| render_markdown_with_links(description, &links)),
+            rating_label: app_config.rating_label(&post.rating),
+            tags: post_tags,
+            sources: sources_by_post
+                .get(&post.id)
+                .into_iter()
+                .flatten()
+                .map(String::as_str)
+                .collect(),
+            pools: pools_by_post
+                .get(&post.id)
+                .into_iter()
+                .flatten()
+                .filter_map(|pool_id| pools_by_id.get(pool_id).copied())
+                .collect(),
+            parent_id: post
+                .parent_id
+                .filter(|parent_id| post_ids.contains(parent_id)),
+            uploaded_at: format_datetime(&post.uploaded_at),
+        }
+        .render()?;
+        write_page(out_dir, &format!("post/{}.html", post.id), contents).await?;
+
+        if !copy_file(files_dir, out_dir, &post.media).await? {
+            missing_files.push(post.media.clone());
+        }
+        if !copy_file(files_dir, out_dir, &post.thumbnail).await? {
+            missing_files.push(post.thumbnail.clone());
+        }
+        // Thumbnail variants are optional, so the export only needs the ones that exist
+        for file_name in thumbnail_file_names(&post.thumbnail).skip(1) {
+            copy_file(files_dir, out_dir, &file_name).await?;
+        }
+    }
+    missing_files.sort();
+    report.missing_files = missing_files;
+    report.posts = posts.len();
+
+    // Post lists
+    let all_post_ids: Vec<i32> = posts.iter().map(|post| post.id).collect();
+    write_post_pages(
+        out_dir,
+        application_name,
+        "Posts",
+        &cards_for(&all_post_ids),
+        "index.html",
+        "posts",
+    )
+    .await?;
+
+    for tag in &tags {
+        let mut tag_post_ids = posts_by_tag.get(&tag.id).cloned().unwrap_or_default();
+        tag_post_ids.sort_by(|a, b| b.cmp(a));
+        write_post_pages(
+            out_dir,
+            application_name,
+            &tag.name,
+            &cards_for(&tag_post_ids),
+            &format!("tag/{}.html", tag.id),
+            &format!("tag/{}", tag.id),
+        )
+        .await?;
+    }
+    write_page(
+        out_dir,
+        "tags.html",
+        ExportTagsTemplate {
+            application_name,
+            root: String::new(),
+            tags: &tags,
+        }
+        .render()?,
+    )
+    .await?;
+    report.tags = tags.len();
+
+    for pool in &pools {
+        let pool_post_ids = posts_by_pool.get(&pool.id).cloned().unwrap_or_default();
+        write_post_pages(
+            out_dir,
+            application_name,
+            &pool.name,
+            &cards_for(&pool_post_ids),
+            &format!("pool/{}.html", pool.id),
+            &format!("pool/{}", pool.id),
+        )
+        .await?;
+    }
+    write_page(
+        out_dir,
+        "pools.html",
+        ExportPoolsTemplate {
+            application_name,
+            root: String::new(),
+            pools: &pools,
+        }
+        .render()?,
+    )
+    .await?;
+    report.pools = pools.len();
+
+    // Stylesheets, including the instance's own
+    for stylesheet in EXPORT_STYLESHEETS {
+        if let Some(content) = Asset::get(stylesheet) {
+            write_page(
+                out_dir,
+                &format!("static/{}", stylesheet),
+                String::from_utf8_lossy(&content.data).into_owned(),
+            )
+            .await?;
+        }
+    }
+    write_page(out_dir, "static/custom.css", app_config.theme_css()).await?;
+
+    Ok(report)
+}
+
+fn format_datetime(datetime: &NaiveDateTime) -> String {
+    datetime.format("%Y-%m-%d %H:%M UTC").to_string()
+}
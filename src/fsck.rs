@@ -0,0 +1,212 @@
+use std::{
+    collections::HashSet,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use sea_orm::{ActiveValue::Set, DatabaseConnection, EntityTrait, QuerySelect};
+
+use crate::{
+    SameyError,
+    config::AppConfig,
+    content::{generate_thumbnails, thumbnail_file_names},
+    entities::{prelude::SameyPost, samey_post},
+    thumbnailer::{Thumbnailer, read_image_dimensions},
+};
+
+/// Files that may be in the files directory without belonging to a post.
+const SITE_FILES: [&str; 2] = ["favicon.ico", "favicon.png"];
+/// Files modified more recently than this are never considered orphaned, since they may belong to an upload in progress.
+const ORPHAN_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+/// Which problems [`fsck`] should try to fix.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FsckOptions {
+    /// Delete files that don't belong to any post.
+    pub remove_orphaned_files: bool,
+    /// Generate thumbnails again for posts whose media still exists.
+    pub regenerate_thumbnails: bool,
+}
+
+/// Problems found by [`fsck`].
+#[derive(Debug, Default)]
+pub struct FsckReport {
+    /// Files that don't belong to any post, sorted by name.
+    pub orphaned_files: Vec<String>,
+    /// IDs of posts whose media file is missing.
+    pub missing_media: Vec<i32>,
+    /// IDs of posts whose thumbnail is missing.
+    pub missing_thumbnails: Vec<i32>,
+    /// IDs of posts whose thumbnail was generated again.
+    pub regenerated_thumbnails: Vec<i32>,
+    /// Whether orphaned files were deleted.
+    pub removed_orphaned_files: bool,
+}
+
+impl FsckReport {
+    pub fn is_clean(&self) -> bool {
+        self.orphaned_files.is_empty()
+            && self.missing_media.is_empty()
+            && self.missing_thumbnails.len() == self.regenerated_thumbnails.len()
+    }
+}
+
+fn is_site_file(file_name: &str) -> bool {
+    SITE_FILES.contains(&file_name)
+        || (file_name.starts_with("icon-") && file_name.ends_with(".png"))
+}
+
+/// Cross-checks the files directory against the database.
+///
+/// Without any [`FsckOptions`], this only reports problems without changing anything.
+///
+/// ```
+/// use samey::{DefaultThumbnailer, FsckOptions, fsck};
+///
+/// # async fn _main() {
+/// let db = sea_orm::Database::connect("sqlite:db.sqlite3?mode=rwc").await.unwrap();
+/// let report = fsck(db, "files", &DefaultThumbnailer::new(), FsckOptions::default())
+///     .await
+///     .expect("Unable to check files");
+/// # }
+/// ```
+pub async fn fsck(
+    db: DatabaseConnection,
+    files_dir: impl AsRef<Path>,
+    thumbnailer: &dyn Thumbnailer,
+    options: FsckOptions,
+) -> Result<FsckReport, SameyError> {
+    let files_dir = files_dir.as_ref();
+    let thumbnail_dimension = AppConfig::new(&db).await?.thumbnail_dimension;
+    let posts: Vec<(i32, String, String, String, Option<String>)> = SameyPost::find()
+        .select_only()
+        .columns([
+            samey_post::Column::Id,
+            samey_post::Column::Media,
+            samey_post::Column::MediaType,
+            samey_post::Column::Thumbnail,
+            samey_post::Column::Attachment,
+        ])
+        .into_tuple()
+        .all(&db)
+        .await?;
+
+    let mut report = FsckReport::default();
+    let mut known_files = HashSet::with_capacity(posts.len() * 4);
+    for (post_id, media, media_type, thumbnail, attachment) in posts {
+        let media_path = files_dir.join(&media);
+        let media_exists = tokio::fs::try_exists(&media_path).await?;
+        if !media_exists {
+            report.missing_media.push(post_id);
+        }
+        // Posts from before thumbnail variants were added also count as missing thumbnails
+        let mut thumbnails_exist = true;
+        for file_name in thumbnail_file_names(&thumbnail) {
+            thumbnails_exist &= tokio::fs::try_exists(files_dir.join(&file_name)).await?;
+            known_files.insert(file_name);
+        }
+        if !thumbnails_exist {
+            report.missing_thumbnails.push(post_id);
+            if media_exists && options.regenerate_thumbnails {
+                let dimensions = generate_thumbnails(
+                    thumbnailer,
+                    &media_path,
+                    media_type == "video",
+                    files_dir,
+                    &thumbnail,
+                    thumbnail_dimension,
+                )
+                .await;
+                if let Ok(dimensions) = dimensions {
+                    SameyPost::update(samey_post::ActiveModel {
+                        id: Set(post_id),
+                        thumbnail_width: Set(dimensions.thumbnail_width.try_into()?),
+                        thumbnail_height: Set(dimensions.thumbnail_height.try_into()?),
+                        thumbnail_variants: Set(true),
+                        ..Default::default()
+                    })
+                    .exec(&db)
+                    .await?;
+                    report.regenerated_thumbnails.push(post_id);
+                }
+            }
+        }
+        known_files.insert(media);
+        known_files.extend(attachment);
+    }
+
+    let grace_period_start = SystemTime::now() - ORPHAN_GRACE_PERIOD;
+    let mut entries = tokio::fs::read_dir(files_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        if !metadata.is_file() || metadata.modified()? > grace_period_start {
+            continue;
+        }
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if known_files.contains(&file_name) || is_site_file(&file_name) {
+            continue;
+        }
+        if options.remove_orphaned_files {
+            tokio::fs::remove_file(entry.path()).await?;
+        }
+        report.orphaned_files.push(file_name);
+    }
+    report.orphaned_files.sort();
+    report.removed_orphaned_files = options.remove_orphaned_files;
+
+    Ok(report)
+}
+
+/// Corrects the stored thumbnail dimensions of every post from the thumbnail files on disk.
+///
+/// Returns how many posts were changed. Posts whose thumbnail is missing or can't be read are skipped.
+///
+/// ```
+/// use samey::fix_thumbnail_dimensions;
+///
+/// # async fn _main() {
+/// let db = sea_orm::Database::connect("sqlite:db.sqlite3?mode=rwc").await.unwrap();
+/// let changed = fix_thumbnail_dimensions(db, "files")
+///     .await
+///     .expect("Unable to fix thumbnail dimensions");
+/// # }
+/// ```
+pub async fn fix_thumbnail_dimensions(
+    db: DatabaseConnection,
+    files_dir: impl AsRef<Path>,
+) -> Result<u64, SameyError> {
+    let files_dir = files_dir.as_ref();
+    let posts: Vec<(i32, String, i32, i32)> = SameyPost::find()
+        .select_only()
+        .columns([
+            samey_post::Column::Id,
+            samey_post::Column::Thumbnail,
+            samey_post::Column::ThumbnailWidth,
+            samey_post::Column::ThumbnailHeight,
+        ])
+        .into_tuple()
+        .all(&db)
+        .await?;
+
+    let mut changed = 0;
+    for (post_id, thumbnail, thumbnail_width, thumbnail_height) in posts {
+        let Ok((width, height)) = read_image_dimensions(&files_dir.join(&thumbnail)).await else {
+            continue;
+        };
+        let (width, height): (i32, i32) = (width.try_into()?, height.try_into()?);
+        if (width, height) == (thumbnail_width, thumbnail_height) {
+            continue;
+        }
+        SameyPost::update(samey_post::ActiveModel {
+            id: Set(post_id),
+            thumbnail_width: Set(width),
+            thumbnail_height: Set(height),
+            ..Default::default()
+        })
+        .exec(&db)
+        .await?;
+        changed += 1;
+    }
+
+    Ok(changed)
+}
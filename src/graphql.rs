@@ -0,0 +1,478 @@
+use std::sync::LazyLock;
+
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Guard, Object, Result, Schema,
+    connection::{Connection, Edge, query},
+};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::extract::State;
+use chrono::NaiveDateTime;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+
+use crate::{
+    AppState,
+    auth::{AuthSession, User},
+    entities::{
+        prelude::{SameyPool, SameyPost, SameyPostSource, SameyTag, SameyUser},
+        samey_pool, samey_post, samey_post_source, samey_tag, samey_user,
+    },
+    query::{
+        filter_pools_by_user, filter_posts_by_user, get_posts_in_pool, get_tags_for_post,
+        search_posts_query,
+    },
+    search::SearchQuery,
+};
+
+const DEFAULT_PAGE_SIZE: usize = 20;
+const MAX_PAGE_SIZE: usize = 100;
+/// Limits on how deeply nested and how large queries can be, so that a single request can't load most of the database.
+const MAX_QUERY_DEPTH: usize = 10;
+const MAX_QUERY_COMPLEXITY: usize = 500;
+
+type SameySchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// The schema is the same for every request, which only differ in the data attached to them.
+static SCHEMA: LazyLock<SameySchema> = LazyLock::new(|| {
+    SameySchema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_QUERY_DEPTH)
+        .limit_complexity(MAX_QUERY_COMPLEXITY)
+        .finish()
+});
+
+pub(crate) async fn graphql(
+    State(AppState { db, .. }): State<AppState>,
+    auth_session: AuthSession,
+    request: GraphQLRequest,
+) -> GraphQLResponse {
+    SCHEMA
+        .execute(request.into_inner().data(db).data(auth_session.user))
+        .await
+        .into()
+}
+
+fn db<'a>(ctx: &Context<'a>) -> &'a DatabaseConnection {
+    ctx.data_unchecked::<DatabaseConnection>()
+}
+
+fn user<'a>(ctx: &Context<'a>) -> Option<&'a User> {
+    ctx.data_unchecked::<Option<User>>().as_ref()
+}
+
+fn page_size(first: Option<usize>) -> usize {
+    first.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE)
+}
+
+// Guards
+
+/// Only allows logged-in users.
+struct UserGuard;
+
+impl Guard for UserGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        match user(ctx) {
+            Some(_) => Ok(()),
+            None => Err("Forbidden".into()),
+        }
+    }
+}
+
+/// Only allows admins.
+struct AdminGuard;
+
+impl Guard for AdminGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        match user(ctx) {
+            Some(user) if user.is_admin => Ok(()),
+            _ => Err("Forbidden".into()),
+        }
+    }
+}
+
+/// Only allows admins, or the user that owns the object being resolved.
+struct OwnerGuard(i32);
+
+impl Guard for OwnerGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        match user(ctx) {
+            Some(user) if user.is_admin || user.id == self.0 => Ok(()),
+            _ => Err("Forbidden".into()),
+        }
+    }
+}
+
+// Objects
+
+pub(crate) struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// The currently logged-in user, if any.
+    async fn viewer(&self, ctx: &Context<'_>) -> Option<UserObject> {
+        user(ctx).map(|user| UserObject {
+            id: user.id,
+            username: user.username.clone(),
+            is_admin: user.is_admin,
+        })
+    }
+
+    async fn post(&self, ctx: &Context<'_>, id: i32) -> Result<Option<Post>> {
+        Ok(filter_posts_by_user(SameyPost::find_by_id(id), user(ctx))
+            .one(db(ctx))
+            .await?
+            .map(Post))
+    }
+
+    /// Searches posts, from newest to oldest.
+    async fn posts(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Space-separated search tags")] tags: Option<String>,
+        after: Option<String>,
+        first: Option<i32>,
+    ) -> Result<Connection<i32, Post>> {
+        query(after, None, first, None, |after, _, first, _| async move {
+            let page_size = page_size(first);
+            let mut search = search_posts_query(
+                &SearchQuery::parse(tags.as_deref().unwrap_or_default()),
+                user(ctx),
+            );
+            if let Some(after) = after {
+                search = search.filter(samey_post::Column::Id.lt(after));
+            }
+            let post_ids = search
+                .select_only()
+                .column(samey_post::Column::Id)
+                .order_by_desc(samey_post::Column::Id)
+                .limit(page_size as u64 + 1)
+                .into_tuple::<i32>()
+                .all(db(ctx))
+                .await?;
+            let has_next_page = post_ids.len() > page_size;
+            let posts = SameyPost::find()
+                .filter(samey_post::Column::Id.is_in(post_ids.into_iter().take(page_size)))
+                .order_by_desc(samey_post::Column::Id)
+                .all(db(ctx))
+                .await?;
+
+            let mut connection = Connection::new(after.is_some(), has_next_page);
+            connection
+                .edges
+                .extend(posts.into_iter().map(|post| Edge::new(post.id, Post(post))));
+            Ok::<_, async_graphql::Error>(connection)
+        })
+        .await
+    }
+
+    async fn tag(&self, ctx: &Context<'_>, name: String) -> Result<Option<Tag>> {
+        Ok(SameyTag::find()
+            .filter(samey_tag::Column::NormalizedName.eq(name.to_lowercase()))
+            .one(db(ctx))
+            .await?
+            .map(Tag))
+    }
+
+    /// Lists all tags in alphabetical order.
+    async fn tags(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        first: Option<i32>,
+    ) -> Result<Connection<String, Tag>> {
+        query(after, None, first, None, |after, _, first, _| async move {
+            let page_size = page_size(first);
+            let mut search = SameyTag::find();
+            if let Some(after) = after.as_ref() {
+                search = search.filter(samey_tag::Column::NormalizedName.gt(after));
+            }
+            let tags = search
+                .order_by_asc(samey_tag::Column::NormalizedName)
+                .limit(page_size as u64 + 1)
+                .all(db(ctx))
+                .await?;
+
+            let mut connection = Connection::new(after.is_some(), tags.len() > page_size);
+            connection.edges.extend(
+                tags.into_iter()
+                    .take(page_size)
+                    .map(|tag| Edge::new(tag.normalized_name.clone(), Tag(tag))),
+            );
+            Ok::<_, async_graphql::Error>(connection)
+        })
+        .await
+    }
+
+    async fn pool(&self, ctx: &Context<'_>, id: i32) -> Result<Option<Pool>> {
+        Ok(filter_pools_by_user(SameyPool::find_by_id(id), user(ctx))
+            .one(db(ctx))
+            .await?
+            .map(Pool))
+    }
+
+    async fn pools(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        first: Option<i32>,
+    ) -> Result<Connection<i32, Pool>> {
+        query(after, None, first, None, |after, _, first, _| async move {
+            let page_size = page_size(first);
+            let mut search = filter_pools_by_user(SameyPool::find(), user(ctx));
+            if let Some(after) = after {
+                search = search.filter(samey_pool::Column::Id.gt(after));
+            }
+            let pools = search
+                .order_by_asc(samey_pool::Column::Id)
+                .limit(page_size as u64 + 1)
+                .all(db(ctx))
+                .await?;
+
+            let mut connection = Connection::new(after.is_some(), pools.len() > page_size);
+            connection.edges.extend(
+                pools
+                    .into_iter()
+                    .take(page_size)
+                    .map(|pool| Edge::new(pool.id, Pool(pool))),
+            );
+            Ok::<_, async_graphql::Error>(connection)
+        })
+        .await
+    }
+
+    #[graphql(guard = "UserGuard")]
+    async fn user(&self, ctx: &Context<'_>, id: i32) -> Result<Option<UserObject>> {
+        Ok(SameyUser::find_by_id(id)
+            .one(db(ctx))
+            .await?
+            .map(UserObject::from))
+    }
+
+    #[graphql(guard = "AdminGuard")]
+    async fn users(
+        &self,
+        ctx: &Context<'_>,
+        after: Option<String>,
+        first: Option<i32>,
+    ) -> Result<Connection<i32, UserObject>> {
+        query(after, None, first, None, |after, _, first, _| async move {
+            let page_size = page_size(first);
+            let mut search = SameyUser::find();
+            if let Some(after) = after {
+                search = search.filter(samey_user::Column::Id.gt(after));
+            }
+            let users = search
+                .order_by_asc(samey_user::Column::Id)
+                .limit(page_size as u64 + 1)
+                .all(db(ctx))
+                .await?;
+
+            let mut connection = Connection::new(after.is_some(), users.len() > page_size);
+            connection.edges.extend(
+                users
+                    .into_iter()
+                    .take(page_size)
+                    .map(|user| Edge::new(user.id, UserObject::from(user))),
+            );
+            Ok::<_, async_graphql::Error>(connection)
+        })
+        .await
+    }
+}
+
+pub(crate) struct Post(samey_post::Model);
+
+#[Object]
+impl Post {
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    async fn media(&self) -> String {
+        format!("/files/{}", self.0.media)
+    }
+
+    async fn width(&self) -> i32 {
+        self.0.width
+    }
+
+    async fn height(&self) -> i32 {
+        self.0.height
+    }
+
+    async fn thumbnail(&self) -> String {
+        format!("/files/{}", self.0.thumbnail)
+    }
+
+    async fn media_type(&self) -> &str {
+        &self.0.media_type
+    }
+
+    async fn rating(&self) -> &str {
+        &self.0.rating
+    }
+
+    async fn title(&self) -> Option<&str> {
+        self.0.title.as_deref()
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    async fn is_public(&self) -> bool {
+        self.0.is_public
+    }
+
+    async fn is_locked(&self) -> bool {
+        self.0.is_locked
+    }
+
+    async fn uploaded_at(&self) -> NaiveDateTime {
+        self.0.uploaded_at
+    }
+
+    /// The user who uploaded this post. Only visible to logged-in users.
+    #[graphql(guard = "UserGuard")]
+    async fn uploader(&self, ctx: &Context<'_>) -> Result<Option<UserObject>> {
+        Ok(SameyUser::find_by_id(self.0.uploader_id)
+            .one(db(ctx))
+            .await?
+            .map(UserObject::from))
+    }
+
+    async fn parent(&self, ctx: &Context<'_>) -> Result<Option<Post>> {
+        let Some(parent_id) = self.0.parent_id else {
+            return Ok(None);
+        };
+        Ok(
+            filter_posts_by_user(SameyPost::find_by_id(parent_id), user(ctx))
+                .one(db(ctx))
+                .await?
+                .map(Post),
+        )
+    }
+
+    async fn children(&self, ctx: &Context<'_>) -> Result<Vec<Post>> {
+        Ok(filter_posts_by_user(
+            SameyPost::find().filter(samey_post::Column::ParentId.eq(self.0.id)),
+            user(ctx),
+        )
+        .order_by_asc(samey_post::Column::Id)
+        .all(db(ctx))
+        .await?
+        .into_iter()
+        .map(Post)
+        .collect())
+    }
+
+    async fn tags(&self, ctx: &Context<'_>) -> Result<Vec<Tag>> {
+        Ok(get_tags_for_post(self.0.id)
+            .all(db(ctx))
+            .await?
+            .into_iter()
+            .map(Tag)
+            .collect())
+    }
+
+    async fn sources(&self, ctx: &Context<'_>) -> Result<Vec<String>> {
+        Ok(SameyPostSource::find()
+            .filter(samey_post_source::Column::PostId.eq(self.0.id))
+            .all(db(ctx))
+            .await?
+            .into_iter()
+            .map(|source| source.url)
+            .collect())
+    }
+}
+
+pub(crate) struct Tag(samey_tag::Model);
+
+#[Object]
+impl Tag {
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn is_protected(&self) -> bool {
+        self.0.is_protected
+    }
+}
+
+pub(crate) struct Pool(samey_pool::Model);
+
+#[Object]
+impl Pool {
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn is_public(&self) -> bool {
+        self.0.is_public
+    }
+
+    /// The user who created this pool. Only visible to logged-in users.
+    #[graphql(guard = "UserGuard")]
+    async fn uploader(&self, ctx: &Context<'_>) -> Result<Option<UserObject>> {
+        Ok(SameyUser::find_by_id(self.0.uploader_id)
+            .one(db(ctx))
+            .await?
+            .map(UserObject::from))
+    }
+
+    /// Posts in this pool, in order.
+    async fn posts(&self, ctx: &Context<'_>) -> Result<Vec<Post>> {
+        let post_ids = get_posts_in_pool(self.0.id, user(ctx))
+            .all(db(ctx))
+            .await?
+            .into_iter()
+            .map(|post| post.id)
+            .collect::<Vec<_>>();
+        let mut posts = SameyPost::find()
+            .filter(samey_post::Column::Id.is_in(post_ids.iter().copied()))
+            .all(db(ctx))
+            .await?;
+        posts.sort_by_key(|post| post_ids.iter().position(|id| *id == post.id));
+        Ok(posts.into_iter().map(Post).collect())
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct UserObject {
+    id: i32,
+    username: String,
+    is_admin: bool,
+}
+
+impl From<samey_user::Model> for UserObject {
+    fn from(user: samey_user::Model) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            is_admin: user.is_admin,
+        }
+    }
+}
+
+#[Object(name = "User")]
+impl UserObject {
+    async fn id(&self) -> i32 {
+        self.id
+    }
+
+    async fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Only visible to admins and to the user themselves.
+    #[graphql(guard = "OwnerGuard(self.id)")]
+    async fn is_admin(&self) -> bool {
+        self.is_admin
+    }
+}
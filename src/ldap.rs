@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use ldap3::{LdapConnAsync, LdapConnSettings, LdapError, Scope, SearchEntry, ldap_escape};
+
+use crate::{SameyError, config::LdapConfig};
+
+/// How long connecting to the directory and each of its operations may take.
+const LDAP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Result code of a bind with the wrong password.
+const LDAP_INVALID_CREDENTIALS: u32 = 49;
+
+/// User whose password was accepted by the directory.
+pub(crate) struct LdapUser {
+    /// Whether they are in the admin group, if one is configured.
+    pub(crate) is_admin: Option<bool>,
+}
+
+/// Checks a username and password against the directory.
+///
+/// Returns `None` if there isn't exactly one user for the filter, or if the password is wrong.
+pub(crate) async fn authenticate_ldap(
+    config: &LdapConfig,
+    username: &str,
+    password: &str,
+) -> Result<Option<LdapUser>, SameyError> {
+    // An empty password would be an unauthenticated bind, which most servers accept for any DN
+    if username.is_empty() || password.is_empty() {
+        return Ok(None);
+    }
+    authenticate(config, username, password)
+        .await
+        .map_err(|err| SameyError::Other(err.to_string()))
+}
+
+async fn authenticate(
+    config: &LdapConfig,
+    username: &str,
+    password: &str,
+) -> Result<Option<LdapUser>, LdapError> {
+    let (conn, mut ldap) = LdapConnAsync::with_settings(
+        LdapConnSettings::new().set_conn_timeout(LDAP_TIMEOUT),
+        &config.url,
+    )
+    .await?;
+    ldap3::drive!(conn);
+
+    if let Some(bind_dn) = &config.bind_dn {
+        ldap.with_timeout(LDAP_TIMEOUT)
+            .simple_bind(bind_dn, config.bind_password.as_deref().unwrap_or(""))
+            .await?
+            .success()?;
+    }
+    let filter = config
+        .user_filter
+        .replace("{username}", &ldap_escape(username));
+    let (entries, _) = ldap
+        .with_timeout(LDAP_TIMEOUT)
+        .search(&config.base_dn, Scope::Subtree, &filter, vec!["memberOf"])
+        .await?
+        .success()?;
+    let mut entries = entries.into_iter();
+    let (Some(entry), None) = (entries.next(), entries.next()) else {
+        ldap.unbind().await?;
+        return Ok(None);
+    };
+    let entry = SearchEntry::construct(entry);
+
+    let result = ldap
+        .with_timeout(LDAP_TIMEOUT)
+        .simple_bind(&entry.dn, password)
+        .await?;
+    ldap.unbind().await?;
+    if result.rc == LDAP_INVALID_CREDENTIALS {
+        return Ok(None);
+    }
+    result.success()?;
+
+    let is_admin = config.admin_group.as_ref().map(|admin_group| {
+        entry
+            .attrs
+            .get("memberOf")
+            .into_iter()
+            .flatten()
+            .any(|group| group.eq_ignore_ascii_case(admin_group))
+    });
+    Ok(Some(LdapUser { is_admin }))
+}
@@ -1,38 +1,110 @@
 //! Sam's small image board.
 
+pub(crate) mod activitypub;
+pub(crate) mod antivirus;
+pub(crate) mod api;
+pub(crate) mod attachments;
+pub(crate) mod audit_log;
 pub(crate) mod auth;
+pub(crate) mod auto_tagger;
+pub(crate) mod cache;
+pub(crate) mod captcha;
 pub(crate) mod config;
+pub(crate) mod content;
+pub(crate) mod context;
+pub(crate) mod crosspost;
+pub(crate) mod demo;
+pub(crate) mod digest;
+pub(crate) mod disk_usage;
+pub(crate) mod email;
 pub(crate) mod entities;
 pub(crate) mod error;
+pub(crate) mod export;
+pub(crate) mod fsck;
+pub(crate) mod graphql;
+pub(crate) mod ldap;
+pub(crate) mod notifications;
+pub(crate) mod permissions;
+pub(crate) mod popularity;
+pub(crate) mod preferences;
 pub(crate) mod query;
+pub(crate) mod render;
+pub(crate) mod schedule;
+pub(crate) mod search;
+pub(crate) mod source_lookup;
+pub(crate) mod sources;
+pub(crate) mod stats;
+pub(crate) mod streaming;
 pub(crate) mod tags;
+pub(crate) mod takedown;
+#[cfg(feature = "test-support")]
+pub mod test_support;
+pub(crate) mod thumbnailer;
+pub(crate) mod ugoira;
+pub(crate) mod validation;
 pub(crate) mod video;
 pub(crate) mod views;
 
 use std::{
     path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 
 use axum::{
     Router,
     extract::DefaultBodyLimit,
-    http::{StatusCode, header::CONTENT_TYPE},
+    http::{Extensions, HeaderMap, StatusCode, Version, header::CONTENT_TYPE},
+    middleware,
     response::IntoResponse,
     routing::{delete, get, post, put},
 };
 use axum_extra::routing::RouterExt;
 use axum_login::AuthManagerLayerBuilder;
+use chrono::{TimeDelta, Utc};
 use password_auth::generate_hash;
-use sea_orm::{ActiveValue::Set, DatabaseConnection, EntityTrait};
+use samey_migration::{Migrator, MigratorTrait, OnConflict};
+use sea_orm::{
+    ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect,
+};
 use tokio::{fs, sync::RwLock};
-use tower_http::services::ServeDir;
+use tower_http::{
+    compression::{
+        CompressionLayer,
+        predicate::{Predicate, SizeAbove},
+    },
+    services::ServeDir,
+    timeout::RequestBodyTimeoutLayer,
+};
 use tower_sessions::SessionManagerLayer;
 
-use crate::auth::{Backend, SessionStorage};
-use crate::config::AppConfig;
-use crate::entities::{prelude::SameyUser, samey_user};
+use crate::audit_log::{AuditAction, record_audit_log};
+use crate::auth::{Backend, SessionStorage, revoke_user_sessions};
+use crate::cache::ObjectCache;
+use crate::captcha::CaptchaState;
+use crate::config::{AppConfig, READ_ONLY_KEY};
+pub use crate::config::{
+    AuthConfig, HeaderAuthConfig, LdapConfig, ProcessConfig, ProcessConfigHandle,
+};
+pub use crate::content::{add_post_to_pool, create_pool, create_post_from_file, set_post_tags};
+use crate::crosspost::spawn_crosspost_jobs;
+pub use crate::demo::seed_demo;
+use crate::digest::spawn_moderation_digest_job;
+pub use crate::disk_usage::recompute_disk_usage;
+use crate::entities::{
+    prelude::{SameyConfig, SameyUser},
+    samey_config, samey_user,
+};
 pub use crate::error::SameyError;
+pub use crate::export::{ExportReport, export_static};
+pub use crate::fsck::{FsckOptions, FsckReport, fix_thumbnail_dimensions, fsck};
+use crate::popularity::{ViewCounter, spawn_view_jobs};
+use crate::query::inactive_users_condition;
+use crate::schedule::spawn_post_schedule_job;
+pub use crate::sources::normalize_sources;
+use crate::stats::StatsCache;
+pub use crate::thumbnailer::{DefaultThumbnailer, MediaDimensions, Thumbnailer, VideoMetadata};
 use crate::views::*;
 
 #[derive(rust_embed::Embed)]
@@ -55,11 +127,30 @@ fn assets_router() -> Router {
     )
 }
 
+/// The service worker must be served from the root to control the whole site.
+async fn service_worker() -> impl IntoResponse {
+    match Asset::get("sw.js") {
+        Some(content) => ([(CONTENT_TYPE, "text/javascript")], content.data).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Responses smaller than this many bytes aren't worth compressing.
+const COMPRESSION_MIN_SIZE: u16 = 256;
+/// Clients that stall while sending a request body for this long are dropped.
+const BODY_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[derive(Clone)]
 pub(crate) struct AppState {
     files_dir: Arc<PathBuf>,
     db: DatabaseConnection,
     app_config: Arc<RwLock<AppConfig>>,
+    process_config: ProcessConfigHandle,
+    object_cache: ObjectCache,
+    captcha: CaptchaState,
+    stats_cache: Arc<StatsCache>,
+    view_counter: Arc<ViewCounter>,
+    thumbnailer: Arc<dyn Thumbnailer>,
 }
 
 /// Helper function to create a single user.
@@ -84,6 +175,7 @@ pub async fn create_user(
         username: Set(username.into()),
         password: Set(generate_hash(password)),
         is_admin: Set(is_admin),
+        created_at: Set(Some(Utc::now().naive_utc())),
         ..Default::default()
     })
     .exec(&db)
@@ -91,8 +183,157 @@ pub async fn create_user(
     Ok(())
 }
 
+/// Helper function to restore admin access, for example when all admins are locked out.
+///
+/// The user is created if they don't exist yet. Otherwise, their password is replaced, they are promoted to admin and
+/// re-enabled, and all of their sessions are logged out. Either way, the change is recorded in the audit log.
+///
+/// Returns whether a new user was created.
+///
+/// ```
+/// use samey::reset_admin;
+///
+/// # async fn _main() {
+/// let db = sea_orm::Database::connect("sqlite:db.sqlite3?mode=rwc").await.unwrap();
+/// reset_admin(db, "admin", "secretPassword").await.expect("Unable to reset admin user");
+/// # }
+/// ```
+pub async fn reset_admin(
+    db: DatabaseConnection,
+    username: &str,
+    password: &str,
+) -> Result<bool, SameyError> {
+    let user = SameyUser::find()
+        .filter(samey_user::Column::Username.eq(username))
+        .one(&db)
+        .await?;
+    let created = match user {
+        Some(user) => {
+            SameyUser::update(samey_user::ActiveModel {
+                id: Set(user.id),
+                password: Set(generate_hash(password)),
+                is_admin: Set(true),
+                is_disabled: Set(false),
+                ..Default::default()
+            })
+            .exec(&db)
+            .await?;
+            revoke_user_sessions(&db, user.id).await?;
+            false
+        }
+        None => {
+            create_user(db.clone(), username, password, true).await?;
+            true
+        }
+    };
+    record_audit_log(
+        &db,
+        None,
+        AuditAction::ResetAdmin,
+        if created {
+            format!("Created admin user {} from the command line", username)
+        } else {
+            format!(
+                "Reset password and admin access of {} from the command line",
+                username
+            )
+        },
+    )
+    .await?;
+    Ok(created)
+}
+
+/// Helper function to find stale accounts, for example to clean them up.
+///
+/// Accounts are stale once they haven't logged in for the given amount of days, or never logged in since registering
+/// that long ago. Admins and users without any recorded activity are never considered stale.
+///
+/// ```
+/// use samey::find_stale_users;
+///
+/// # async fn _main() {
+/// let db = sea_orm::Database::connect("sqlite:db.sqlite3?mode=rwc").await.unwrap();
+/// let usernames = find_stale_users(db, 365).await.expect("Unable to find stale users");
+/// # }
+/// ```
+pub async fn find_stale_users(
+    db: DatabaseConnection,
+    inactive_days: u32,
+) -> Result<Vec<String>, SameyError> {
+    let inactive_since = Utc::now().naive_utc() - TimeDelta::days(inactive_days.into());
+    Ok(SameyUser::find()
+        .select_only()
+        .column(samey_user::Column::Username)
+        .filter(inactive_users_condition(inactive_since))
+        .filter(samey_user::Column::IsAdmin.eq(false))
+        .order_by_asc(samey_user::Column::Username)
+        .into_tuple()
+        .all(&db)
+        .await?)
+}
+
+/// Helper function to toggle read-only mode.
+///
+/// While enabled, all requests that could change content are rejected. It can also be toggled from the settings page.
+///
+/// ```
+/// use samey::set_read_only;
+///
+/// # async fn _main() {
+/// let db = sea_orm::Database::connect("sqlite:db.sqlite3?mode=rwc").await.unwrap();
+/// set_read_only(db, true).await.expect("Unable to enable read-only mode");
+/// # }
+/// ```
+pub async fn set_read_only(db: DatabaseConnection, read_only: bool) -> Result<(), SameyError> {
+    SameyConfig::insert(samey_config::ActiveModel {
+        key: Set(READ_ONLY_KEY.into()),
+        data: Set(read_only.into()),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::column(samey_config::Column::Key)
+            .update_column(samey_config::Column::Data)
+            .to_owned(),
+    )
+    .exec(&db)
+    .await?;
+    Ok(())
+}
+
+/// Helper function to check that the database schema is up to date.
+///
+/// Pending migrations are applied if `apply` is set. Otherwise, they are returned as
+/// [`SameyError::PendingMigrations`], so that the schema only changes when an operator asks for it.
+///
+/// ```
+/// use samey::check_migrations;
+///
+/// # async fn _main() {
+/// let db = sea_orm::Database::connect("sqlite:db.sqlite3?mode=rwc").await.unwrap();
+/// check_migrations(db, true).await.expect("Unable to apply migrations");
+/// # }
+/// ```
+pub async fn check_migrations(db: DatabaseConnection, apply: bool) -> Result<(), SameyError> {
+    let pending = Migrator::get_pending_migrations(&db).await?;
+    if pending.is_empty() {
+        return Ok(());
+    }
+    if !apply {
+        return Err(SameyError::PendingMigrations(
+            pending
+                .iter()
+                .map(|migration| migration.name().to_owned())
+                .collect(),
+        ));
+    }
+    Migrator::up(&db, None).await?;
+    Ok(())
+}
+
 /// Creates an Axum router for a Samey application.
 ///
+/// Fails with [`SameyError::PendingMigrations`] if the database schema isn't up to date, see [`check_migrations`].
+///
 /// ```
 /// use samey::get_router;
 ///
@@ -107,23 +348,108 @@ pub async fn get_router(
     db: DatabaseConnection,
     files_dir: impl AsRef<Path>,
 ) -> Result<Router, SameyError> {
+    get_router_with_thumbnailer(db, files_dir, DefaultThumbnailer::new()).await
+}
+
+/// Creates an Axum router for a Samey application, generating thumbnails with a custom [`Thumbnailer`].
+///
+/// ```
+/// use samey::{DefaultThumbnailer, get_router_with_thumbnailer};
+///
+/// # async fn _main() {
+/// let db = sea_orm::Database::connect("sqlite:db.sqlite3?mode=rwc").await.unwrap();
+/// let app = get_router_with_thumbnailer(db, "files", DefaultThumbnailer::images_only())
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+pub async fn get_router_with_thumbnailer(
+    db: DatabaseConnection,
+    files_dir: impl AsRef<Path>,
+    thumbnailer: impl Thumbnailer + 'static,
+) -> Result<Router, SameyError> {
+    get_router_with_process_config(
+        db,
+        files_dir,
+        thumbnailer,
+        ProcessConfigHandle::new(ProcessConfig::default()),
+    )
+    .await
+}
+
+/// Creates an Axum router for a Samey application, with process-level settings that can be reloaded at runtime.
+///
+/// ```
+/// use samey::{DefaultThumbnailer, ProcessConfig, ProcessConfigHandle, get_router_with_process_config};
+///
+/// # async fn _main() {
+/// let db = sea_orm::Database::connect("sqlite:db.sqlite3?mode=rwc").await.unwrap();
+/// let process_config = ProcessConfigHandle::new(ProcessConfig::default());
+/// let app = get_router_with_process_config(
+///     db,
+///     "files",
+///     DefaultThumbnailer::new(),
+///     process_config.clone(),
+/// )
+/// .await
+/// .unwrap();
+/// # }
+/// ```
+pub async fn get_router_with_process_config(
+    db: DatabaseConnection,
+    files_dir: impl AsRef<Path>,
+    thumbnailer: impl Thumbnailer + 'static,
+    process_config: ProcessConfigHandle,
+) -> Result<Router, SameyError> {
+    check_migrations(db.clone(), false).await?;
     let state = AppState {
         files_dir: Arc::new(files_dir.as_ref().to_owned()),
         db: db.clone(),
         app_config: Arc::new(RwLock::new(AppConfig::new(&db).await?)),
+        process_config,
+        object_cache: ObjectCache::default(),
+        captcha: CaptchaState::default(),
+        stats_cache: Arc::new(StatsCache::default()),
+        view_counter: Arc::new(ViewCounter::default()),
+        thumbnailer: Arc::new(thumbnailer),
     };
     fs::create_dir_all(files_dir.as_ref()).await?;
+    spawn_view_jobs(db.clone(), state.view_counter.clone());
+    spawn_crosspost_jobs(db.clone(), state.app_config.clone());
+    spawn_moderation_digest_job(db.clone(), state.app_config.clone());
+    spawn_post_schedule_job(
+        db.clone(),
+        state.app_config.clone(),
+        state.files_dir.clone(),
+    );
+
+    let process_config = state.process_config.clone();
+    let compression_layer =
+        CompressionLayer::new().compress_when(SizeAbove::new(COMPRESSION_MIN_SIZE).and(
+            move |_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions| {
+                process_config.compress_responses() && is_compressible(headers)
+            },
+        ));
 
     let session_store = SessionStorage::new(db.clone());
     let session_layer = SessionManagerLayer::new(session_store).with_expiry(
         tower_sessions::Expiry::OnInactivity(time::Duration::weeks(1)),
     );
-    let auth_layer = AuthManagerLayerBuilder::new(Backend::new(db), session_layer).build();
+    let auth_layer = AuthManagerLayerBuilder::new(
+        Backend::new(db, state.process_config.clone()),
+        session_layer,
+    )
+    .build();
 
     Ok(Router::new()
         // Auth routes
         .route_with_tsr("/login", get(login_page).post(login))
         .route_with_tsr("/logout", get(logout))
+        // Age confirmation routes
+        .route_with_tsr(
+            "/age_confirmation",
+            get(age_confirmation_page).post(confirm_age),
+        )
         // Tags routes
         .route_with_tsr("/search_tags", post(search_tags))
         .route_with_tsr("/select_tag", post(select_tag))
@@ -132,39 +458,240 @@ pub async fn get_router(
             "/upload",
             get(upload_page)
                 .post(upload)
-                .layer(DefaultBodyLimit::max(100_000_000)),
+                .layer(DefaultBodyLimit::disable())
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    upload_size_limit,
+                )),
         )
         .route_with_tsr("/post/{post_id}", get(view_post_page).delete(delete_post))
+        .route_with_tsr("/post/{post_id}/publish", post(publish_draft))
+        .route_with_tsr("/drafts", get(drafts))
+        .route_with_tsr("/post/{post_id}/download", get(download_post))
+        .route_with_tsr("/post/{post_id}/thumbnail", get(post_thumbnail))
+        .route_with_tsr(
+            "/post/{post_id}/download/attachment",
+            get(download_post_attachment),
+        )
+        .route_with_tsr(
+            "/post/{post_id}/attachments",
+            post(add_attachment)
+                .layer(DefaultBodyLimit::disable())
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    upload_size_limit,
+                )),
+        )
+        .route_with_tsr(
+            "/post_attachment/{attachment_id}",
+            get(download_attachment).delete(delete_attachment),
+        )
+        .route_with_tsr("/post/{post_id}/tag_suggestions", post(tag_suggestions))
+        .route_with_tsr("/post/{post_id}/card_tag", post(post_card_tag))
         .route_with_tsr("/post_details/{post_id}/edit", get(edit_post_details))
         .route_with_tsr(
             "/post_details/{post_id}",
             get(post_details).put(submit_post_details),
         )
+        .route_with_tsr("/post/{post_id}/source_lookup", post(source_lookup))
         .route_with_tsr("/post_source", post(add_post_source))
         // Pool routes
         .route_with_tsr("/create_pool", get(create_pool_page))
         .route_with_tsr("/pools", get(get_pools))
         .route_with_tsr("/pools/{page}", get(get_pools_page))
-        .route_with_tsr("/pool", post(create_pool))
+        .route_with_tsr("/pool", post(views::create_pool))
         .route_with_tsr("/pool/{pool_id}", get(view_pool).delete(delete_pool))
+        .route("/pool/{pool_id}/slideshow.json", get(pool_slideshow))
         .route_with_tsr("/pool/{pool_id}/name", put(change_pool_name))
         .route_with_tsr("/pool/{pool_id}/public", put(change_pool_visibility))
-        .route_with_tsr("/pool/{pool_id}/post", post(add_post_to_pool))
+        .route_with_tsr("/pool/{pool_id}/post", post(views::add_post_to_pool))
         .route_with_tsr("/pool/{pool_id}/sort", put(sort_pool))
         .route_with_tsr("/pool_post/{pool_post_id}", delete(remove_pool_post))
         // Bulk edit tag routes
         .route_with_tsr("/bulk_edit_tag", get(bulk_edit_tag).post(edit_tag))
+        .route_with_tsr("/protect_tag", post(protect_tag))
+        // Moderation routes
+        .route_with_tsr("/post/{post_id}/report", post(report_post))
+        .route_with_tsr("/post/{post_id}/restore", post(restore_post))
+        .route_with_tsr("/takedown", get(takedown_page).post(submit_takedown))
+        .route_with_tsr("/moderation", get(moderation))
+        .route_with_tsr("/moderation/resolve", post(resolve_reports))
+        .route_with_tsr("/moderation/digest", post(send_digest))
+        .route_with_tsr("/moderation/takedown/{request_id}", post(review_takedown))
+        .route_with_tsr("/fsck", get(fsck_page).post(run_fsck))
+        .route_with_tsr("/admin/disk_usage", get(disk_usage))
+        // User management routes
+        .route_with_tsr("/admin/users", get(admin_users))
+        .route_with_tsr("/admin/users/{page}", get(admin_users_page))
+        .route_with_tsr("/admin/user/{user_id}/role", post(change_user_role))
+        .route_with_tsr("/admin/user/{user_id}/disabled", post(change_user_disabled))
+        .route_with_tsr("/admin/user/{user_id}/password", post(reset_user_password))
+        // Integration routes
+        .route_with_tsr("/integrations", get(integrations).post(add_integration))
+        .route_with_tsr("/integration/{integration_id}", delete(delete_integration))
+        .route_with_tsr(
+            "/integration/{integration_id}/retry",
+            post(retry_integration),
+        )
+        // Curation routes
+        .route_with_tsr("/curate", get(curate))
+        .route_with_tsr("/curate/{post_id}/rating", put(curate_rating))
+        .route_with_tsr("/curate/{post_id}/tags", put(curate_tags))
+        // Notification routes
+        .route_with_tsr("/notifications", get(notifications))
+        .route_with_tsr("/notifications/read", post(read_notifications))
+        // Calendar routes
+        .route_with_tsr("/calendar", get(calendar))
+        .route_with_tsr("/calendar/{year}/{month}", get(calendar_month))
+        // Stats routes
+        .route_with_tsr("/stats", get(stats))
+        // Popularity routes
+        .route_with_tsr("/popular", get(popular))
+        .route_with_tsr("/popular/{period}", get(popular_period))
+        // Preferences routes
+        .route_with_tsr("/preferences", get(preferences).post(update_preferences))
+        .route_with_tsr(
+            "/preferences/avatar",
+            post(upload_avatar).delete(delete_avatar),
+        )
+        .route_with_tsr(
+            "/preferences/rss-token",
+            post(create_rss_token).delete(delete_rss_token),
+        )
         // Settings routes
-        .route_with_tsr("/settings", get(settings).post(update_settings))
+        .route_with_tsr(
+            "/settings",
+            get(general_settings).post(update_general_settings),
+        )
+        .route_with_tsr(
+            "/settings/uploads",
+            get(upload_settings).post(update_upload_settings),
+        )
+        .route_with_tsr(
+            "/settings/integrations",
+            get(integration_settings).post(update_integration_settings),
+        )
+        .route_with_tsr(
+            "/settings/appearance",
+            get(appearance_settings).post(update_appearance_settings),
+        )
+        .route_with_tsr(
+            "/settings/appearance/preview",
+            post(preview_appearance_settings),
+        )
+        .route_with_tsr("/settings/favicon", post(upload_favicon))
+        .route_with_tsr("/settings/reload_config", post(reload_config))
         // Search routes
         .route_with_tsr("/posts", get(posts))
+        .route("/posts.json", get(posts_json))
         .route_with_tsr("/posts/{page}", get(posts_page))
+        .route_with_tsr("/posts/fragment/{page}", get(posts_fragment))
+        // API routes
+        .route("/api/openapi.json", get(api::openapi))
+        .route_with_tsr(
+            "/api/v1/posts",
+            get(api::posts)
+                .post(api::upload_post)
+                .layer(DefaultBodyLimit::disable())
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    upload_size_limit,
+                )),
+        )
+        .route_with_tsr("/api/v1/posts/batch/tags", post(api::batch_tags))
+        .route_with_tsr("/api/v1/posts/batch/rating", post(api::batch_rating))
+        .route_with_tsr("/api/v1/posts/batch/delete", post(api::batch_delete))
+        .route_with_tsr("/api/v1/posts/{post_id}", get(api::post))
+        .route_with_tsr(
+            "/api/v1/posts/{post_id}/neighbours",
+            get(api::post_neighbours),
+        )
+        .route_with_tsr("/api/v1/pools", get(api::pools))
+        .route_with_tsr("/api/v1/pools/{pool_id}", get(api::pool))
+        .route_with_tsr("/api/v1/pools/{pool_id}/order", put(api::sort_pool))
+        .route_with_tsr("/api/v1/tags/autocomplete", get(api::tags_autocomplete))
+        .route_with_tsr("/api/v1/changes", get(api::changes))
+        .route("/graphql", post(graphql::graphql))
+        // ActivityPub routes
+        .route("/.well-known/webfinger", get(activitypub::webfinger))
+        .route("/ap/actor", get(activitypub::actor))
+        .route("/ap/inbox", post(activitypub::inbox))
+        .route("/ap/outbox", get(activitypub::outbox))
+        .route("/ap/followers", get(activitypub::followers))
+        .route("/ap/post/{post_id}", get(activitypub::post_note))
         // Other routes
         .route_with_tsr("/remove", delete(remove_field))
         .route("/posts.xml", get(rss_page))
+        .route("/activity.xml", get(activity_xml))
+        .route("/activity.json", get(activity_json))
+        .route("/robots.txt", get(robots_txt))
+        .route("/manifest.webmanifest", get(manifest))
+        .route("/favicon.ico", get(favicon_ico))
+        .route("/custom.css", get(custom_css))
+        .route("/sw.js", get(service_worker))
+        .route_with_tsr("/oembed", get(oembed))
         .route("/", get(index))
-        .with_state(state)
-        .nest_service("/files", ServeDir::new(files_dir))
+        .with_state(state.clone())
+        .nest(
+            "/files",
+            Router::new()
+                .fallback_service(ServeDir::new(files_dir))
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    taken_down_files_guard,
+                ))
+                .layer(middleware::from_fn(private_files_guard)),
+        )
         .nest("/static", assets_router())
-        .layer(auth_layer))
+        .layer(middleware::from_fn_with_state(state.clone(), hotlink_guard))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            age_confirmation_gate,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            read_only_guard,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            cache_invalidation,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            session_binding_guard,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            header_authentication,
+        ))
+        .layer(middleware::from_fn_with_state(state, robots_tag_header))
+        .layer(auth_layer)
+        .layer(middleware::from_fn(request_timeout))
+        .layer(RequestBodyTimeoutLayer::new(BODY_READ_TIMEOUT))
+        .layer(compression_layer))
+}
+
+/// Checks if a response is text worth compressing, such as pages, JSON and feeds.
+///
+/// Images and videos are left alone, since they are already compressed.
+fn is_compressible(headers: &HeaderMap) -> bool {
+    let Some(content_type) = headers
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+    else {
+        return false;
+    };
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(
+            essence.as_str(),
+            "application/json" | "application/javascript" | "application/xml" | "image/svg+xml"
+        )
 }
@@ -1,36 +1,270 @@
 use std::{
-    net::{IpAddr, Ipv6Addr},
-    path::PathBuf,
+    fs,
+    io::{self, Write},
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    path::{Path, PathBuf},
+    process,
 };
 
 use clap::{Parser, Subcommand};
-use samey::{create_user, get_router};
-use samey_migration::{Migrator, MigratorTrait};
+use samey::{
+    AuthConfig, DefaultThumbnailer, FsckOptions, HeaderAuthConfig, LdapConfig, ProcessConfig,
+    ProcessConfigHandle, Thumbnailer, check_migrations, create_user, export_static,
+    find_stale_users, fix_thumbnail_dimensions, fsck, get_router_with_process_config,
+    normalize_sources, recompute_disk_usage, reset_admin, seed_demo, set_read_only,
+};
+use samey_migration::{MigrationStatus, Migrator, MigratorTrait};
 use sea_orm::Database;
+use serde::Deserialize;
+
+const DEFAULT_DATABASE: &str = "sqlite:db.sqlite3?mode=rwc";
+const DEFAULT_FILES_DIRECTORY: &str = "files";
+const DEFAULT_PORT: u16 = 3000;
+const DEFAULT_FFMPEG_PATH: &str = "ffmpeg";
+const DEFAULT_FFPROBE_PATH: &str = "ffprobe";
+const DEFAULT_MAX_FFMPEG_PROCESSES: usize = 2;
+const DEFAULT_LDAP_USER_FILTER: &str = "(uid={username})";
+const DEFAULT_USERNAME_HEADER: &str = "Remote-User";
 
+/// Options are read from CLI flags first, then environment variables, then the config file.
 #[derive(Parser)]
 struct Config {
-    #[arg(short, long, default_value = "sqlite:db.sqlite3?mode=rwc")]
-    database: String,
+    /// TOML file with values for any of the options that aren't set otherwise.
+    #[arg(long, env = "SAMEY_CONFIG")]
+    config: Option<PathBuf>,
 
-    #[arg(short, long, default_value = "files")]
-    files_directory: PathBuf,
+    /// [default: sqlite:db.sqlite3?mode=rwc]
+    #[arg(short, long, env = "SAMEY_DATABASE")]
+    database: Option<String>,
+
+    /// [default: files]
+    #[arg(short, long, env = "SAMEY_FILES_DIRECTORY")]
+    files_directory: Option<PathBuf>,
 
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
+/// Contents of the `--config` file, such as:
+///
+/// ```toml
+/// database = "sqlite:/var/lib/samey/db.sqlite3?mode=rwc"
+/// files_directory = "/var/lib/samey/files"
+/// address = "127.0.0.1"
+/// port = 3000
+/// read_only = false
+/// auto_migrate = true
+/// max_ffmpeg_processes = 4
+/// max_upload_size = 100000000
+/// compress_responses = true
+///
+/// [auth]
+/// backend = "header"
+/// username_header = "Remote-User"
+/// groups_header = "Remote-Groups"
+/// admin_group = "admins"
+/// trusted_proxies = ["127.0.0.1", "::1"]
+/// ```
+///
+/// Limits such as `max_upload_size` and `compress_responses`, and the `[auth]` table, are read again on SIGHUP, or
+/// from the settings page.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    database: Option<String>,
+    files_directory: Option<PathBuf>,
+    address: Option<IpAddr>,
+    port: Option<u16>,
+    read_only: Option<bool>,
+    auto_migrate: Option<bool>,
+    ffmpeg_path: Option<PathBuf>,
+    ffprobe_path: Option<PathBuf>,
+    max_ffmpeg_processes: Option<usize>,
+    max_upload_size: Option<usize>,
+    compress_responses: Option<bool>,
+    auth: Option<AuthConfigFile>,
+}
+
+/// The `[auth]` table of the config file, where `backend` is one of `local`, `ldap` or `header`.
+///
+/// ```toml
+/// [auth]
+/// backend = "ldap"
+/// url = "ldaps://ldap.example.com"
+/// bind_dn = "cn=samey,ou=services,dc=example,dc=com"
+/// bind_password = "secret"
+/// base_dn = "ou=people,dc=example,dc=com"
+/// user_filter = "(&(objectClass=person)(uid={username}))"
+/// admin_group = "cn=admins,ou=groups,dc=example,dc=com"
+/// ```
+#[derive(Deserialize, Clone)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+enum AuthConfigFile {
+    Local,
+    Ldap(LdapConfigFile),
+    Header(HeaderAuthConfigFile),
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+struct LdapConfigFile {
+    url: String,
+    bind_dn: Option<String>,
+    bind_password: Option<String>,
+    base_dn: String,
+    user_filter: Option<String>,
+    admin_group: Option<String>,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+struct HeaderAuthConfigFile {
+    username_header: Option<String>,
+    groups_header: Option<String>,
+    admin_group: Option<String>,
+    trusted_proxies: Vec<IpAddr>,
+}
+
+impl From<AuthConfigFile> for AuthConfig {
+    fn from(auth: AuthConfigFile) -> Self {
+        match auth {
+            AuthConfigFile::Local => AuthConfig::Local,
+            AuthConfigFile::Ldap(ldap) => AuthConfig::Ldap(LdapConfig {
+                url: ldap.url,
+                bind_dn: ldap.bind_dn,
+                bind_password: ldap.bind_password,
+                base_dn: ldap.base_dn,
+                user_filter: ldap
+                    .user_filter
+                    .unwrap_or_else(|| DEFAULT_LDAP_USER_FILTER.into()),
+                admin_group: ldap.admin_group,
+            }),
+            AuthConfigFile::Header(header) => AuthConfig::Header(HeaderAuthConfig {
+                username_header: header
+                    .username_header
+                    .unwrap_or_else(|| DEFAULT_USERNAME_HEADER.into()),
+                groups_header: header.groups_header,
+                admin_group: header.admin_group,
+                trusted_proxies: header.trusted_proxies,
+            }),
+        }
+    }
+}
+
+impl ConfigFile {
+    fn load(path: Option<&Path>) -> Self {
+        let Some(path) = path else {
+            return ConfigFile::default();
+        };
+        ConfigFile::read(path).expect("Unable to load config file")
+    }
+
+    fn read(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("Unable to read {}: {}", path.display(), err))?;
+        let config: Self = toml::from_str(&contents)
+            .map_err(|err| format!("Invalid {}: {}", path.display(), err))?;
+        if let Some(AuthConfigFile::Header(header)) = &config.auth {
+            if header.trusted_proxies.is_empty() {
+                return Err(format!(
+                    "Invalid {}: header authentication needs at least one address in trusted_proxies",
+                    path.display()
+                ));
+            }
+        }
+        Ok(config)
+    }
+
+    fn process_options(&self) -> ProcessOptions {
+        ProcessOptions {
+            max_upload_size: self.max_upload_size,
+            compress_responses: self.compress_responses,
+            auth: self.auth.clone(),
+        }
+    }
+}
+
+/// Options for [`ProcessConfig`], which may be unset in any given source.
+#[derive(Clone)]
+struct ProcessOptions {
+    max_upload_size: Option<usize>,
+    compress_responses: Option<bool>,
+    /// Only set from the config file, since it has too many options for flags.
+    auth: Option<AuthConfigFile>,
+}
+
+impl ProcessOptions {
+    /// Fills in unset options from another source with lower precedence.
+    fn or(self, other: ProcessOptions) -> ProcessOptions {
+        ProcessOptions {
+            max_upload_size: self.max_upload_size.or(other.max_upload_size),
+            compress_responses: self.compress_responses.or(other.compress_responses),
+            auth: self.auth.or(other.auth),
+        }
+    }
+
+    fn resolve(self) -> ProcessConfig {
+        let default = ProcessConfig::default();
+        ProcessConfig {
+            max_upload_size: self.max_upload_size.unwrap_or(default.max_upload_size),
+            compress_responses: self
+                .compress_responses
+                .unwrap_or(default.compress_responses),
+            auth: self.auth.map(AuthConfig::from).unwrap_or(default.auth),
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     Run {
-        #[arg(short, long, default_value_t = IpAddr::V6(Ipv6Addr::UNSPECIFIED))]
-        address: IpAddr,
+        /// [default: ::]
+        #[arg(short, long, env = "SAMEY_ADDRESS")]
+        address: Option<IpAddr>,
+
+        /// [default: 3000]
+        #[arg(short, long, env = "SAMEY_PORT")]
+        port: Option<u16>,
+
+        /// Enable read-only mode on startup. It can be disabled again from the settings page.
+        #[arg(long, env = "SAMEY_READ_ONLY")]
+        read_only: bool,
+
+        /// Apply pending migrations on startup, instead of refusing to start.
+        #[arg(long, env = "SAMEY_AUTO_MIGRATE")]
+        auto_migrate: bool,
+
+        /// Path to the ffmpeg binary, used for video thumbnails. [default: ffmpeg]
+        #[arg(long, env = "SAMEY_FFMPEG_PATH")]
+        ffmpeg_path: Option<PathBuf>,
+
+        /// Path to the ffprobe binary, used for video dimensions. [default: ffprobe]
+        #[arg(long, env = "SAMEY_FFPROBE_PATH")]
+        ffprobe_path: Option<PathBuf>,
 
-        #[arg(short, long, default_value_t = 3000)]
-        port: u16,
+        /// How many videos can be processed at once. [default: 2]
+        #[arg(long, env = "SAMEY_MAX_FFMPEG_PROCESSES")]
+        max_ffmpeg_processes: Option<usize>,
+
+        /// Maximum size of an upload in bytes. [default: 100000000]
+        #[arg(long, env = "SAMEY_MAX_UPLOAD_SIZE")]
+        max_upload_size: Option<usize>,
+
+        /// Compress pages, API responses and feeds for clients that support it. [default: true]
+        #[arg(long, env = "SAMEY_COMPRESS_RESPONSES")]
+        compress_responses: Option<bool>,
     },
 
-    Migrate,
+    /// Apply pending migrations.
+    Migrate {
+        /// List migrations and whether they have been applied, without changing anything.
+        #[arg(long, conflicts_with = "down")]
+        status: bool,
+
+        /// Roll back the given amount of applied migrations instead.
+        #[arg(long, num_args = 0..=1, default_missing_value = "1")]
+        down: Option<u32>,
+    },
 
     AddAdminUser {
         #[arg(short, long)]
@@ -39,13 +273,73 @@ enum Commands {
         #[arg(short, long)]
         password: String,
     },
+
+    /// Restore admin access by creating the user, or by resetting their password and promoting them.
+    ResetAdmin {
+        #[arg(short, long)]
+        username: String,
+
+        #[arg(short, long)]
+        password: String,
+
+        /// Skip the confirmation prompt.
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Generate demo users, posts, tags and pools for local development.
+    SeedDemo {
+        #[arg(short, long, default_value_t = 100)]
+        count: u32,
+    },
+
+    /// Cross-check the files directory against the database.
+    Fsck {
+        /// Delete files that don't belong to any post.
+        #[arg(long)]
+        remove_orphaned_files: bool,
+
+        /// Generate thumbnails again for posts whose media still exists.
+        #[arg(long)]
+        regenerate_thumbnails: bool,
+    },
+
+    /// Rewrite stored source URLs into their canonical form and remove duplicates.
+    NormalizeSources,
+
+    /// Correct the stored thumbnail dimensions of every post from the thumbnail files.
+    FixThumbnailDimensions,
+
+    /// Measure the files of every post again to update how much disk space each one takes.
+    RecomputeDiskUsage,
+
+    /// Render published posts, public pools and tags into a static site, with copies of their media.
+    ExportStatic {
+        /// Directory to write the site into. Existing files with the same names are overwritten.
+        #[arg(short, long)]
+        out: PathBuf,
+    },
+
+    /// List users who haven't logged in for a while, excluding admins.
+    StaleUsers {
+        /// How many days without logging in make an account stale.
+        #[arg(long, default_value_t = 365)]
+        inactive_days: u32,
+    },
 }
 
 impl Default for Commands {
     fn default() -> Self {
         Commands::Run {
-            address: IpAddr::V6(Ipv6Addr::UNSPECIFIED),
-            port: 3000,
+            address: None,
+            port: None,
+            read_only: false,
+            auto_migrate: false,
+            ffmpeg_path: None,
+            ffprobe_path: None,
+            max_ffmpeg_processes: None,
+            max_upload_size: None,
+            compress_responses: None,
         }
     }
 }
@@ -53,14 +347,41 @@ impl Default for Commands {
 #[tokio::main]
 async fn main() {
     let config = Config::parse();
-    let db = Database::connect(config.database)
-        .await
-        .expect("Unable to connect to database");
+    let config_file = ConfigFile::load(config.config.as_deref());
+    let config_file_process_options = config_file.process_options();
+    let files_directory = config
+        .files_directory
+        .or(config_file.files_directory)
+        .unwrap_or_else(|| DEFAULT_FILES_DIRECTORY.into());
+    let db = Database::connect(
+        config
+            .database
+            .or(config_file.database)
+            .unwrap_or_else(|| DEFAULT_DATABASE.into()),
+    )
+    .await
+    .expect("Unable to connect to database");
     match config.command.unwrap_or_default() {
-        Commands::Migrate => {
-            Migrator::up(&db, None)
-                .await
-                .expect("Unable to apply migrations");
+        Commands::Migrate { status, down } => {
+            if status {
+                for migration in Migrator::get_migration_with_status(&db)
+                    .await
+                    .expect("Unable to get migrations")
+                {
+                    match migration.status() {
+                        MigrationStatus::Applied => println!("Applied  {}", migration.name()),
+                        MigrationStatus::Pending => println!("Pending  {}", migration.name()),
+                    }
+                }
+            } else if let Some(steps) = down {
+                Migrator::down(&db, Some(steps))
+                    .await
+                    .expect("Unable to roll back migrations");
+            } else {
+                Migrator::up(&db, None)
+                    .await
+                    .expect("Unable to apply migrations");
+            }
         }
 
         Commands::AddAdminUser { username, password } => {
@@ -69,13 +390,211 @@ async fn main() {
                 .expect("Unable to add admin user");
         }
 
-        Commands::Run { address, port } => {
+        Commands::ResetAdmin {
+            username,
+            password,
+            yes,
+        } => {
+            if !yes {
+                print!(
+                    "This will give {} admin access and log out all of their sessions. Continue? [y/N] ",
+                    username
+                );
+                io::stdout().flush().expect("Unable to write prompt");
+                let mut answer = String::new();
+                io::stdin()
+                    .read_line(&mut answer)
+                    .expect("Unable to read answer");
+                if !matches!(answer.trim(), "y" | "Y" | "yes") {
+                    println!("Aborted");
+                    return;
+                }
+            }
+            Migrator::up(&db, None)
+                .await
+                .expect("Unable to apply migrations");
+            if reset_admin(db, &username, &password)
+                .await
+                .expect("Unable to reset admin user")
+            {
+                println!("Created admin user {}", username);
+            } else {
+                println!("Reset admin user {}", username);
+            }
+        }
+
+        Commands::SeedDemo { count } => {
+            Migrator::up(&db, None)
+                .await
+                .expect("Unable to apply migrations");
+            seed_demo(db, files_directory, count)
+                .await
+                .expect("Unable to seed demo data");
+        }
+
+        Commands::Fsck {
+            remove_orphaned_files,
+            regenerate_thumbnails,
+        } => {
+            let report = fsck(
+                db,
+                files_directory,
+                &DefaultThumbnailer::new(),
+                FsckOptions {
+                    remove_orphaned_files,
+                    regenerate_thumbnails,
+                },
+            )
+            .await
+            .expect("Unable to check files");
+            for file in &report.orphaned_files {
+                if report.removed_orphaned_files {
+                    println!("Removed orphaned file: {}", file);
+                } else {
+                    println!("Orphaned file: {}", file);
+                }
+            }
+            for post_id in &report.missing_media {
+                println!("Missing media for post #{}", post_id);
+            }
+            for post_id in &report.missing_thumbnails {
+                if report.regenerated_thumbnails.contains(post_id) {
+                    println!("Regenerated thumbnail for post #{}", post_id);
+                } else {
+                    println!("Missing thumbnail for post #{}", post_id);
+                }
+            }
+            if report.is_clean() {
+                println!("No problems found");
+            }
+        }
+
+        Commands::NormalizeSources => {
+            Migrator::up(&db, None)
+                .await
+                .expect("Unable to apply migrations");
+            let changed = normalize_sources(db)
+                .await
+                .expect("Unable to normalize sources");
+            println!("Normalized {} source(s)", changed);
+        }
+
+        Commands::FixThumbnailDimensions => {
+            Migrator::up(&db, None)
+                .await
+                .expect("Unable to apply migrations");
+            let changed = fix_thumbnail_dimensions(db, files_directory)
+                .await
+                .expect("Unable to fix thumbnail dimensions");
+            println!("Fixed thumbnail dimensions of {} post(s)", changed);
+        }
+
+        Commands::RecomputeDiskUsage => {
             Migrator::up(&db, None)
                 .await
                 .expect("Unable to apply migrations");
-            let app = get_router(db, config.files_directory)
+            let changed = recompute_disk_usage(db, files_directory)
+                .await
+                .expect("Unable to recompute disk usage");
+            println!("Updated disk usage of {} post(s)", changed);
+        }
+
+        Commands::ExportStatic { out } => {
+            Migrator::up(&db, None)
                 .await
-                .expect("Unable to start router");
+                .expect("Unable to apply migrations");
+            let report = export_static(db, files_directory, &out)
+                .await
+                .expect("Unable to export static site");
+            for file in &report.missing_files {
+                println!("Missing file: {}", file);
+            }
+            println!(
+                "Exported {} post(s), {} pool(s) and {} tag(s) to {}",
+                report.posts,
+                report.pools,
+                report.tags,
+                out.display()
+            );
+        }
+
+        Commands::StaleUsers { inactive_days } => {
+            for username in find_stale_users(db, inactive_days)
+                .await
+                .expect("Unable to find stale users")
+            {
+                println!("{}", username);
+            }
+        }
+
+        Commands::Run {
+            address,
+            port,
+            read_only,
+            auto_migrate,
+            ffmpeg_path,
+            ffprobe_path,
+            max_ffmpeg_processes,
+            max_upload_size,
+            compress_responses,
+        } => {
+            let process_options = ProcessOptions {
+                max_upload_size,
+                compress_responses,
+                auth: None,
+            };
+            let mut process_config = ProcessConfigHandle::new(
+                process_options
+                    .clone()
+                    .or(config_file_process_options)
+                    .resolve(),
+            );
+            if let Some(config_path) = config.config {
+                process_config = process_config.with_loader(move || {
+                    Ok(process_options
+                        .clone()
+                        .or(ConfigFile::read(&config_path)?.process_options())
+                        .resolve())
+                });
+            }
+            let address = address
+                .or(config_file.address)
+                .unwrap_or(IpAddr::V6(Ipv6Addr::UNSPECIFIED));
+            let port = port.or(config_file.port).unwrap_or(DEFAULT_PORT);
+            let read_only = read_only || config_file.read_only.unwrap_or(false);
+            let auto_migrate = auto_migrate || config_file.auto_migrate.unwrap_or(false);
+            let ffmpeg_path = ffmpeg_path
+                .or(config_file.ffmpeg_path)
+                .unwrap_or_else(|| DEFAULT_FFMPEG_PATH.into());
+            let ffprobe_path = ffprobe_path
+                .or(config_file.ffprobe_path)
+                .unwrap_or_else(|| DEFAULT_FFPROBE_PATH.into());
+            let max_ffmpeg_processes = max_ffmpeg_processes
+                .or(config_file.max_ffmpeg_processes)
+                .unwrap_or(DEFAULT_MAX_FFMPEG_PROCESSES);
+            if let Err(err) = check_migrations(db.clone(), auto_migrate).await {
+                eprintln!("{}", err);
+                eprintln!("Run `samey migrate` first, or start with --auto-migrate to apply them");
+                process::exit(1);
+            }
+            if read_only {
+                set_read_only(db.clone(), true)
+                    .await
+                    .expect("Unable to enable read-only mode");
+            }
+            let thumbnailer = DefaultThumbnailer::with_binaries(ffmpeg_path, ffprobe_path)
+                .with_max_concurrent_processes(max_ffmpeg_processes);
+            if !thumbnailer.supports_video() {
+                println!("FFmpeg is not available, video uploads are disabled");
+            }
+            let app = get_router_with_process_config(
+                db,
+                files_directory,
+                thumbnailer,
+                process_config.clone(),
+            )
+            .await
+            .expect("Unable to start router");
             let listener = tokio::net::TcpListener::bind((address, port))
                 .await
                 .expect("Unable to bind TCP listener");
@@ -86,6 +605,16 @@ async fn main() {
             }
             #[cfg(unix)]
             {
+                let mut signal_hangup =
+                    tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).unwrap();
+                tokio::spawn(async move {
+                    while signal_hangup.recv().await.is_some() {
+                        match process_config.reload().await {
+                            Ok(()) => println!("Reloaded config"),
+                            Err(err) => println!("Unable to reload config: {}", err),
+                        }
+                    }
+                });
                 let mut signal_terminate =
                     tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
                         .unwrap();
@@ -94,7 +623,10 @@ async fn main() {
                         .unwrap();
 
                 tokio::select! {
-                    server = axum::serve(listener, app) => {
+                    server = axum::serve(
+                        listener,
+                        app.into_make_service_with_connect_info::<SocketAddr>(),
+                    ) => {
                         server.unwrap();
                     },
                     _ = signal_terminate.recv() => {
@@ -107,7 +639,12 @@ async fn main() {
             }
             #[cfg(not(unix))]
             {
-                axum::serve(listener, app).await.unwrap();
+                axum::serve(
+                    listener,
+                    app.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .await
+                .unwrap();
             }
         }
     }
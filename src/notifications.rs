@@ -0,0 +1,209 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
+use sea_orm::{
+    ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter,
+    QuerySelect,
+};
+use tokio::sync::RwLock;
+
+use crate::{
+    SameyError,
+    auth::User,
+    config::AppConfig,
+    email::send_email,
+    entities::{
+        prelude::{SameyNotification, SameyNotificationSetting, SameyPost, SameyUser},
+        samey_notification, samey_notification_setting, samey_user,
+    },
+    query::filter_posts_by_user,
+};
+
+pub(crate) const MENTION_PREFIX: char = '@';
+
+#[derive(strum::Display, Debug)]
+pub(crate) enum NotificationKind {
+    #[strum(serialize = "mention")]
+    Mention,
+    #[strum(serialize = "pool_add")]
+    PoolAdd,
+}
+
+impl NotificationKind {
+    /// The setting that users enable to get this kind of notification by email.
+    fn email_setting(&self) -> samey_notification_setting::Column {
+        match self {
+            NotificationKind::Mention => samey_notification_setting::Column::EmailOnMention,
+            NotificationKind::PoolAdd => samey_notification_setting::Column::EmailOnPoolAdd,
+        }
+    }
+
+    fn email_subject(&self, actor: &str, application_name: &str) -> String {
+        match self {
+            NotificationKind::Mention => format!("{} mentioned you on {}", actor, application_name),
+            NotificationKind::PoolAdd => format!(
+                "{} added your post to a pool on {}",
+                actor, application_name
+            ),
+        }
+    }
+}
+
+/// Returns all usernames mentioned as `@username` in the text.
+pub(crate) fn parse_mentions(text: &str) -> HashSet<&str> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-' || c == MENTION_PREFIX))
+        .filter_map(|word| word.strip_prefix(MENTION_PREFIX))
+        .filter(|username| !username.is_empty() && !username.contains(MENTION_PREFIX))
+        .collect()
+}
+
+/// Notifies users who are newly mentioned in a post's text.
+///
+/// Mentions already present in `old_text` are ignored, so that editing a post doesn't notify the
+/// same users again. Users never get notified for their own mentions.
+pub(crate) async fn notify_mentions(
+    db: &DatabaseConnection,
+    app_config: &RwLock<AppConfig>,
+    actor_id: i32,
+    post_id: i32,
+    old_text: Option<&str>,
+    new_text: Option<&str>,
+) -> Result<(), SameyError> {
+    let old_mentions = old_text.map(parse_mentions).unwrap_or_default();
+    let new_mentions: Vec<&str> = new_text
+        .map(parse_mentions)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|username| !old_mentions.contains(username))
+        .collect();
+    if new_mentions.is_empty() {
+        return Ok(());
+    }
+
+    let user_ids: Vec<i32> = SameyUser::find()
+        .select_only()
+        .column(samey_user::Column::Id)
+        .filter(samey_user::Column::Username.is_in(new_mentions))
+        .into_tuple()
+        .all(db)
+        .await?;
+
+    dispatch_notifications(
+        db,
+        app_config,
+        NotificationKind::Mention,
+        actor_id,
+        post_id,
+        user_ids,
+    )
+    .await
+}
+
+/// Notifies the uploader of a post that another user added it to a pool.
+pub(crate) async fn notify_pool_add(
+    db: &DatabaseConnection,
+    app_config: &RwLock<AppConfig>,
+    actor_id: i32,
+    post_id: i32,
+    uploader_id: i32,
+) -> Result<(), SameyError> {
+    dispatch_notifications(
+        db,
+        app_config,
+        NotificationKind::PoolAdd,
+        actor_id,
+        post_id,
+        vec![uploader_id],
+    )
+    .await
+}
+
+/// Creates a notification for each user, and emails the ones who opted into this kind of notification and can see
+/// the post.
+///
+/// Users never get notified of their own actions. Emails are sent in the background, so a slow or
+/// failing SMTP server doesn't hold up the request.
+async fn dispatch_notifications(
+    db: &DatabaseConnection,
+    app_config: &RwLock<AppConfig>,
+    kind: NotificationKind,
+    actor_id: i32,
+    post_id: i32,
+    mut user_ids: Vec<i32>,
+) -> Result<(), SameyError> {
+    user_ids.retain(|user_id| *user_id != actor_id);
+    if user_ids.is_empty() {
+        return Ok(());
+    }
+
+    let created_at = Utc::now().naive_utc();
+    SameyNotification::insert_many(user_ids.iter().map(|user_id| {
+        samey_notification::ActiveModel {
+            user_id: Set(*user_id),
+            actor_id: Set(actor_id),
+            post_id: Set(post_id),
+            kind: Set(kind.to_string()),
+            is_read: Set(false),
+            created_at: Set(created_at),
+            ..Default::default()
+        }
+    }))
+    .exec(db)
+    .await?;
+
+    let config = app_config.read().await.clone();
+    if !config.email_enabled() {
+        return Ok(());
+    }
+    let recipients: HashMap<i32, String> = SameyNotificationSetting::find()
+        .select_only()
+        .column(samey_notification_setting::Column::UserId)
+        .column(samey_notification_setting::Column::Email)
+        .filter(samey_notification_setting::Column::UserId.is_in(user_ids))
+        .filter(samey_notification_setting::Column::Email.is_not_null())
+        .filter(kind.email_setting().eq(true))
+        .into_tuple::<(i32, String)>()
+        .all(db)
+        .await?
+        .into_iter()
+        .collect();
+    // Like the notification list, emails leave out posts that the user can't see, such as private or draft posts
+    let mut emails = Vec::new();
+    for user in SameyUser::find()
+        .filter(samey_user::Column::Id.is_in(recipients.keys().copied()))
+        .all(db)
+        .await?
+    {
+        let user = User::from(user);
+        let can_see_post = filter_posts_by_user(SameyPost::find_by_id(post_id), Some(&user))
+            .count(db)
+            .await?
+            > 0;
+        if can_see_post {
+            emails.extend(recipients.get(&user.id).cloned());
+        }
+    }
+    if emails.is_empty() {
+        return Ok(());
+    }
+    let actor = SameyUser::find_by_id(actor_id)
+        .one(db)
+        .await?
+        .map(|user| user.username)
+        .unwrap_or_default();
+
+    let subject = kind.email_subject(&actor, &config.application_name);
+    let body = format!(
+        "{}.\n\nSee the post at {}/post/{}\n\nYou can change which emails you get at {}/preferences\n",
+        subject, config.base_url, post_id, config.base_url
+    );
+    tokio::spawn(async move {
+        for email in emails {
+            if let Err(err) = send_email(&config, &[email], &subject, body.clone()).await {
+                println!("Error when sending a notification email - {}", err);
+            }
+        }
+    });
+
+    Ok(())
+}
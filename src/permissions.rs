@@ -0,0 +1,173 @@
+use std::marker::PhantomData;
+
+use axum::{
+    extract::{FromRequestParts, Query, RawPathParams},
+    http::request::Parts,
+};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Select};
+use serde::Deserialize;
+
+use crate::{
+    AppState, SameyError,
+    auth::{AuthSession, User, hash_rss_token},
+    entities::{
+        prelude::{SameyPool, SameyPost, SameyUser},
+        samey_user,
+    },
+};
+
+/// What a user must be to use a route, checked by [`RequireRole`].
+pub(crate) trait Role {
+    fn is_granted_to(user: &User) -> bool;
+}
+
+/// Users who can manage the whole instance.
+pub(crate) struct Admin;
+
+impl Role for Admin {
+    fn is_granted_to(user: &User) -> bool {
+        user.is_admin
+    }
+}
+
+/// Extractor for the logged in user, which rejects the request as forbidden unless they have the role `R`.
+pub(crate) struct RequireRole<R: Role>(pub(crate) User, pub(crate) PhantomData<R>);
+
+impl<R: Role, S: Send + Sync> FromRequestParts<S> for RequireRole<R> {
+    type Rejection = SameyError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match current_user(parts, state).await? {
+            Some(user) if R::is_granted_to(&user) => Ok(Self(user, PhantomData)),
+            _ => Err(SameyError::Forbidden),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct FeedQuery {
+    token: Option<String>,
+}
+
+/// Extractor for the user reading a feed, from the personal RSS token in the `token` query parameter, or else from
+/// the session.
+///
+/// Feed readers can't log in, so a token stands in for the session of its user. Unknown tokens, and tokens of
+/// disabled users, are rejected as forbidden.
+pub(crate) struct FeedUser(pub(crate) Option<User>);
+
+impl FromRequestParts<AppState> for FeedUser {
+    type Rejection = SameyError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Query(query) = Query::<FeedQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(|err| SameyError::BadRequest(err.body_text()))?;
+        let Some(token) = query.token else {
+            return Ok(Self(current_user(parts, state).await?));
+        };
+        SameyUser::find()
+            .filter(samey_user::Column::RssTokenHash.eq(hash_rss_token(&token)))
+            .filter(samey_user::Column::IsDisabled.eq(false))
+            .one(&state.db)
+            .await?
+            .map(|user| Self(Some(user.into())))
+            .ok_or(SameyError::Forbidden)
+    }
+}
+
+/// Like [`RequireRole`], for feeds that can also be read with a personal RSS token, as with [`FeedUser`].
+pub(crate) struct RequireFeedRole<R: Role>(PhantomData<R>);
+
+impl<R: Role> FromRequestParts<AppState> for RequireFeedRole<R> {
+    type Rejection = SameyError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        match FeedUser::from_request_parts(parts, state).await?.0 {
+            Some(user) if R::is_granted_to(&user) => Ok(Self(PhantomData)),
+            _ => Err(SameyError::Forbidden),
+        }
+    }
+}
+
+/// Entities that belong to a user, who can change them along with admins.
+pub(crate) trait Owned: EntityTrait {
+    /// Name of the route parameter with the ID of the entity.
+    const ID_PARAM: &'static str;
+
+    fn find_by_param(id: i32) -> Select<Self>;
+
+    fn is_editable_by(model: &Self::Model, user: &User) -> bool;
+}
+
+impl Owned for SameyPost {
+    const ID_PARAM: &'static str = "post_id";
+
+    fn find_by_param(id: i32) -> Select<Self> {
+        Self::find_by_id(id)
+    }
+
+    /// Locked posts, including those that were taken down, can only be changed by admins.
+    fn is_editable_by(post: &Self::Model, user: &User) -> bool {
+        user.is_admin || (post.uploader_id == user.id && !post.is_locked)
+    }
+}
+
+impl Owned for SameyPool {
+    const ID_PARAM: &'static str = "pool_id";
+
+    fn find_by_param(id: i32) -> Select<Self> {
+        Self::find_by_id(id)
+    }
+
+    fn is_editable_by(pool: &Self::Model, user: &User) -> bool {
+        user.is_admin || pool.uploader_id == user.id
+    }
+}
+
+/// Extractor for the logged in user and the entity from the route, such as the post for `/post/{post_id}`.
+///
+/// Rejects the request as not found if the entity doesn't exist, and as forbidden unless the user can edit it.
+pub(crate) struct RequireOwnerOrAdmin<E: Owned>(pub(crate) User, pub(crate) E::Model);
+
+impl<E: Owned> FromRequestParts<AppState> for RequireOwnerOrAdmin<E> {
+    type Rejection = SameyError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let params = RawPathParams::from_request_parts(parts, state)
+            .await
+            .map_err(|_| SameyError::NotFound)?;
+        let id = params
+            .iter()
+            .find(|(name, _)| *name == E::ID_PARAM)
+            .and_then(|(_, id)| id.parse().ok())
+            .ok_or(SameyError::NotFound)?;
+        let model = E::find_by_param(id)
+            .one(&state.db)
+            .await?
+            .ok_or(SameyError::NotFound)?;
+        match current_user(parts, state).await? {
+            Some(user) if E::is_editable_by(&model, &user) => Ok(Self(user, model)),
+            _ => Err(SameyError::Forbidden),
+        }
+    }
+}
+
+async fn current_user<S: Send + Sync>(
+    parts: &mut Parts,
+    state: &S,
+) -> Result<Option<User>, SameyError> {
+    let auth_session = AuthSession::from_request_parts(parts, state)
+        .await
+        .map_err(|(_, message)| SameyError::Other(message.into()))?;
+    Ok(auth_session.user)
+}
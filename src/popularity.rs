@@ -0,0 +1,243 @@
+use std::{
+    collections::HashMap,
+    mem,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{Days, NaiveDate, Utc};
+use samey_migration::{Expr, OnConflict, Query};
+use sea_orm::{
+    ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, TransactionTrait, sea_query::SimpleExpr,
+};
+use strum::IntoEnumIterator;
+
+use crate::{
+    SameyError,
+    auth::User,
+    entities::{
+        prelude::{SameyPopularPost, SameyPost, SameyPostView},
+        samey_popular_post, samey_post, samey_post_view,
+    },
+    query::{PostOverview, search_posts_query},
+    search::SearchQuery,
+};
+
+/// How often buffered views are written to the database.
+const VIEW_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+/// How often old daily view counts are removed.
+const VIEW_PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Daily view counts older than this are removed, since only recent popularity is shown.
+const VIEW_HISTORY_DAYS: u64 = 31;
+/// How often the rankings of popular posts are computed again.
+const POPULAR_REFRESH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const POPULAR_INSERT_CHUNK_SIZE: usize = 1000;
+const POPULAR_POSTS_LIMIT: u64 = 50;
+
+/// Time window over which popular posts are ranked.
+#[derive(strum::EnumIter, strum::Display, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PopularPeriod {
+    #[strum(serialize = "day")]
+    Day,
+    #[strum(serialize = "week")]
+    Week,
+    #[strum(serialize = "month")]
+    Month,
+}
+
+impl PopularPeriod {
+    /// How many days are counted, including today.
+    pub(crate) fn days(self) -> u64 {
+        match self {
+            Self::Day => 1,
+            Self::Week => 7,
+            Self::Month => 30,
+        }
+    }
+}
+
+/// Post views that haven't been written to the database yet, to avoid a write for every request.
+#[derive(Default)]
+pub(crate) struct ViewCounter(Mutex<HashMap<i32, i32>>);
+
+impl ViewCounter {
+    pub(crate) fn record(&self, post_id: i32) {
+        if let Ok(mut views) = self.0.lock() {
+            *views.entry(post_id).or_default() += 1;
+        }
+    }
+
+    fn take(&self) -> HashMap<i32, i32> {
+        self.0
+            .lock()
+            .map(|mut views| mem::take(&mut *views))
+            .unwrap_or_default()
+    }
+}
+
+/// Spawns the background tasks that flush buffered views, rank popular posts, and prune old daily view counts.
+pub(crate) fn spawn_view_jobs(db: DatabaseConnection, view_counter: Arc<ViewCounter>) {
+    let flush_db = db.clone();
+    let refresh_db = db.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(VIEW_FLUSH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = flush_views(&flush_db, &view_counter).await {
+                println!("Error when flushing post views - {}", err);
+            }
+        }
+    });
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POPULAR_REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = refresh_popular_posts(&refresh_db).await {
+                println!("Error when ranking popular posts - {}", err);
+            }
+        }
+    });
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(VIEW_PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = prune_views(&db).await {
+                println!("Error when pruning post views - {}", err);
+            }
+        }
+    });
+}
+
+/// Adds buffered views to each post's total, and to today's count.
+async fn flush_views(
+    db: &DatabaseConnection,
+    view_counter: &ViewCounter,
+) -> Result<(), SameyError> {
+    let views = view_counter.take();
+    if views.is_empty() {
+        return Ok(());
+    }
+    let today = Utc::now().date_naive();
+
+    let txn = db.begin().await?;
+    for (post_id, post_views) in views {
+        let result = SameyPost::update_many()
+            .col_expr(
+                samey_post::Column::ViewCount,
+                Expr::col(samey_post::Column::ViewCount).add(post_views),
+            )
+            .filter(samey_post::Column::Id.eq(post_id))
+            .exec(&txn)
+            .await?;
+        // The post was deleted since it was viewed
+        if result.rows_affected == 0 {
+            continue;
+        }
+        SameyPostView::insert(samey_post_view::ActiveModel {
+            post_id: Set(post_id),
+            day: Set(today),
+            views: Set(post_views),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::columns([
+                samey_post_view::Column::PostId,
+                samey_post_view::Column::Day,
+            ])
+            .value(
+                samey_post_view::Column::Views,
+                Expr::col((SameyPostView, samey_post_view::Column::Views)).add(post_views),
+            )
+            .to_owned(),
+        )
+        .exec_without_returning(&txn)
+        .await?;
+    }
+    txn.commit().await?;
+
+    Ok(())
+}
+
+async fn prune_views(db: &DatabaseConnection) -> Result<(), SameyError> {
+    SameyPostView::delete_many()
+        .filter(samey_post_view::Column::Day.lt(days_ago(VIEW_HISTORY_DAYS)))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+fn days_ago(days: u64) -> NaiveDate {
+    let today = Utc::now().date_naive();
+    today.checked_sub_days(Days::new(days)).unwrap_or(today)
+}
+
+/// Replaces the ranking of every [`PopularPeriod`] with the current daily view counts.
+async fn refresh_popular_posts(db: &DatabaseConnection) -> Result<(), SameyError> {
+    let txn = db.begin().await?;
+    SameyPopularPost::delete_many().exec(&txn).await?;
+    for period in PopularPeriod::iter() {
+        let views: Vec<(i32, i64)> = SameyPostView::find()
+            .select_only()
+            .column(samey_post_view::Column::PostId)
+            .column_as(samey_post_view::Column::Views.sum(), "views")
+            .filter(samey_post_view::Column::Day.gte(days_ago(period.days() - 1)))
+            .group_by(samey_post_view::Column::PostId)
+            .into_tuple()
+            .all(&txn)
+            .await?;
+        for chunk in views.chunks(POPULAR_INSERT_CHUNK_SIZE) {
+            SameyPopularPost::insert_many(chunk.iter().map(|(post_id, views)| {
+                samey_popular_post::ActiveModel {
+                    period: Set(period.to_string()),
+                    post_id: Set(*post_id),
+                    views: Set((*views).try_into().unwrap_or(i32::MAX)),
+                    ..Default::default()
+                }
+            }))
+            .exec_without_returning(&txn)
+            .await?;
+        }
+    }
+    txn.commit().await?;
+    Ok(())
+}
+
+/// Returns the most viewed posts of the given period that the user can see.
+pub(crate) async fn get_popular_posts(
+    db: &DatabaseConnection,
+    user: Option<&User>,
+    period: PopularPeriod,
+) -> Result<Vec<PostOverview>, SameyError> {
+    let views = SimpleExpr::SubQuery(
+        None,
+        Box::new(
+            Query::select()
+                .column(samey_popular_post::Column::Views)
+                .from(SameyPopularPost)
+                .and_where(
+                    Expr::col((SameyPopularPost, samey_popular_post::Column::PostId))
+                        .equals((SameyPost, samey_post::Column::Id)),
+                )
+                .and_where(samey_popular_post::Column::Period.eq(period.to_string()))
+                .to_owned()
+                .into_sub_query_statement(),
+        ),
+    );
+    Ok(search_posts_query(&SearchQuery::default(), user)
+        .filter(
+            samey_post::Column::Id.in_subquery(
+                Query::select()
+                    .column(samey_popular_post::Column::PostId)
+                    .from(SameyPopularPost)
+                    .and_where(samey_popular_post::Column::Period.eq(period.to_string()))
+                    .to_owned(),
+            ),
+        )
+        .order_by_desc(views)
+        .order_by_desc(samey_post::Column::Id)
+        .limit(POPULAR_POSTS_LIMIT)
+        .into_model::<PostOverview>()
+        .all(db)
+        .await?)
+}
@@ -0,0 +1,145 @@
+use std::str::FromStr;
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+use chrono::{Locale, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use tower_sessions::Session;
+
+use crate::{AppState, SameyError, auth::AuthSession};
+
+pub(crate) const TIMEZONE_SESSION_KEY: &str = "timezone";
+pub(crate) const LOCALE_SESSION_KEY: &str = "locale";
+pub(crate) const POSTS_PER_PAGE_SESSION_KEY: &str = "posts_per_page";
+pub(crate) const THUMBNAIL_DENSITY_SESSION_KEY: &str = "thumbnail_density";
+pub(crate) const AUTOPLAY_VIDEOS_SESSION_KEY: &str = "autoplay_videos";
+
+/// Page sizes that visitors may pick for post lists.
+pub(crate) const POSTS_PER_PAGE_CHOICES: [u64; 4] = [20, 50, 100, 200];
+pub(crate) const DEFAULT_POSTS_PER_PAGE: u64 = 50;
+
+/// How closely thumbnails are packed in post lists.
+#[derive(
+    strum::EnumIter, strum::EnumString, strum::Display, Debug, Default, Clone, Copy, PartialEq, Eq,
+)]
+pub(crate) enum ThumbnailDensity {
+    #[strum(serialize = "compact")]
+    Compact,
+    #[default]
+    #[strum(serialize = "normal")]
+    Normal,
+    #[strum(serialize = "spacious")]
+    Spacious,
+}
+
+/// How the site is displayed to the current visitor, such as the timezone and locale of timestamps.
+///
+/// Users keep their preferences in their account, and guests in their session.
+#[derive(Debug, Clone)]
+pub(crate) struct Preferences {
+    pub(crate) timezone: Option<Tz>,
+    pub(crate) locale: Option<Locale>,
+    pub(crate) posts_per_page: u64,
+    pub(crate) thumbnail_density: ThumbnailDensity,
+    pub(crate) autoplay_videos: bool,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            timezone: None,
+            locale: None,
+            posts_per_page: DEFAULT_POSTS_PER_PAGE,
+            thumbnail_density: ThumbnailDensity::default(),
+            autoplay_videos: false,
+        }
+    }
+}
+
+impl Preferences {
+    /// Parses stored preferences, ignoring any invalid values.
+    pub(crate) fn parse(
+        timezone: Option<&str>,
+        locale: Option<&str>,
+        posts_per_page: Option<u64>,
+        thumbnail_density: Option<&str>,
+        autoplay_videos: bool,
+    ) -> Self {
+        Self {
+            timezone: timezone.and_then(|timezone| Tz::from_str(timezone).ok()),
+            locale: locale.and_then(|locale| Locale::from_str(locale).ok()),
+            posts_per_page: posts_per_page
+                .filter(|posts_per_page| POSTS_PER_PAGE_CHOICES.contains(posts_per_page))
+                .unwrap_or(DEFAULT_POSTS_PER_PAGE),
+            thumbnail_density: thumbnail_density
+                .and_then(|thumbnail_density| ThumbnailDensity::from_str(thumbnail_density).ok())
+                .unwrap_or_default(),
+            autoplay_videos,
+        }
+    }
+
+    /// Formats a UTC timestamp for display.
+    pub(crate) fn format_datetime(&self, datetime: &NaiveDateTime) -> String {
+        let datetime = Utc
+            .from_utc_datetime(datetime)
+            .with_timezone(&self.timezone.unwrap_or(Tz::UTC));
+        match self.locale {
+            Some(locale) => datetime.format_localized("%x %X %Z", locale).to_string(),
+            None => datetime.format("%Y-%m-%d %H:%M:%S %Z").to_string(),
+        }
+    }
+}
+
+impl FromRequestParts<AppState> for Preferences {
+    type Rejection = SameyError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let auth_session = AuthSession::from_request_parts(parts, state)
+            .await
+            .map_err(|(_, err)| SameyError::Other(err.into()))?;
+        if let Some(user) = auth_session.user {
+            return Ok(Self::parse(
+                user.timezone.as_deref(),
+                user.locale.as_deref(),
+                user.posts_per_page
+                    .and_then(|posts_per_page| posts_per_page.try_into().ok()),
+                user.thumbnail_density.as_deref(),
+                user.autoplay_videos,
+            ));
+        }
+
+        let session = Session::from_request_parts(parts, state)
+            .await
+            .map_err(|(_, err)| SameyError::Other(err.into()))?;
+        let timezone = session
+            .get::<String>(TIMEZONE_SESSION_KEY)
+            .await
+            .map_err(|err| SameyError::Other(err.to_string()))?;
+        let locale = session
+            .get::<String>(LOCALE_SESSION_KEY)
+            .await
+            .map_err(|err| SameyError::Other(err.to_string()))?;
+        let posts_per_page = session
+            .get::<u64>(POSTS_PER_PAGE_SESSION_KEY)
+            .await
+            .map_err(|err| SameyError::Other(err.to_string()))?;
+        let thumbnail_density = session
+            .get::<String>(THUMBNAIL_DENSITY_SESSION_KEY)
+            .await
+            .map_err(|err| SameyError::Other(err.to_string()))?;
+        let autoplay_videos = session
+            .get::<bool>(AUTOPLAY_VIDEOS_SESSION_KEY)
+            .await
+            .map_err(|err| SameyError::Other(err.to_string()))?
+            .unwrap_or(false);
+        Ok(Self::parse(
+            timezone.as_deref(),
+            locale.as_deref(),
+            posts_per_page,
+            thumbnail_density.as_deref(),
+            autoplay_videos,
+        ))
+    }
+}
@@ -1,27 +1,49 @@
 use std::collections::HashSet;
 
-use chrono::NaiveDateTime;
-use samey_migration::{Expr, Query};
+use chrono::{NaiveDateTime, NaiveTime, Utc};
+use samey_migration::{Expr, Func, Query};
 use sea_orm::{
     ColumnTrait, Condition, DatabaseConnection, EntityTrait, FromQueryResult, IntoIdentity,
-    IntoSimpleExpr, QueryFilter, QueryOrder, QuerySelect, RelationTrait, Select, SelectColumns,
-    SelectModel, Selector,
+    IntoSimpleExpr, Order, QueryFilter, QueryOrder, QuerySelect, RelationTrait, Select,
+    SelectColumns, SelectModel, Selector, sea_query::SimpleExpr,
 };
 
 use crate::{
     SameyError,
     auth::User,
+    content::thumbnail_srcset,
+    crosspost::MAX_CROSSPOST_ATTEMPTS,
+    disk_usage::POST_DISK_USAGE_SQL,
     entities::{
-        prelude::{SameyPool, SameyPoolPost, SameyPost, SameyTag, SameyTagPost},
-        samey_pool, samey_pool_post, samey_post, samey_tag, samey_tag_post,
+        prelude::{
+            SameyCrosspost, SameyIntegration, SameyNotification, SameyPool, SameyPoolPost,
+            SameyPost, SameyPostReport, SameyPostSource, SameyTag, SameyTagPost,
+            SameyTakedownRequest, SameyUser,
+        },
+        samey_crosspost, samey_integration, samey_notification, samey_pool, samey_pool_post,
+        samey_post, samey_post_report, samey_post_source, samey_tag, samey_tag_post,
+        samey_takedown_request, samey_user,
     },
-    tags::{MEDIA_TYPE_PREFIX, NEGATIVE_PREFIX, RATING_PREFIX},
+    preferences::ThumbnailDensity,
+    search::{Attribute, Comparison, SearchQuery, SearchTerm, SearchToken},
+    tags::{PostsOrder, UNRATED, extract_tag_tokens, levenshtein},
+    takedown::TakedownStatus,
 };
 
+/// Tokens at least this long may match tags with a single typo.
+const FUZZY_TOKEN_LENGTH: usize = 5;
+/// Maximum amount of candidate tags considered when suggesting tags from text.
+const MAX_TAG_CANDIDATES: u64 = 500;
+/// Maximum amount of tags suggested by autocompletion.
+const AUTOCOMPLETE_LIMIT: usize = 10;
+/// Autocompletion input at least this long may match tags with a typo.
+const AUTOCOMPLETE_FUZZY_LENGTH: usize = 4;
+
 #[derive(Debug, FromQueryResult)]
 pub(crate) struct PostOverview {
     pub(crate) id: i32,
     pub(crate) thumbnail: String,
+    pub(crate) thumbnail_variants: bool,
     pub(crate) media: String,
     pub(crate) title: Option<String>,
     pub(crate) description: Option<String>,
@@ -31,38 +53,315 @@ pub(crate) struct PostOverview {
     pub(crate) rating: String,
 }
 
+impl PostOverview {
+    pub(crate) fn thumbnail_srcset(&self, density: &ThumbnailDensity) -> Option<String> {
+        thumbnail_srcset(&self.thumbnail, self.thumbnail_variants, *density)
+    }
+}
+
 pub(crate) fn search_posts(
-    tags: Option<&Vec<&str>>,
+    search: &SearchQuery,
     user: Option<&User>,
 ) -> Selector<SelectModel<PostOverview>> {
+    sort_posts(search_posts_query(search, user), search.order, Order::Desc)
+        .into_model::<PostOverview>()
+}
+
+/// Sorts posts in the given order, from the first result to the last with [`Order::Desc`].
+fn sort_posts(
+    query: Select<SameyPost>,
+    posts_order: PostsOrder,
+    order: Order,
+) -> Select<SameyPost> {
+    let query = match posts_order {
+        PostsOrder::Newest => query,
+        PostsOrder::Views => query.order_by(samey_post::Column::ViewCount, order.clone()),
+    };
+    query.order_by(samey_post::Column::Id, order)
+}
+
+/// Filters posts that are listed before or after the given post, in the given order.
+async fn posts_around(
+    db: &DatabaseConnection,
+    posts_order: PostsOrder,
+    post_id: i32,
+    before: bool,
+) -> Result<SimpleExpr, SameyError> {
+    let id_condition = if before {
+        samey_post::Column::Id.gt(post_id)
+    } else {
+        samey_post::Column::Id.lt(post_id)
+    };
+    Ok(match posts_order {
+        PostsOrder::Newest => id_condition,
+        PostsOrder::Views => {
+            // A deleted post is considered to have no views
+            let view_count = SameyPost::find_by_id(post_id)
+                .select_only()
+                .column(samey_post::Column::ViewCount)
+                .into_tuple::<i32>()
+                .one(db)
+                .await?
+                .unwrap_or_default();
+            let view_count_condition = if before {
+                samey_post::Column::ViewCount.gt(view_count)
+            } else {
+                samey_post::Column::ViewCount.lt(view_count)
+            };
+            view_count_condition.or(samey_post::Column::ViewCount
+                .eq(view_count)
+                .and(id_condition))
+        }
+    })
+}
+
+/// Position of a page of posts, in the order of the search.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum PostsCursor {
+    /// Zero-indexed page, using an offset.
+    Page(u64),
+    /// Posts listed before the given ID.
+    Before(i32),
+    /// Posts listed after the given ID.
+    After(i32),
+}
+
+#[derive(Debug)]
+pub(crate) struct PostsKeysetPage {
+    pub(crate) posts: Vec<PostOverview>,
+    pub(crate) previous_id: Option<i32>,
+    pub(crate) next_id: Option<i32>,
+}
+
+/// Fetches a page of posts without counting all results, returning the IDs
+/// to use as `before_id` and `after_id` for the neighbouring pages.
+pub(crate) async fn search_posts_keyset(
+    db: &DatabaseConnection,
+    search: &SearchQuery,
+    user: Option<&User>,
+    cursor: PostsCursor,
+    page_size: u64,
+) -> Result<PostsKeysetPage, SameyError> {
+    let query = search_posts_query(search, user);
+    let posts_order = search.order;
+    let (posts, has_previous, has_next) = match cursor {
+        PostsCursor::Page(page) => {
+            let mut posts = sort_posts(query, posts_order, Order::Desc)
+                .offset(page * page_size)
+                .limit(page_size + 1)
+                .into_model::<PostOverview>()
+                .all(db)
+                .await?;
+            let has_next = posts.len() as u64 > page_size;
+            posts.truncate(page_size as usize);
+            (posts, page > 0, has_next)
+        }
+        PostsCursor::Before(before_id) => {
+            let mut posts = sort_posts(
+                query.filter(posts_around(db, posts_order, before_id, true).await?),
+                posts_order,
+                Order::Asc,
+            )
+            .limit(page_size + 1)
+            .into_model::<PostOverview>()
+            .all(db)
+            .await?;
+            let has_previous = posts.len() as u64 > page_size;
+            posts.truncate(page_size as usize);
+            posts.reverse();
+            (posts, has_previous, true)
+        }
+        PostsCursor::After(after_id) => {
+            let mut posts = sort_posts(
+                query.filter(posts_around(db, posts_order, after_id, false).await?),
+                posts_order,
+                Order::Desc,
+            )
+            .limit(page_size + 1)
+            .into_model::<PostOverview>()
+            .all(db)
+            .await?;
+            let has_next = posts.len() as u64 > page_size;
+            posts.truncate(page_size as usize);
+            (posts, true, has_next)
+        }
+    };
+
+    Ok(PostsKeysetPage {
+        previous_id: posts.first().filter(|_| has_previous).map(|post| post.id),
+        next_id: posts.last().filter(|_| has_next).map(|post| post.id),
+        posts,
+    })
+}
+
+/// Returns the IDs of the results listed right before and after the given post in a search.
+pub(crate) async fn get_search_neighbours(
+    db: &DatabaseConnection,
+    search: &SearchQuery,
+    user: Option<&User>,
+    post_id: i32,
+) -> Result<(Option<i32>, Option<i32>), SameyError> {
+    let posts_order = search.order;
+    let previous_id = sort_posts(
+        search_posts_query(search, user)
+            .filter(posts_around(db, posts_order, post_id, true).await?),
+        posts_order,
+        Order::Asc,
+    )
+    .select_only()
+    .column(samey_post::Column::Id)
+    .into_tuple::<i32>()
+    .one(db)
+    .await?;
+    let next_id = sort_posts(
+        search_posts_query(search, user)
+            .filter(posts_around(db, posts_order, post_id, false).await?),
+        posts_order,
+        Order::Desc,
+    )
+    .select_only()
+    .column(samey_post::Column::Id)
+    .into_tuple::<i32>()
+    .one(db)
+    .await?;
+    Ok((previous_id, next_id))
+}
+
+/// Counts the tags of each post in a [`SameyPost`] query.
+fn post_tag_count() -> SimpleExpr {
+    SimpleExpr::SubQuery(
+        None,
+        Box::new(
+            Query::select()
+                .expr(samey_tag_post::Column::Id.count())
+                .from(SameyTagPost)
+                .and_where(
+                    Expr::col((SameyTagPost, samey_tag_post::Column::PostId))
+                        .equals((SameyPost, samey_post::Column::Id)),
+                )
+                .to_owned()
+                .into_sub_query_statement(),
+        ),
+    )
+}
+
+/// Counts the sources of each post in a [`SameyPost`] query.
+fn post_source_count() -> SimpleExpr {
+    SimpleExpr::SubQuery(
+        None,
+        Box::new(
+            Query::select()
+                .expr(samey_post_source::Column::Id.count())
+                .from(SameyPostSource)
+                .and_where(
+                    Expr::col((SameyPostSource, samey_post_source::Column::PostId))
+                        .equals((SameyPost, samey_post::Column::Id)),
+                )
+                .to_owned()
+                .into_sub_query_statement(),
+        ),
+    )
+}
+
+/// SQL for the host of a source URL, which is what follows the scheme up to the first slash.
+fn source_host_sql() -> String {
+    let rest = "substr(\"samey_post_source\".\"url\", instr(\"samey_post_source\".\"url\", '://') + 3) || '/'";
+    format!("substr({0}, 1, instr({0}, '/') - 1)", rest)
+}
+
+/// Filters posts by a search token that isn't a tag, rating or media type.
+fn meta_token_condition(token: &SearchToken) -> Option<SimpleExpr> {
+    Some(match token {
+        SearchToken::Tag(_) | SearchToken::Rating(_) | SearchToken::MediaType(_) => return None,
+        SearchToken::Date(start) => {
+            let end = start.succ_opt()?;
+            samey_post::Column::UploadedAt
+                .gte(start.and_time(NaiveTime::MIN))
+                .and(samey_post::Column::UploadedAt.lt(end.and_time(NaiveTime::MIN)))
+        }
+        SearchToken::Text(text) => samey_post::Column::Title
+            .contains(text)
+            .or(samey_post::Column::Description.contains(text))
+            .or(samey_post::Column::TranslatedTitle.contains(text))
+            .or(samey_post::Column::TranslatedDescription.contains(text)),
+        SearchToken::TagCount(comparison, count) => {
+            let tag_count = Expr::expr(post_tag_count());
+            match comparison {
+                Comparison::Less => tag_count.lt(*count),
+                Comparison::LessOrEqual => tag_count.lte(*count),
+                Comparison::Equal => tag_count.eq(*count),
+                Comparison::GreaterOrEqual => tag_count.gte(*count),
+                Comparison::Greater => tag_count.gt(*count),
+            }
+        }
+        SearchToken::SourceDomain(domain) => {
+            let host = source_host_sql();
+            samey_post::Column::Id.in_subquery(
+                Query::select()
+                    .column(samey_post_source::Column::PostId)
+                    .from(SameyPostSource)
+                    .and_where(
+                        Expr::cust_with_values(format!("{} = ?", host), [domain]).or(
+                            Expr::cust_with_values(
+                                format!("{} LIKE ?", host),
+                                [format!("%.{}", domain)],
+                            ),
+                        ),
+                    )
+                    .to_owned(),
+            )
+        }
+        SearchToken::Has(attribute, has_any) => {
+            let condition = match attribute {
+                Attribute::Source => samey_post::Column::Id.in_subquery(
+                    Query::select()
+                        .column(samey_post_source::Column::PostId)
+                        .from(SameyPostSource)
+                        .to_owned(),
+                ),
+                Attribute::Description => samey_post::Column::Description
+                    .is_not_null()
+                    .and(samey_post::Column::Description.ne("")),
+                Attribute::Parent => samey_post::Column::ParentId.is_not_null(),
+                Attribute::Pool => samey_post::Column::Id.in_subquery(
+                    Query::select()
+                        .column(samey_pool_post::Column::PostId)
+                        .from(SameyPoolPost)
+                        .to_owned(),
+                ),
+            };
+            if *has_any { condition } else { condition.not() }
+        }
+    })
+}
+
+pub(crate) fn search_posts_query(search: &SearchQuery, user: Option<&User>) -> Select<SameyPost> {
     let mut include_tags = HashSet::<String>::new();
     let mut exclude_tags = HashSet::<String>::new();
     let mut include_ratings = HashSet::<String>::new();
     let mut exclude_ratings = HashSet::<String>::new();
     let mut include_types = HashSet::<String>::new();
     let mut exclude_types = HashSet::<String>::new();
-    if let Some(tags) = tags {
-        for tag in tags.iter().map(|tag| tag.to_lowercase()) {
-            if let Some(negative_tag) = tag.strip_prefix(NEGATIVE_PREFIX) {
-                if let Some(negative_rating_tag) = negative_tag.strip_prefix(RATING_PREFIX) {
-                    exclude_ratings.insert(negative_rating_tag.into());
-                } else if let Some(negative_type_tag) = negative_tag.strip_prefix(MEDIA_TYPE_PREFIX)
-                {
-                    exclude_types.insert(negative_type_tag.into());
-                } else {
-                    exclude_tags.insert(negative_tag.into());
+    let mut meta_conditions = Vec::<SimpleExpr>::new();
+    for SearchTerm { negated, token } in &search.terms {
+        let (values, value) = match (token, negated) {
+            (SearchToken::Tag(tag), false) => (&mut include_tags, tag),
+            (SearchToken::Tag(tag), true) => (&mut exclude_tags, tag),
+            (SearchToken::Rating(rating), false) => (&mut include_ratings, rating),
+            (SearchToken::Rating(rating), true) => (&mut exclude_ratings, rating),
+            (SearchToken::MediaType(media_type), false) => (&mut include_types, media_type),
+            (SearchToken::MediaType(media_type), true) => (&mut exclude_types, media_type),
+            _ => {
+                if let Some(condition) = meta_token_condition(token) {
+                    meta_conditions.push(if *negated { condition.not() } else { condition });
                 }
-            } else if let Some(rating_tag) = tag.strip_prefix(RATING_PREFIX) {
-                include_ratings.insert(rating_tag.into());
-            } else if let Some(type_tag) = tag.strip_prefix(MEDIA_TYPE_PREFIX) {
-                include_types.insert(type_tag.into());
-            } else {
-                include_tags.insert(tag);
+                continue;
             }
-        }
+        };
+        values.insert(value.clone());
     }
 
-    let query = if include_tags.is_empty() && exclude_tags.is_empty() {
+    let mut query = if include_tags.is_empty() && exclude_tags.is_empty() {
         let mut query = SameyPost::find()
             .select_only()
             .column(samey_post::Column::Id)
@@ -71,6 +370,7 @@ pub(crate) fn search_posts(
             .column(samey_post::Column::Description)
             .column(samey_post::Column::UploadedAt)
             .column(samey_post::Column::Thumbnail)
+            .column(samey_post::Column::ThumbnailVariants)
             .column(samey_post::Column::Rating)
             .column(samey_post::Column::MediaType)
             .column_as(
@@ -104,6 +404,7 @@ pub(crate) fn search_posts(
             .column(samey_post::Column::Description)
             .column(samey_post::Column::UploadedAt)
             .column(samey_post::Column::Thumbnail)
+            .column(samey_post::Column::ThumbnailVariants)
             .column(samey_post::Column::Rating)
             .column(samey_post::Column::MediaType)
             .column_as(
@@ -168,11 +469,39 @@ pub(crate) fn search_posts(
         }
         query
     };
+    for condition in meta_conditions {
+        query = query.filter(condition);
+    }
 
-    filter_posts_by_user(query, user)
+    // Drafts are only listed on their uploader's drafts page
+    filter_posts_by_user(query.filter(samey_post::Column::IsDraft.eq(false)), user)
         .group_by(samey_post::Column::Id)
-        .order_by_desc(samey_post::Column::Id)
-        .into_model::<PostOverview>()
+}
+
+#[derive(Debug, FromQueryResult)]
+pub(crate) struct DayCount {
+    pub(crate) day: String,
+    pub(crate) post_count: i64,
+}
+
+/// Counts the posts uploaded on each day between `start` and `end`, as `YYYY-MM-DD` in UTC.
+pub(crate) fn get_upload_counts_by_day(
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    user: Option<&User>,
+) -> Selector<SelectModel<DayCount>> {
+    filter_posts_by_user(SameyPost::find(), user)
+        .select_only()
+        .column_as(
+            Expr::cust("strftime('%Y-%m-%d', \"samey_post\".\"uploaded_at\")"),
+            "day",
+        )
+        .column_as(samey_post::Column::Id.count(), "post_count")
+        .filter(samey_post::Column::UploadedAt.gte(start))
+        .filter(samey_post::Column::UploadedAt.lt(end))
+        .group_by(Expr::col("day".into_identity()))
+        .order_by_asc(Expr::col("day".into_identity()))
+        .into_model::<DayCount>()
 }
 
 pub(crate) fn get_tags_for_post(post_id: i32) -> Select<SameyTag> {
@@ -182,6 +511,122 @@ pub(crate) fn get_tags_for_post(post_id: i32) -> Select<SameyTag> {
         .order_by_asc(samey_tag::Column::Name)
 }
 
+pub(crate) fn get_protected_tags() -> Select<SameyTag> {
+    SameyTag::find()
+        .filter(samey_tag::Column::IsProtected.into_simple_expr())
+        .order_by_asc(samey_tag::Column::Name)
+}
+
+/// Sorts the space-separated tags of each post by name.
+pub(crate) fn sort_post_overview_tags(posts: Vec<PostOverview>) -> Vec<PostOverview> {
+    posts
+        .into_iter()
+        .map(|post| {
+            let tags: Option<String> = post.tags.map(|tags| {
+                let mut tags_vec = tags.split_ascii_whitespace().collect::<Vec<&str>>();
+                tags_vec.sort();
+                tags_vec.join(" ")
+            });
+            PostOverview { tags, ..post }
+        })
+        .collect()
+}
+
+#[derive(Debug, FromQueryResult)]
+pub(crate) struct TagCount {
+    pub(crate) name: String,
+    pub(crate) post_count: i64,
+}
+
+/// Counts posts for each tag, including private posts.
+fn tags_with_post_count() -> Select<SameyTag> {
+    SameyTag::find()
+        .select_only()
+        .column(samey_tag::Column::Name)
+        .column_as(samey_tag_post::Column::Id.count(), "post_count")
+        .left_join(SameyTagPost)
+        .group_by(samey_tag::Column::Id)
+        .order_by_desc(Expr::col("post_count".into_identity()))
+        .order_by_asc(samey_tag::Column::Name)
+}
+
+/// Returns tags to autocomplete `input` with, most relevant first.
+///
+/// Tags starting with the input come first, then tags containing it (such as `artist:input`), then tags that
+/// start with a single typo away from it. Within each group, tags with more posts come first.
+pub(crate) async fn autocomplete_tags(
+    db: &DatabaseConnection,
+    input: &str,
+) -> Result<Vec<TagCount>, SameyError> {
+    let input = input.to_lowercase();
+    let mut matches: Vec<(u8, TagCount)> = tags_with_post_count()
+        .filter(samey_tag::Column::NormalizedName.contains(&input))
+        .limit(MAX_TAG_CANDIDATES)
+        .into_model::<TagCount>()
+        .all(db)
+        .await?
+        .into_iter()
+        .filter_map(|tag| {
+            let name = tag.name.to_lowercase();
+            if name.starts_with(&input) {
+                Some((0, tag))
+            } else if name.contains(&input) {
+                Some((1, tag))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let input_length = input.chars().count();
+    if matches.len() < AUTOCOMPLETE_LIMIT && input_length >= AUTOCOMPLETE_FUZZY_LENGTH {
+        let first_char: String = input.chars().take(1).collect();
+        let fuzzy_matches = tags_with_post_count()
+            .filter(samey_tag::Column::NormalizedName.starts_with(first_char))
+            .filter(samey_tag::Column::NormalizedName.contains(&input).not())
+            .limit(MAX_TAG_CANDIDATES)
+            .into_model::<TagCount>()
+            .all(db)
+            .await?
+            .into_iter()
+            .filter(|tag| {
+                let name = tag.name.to_lowercase();
+                // Compare against the start of the tag, since the input may be incomplete
+                let prefix: String = name.chars().take(input_length).collect();
+                levenshtein(&input, &prefix) <= 1
+            })
+            .map(|tag| (2, tag));
+        matches.extend(fuzzy_matches);
+    }
+
+    // Stable sort, keeping the post count order within each group
+    matches.sort_by_key(|(group, _)| *group);
+    Ok(matches
+        .into_iter()
+        .take(AUTOCOMPLETE_LIMIT)
+        .map(|(_, tag)| tag)
+        .collect())
+}
+
+/// Returns the tags used by the most public posts.
+pub(crate) fn get_top_tags(limit: u64) -> Selector<SelectModel<TagCount>> {
+    SameyTag::find()
+        .select_only()
+        .column(samey_tag::Column::Name)
+        .column_as(samey_tag_post::Column::Id.count(), "post_count")
+        .inner_join(SameyTagPost)
+        .join(
+            sea_orm::JoinType::InnerJoin,
+            samey_tag_post::Relation::SameyPost.def(),
+        )
+        .filter(published_posts_condition())
+        .group_by(samey_tag::Column::Id)
+        .order_by_desc(Expr::col("post_count".into_identity()))
+        .order_by_asc(samey_tag::Column::Name)
+        .limit(limit)
+        .into_model::<TagCount>()
+}
+
 #[derive(Debug)]
 pub(crate) struct PostPoolData {
     pub(crate) id: i32,
@@ -202,20 +647,16 @@ pub(crate) async fn get_pool_data_for_post(
     post_id: i32,
     user: Option<&User>,
 ) -> Result<Vec<PostPoolData>, SameyError> {
-    let mut query = SameyPool::find()
-        .inner_join(SameyPoolPost)
-        .select_column(samey_pool_post::Column::Position)
-        .filter(samey_pool_post::Column::PostId.eq(post_id));
-    query = match user {
-        None => query.filter(samey_pool::Column::IsPublic.into_simple_expr()),
-        Some(user) if user.is_admin => query,
-        Some(user) => query.filter(
-            Condition::any()
-                .add(samey_pool::Column::IsPublic.into_simple_expr())
-                .add(samey_pool::Column::UploaderId.eq(user.id)),
-        ),
-    };
-    let pools = query.into_model::<PostInPool>().all(db).await?;
+    let pools = filter_pools_by_user(
+        SameyPool::find()
+            .inner_join(SameyPoolPost)
+            .select_column(samey_pool_post::Column::Position)
+            .filter(samey_pool_post::Column::PostId.eq(post_id)),
+        user,
+    )
+    .into_model::<PostInPool>()
+    .all(db)
+    .await?;
 
     let mut post_pool_datas = Vec::with_capacity(pools.len());
     for pool in pools.into_iter() {
@@ -244,6 +685,7 @@ pub(crate) async fn get_pool_data_for_post(
 pub(crate) struct PoolPost {
     pub(crate) id: i32,
     pub(crate) thumbnail: String,
+    pub(crate) thumbnail_variants: bool,
     pub(crate) rating: String,
     pub(crate) media_type: String,
     pub(crate) pool_post_id: i32,
@@ -251,6 +693,12 @@ pub(crate) struct PoolPost {
     pub(crate) tags: String,
 }
 
+impl PoolPost {
+    pub(crate) fn thumbnail_srcset(&self, density: &ThumbnailDensity) -> Option<String> {
+        thumbnail_srcset(&self.thumbnail, self.thumbnail_variants, *density)
+    }
+}
+
 pub(crate) fn get_posts_in_pool(
     pool_id: i32,
     user: Option<&User>,
@@ -259,6 +707,7 @@ pub(crate) fn get_posts_in_pool(
         SameyPost::find()
             .column(samey_post::Column::Id)
             .column(samey_post::Column::Thumbnail)
+            .column(samey_post::Column::ThumbnailVariants)
             .column(samey_post::Column::Rating)
             .column(samey_post::Column::MediaType)
             .column_as(samey_pool_post::Column::Id, "pool_post_id")
@@ -281,21 +730,366 @@ pub(crate) fn get_posts_in_pool(
     .into_model::<PoolPost>()
 }
 
+/// Matches posts that anyone may see, which are public, not drafts, past their scheduled time and not expired yet.
+pub(crate) fn published_posts_condition() -> Condition {
+    let now = Utc::now().naive_utc();
+    Condition::all()
+        .add(samey_post::Column::IsPublic.into_simple_expr())
+        .add(samey_post::Column::IsDraft.eq(false))
+        .add(samey_post::Column::TakenDownAt.is_null())
+        .add(
+            Condition::any()
+                .add(samey_post::Column::PublishAt.is_null())
+                .add(samey_post::Column::PublishAt.lte(now)),
+        )
+        .add(
+            Condition::any()
+                .add(samey_post::Column::ExpiresAt.is_null())
+                .add(samey_post::Column::ExpiresAt.gt(now)),
+        )
+}
+
+/// Whether anyone may see a post, the same as [`published_posts_condition`].
+pub(crate) fn is_post_published(post: &samey_post::Model) -> bool {
+    let now = Utc::now().naive_utc();
+    post.is_public
+        && !post.is_draft
+        && post.taken_down_at.is_none()
+        && post.publish_at.is_none_or(|publish_at| publish_at <= now)
+        && post.expires_at.is_none_or(|expires_at| expires_at > now)
+}
+
 pub(crate) fn filter_posts_by_user(
     query: Select<SameyPost>,
     user: Option<&User>,
 ) -> Select<SameyPost> {
     match user {
-        None => query.filter(samey_post::Column::IsPublic.into_simple_expr()),
+        None => query.filter(published_posts_condition()),
+        Some(user) if user.is_admin => query,
+        Some(user) => query.filter(
+            Condition::any().add(published_posts_condition()).add(
+                Condition::all()
+                    .add(samey_post::Column::UploaderId.eq(user.id))
+                    .add(samey_post::Column::TakenDownAt.is_null()),
+            ),
+        ),
+    }
+}
+
+pub(crate) fn filter_pools_by_user(
+    query: Select<SameyPool>,
+    user: Option<&User>,
+) -> Select<SameyPool> {
+    match user {
+        None => query.filter(samey_pool::Column::IsPublic.into_simple_expr()),
         Some(user) if user.is_admin => query,
         Some(user) => query.filter(
             Condition::any()
-                .add(samey_post::Column::IsPublic.into_simple_expr())
-                .add(samey_post::Column::UploaderId.eq(user.id)),
+                .add(samey_pool::Column::IsPublic.into_simple_expr())
+                .add(samey_pool::Column::UploaderId.eq(user.id)),
         ),
     }
 }
 
+#[derive(Debug, FromQueryResult)]
+pub(crate) struct PendingPostReport {
+    pub(crate) id: i32,
+    pub(crate) post_id: i32,
+    pub(crate) thumbnail: String,
+    pub(crate) reason: String,
+    pub(crate) reporter: String,
+    pub(crate) created_at: NaiveDateTime,
+}
+
+pub(crate) fn get_pending_post_reports() -> Selector<SelectModel<PendingPostReport>> {
+    SameyPostReport::find()
+        .select_only()
+        .column(samey_post_report::Column::Id)
+        .column(samey_post_report::Column::PostId)
+        .column(samey_post::Column::Thumbnail)
+        .column(samey_post_report::Column::Reason)
+        .column_as(samey_user::Column::Username, "reporter")
+        .column(samey_post_report::Column::CreatedAt)
+        .inner_join(SameyPost)
+        .join(
+            sea_orm::JoinType::InnerJoin,
+            samey_post_report::Relation::SameyUser1.def(),
+        )
+        .filter(samey_post_report::Column::ResolvedAt.is_null())
+        .order_by_asc(samey_post_report::Column::CreatedAt)
+        .into_model::<PendingPostReport>()
+}
+
+#[derive(Debug, FromQueryResult)]
+pub(crate) struct PendingTakedownRequest {
+    pub(crate) id: i32,
+    pub(crate) post_id: Option<i32>,
+    pub(crate) thumbnail: Option<String>,
+    pub(crate) claimant_name: String,
+    pub(crate) claimant_email: String,
+    pub(crate) reason: String,
+    pub(crate) created_at: NaiveDateTime,
+}
+
+/// Returns the takedown requests that haven't been reviewed yet, oldest first.
+///
+/// Requests whose post was deleted in the meantime are included without one, so that they can still be dismissed.
+pub(crate) fn get_pending_takedown_requests() -> Selector<SelectModel<PendingTakedownRequest>> {
+    SameyTakedownRequest::find()
+        .select_only()
+        .column(samey_takedown_request::Column::Id)
+        .column(samey_takedown_request::Column::PostId)
+        .column(samey_post::Column::Thumbnail)
+        .column(samey_takedown_request::Column::ClaimantName)
+        .column(samey_takedown_request::Column::ClaimantEmail)
+        .column(samey_takedown_request::Column::Reason)
+        .column(samey_takedown_request::Column::CreatedAt)
+        .left_join(SameyPost)
+        .filter(samey_takedown_request::Column::Status.eq(TakedownStatus::Pending.to_string()))
+        .order_by_asc(samey_takedown_request::Column::CreatedAt)
+        .into_model::<PendingTakedownRequest>()
+}
+
+#[derive(Debug, FromQueryResult)]
+pub(crate) struct UserOverview {
+    pub(crate) id: i32,
+    pub(crate) username: String,
+    pub(crate) avatar: Option<String>,
+    pub(crate) is_admin: bool,
+    pub(crate) is_disabled: bool,
+    pub(crate) last_login_at: Option<NaiveDateTime>,
+    pub(crate) created_at: Option<NaiveDateTime>,
+    pub(crate) upload_count: i64,
+    pub(crate) disk_usage: i64,
+}
+
+fn user_overviews() -> Select<SameyUser> {
+    SameyUser::find()
+        .select_only()
+        .column(samey_user::Column::Id)
+        .column(samey_user::Column::Username)
+        .column(samey_user::Column::Avatar)
+        .column(samey_user::Column::IsAdmin)
+        .column(samey_user::Column::IsDisabled)
+        .column(samey_user::Column::LastLoginAt)
+        .column(samey_user::Column::CreatedAt)
+        .column_as(
+            SimpleExpr::SubQuery(
+                None,
+                Box::new(
+                    Query::select()
+                        .expr(samey_post::Column::Id.count())
+                        .from(SameyPost)
+                        .and_where(
+                            Expr::col((SameyPost, samey_post::Column::UploaderId))
+                                .equals((SameyUser, samey_user::Column::Id)),
+                        )
+                        .to_owned()
+                        .into_sub_query_statement(),
+                ),
+            ),
+            "upload_count",
+        )
+        .column_as(
+            Expr::cust(format!(
+                "(SELECT COALESCE(SUM({}), 0) FROM \"samey_post\" WHERE \"samey_post\".\"uploader_id\" = \"samey_user\".\"id\")",
+                POST_DISK_USAGE_SQL
+            )),
+            "disk_usage",
+        )
+}
+
+/// Matches users who last logged in before the given time, or who registered before then and never logged in.
+pub(crate) fn inactive_users_condition(inactive_since: NaiveDateTime) -> SimpleExpr {
+    Expr::expr(Func::coalesce([
+        Expr::col(samey_user::Column::LastLoginAt).into(),
+        Expr::col(samey_user::Column::CreatedAt).into(),
+    ]))
+    .lt(inactive_since)
+}
+
+/// Lists users by username with how many posts each uploaded.
+///
+/// Users may be filtered by text in their username, and by being inactive since the given time.
+pub(crate) fn get_user_overviews(
+    search: Option<&str>,
+    inactive_since: Option<NaiveDateTime>,
+) -> Selector<SelectModel<UserOverview>> {
+    let mut query = user_overviews();
+    if let Some(search) = search.map(str::trim).filter(|search| !search.is_empty()) {
+        query = query.filter(samey_user::Column::Username.contains(search));
+    }
+    if let Some(inactive_since) = inactive_since {
+        query = query.filter(inactive_users_condition(inactive_since));
+    }
+    query
+        .order_by_asc(samey_user::Column::Username)
+        .into_model::<UserOverview>()
+}
+
+pub(crate) fn get_user_overview(user_id: i32) -> Selector<SelectModel<UserOverview>> {
+    user_overviews()
+        .filter(samey_user::Column::Id.eq(user_id))
+        .into_model::<UserOverview>()
+}
+
+#[derive(Debug, FromQueryResult)]
+pub(crate) struct IntegrationOverview {
+    pub(crate) id: i32,
+    pub(crate) kind: String,
+    pub(crate) name: String,
+    pub(crate) tag_filter: String,
+    pub(crate) rate_limited_until: Option<NaiveDateTime>,
+    pub(crate) delivered: i64,
+    pub(crate) pending: i64,
+    pub(crate) failed: i64,
+}
+
+/// Returns every integration, with how many of its crossposts were delivered, are queued, or gave up.
+pub(crate) fn get_integration_overviews() -> Selector<SelectModel<IntegrationOverview>> {
+    SameyIntegration::find()
+        .select_only()
+        .column(samey_integration::Column::Id)
+        .column(samey_integration::Column::Kind)
+        .column(samey_integration::Column::Name)
+        .column(samey_integration::Column::TagFilter)
+        .column(samey_integration::Column::RateLimitedUntil)
+        .column_as(samey_crosspost::Column::DeliveredAt.count(), "delivered")
+        .column_as(
+            Expr::cust(format!(
+                "COALESCE(SUM(\"samey_crosspost\".\"delivered_at\" IS NULL AND \"samey_crosspost\".\"attempts\" < {}), 0)",
+                MAX_CROSSPOST_ATTEMPTS
+            )),
+            "pending",
+        )
+        .column_as(
+            Expr::cust(format!(
+                "COALESCE(SUM(\"samey_crosspost\".\"delivered_at\" IS NULL AND \"samey_crosspost\".\"attempts\" >= {}), 0)",
+                MAX_CROSSPOST_ATTEMPTS
+            )),
+            "failed",
+        )
+        .left_join(SameyCrosspost)
+        .group_by(samey_integration::Column::Id)
+        .order_by_asc(samey_integration::Column::Id)
+        .into_model::<IntegrationOverview>()
+}
+
+#[derive(Debug, FromQueryResult)]
+pub(crate) struct CrosspostError {
+    pub(crate) post_id: i32,
+    pub(crate) integration: String,
+    pub(crate) attempts: i32,
+    pub(crate) last_error: String,
+}
+
+/// Returns undelivered crossposts that failed at least once, including those that will be retried.
+pub(crate) fn get_crosspost_errors(limit: u64) -> Selector<SelectModel<CrosspostError>> {
+    SameyCrosspost::find()
+        .select_only()
+        .column(samey_crosspost::Column::PostId)
+        .column_as(samey_integration::Column::Name, "integration")
+        .column(samey_crosspost::Column::Attempts)
+        .column(samey_crosspost::Column::LastError)
+        .inner_join(SameyIntegration)
+        .filter(samey_crosspost::Column::DeliveredAt.is_null())
+        .filter(samey_crosspost::Column::LastError.is_not_null())
+        .order_by_desc(samey_crosspost::Column::RunAt)
+        .limit(limit)
+        .into_model::<CrosspostError>()
+}
+
+#[derive(Debug, FromQueryResult)]
+pub(crate) struct CurationPost {
+    pub(crate) id: i32,
+    pub(crate) thumbnail: String,
+    pub(crate) rating: String,
+    pub(crate) tags: Option<String>,
+    pub(crate) tag_count: i64,
+    pub(crate) source_count: i64,
+}
+
+fn curation_posts() -> Select<SameyPost> {
+    SameyPost::find()
+        .select_only()
+        .column(samey_post::Column::Id)
+        .column(samey_post::Column::Thumbnail)
+        .column(samey_post::Column::Rating)
+        .column_as(
+            Expr::cust("GROUP_CONCAT(\"samey_tag\".\"name\", ' ')"),
+            "tags",
+        )
+        .column_as(post_tag_count(), "tag_count")
+        .column_as(post_source_count(), "source_count")
+        .left_join(SameyTagPost)
+        .join(
+            sea_orm::JoinType::LeftJoin,
+            samey_tag_post::Relation::SameyTag.def(),
+        )
+        .group_by(samey_post::Column::Id)
+}
+
+/// Returns posts that are unrated, have less than `min_tags` tags, or have no sources, oldest first.
+pub(crate) fn get_posts_needing_curation(min_tags: u32) -> Selector<SelectModel<CurationPost>> {
+    curation_posts()
+        .filter(
+            Condition::any()
+                .add(samey_post::Column::Rating.eq(UNRATED))
+                .add(Expr::expr(post_tag_count()).lt(min_tags))
+                .add(Expr::expr(post_source_count()).eq(0)),
+        )
+        .order_by_asc(samey_post::Column::Id)
+        .into_model::<CurationPost>()
+}
+
+/// Returns a user's drafts, newest first.
+pub(crate) fn get_drafts_for_user(user_id: i32) -> Selector<SelectModel<CurationPost>> {
+    curation_posts()
+        .filter(samey_post::Column::UploaderId.eq(user_id))
+        .filter(samey_post::Column::IsDraft.eq(true))
+        .order_by_desc(samey_post::Column::Id)
+        .into_model::<CurationPost>()
+}
+
+pub(crate) fn get_curation_post(post_id: i32) -> Selector<SelectModel<CurationPost>> {
+    curation_posts()
+        .filter(samey_post::Column::Id.eq(post_id))
+        .into_model::<CurationPost>()
+}
+
+#[derive(Debug, FromQueryResult)]
+pub(crate) struct NotificationOverview {
+    pub(crate) id: i32,
+    pub(crate) kind: String,
+    pub(crate) is_read: bool,
+    pub(crate) created_at: NaiveDateTime,
+    pub(crate) post_id: i32,
+    pub(crate) thumbnail: String,
+    pub(crate) actor: String,
+}
+
+pub(crate) fn get_notifications_for_user(user: &User) -> Select<SameyPost> {
+    filter_posts_by_user(
+        SameyPost::find()
+            .select_only()
+            .column_as(samey_notification::Column::Id, "id")
+            .column(samey_notification::Column::Kind)
+            .column(samey_notification::Column::IsRead)
+            .column(samey_notification::Column::CreatedAt)
+            .column_as(samey_post::Column::Id, "post_id")
+            .column(samey_post::Column::Thumbnail)
+            .column_as(samey_user::Column::Username, "actor")
+            .inner_join(SameyNotification)
+            .join(
+                sea_orm::JoinType::InnerJoin,
+                samey_notification::Relation::SameyUser2.def(),
+            )
+            .filter(samey_notification::Column::UserId.eq(user.id)),
+        Some(user),
+    )
+    .order_by_desc(samey_notification::Column::CreatedAt)
+}
+
 pub(crate) async fn clean_dangling_tags(db: &DatabaseConnection) -> Result<(), SameyError> {
     let dangling_tags = SameyTag::find()
         .select_column_as(samey_tag_post::Column::Id.count(), "count")
@@ -310,3 +1104,46 @@ pub(crate) async fn clean_dangling_tags(db: &DatabaseConnection) -> Result<(), S
         .await?;
     Ok(())
 }
+
+/// Suggests existing tags that match tokens from uploaded file names or source URLs.
+///
+/// Tags in `exclude` (by normalized name) are never suggested.
+pub(crate) async fn suggest_tags_from_text(
+    db: &DatabaseConnection,
+    texts: &[&str],
+    exclude: &HashSet<String>,
+) -> Result<Vec<String>, SameyError> {
+    let tokens: HashSet<String> = texts
+        .iter()
+        .flat_map(|text| extract_tag_tokens(text))
+        .collect();
+    if tokens.is_empty() {
+        return Ok(vec![]);
+    }
+
+    // Fuzzy matches must share their first characters, to keep the candidates to a manageable amount
+    let condition = tokens.iter().fold(Condition::any(), |condition, token| {
+        let prefix: String = token.chars().take(2).collect();
+        condition
+            .add(samey_tag::Column::NormalizedName.eq(token.as_str()))
+            .add(samey_tag::Column::NormalizedName.starts_with(prefix))
+    });
+    let candidates = SameyTag::find()
+        .filter(condition)
+        .limit(MAX_TAG_CANDIDATES)
+        .all(db)
+        .await?;
+
+    Ok(candidates
+        .into_iter()
+        .filter(|tag| !exclude.contains(&tag.normalized_name))
+        .filter(|tag| {
+            tokens.iter().any(|token| {
+                *token == tag.normalized_name
+                    || (token.chars().count() >= FUZZY_TOKEN_LENGTH
+                        && levenshtein(token, &tag.normalized_name) <= 1)
+            })
+        })
+        .map(|tag| tag.name)
+        .collect())
+}
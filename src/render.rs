@@ -0,0 +1,184 @@
+use percent_encoding::{NON_ALPHANUMERIC, utf8_percent_encode};
+use pulldown_cmark::{CowStr, Event, LinkType, Parser, Tag, TagEnd, TextMergeStream, html};
+
+/// Custom filters for templates.
+pub(crate) mod filters {
+    /// Renders markdown into HTML, including post references and tag links.
+    pub(crate) fn markdown(
+        s: impl std::fmt::Display,
+    ) -> askama::Result<askama::filters::Safe<String>> {
+        Ok(askama::filters::Safe(super::render_markdown(
+            &s.to_string(),
+        )))
+    }
+}
+
+/// Where post references and tag links in rendered markdown lead to.
+pub(crate) trait MarkdownLinks {
+    fn post(&self, post_id: i32) -> String;
+    fn post_thumbnail(&self, post_id: i32) -> String;
+    fn tag(&self, tag: &str) -> String;
+}
+
+/// Links to the pages of the running server.
+struct ServerLinks;
+
+impl MarkdownLinks for ServerLinks {
+    fn post(&self, post_id: i32) -> String {
+        format!("/post/{}", post_id)
+    }
+
+    fn post_thumbnail(&self, post_id: i32) -> String {
+        format!("/post/{}/thumbnail", post_id)
+    }
+
+    fn tag(&self, tag: &str) -> String {
+        format!("/posts?tags={}", utf8_percent_encode(tag, NON_ALPHANUMERIC))
+    }
+}
+
+/// Piece of text that may refer to other content.
+enum ContentToken<'a> {
+    Text(&'a str),
+    /// A `>>123` reference to a post, shown as its thumbnail.
+    PostReference(i32),
+    /// A `{{tag_name}}` link to a search for a tag.
+    TagLink(&'a str),
+}
+
+/// Returns the post ID of a `>>123` reference at the start of `text`, along with the length of the reference.
+fn parse_post_reference(text: &str) -> Option<(i32, usize)> {
+    let digits = text.strip_prefix(">>")?;
+    let length = digits
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    let post_id = digits[..length].parse().ok()?;
+    Some((post_id, length + 2))
+}
+
+/// Returns the tag of a `{{tag_name}}` link at the start of `text`, along with the length of the link.
+fn parse_tag_link(text: &str) -> Option<(&str, usize)> {
+    let rest = text.strip_prefix("{{")?;
+    let end = rest.find("}}")?;
+    let tag = &rest[..end];
+    if tag.is_empty() || tag.contains(|c: char| c.is_whitespace() || c == '{' || c == '}') {
+        return None;
+    }
+    Some((tag, end + 4))
+}
+
+/// Splits text into plain text, post references and tag links.
+fn tokenize(text: &str) -> Vec<ContentToken<'_>> {
+    let mut tokens = Vec::new();
+    let mut text_start = 0;
+    let mut index = 0;
+    while index < text.len() {
+        let rest = &text[index..];
+        let (token, length) = if let Some((post_id, length)) = parse_post_reference(rest) {
+            (ContentToken::PostReference(post_id), length)
+        } else if let Some((tag, length)) = parse_tag_link(rest) {
+            (ContentToken::TagLink(tag), length)
+        } else {
+            index += rest.chars().next().map_or(1, char::len_utf8);
+            continue;
+        };
+        if text_start < index {
+            tokens.push(ContentToken::Text(&text[text_start..index]));
+        }
+        tokens.push(token);
+        index += length;
+        text_start = index;
+    }
+    if text_start < text.len() {
+        tokens.push(ContentToken::Text(&text[text_start..]));
+    }
+    tokens
+}
+
+/// Turns a piece of text into markdown events, with links for post references and tag links.
+fn token_events(text: &str, links: &dyn MarkdownLinks) -> Vec<Event<'static>> {
+    let mut events = Vec::new();
+    for token in tokenize(text) {
+        match token {
+            ContentToken::Text(text) => events.push(Event::Text(text.to_owned().into())),
+            ContentToken::PostReference(post_id) => {
+                let link = Tag::Link {
+                    link_type: LinkType::Inline,
+                    dest_url: links.post(post_id).into(),
+                    title: format!("Post #{}", post_id).into(),
+                    id: CowStr::Borrowed(""),
+                };
+                let image = Tag::Image {
+                    link_type: LinkType::Inline,
+                    dest_url: links.post_thumbnail(post_id).into(),
+                    title: CowStr::Borrowed(""),
+                    id: CowStr::Borrowed(""),
+                };
+                events.extend([
+                    Event::Start(link),
+                    Event::Start(image),
+                    Event::Text(format!(">>{}", post_id).into()),
+                    Event::End(TagEnd::Image),
+                    Event::End(TagEnd::Link),
+                ]);
+            }
+            ContentToken::TagLink(tag) => {
+                let link = Tag::Link {
+                    link_type: LinkType::Inline,
+                    dest_url: links.tag(tag).into(),
+                    title: CowStr::Borrowed(""),
+                    id: CowStr::Borrowed(""),
+                };
+                events.extend([
+                    Event::Start(link),
+                    Event::Text(tag.to_owned().into()),
+                    Event::End(TagEnd::Link),
+                ]);
+            }
+        }
+    }
+    events
+}
+
+/// Escapes post references at the start of lines, which markdown would otherwise read as nested block quotes.
+fn escape_leading_post_references(text: &str) -> String {
+    text.split_inclusive('\n')
+        .map(|line| {
+            if parse_post_reference(line).is_some() {
+                format!("\\{}", line)
+            } else {
+                line.to_owned()
+            }
+        })
+        .collect()
+}
+
+/// Renders markdown written by users into sanitized HTML.
+///
+/// Besides regular markdown, `>>123` links to a post with its thumbnail, and `{{tag_name}}` links to a search for
+/// a tag. Neither is replaced inside of links or code. Raw HTML in the markdown is kept only if it's harmless, so
+/// scripts, event handlers and the like are removed.
+pub(crate) fn render_markdown(text: &str) -> String {
+    render_markdown_with_links(text, &ServerLinks)
+}
+
+/// Renders markdown like [`render_markdown`], with post references and tag links leading somewhere else.
+pub(crate) fn render_markdown_with_links(text: &str, links: &dyn MarkdownLinks) -> String {
+    let text = escape_leading_post_references(text);
+    let mut literal_depth = 0usize;
+    let events = TextMergeStream::new(Parser::new(&text)).flat_map(|event| match event {
+        Event::Start(Tag::Link { .. } | Tag::Image { .. } | Tag::CodeBlock(_)) => {
+            literal_depth += 1;
+            vec![event]
+        }
+        Event::End(TagEnd::Link | TagEnd::Image | TagEnd::CodeBlock) => {
+            literal_depth = literal_depth.saturating_sub(1);
+            vec![event]
+        }
+        Event::Text(text) if literal_depth == 0 => token_events(&text, links),
+        event => vec![event],
+    });
+    let mut output = String::new();
+    html::push_html(&mut output, events);
+    ammonia::clean(&output)
+}
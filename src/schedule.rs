@@ -0,0 +1,142 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{NaiveDateTime, Utc};
+use samey_migration::Expr;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use tokio::sync::RwLock;
+
+use crate::{
+    SameyError,
+    activitypub::{Federation, federate_post},
+    config::AppConfig,
+    content::remove_post_files,
+    crosspost::queue_crossposts,
+    entities::{prelude::SameyPost, samey_post},
+    query::clean_dangling_tags,
+};
+
+/// How often to check for posts that are due to be published or expire.
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Spawns the background task that publishes scheduled posts and expires posts.
+///
+/// Posts are already hidden or shown at their scheduled times by the query filters, so this only applies the changes
+/// that last, and federates and cross-posts them.
+pub(crate) fn spawn_post_schedule_job(
+    db: DatabaseConnection,
+    app_config: Arc<RwLock<AppConfig>>,
+    files_dir: Arc<PathBuf>,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SCHEDULE_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = expire_posts(&db, &app_config, &files_dir).await {
+                println!("Error when expiring posts - {}", err);
+            }
+            if let Err(err) = publish_scheduled_posts(&db, &app_config).await {
+                println!("Error when publishing scheduled posts - {}", err);
+            }
+        }
+    });
+}
+
+/// Makes expired posts private, or deletes them if the instance is set to.
+async fn expire_posts(
+    db: &DatabaseConnection,
+    app_config: &RwLock<AppConfig>,
+    files_dir: &Path,
+) -> Result<(), SameyError> {
+    let posts = SameyPost::find()
+        .filter(samey_post::Column::ExpiresAt.lte(Utc::now().naive_utc()))
+        .all(db)
+        .await?;
+    if posts.is_empty() {
+        return Ok(());
+    }
+    let post_ids: Vec<i32> = posts.iter().map(|post| post.id).collect();
+    let (delete_expired_posts, federation) = {
+        let app_config = app_config.read().await;
+        (
+            app_config.delete_expired_posts,
+            Federation::from_config(&app_config),
+        )
+    };
+
+    if delete_expired_posts {
+        SameyPost::delete_many()
+            .filter(samey_post::Column::Id.is_in(post_ids))
+            .exec(db)
+            .await?;
+    } else {
+        SameyPost::update_many()
+            .col_expr(samey_post::Column::IsPublic, Expr::value(false))
+            .col_expr(
+                samey_post::Column::ExpiresAt,
+                Expr::value(Option::<NaiveDateTime>::None),
+            )
+            .col_expr(
+                samey_post::Column::UpdatedAt,
+                Expr::value(Utc::now().naive_utc()),
+            )
+            .col_expr(
+                samey_post::Column::Version,
+                Expr::col(samey_post::Column::Version).add(1),
+            )
+            .filter(samey_post::Column::Id.is_in(post_ids))
+            .exec(db)
+            .await?;
+    }
+
+    // Federated posts are removed from other servers
+    if let Some(federation) = federation {
+        for post in posts.iter().filter(|post| post.is_public) {
+            federate_post(db.clone(), federation.clone(), post.id);
+        }
+    }
+    if delete_expired_posts {
+        for post in &posts {
+            remove_post_files(files_dir, post).await;
+        }
+        clean_dangling_tags(db).await?;
+    }
+    Ok(())
+}
+
+/// Federates and cross-posts public posts whose scheduled time came, then clears their schedule.
+async fn publish_scheduled_posts(
+    db: &DatabaseConnection,
+    app_config: &RwLock<AppConfig>,
+) -> Result<(), SameyError> {
+    let posts = SameyPost::find()
+        .filter(samey_post::Column::IsPublic.eq(true))
+        .filter(samey_post::Column::IsDraft.eq(false))
+        .filter(samey_post::Column::PublishAt.lte(Utc::now().naive_utc()))
+        .all(db)
+        .await?;
+    if posts.is_empty() {
+        return Ok(());
+    }
+
+    SameyPost::update_many()
+        .col_expr(
+            samey_post::Column::PublishAt,
+            Expr::value(Option::<NaiveDateTime>::None),
+        )
+        .filter(samey_post::Column::Id.is_in(posts.iter().map(|post| post.id)))
+        .exec(db)
+        .await?;
+
+    let federation = Federation::from_config(&*app_config.read().await);
+    for post in posts {
+        if let Some(federation) = federation.clone() {
+            federate_post(db.clone(), federation, post.id);
+        }
+        queue_crossposts(db, post.id).await?;
+    }
+    Ok(())
+}
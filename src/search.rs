@@ -0,0 +1,382 @@
+use chrono::NaiveDate;
+use strum::IntoEnumIterator;
+
+use crate::{
+    sources::canonical_source_domain,
+    tags::{
+        DATE_PREFIX, DESCRIPTION_PREFIX, MEDIA_TYPE_PREFIX, NEGATIVE_PREFIX, ORDER_PREFIX,
+        PARENT_PREFIX, POOL_PREFIX, PostsOrder, RATING_PREFIX, SOURCE_PREFIX, TAG_COUNT_PREFIX,
+        TEXT_PREFIX,
+    },
+};
+
+/// A parsed post search, such as `cat -dog rating:s tagcount:>3 order:views`.
+#[derive(Debug, Default)]
+pub(crate) struct SearchQuery {
+    pub(crate) terms: Vec<SearchTerm>,
+    /// How results are sorted, from the last `order:` token.
+    pub(crate) order: PostsOrder,
+    /// Malformed tokens, which are left out of the search.
+    pub(crate) errors: Vec<SearchError>,
+}
+
+/// A single token of a search, which excludes matching posts if negated with `-`.
+#[derive(Debug)]
+pub(crate) struct SearchTerm {
+    pub(crate) negated: bool,
+    pub(crate) token: SearchToken,
+}
+
+#[derive(Debug)]
+pub(crate) enum SearchToken {
+    /// A normalized tag name.
+    Tag(String),
+    /// `rating:s`
+    Rating(String),
+    /// `type:image`
+    MediaType(String),
+    /// `date:2025-04-20`
+    Date(NaiveDate),
+    /// `text:word`, matching titles and descriptions.
+    Text(String),
+    /// `tagcount:<5`
+    TagCount(Comparison, u32),
+    /// `source:pixiv.net`, with the canonical domain.
+    SourceDomain(String),
+    /// `source:any`, `description:none`, `parent:any` or `pool:none`
+    Has(Attribute, bool),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+/// Optional parts of a post, searched with `any` or `none`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Attribute {
+    Source,
+    Description,
+    Parent,
+    Pool,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Ignored \"{token}\": {message}")]
+pub(crate) struct SearchError {
+    pub(crate) token: String,
+    pub(crate) message: String,
+}
+
+impl SearchQuery {
+    /// Parses space-separated search tokens.
+    ///
+    /// Tokens are case-insensitive. Malformed tokens with a known prefix are reported in [`SearchQuery::errors`]
+    /// instead of being searched as tags, since no tag would ever match them.
+    pub(crate) fn parse(text: &str) -> Self {
+        let mut query = SearchQuery::default();
+        for token in text.split_whitespace() {
+            let token = token.to_lowercase();
+            match parse_token(&token, &mut query.order) {
+                Ok(Some(term)) => query.terms.push(term),
+                Ok(None) => (),
+                Err(message) => query.errors.push(SearchError { token, message }),
+            }
+        }
+        query
+    }
+}
+
+/// Parses a lowercase token into a search term, or updates the order for `order:` tokens.
+fn parse_token(token: &str, order: &mut PostsOrder) -> Result<Option<SearchTerm>, String> {
+    let (negated, token) = match token.strip_prefix(NEGATIVE_PREFIX) {
+        Some("") => return Err("missing a tag to exclude".into()),
+        Some(token) => (true, token),
+        None => (false, token),
+    };
+
+    if let Some(value) = token.strip_prefix(ORDER_PREFIX) {
+        if negated {
+            return Err("orders can't be excluded".into());
+        }
+        *order = PostsOrder::iter()
+            .find(|order| order.to_string() == value)
+            .ok_or_else(|| {
+                format!(
+                    "the order must be one of {}",
+                    PostsOrder::iter()
+                        .map(|order| order.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })?;
+        return Ok(None);
+    }
+
+    let token = if let Some(rating) = token.strip_prefix(RATING_PREFIX) {
+        if rating.is_empty() {
+            return Err("missing a rating".into());
+        }
+        SearchToken::Rating(rating.into())
+    } else if let Some(media_type) = token.strip_prefix(MEDIA_TYPE_PREFIX) {
+        if media_type.is_empty() {
+            return Err("missing a media type".into());
+        }
+        SearchToken::MediaType(media_type.into())
+    } else if let Some(date) = token.strip_prefix(DATE_PREFIX) {
+        SearchToken::Date(
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map_err(|_| "dates must look like 2025-04-20".to_owned())?,
+        )
+    } else if let Some(text) = token.strip_prefix(TEXT_PREFIX) {
+        if text.is_empty() {
+            return Err("missing the text to search for".into());
+        }
+        SearchToken::Text(text.into())
+    } else if let Some(comparison) = token.strip_prefix(TAG_COUNT_PREFIX) {
+        let (comparison, count) = parse_comparison(comparison);
+        SearchToken::TagCount(
+            comparison,
+            count.parse().map_err(|_| {
+                "tag counts must be a number, optionally after <, <=, > or >=".to_owned()
+            })?,
+        )
+    } else if let Some(source) = token.strip_prefix(SOURCE_PREFIX) {
+        match parse_any_or_none(source) {
+            Some(has_any) => SearchToken::Has(Attribute::Source, has_any),
+            None if source.contains('.')
+                && source
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-') =>
+            {
+                SearchToken::SourceDomain(canonical_source_domain(source).into())
+            }
+            None => return Err("sources must be any, none or a domain like example.com".into()),
+        }
+    } else if let Some((attribute, value)) = [
+        (DESCRIPTION_PREFIX, Attribute::Description),
+        (PARENT_PREFIX, Attribute::Parent),
+        (POOL_PREFIX, Attribute::Pool),
+    ]
+    .into_iter()
+    .find_map(|(prefix, attribute)| token.strip_prefix(prefix).map(|value| (attribute, value)))
+    {
+        SearchToken::Has(
+            attribute,
+            parse_any_or_none(value).ok_or_else(|| "must be any or none".to_owned())?,
+        )
+    } else {
+        SearchToken::Tag(token.into())
+    };
+    Ok(Some(SearchTerm { negated, token }))
+}
+
+fn parse_comparison(text: &str) -> (Comparison, &str) {
+    if let Some(value) = text.strip_prefix("<=") {
+        (Comparison::LessOrEqual, value)
+    } else if let Some(value) = text.strip_prefix(">=") {
+        (Comparison::GreaterOrEqual, value)
+    } else if let Some(value) = text.strip_prefix('<') {
+        (Comparison::Less, value)
+    } else if let Some(value) = text.strip_prefix('>') {
+        (Comparison::Greater, value)
+    } else {
+        (Comparison::Equal, text)
+    }
+}
+
+fn parse_any_or_none(value: &str) -> Option<bool> {
+    match value {
+        "any" => Some(true),
+        "none" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the tokens of the errors in a query.
+    fn error_tokens(query: &SearchQuery) -> Vec<&str> {
+        query
+            .errors
+            .iter()
+            .map(|error| error.token.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn parses_tags_and_negation() {
+        let query = SearchQuery::parse("Cat -dog");
+        assert!(query.errors.is_empty());
+        assert!(matches!(
+            &query.terms[..],
+            [
+                SearchTerm { negated: false, token: SearchToken::Tag(cat) },
+                SearchTerm { negated: true, token: SearchToken::Tag(dog) },
+            ] if cat == "cat" && dog == "dog"
+        ));
+    }
+
+    #[test]
+    fn rejects_lone_negation() {
+        let query = SearchQuery::parse("-");
+        assert!(query.terms.is_empty());
+        assert_eq!(error_tokens(&query), ["-"]);
+    }
+
+    #[test]
+    fn parses_rating() {
+        let query = SearchQuery::parse("rating:s -rating:e rating:");
+        assert!(matches!(
+            &query.terms[..],
+            [
+                SearchTerm { negated: false, token: SearchToken::Rating(safe) },
+                SearchTerm { negated: true, token: SearchToken::Rating(explicit) },
+            ] if safe == "s" && explicit == "e"
+        ));
+        assert_eq!(error_tokens(&query), ["rating:"]);
+    }
+
+    #[test]
+    fn parses_order() {
+        assert_eq!(SearchQuery::parse("").order, PostsOrder::Newest);
+        assert_eq!(SearchQuery::parse("order:views").order, PostsOrder::Views);
+        // The last valid order wins
+        assert_eq!(
+            SearchQuery::parse("order:views order:newest").order,
+            PostsOrder::Newest
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_and_negated_orders() {
+        let query = SearchQuery::parse("order:views order:random -order:newest");
+        assert_eq!(query.order, PostsOrder::Views);
+        assert!(query.terms.is_empty());
+        assert_eq!(error_tokens(&query), ["order:random", "-order:newest"]);
+    }
+
+    #[test]
+    fn parses_tag_count_comparisons() {
+        let query =
+            SearchQuery::parse("tagcount:3 tagcount:>3 tagcount:>=3 tagcount:<3 tagcount:<=3");
+        assert!(query.errors.is_empty());
+        assert!(matches!(
+            &query.terms[..],
+            [
+                SearchTerm {
+                    token: SearchToken::TagCount(Comparison::Equal, 3),
+                    ..
+                },
+                SearchTerm {
+                    token: SearchToken::TagCount(Comparison::Greater, 3),
+                    ..
+                },
+                SearchTerm {
+                    token: SearchToken::TagCount(Comparison::GreaterOrEqual, 3),
+                    ..
+                },
+                SearchTerm {
+                    token: SearchToken::TagCount(Comparison::Less, 3),
+                    ..
+                },
+                SearchTerm {
+                    token: SearchToken::TagCount(Comparison::LessOrEqual, 3),
+                    ..
+                },
+            ]
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_tag_counts() {
+        let query = SearchQuery::parse("tagcount: tagcount:>many tagcount:<=-1 tagcount:=>3");
+        assert!(query.terms.is_empty());
+        assert_eq!(
+            error_tokens(&query),
+            [
+                "tagcount:",
+                "tagcount:>many",
+                "tagcount:<=-1",
+                "tagcount:=>3"
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_sources() {
+        let query =
+            SearchQuery::parse("source:www.Twitter.com source:pixiv.net source:any -source:none");
+        assert!(query.errors.is_empty());
+        assert!(matches!(
+            &query.terms[..],
+            [
+                SearchTerm { token: SearchToken::SourceDomain(twitter), .. },
+                SearchTerm { token: SearchToken::SourceDomain(pixiv), .. },
+                SearchTerm { negated: false, token: SearchToken::Has(Attribute::Source, true) },
+                SearchTerm { negated: true, token: SearchToken::Has(Attribute::Source, false) },
+            ] if twitter == "x.com" && pixiv == "pixiv.net"
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_sources() {
+        let query = SearchQuery::parse("source: source:pixiv source:https://pixiv.net");
+        assert!(query.terms.is_empty());
+        assert_eq!(
+            error_tokens(&query),
+            ["source:", "source:pixiv", "source:https://pixiv.net"]
+        );
+    }
+
+    #[test]
+    fn parses_dates() {
+        let query = SearchQuery::parse("date:2025-04-20");
+        assert!(matches!(
+            &query.terms[..],
+            [SearchTerm { token: SearchToken::Date(date), .. }]
+                if *date == NaiveDate::from_ymd_opt(2025, 4, 20).unwrap()
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_dates() {
+        let query = SearchQuery::parse("date: date:2025-13-01 date:20/04/2025 date:yesterday");
+        assert!(query.terms.is_empty());
+        assert_eq!(
+            error_tokens(&query),
+            [
+                "date:",
+                "date:2025-13-01",
+                "date:20/04/2025",
+                "date:yesterday"
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_malformed_tokens_out_of_terms() {
+        let query = SearchQuery::parse("cat date:soon -dog pool:maybe tagcount:lots");
+        assert!(matches!(
+            &query.terms[..],
+            [
+                SearchTerm { negated: false, token: SearchToken::Tag(cat) },
+                SearchTerm { negated: true, token: SearchToken::Tag(dog) },
+            ] if cat == "cat" && dog == "dog"
+        ));
+        assert_eq!(
+            error_tokens(&query),
+            ["date:soon", "pool:maybe", "tagcount:lots"]
+        );
+        assert_eq!(
+            query.errors[0].to_string(),
+            "Ignored \"date:soon\": dates must look like 2025-04-20"
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use std::{collections::HashSet, path::Path, time::Duration};
+
+use reqwest::multipart::{Form, Part};
+use serde::Deserialize;
+
+use crate::SameyError;
+
+const SAUCENAO_URL: &str = "https://saucenao.com/search.php";
+const IQDB_URL: &str = "https://iqdb.org/";
+/// How long each reverse image search service may take to reply.
+const SOURCE_LOOKUP_TIMEOUT: Duration = Duration::from_secs(30);
+/// Matches less similar than this percentage are rarely the same image.
+const MIN_SIMILARITY: f64 = 70.0;
+const SAUCENAO_RESULTS: u32 = 8;
+
+/// A possible source of an image, found by a reverse image search.
+#[derive(Debug)]
+pub(crate) struct SourceCandidate {
+    pub(crate) url: String,
+    /// Percentage between 0 and 100.
+    pub(crate) similarity: f64,
+    pub(crate) service: &'static str,
+}
+
+#[derive(Deserialize)]
+struct SauceNaoResponse {
+    header: SauceNaoHeader,
+    #[serde(default)]
+    results: Vec<SauceNaoResult>,
+}
+
+#[derive(Deserialize)]
+struct SauceNaoHeader {
+    status: i32,
+    message: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SauceNaoResult {
+    header: SauceNaoResultHeader,
+    data: SauceNaoResultData,
+}
+
+#[derive(Deserialize)]
+struct SauceNaoResultHeader {
+    similarity: String,
+}
+
+#[derive(Deserialize)]
+struct SauceNaoResultData {
+    #[serde(default)]
+    ext_urls: Vec<String>,
+    source: Option<String>,
+}
+
+/// Searches SauceNAO and/or IQDB for sources of an image, most similar first.
+///
+/// SauceNAO is only queried when an API key is given. A service that fails is skipped, unless every service fails.
+pub(crate) async fn lookup_sources(
+    saucenao_api_key: &str,
+    iqdb_enabled: bool,
+    image_path: &Path,
+) -> Result<Vec<SourceCandidate>, SameyError> {
+    let image = tokio::fs::read(image_path).await?;
+    let file_name = image_path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut results = Vec::new();
+    if !saucenao_api_key.is_empty() {
+        results.push(search_saucenao(saucenao_api_key, image.clone(), file_name.clone()).await);
+    }
+    if iqdb_enabled {
+        results.push(search_iqdb(image, file_name).await);
+    }
+
+    let mut candidates: Vec<SourceCandidate> = Vec::new();
+    let mut last_error = None;
+    for result in results {
+        match result {
+            Ok(found) => candidates.extend(found),
+            Err(err) => {
+                println!("Error when looking up sources - {}", err);
+                last_error = Some(err);
+            }
+        }
+    }
+    if candidates.is_empty() {
+        if let Some(err) = last_error {
+            return Err(err);
+        }
+    }
+
+    candidates.retain(|candidate| candidate.similarity >= MIN_SIMILARITY);
+    candidates.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    let mut seen = HashSet::new();
+    candidates.retain(|candidate| seen.insert(candidate.url.clone()));
+    Ok(candidates)
+}
+
+async fn search_saucenao(
+    api_key: &str,
+    image: Vec<u8>,
+    file_name: String,
+) -> Result<Vec<SourceCandidate>, SameyError> {
+    let form = Form::new().part("file", Part::bytes(image).file_name(file_name));
+    let response: SauceNaoResponse = reqwest::Client::new()
+        .post(SAUCENAO_URL)
+        .query(&[
+            ("output_type", "2"),
+            ("api_key", api_key),
+            ("numres", &SAUCENAO_RESULTS.to_string()),
+        ])
+        .multipart(form)
+        .timeout(SOURCE_LOOKUP_TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| SameyError::Other(format!("SauceNAO request failed: {}", err)))?
+        .json()
+        .await
+        .map_err(|err| SameyError::Other(format!("Invalid SauceNAO response: {}", err)))?;
+    // Negative statuses are client errors, like an invalid key; positive ones are server errors
+    if response.header.status != 0 {
+        return Err(SameyError::Other(format!(
+            "SauceNAO search failed: {}",
+            response
+                .header
+                .message
+                .unwrap_or_else(|| format!("status {}", response.header.status))
+        )));
+    }
+
+    Ok(response
+        .results
+        .into_iter()
+        .flat_map(|result| {
+            let similarity = result.header.similarity.parse().unwrap_or_default();
+            result
+                .data
+                .ext_urls
+                .into_iter()
+                .chain(
+                    result
+                        .data
+                        .source
+                        .filter(|source| source.starts_with("http")),
+                )
+                .map(move |url| SourceCandidate {
+                    url,
+                    similarity,
+                    service: "SauceNAO",
+                })
+        })
+        .collect())
+}
+
+async fn search_iqdb(
+    image: Vec<u8>,
+    file_name: String,
+) -> Result<Vec<SourceCandidate>, SameyError> {
+    let form = Form::new().part("file", Part::bytes(image).file_name(file_name));
+    let html = reqwest::Client::new()
+        .post(IQDB_URL)
+        .multipart(form)
+        .timeout(SOURCE_LOOKUP_TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| SameyError::Other(format!("IQDB request failed: {}", err)))?
+        .text()
+        .await
+        .map_err(|err| SameyError::Other(format!("Invalid IQDB response: {}", err)))?;
+    Ok(parse_iqdb_matches(&html))
+}
+
+/// Extracts matches from IQDB's results page, where each match is a table with a link and its similarity.
+fn parse_iqdb_matches(html: &str) -> Vec<SourceCandidate> {
+    html.split("<table>")
+        .filter(|table| !table.contains("Your image"))
+        .filter_map(|table| {
+            let href = table.split("href=\"").nth(1)?.split('"').next()?;
+            let similarity = table
+                .split("% similarity")
+                .next()
+                .filter(|before| before.len() < table.len())?
+                .rsplit('>')
+                .next()?
+                .trim()
+                .parse()
+                .ok()?;
+            let url = match href.strip_prefix("//") {
+                Some(url) => format!("https://{}", url),
+                None => href.to_owned(),
+            };
+            url.starts_with("http").then_some(SourceCandidate {
+                url: url.replace("&amp;", "&"),
+                similarity,
+                service: "IQDB",
+            })
+        })
+        .collect()
+}
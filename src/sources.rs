@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+
+use reqwest::Url;
+use sea_orm::{ActiveValue::Set, DatabaseConnection, EntityTrait, QueryOrder, TransactionTrait};
+
+use crate::{
+    SameyError,
+    entities::{prelude::SameyPostSource, samey_post_source},
+};
+
+/// Query parameters that only track where a link was shared from.
+const TRACKING_PARAMS: [&str; 9] = [
+    "fbclid", "gclid", "igshid", "mc_cid", "mc_eid", "ref", "ref_src", "ref_url", "si",
+];
+const TWITTER_DOMAINS: [&str; 7] = [
+    "twitter.com",
+    "mobile.twitter.com",
+    "x.com",
+    "mobile.x.com",
+    "fxtwitter.com",
+    "vxtwitter.com",
+    "fixupx.com",
+];
+
+/// Returns the domain that sources of a site are stored under, so that `source:twitter.com` also finds X posts.
+pub(crate) fn canonical_source_domain(domain: &str) -> &str {
+    let domain = domain.strip_prefix("www.").unwrap_or(domain);
+    if TWITTER_DOMAINS.contains(&domain) {
+        "x.com"
+    } else {
+        domain
+    }
+}
+
+/// Rewrites a source URL into a canonical form, so that the same source is only stored once.
+///
+/// Tracking parameters and fragments are removed, and Twitter/X and pixiv links are rewritten to a single form for
+/// each post or artist. Text that isn't an HTTP(S) URL is only trimmed.
+pub(crate) fn normalize_source_url(source: &str) -> String {
+    let source = source.trim();
+    let Ok(mut url) = Url::parse(source) else {
+        return source.to_owned();
+    };
+    if !["http", "https"].contains(&url.scheme()) {
+        return source.to_owned();
+    }
+    let domain = canonical_source_domain(url.host_str().unwrap_or_default()).to_owned();
+    url.set_fragment(None);
+    let query: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key.as_ref()))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+
+    if domain == "x.com" {
+        let segments: Vec<&str> = url.path_segments().into_iter().flatten().collect();
+        // Links to media of a post, such as `/artist/status/1/photo/1`, point to the post itself
+        return match segments.as_slice() {
+            [user, "status", id, ..] => format!("https://x.com/{}/status/{}", user, id),
+            [user] | [user, ""] if !user.is_empty() => format!("https://x.com/{}", user),
+            _ => format!("https://x.com{}", url.path()),
+        };
+    }
+
+    if domain == "pixiv.net" {
+        let param = |name: &str| {
+            query
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        let segments: Vec<&str> = url
+            .path_segments()
+            .into_iter()
+            .flatten()
+            // Localized pages such as `/en/artworks/1` have a language prefix
+            .skip_while(|segment| {
+                segment.len() == 2 && segment.chars().all(|c| c.is_ascii_lowercase())
+            })
+            .collect();
+        let canonical = match segments.as_slice() {
+            ["artworks", id, ..] | ["i", id] => Some(format!("artworks/{}", id)),
+            ["member_illust.php"] => param("illust_id").map(|id| format!("artworks/{}", id)),
+            ["users", id, ..] => Some(format!("users/{}", id)),
+            ["member.php"] => param("id").map(|id| format!("users/{}", id)),
+            _ => None,
+        };
+        if let Some(canonical) = canonical {
+            return format!("https://www.pixiv.net/{}", canonical);
+        }
+    }
+
+    if query.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(query);
+    }
+    url.to_string()
+}
+
+/// Normalizes every stored source URL, removing sources that turn out to be duplicates of another source of the same
+/// post.
+///
+/// Returns how many sources were changed or removed. Sources entered from now on are normalized when saved.
+///
+/// ```
+/// use samey::normalize_sources;
+///
+/// # async fn _main() {
+/// let db = sea_orm::Database::connect("sqlite:db.sqlite3?mode=rwc").await.unwrap();
+/// let changed = normalize_sources(db).await.expect("Unable to normalize sources");
+/// # }
+/// ```
+pub async fn normalize_sources(db: DatabaseConnection) -> Result<u64, SameyError> {
+    let sources = SameyPostSource::find()
+        .order_by_asc(samey_post_source::Column::Id)
+        .all(&db)
+        .await?;
+
+    let mut seen = HashSet::new();
+    let mut changed = 0;
+    let txn = db.begin().await?;
+    // Updates wait until every duplicate is deleted, so that they can't clash with the unique index
+    let mut updates = Vec::new();
+    for source in sources {
+        let url = normalize_source_url(&source.url);
+        if !seen.insert((source.post_id, url.clone())) {
+            SameyPostSource::delete_by_id(source.id).exec(&txn).await?;
+            changed += 1;
+        } else if url != source.url {
+            updates.push((source.id, url));
+        }
+    }
+    for (id, url) in updates {
+        SameyPostSource::update(samey_post_source::ActiveModel {
+            id: Set(id),
+            url: Set(url),
+            ..Default::default()
+        })
+        .exec(&txn)
+        .await?;
+        changed += 1;
+    }
+    txn.commit().await?;
+
+    Ok(changed)
+}
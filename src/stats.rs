@@ -0,0 +1,119 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use samey_migration::Expr;
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, EntityTrait, FromQueryResult, IntoIdentity, QueryFilter,
+    QueryOrder, QuerySelect, RelationTrait,
+};
+use tokio::sync::RwLock;
+
+use crate::{
+    SameyError,
+    entities::{prelude::SameyPost, samey_post, samey_user},
+    query::{TagCount, get_top_tags, published_posts_condition},
+};
+
+/// How long computed statistics are reused before querying the database again.
+const STATS_CACHE_DURATION: Duration = Duration::from_secs(10 * 60);
+const STATS_TOP_UPLOADERS: u64 = 10;
+const STATS_TOP_TAGS: u64 = 20;
+
+#[derive(Debug, FromQueryResult)]
+pub(crate) struct RatingCount {
+    pub(crate) rating: String,
+    pub(crate) post_count: i64,
+}
+
+#[derive(Debug, FromQueryResult)]
+pub(crate) struct UploaderCount {
+    pub(crate) username: String,
+    pub(crate) post_count: i64,
+}
+
+#[derive(Debug, FromQueryResult)]
+pub(crate) struct MonthCount {
+    pub(crate) month: String,
+    pub(crate) post_count: i64,
+}
+
+/// Statistics about public posts.
+#[derive(Debug)]
+pub(crate) struct Stats {
+    pub(crate) total_posts: i64,
+    pub(crate) posts_by_rating: Vec<RatingCount>,
+    pub(crate) top_uploaders: Vec<UploaderCount>,
+    pub(crate) top_tags: Vec<TagCount>,
+    pub(crate) uploads_per_month: Vec<MonthCount>,
+}
+
+#[derive(Default)]
+pub(crate) struct StatsCache(RwLock<Option<(Instant, Arc<Stats>)>>);
+
+impl StatsCache {
+    /// Returns cached statistics, or computes them if the cache is empty or stale.
+    pub(crate) async fn get(&self, db: &DatabaseConnection) -> Result<Arc<Stats>, SameyError> {
+        if let Some((computed_at, stats)) = self.0.read().await.as_ref() {
+            if computed_at.elapsed() < STATS_CACHE_DURATION {
+                return Ok(stats.clone());
+            }
+        }
+        let stats = Arc::new(compute_stats(db).await?);
+        *self.0.write().await = Some((Instant::now(), stats.clone()));
+        Ok(stats)
+    }
+}
+
+async fn compute_stats(db: &DatabaseConnection) -> Result<Stats, SameyError> {
+    let posts_by_rating = SameyPost::find()
+        .select_only()
+        .column(samey_post::Column::Rating)
+        .column_as(samey_post::Column::Id.count(), "post_count")
+        .filter(published_posts_condition())
+        .group_by(samey_post::Column::Rating)
+        .order_by_asc(samey_post::Column::Rating)
+        .into_model::<RatingCount>()
+        .all(db)
+        .await?;
+
+    let top_uploaders = SameyPost::find()
+        .select_only()
+        .column(samey_user::Column::Username)
+        .column_as(samey_post::Column::Id.count(), "post_count")
+        .join(
+            sea_orm::JoinType::InnerJoin,
+            samey_post::Relation::SameyUser.def(),
+        )
+        .filter(published_posts_condition())
+        .group_by(samey_user::Column::Id)
+        .order_by_desc(Expr::col("post_count".into_identity()))
+        .order_by_asc(samey_user::Column::Username)
+        .limit(STATS_TOP_UPLOADERS)
+        .into_model::<UploaderCount>()
+        .all(db)
+        .await?;
+
+    let uploads_per_month = SameyPost::find()
+        .select_only()
+        .column_as(
+            Expr::cust("strftime('%Y-%m', \"samey_post\".\"uploaded_at\")"),
+            "month",
+        )
+        .column_as(samey_post::Column::Id.count(), "post_count")
+        .filter(published_posts_condition())
+        .group_by(Expr::col("month".into_identity()))
+        .order_by_asc(Expr::col("month".into_identity()))
+        .into_model::<MonthCount>()
+        .all(db)
+        .await?;
+
+    Ok(Stats {
+        total_posts: posts_by_rating.iter().map(|rating| rating.post_count).sum(),
+        posts_by_rating,
+        top_uploaders,
+        top_tags: get_top_tags(STATS_TOP_TAGS).all(db).await?,
+        uploads_per_month,
+    })
+}
@@ -0,0 +1,91 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use sea_orm::{ActiveValue::Set, DatabaseConnection, EntityTrait};
+
+use crate::{
+    SameyError,
+    disk_usage::update_post_stored_size,
+    entities::{prelude::SameyPost, samey_post},
+    thumbnailer::Thumbnailer,
+    video::HLS_PLAYLIST_NAME,
+};
+
+/// Prefix of the directories in the files directory where HLS streams are written.
+pub(crate) const STREAM_DIRECTORY_PREFIX: &str = "hls-";
+
+/// Returns the directory in the files directory where the HLS stream of a video is written.
+fn stream_directory(media: &str) -> String {
+    let name = Path::new(media)
+        .file_stem()
+        .map_or_else(|| media.into(), |stem| stem.to_string_lossy());
+    format!("{}{}", STREAM_DIRECTORY_PREFIX, name)
+}
+
+/// Generates an HLS stream for a post's video in the background.
+///
+/// The post only links to the stream once every segment has been written, so it keeps playing the original video
+/// until then.
+pub(crate) fn spawn_stream_generation(
+    db: DatabaseConnection,
+    files_dir: Arc<PathBuf>,
+    thumbnailer: Arc<dyn Thumbnailer>,
+    post_id: i32,
+    media: String,
+) {
+    tokio::spawn(async move {
+        if let Err(err) =
+            generate_stream(&db, &files_dir, thumbnailer.as_ref(), post_id, &media).await
+        {
+            println!(
+                "Error when generating stream for post {} - {}",
+                post_id, err
+            );
+        }
+    });
+}
+
+async fn generate_stream(
+    db: &DatabaseConnection,
+    files_dir: &Path,
+    thumbnailer: &dyn Thumbnailer,
+    post_id: i32,
+    media: &str,
+) -> Result<(), SameyError> {
+    let directory = stream_directory(media);
+    let output_dir = files_dir.join(&directory);
+    tokio::fs::create_dir_all(&output_dir).await?;
+    if let Err(err) = thumbnailer
+        .stream(&files_dir.join(media), &output_dir)
+        .await
+    {
+        // Don't leave partial streams behind
+        let _ = tokio::fs::remove_dir_all(&output_dir).await;
+        return Err(err);
+    }
+
+    let result = SameyPost::update(samey_post::ActiveModel {
+        id: Set(post_id),
+        stream_playlist: Set(Some(format!("{}/{}", directory, HLS_PLAYLIST_NAME))),
+        ..Default::default()
+    })
+    .exec(db)
+    .await;
+    if result.is_err() {
+        // The post may have been deleted in the meantime
+        let _ = tokio::fs::remove_dir_all(&output_dir).await;
+    }
+    result?;
+    update_post_stored_size(db, files_dir, post_id).await
+}
+
+/// Removes the HLS stream of a post, if it has one.
+pub(crate) async fn remove_stream_files(files_dir: &Path, stream_playlist: Option<&str>) {
+    if let Some(directory) = stream_playlist.and_then(|playlist| Path::new(playlist).parent()) {
+        if !directory.as_os_str().is_empty() {
+            let _ = tokio::fs::remove_dir_all(files_dir.join(directory)).await;
+        }
+    }
+}
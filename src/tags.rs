@@ -1,17 +1,161 @@
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::SameyError;
+
 pub(crate) const NEGATIVE_PREFIX: &str = "-";
 pub(crate) const RATING_PREFIX: &str = "rating:";
 pub(crate) const MEDIA_TYPE_PREFIX: &str = "type:";
+pub(crate) const TAG_COUNT_PREFIX: &str = "tagcount:";
+pub(crate) const SOURCE_PREFIX: &str = "source:";
+pub(crate) const DESCRIPTION_PREFIX: &str = "description:";
+pub(crate) const PARENT_PREFIX: &str = "parent:";
+pub(crate) const POOL_PREFIX: &str = "pool:";
+pub(crate) const ORDER_PREFIX: &str = "order:";
+pub(crate) const DATE_PREFIX: &str = "date:";
+pub(crate) const TEXT_PREFIX: &str = "text:";
 
-#[derive(strum::EnumIter, strum::Display, Debug)]
-pub(crate) enum Rating {
-    #[strum(serialize = "u")]
-    Unrated,
-    #[strum(serialize = "s")]
-    Safe,
-    #[strum(serialize = "q")]
-    Questionable,
-    #[strum(serialize = "e")]
-    Explicit,
+/// Rating of posts that haven't been rated yet, which new uploads always start with.
+pub(crate) const UNRATED: &str = "u";
+
+/// A rating that posts can be given, configured per instance in the settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Rating {
+    /// Value stored in posts and matched by `rating:` searches.
+    pub(crate) code: String,
+    pub(crate) label: String,
+    /// Color in `#rrggbb` format, or empty to use the default text color.
+    pub(crate) color: String,
+    /// Sensitive posts may require age confirmation, be hidden from search engines, and get a content warning when
+    /// shared.
+    pub(crate) sensitive: bool,
+}
+
+impl Rating {
+    fn new(code: &str, label: &str, color: &str, sensitive: bool) -> Self {
+        Self {
+            code: code.into(),
+            label: label.into(),
+            color: color.into(),
+            sensitive,
+        }
+    }
+}
+
+/// Returns the label of a rating, including unrated posts and ratings that were removed from the settings.
+pub(crate) fn rating_label(ratings: &[Rating], code: &str) -> String {
+    if code == UNRATED {
+        return "Unrated".into();
+    }
+    ratings
+        .iter()
+        .find(|rating| rating.code == code)
+        .map(|rating| rating.label.clone())
+        .unwrap_or_else(|| code.to_uppercase())
+}
+
+/// Whether posts with this rating should be treated as not safe for everyone.
+pub(crate) fn is_sensitive_rating(ratings: &[Rating], code: &str) -> bool {
+    ratings
+        .iter()
+        .any(|rating| rating.code == code && rating.sensitive)
+}
+
+/// Ratings of instances that haven't customized them.
+pub(crate) fn default_ratings() -> Vec<Rating> {
+    vec![
+        Rating::new("s", "Safe", "", false),
+        Rating::new("q", "Questionable", "", true),
+        Rating::new("e", "Explicit", "", true),
+    ]
+}
+
+/// Parses ratings from the settings, with one `code = Label, #color, sensitive` rating per line.
+///
+/// The color and the `sensitive` flag are optional.
+pub(crate) fn parse_ratings(text: &str) -> Result<Vec<Rating>, SameyError> {
+    let mut ratings: Vec<Rating> = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let (code, rest) = line
+            .split_once('=')
+            .ok_or_else(|| SameyError::BadRequest(format!("Invalid rating \"{}\"", line)))?;
+        let code = code.trim().to_lowercase();
+        if code.is_empty()
+            || code.len() > 8
+            || !code
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        {
+            return Err(SameyError::BadRequest(format!(
+                "Rating code \"{}\" must be 1 to 8 letters or digits",
+                code
+            )));
+        }
+        if code == UNRATED {
+            return Err(SameyError::BadRequest(format!(
+                "Rating code \"{}\" is reserved for unrated posts",
+                UNRATED
+            )));
+        }
+        if ratings.iter().any(|rating| rating.code == code) {
+            return Err(SameyError::BadRequest(format!(
+                "Rating code \"{}\" is used more than once",
+                code
+            )));
+        }
+        let mut parts = rest.split(',').map(str::trim);
+        let label = parts.next().unwrap_or_default();
+        if label.is_empty() {
+            return Err(SameyError::BadRequest(format!(
+                "Rating \"{}\" needs a label",
+                code
+            )));
+        }
+        let mut color = "";
+        let mut sensitive = false;
+        for part in parts {
+            match part {
+                "sensitive" => sensitive = true,
+                part if part.strip_prefix('#').is_some_and(|hex| {
+                    hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit())
+                }) =>
+                {
+                    color = part
+                }
+                part => {
+                    return Err(SameyError::BadRequest(format!(
+                        "Unknown option \"{}\" for rating \"{}\"",
+                        part, code
+                    )));
+                }
+            }
+        }
+        ratings.push(Rating::new(&code, label, color, sensitive));
+    }
+    if ratings.is_empty() {
+        return Err(SameyError::BadRequest(
+            "At least one rating is required".into(),
+        ));
+    }
+    Ok(ratings)
+}
+
+/// Formats ratings in the format read by [`parse_ratings`].
+pub(crate) fn format_ratings(ratings: &[Rating]) -> String {
+    ratings
+        .iter()
+        .map(|rating| {
+            let mut line = format!("{} = {}", rating.code, rating.label);
+            if !rating.color.is_empty() {
+                line.push_str(", ");
+                line.push_str(&rating.color);
+            }
+            if rating.sensitive {
+                line.push_str(", sensitive");
+            }
+            line
+        })
+        .join("\n")
 }
 
 #[derive(strum::EnumIter, strum::Display, Debug)]
@@ -21,3 +165,59 @@ pub(crate) enum MediaType {
     #[strum(serialize = "video")]
     Video,
 }
+
+/// How search results are sorted, with the `order:` token.
+#[derive(strum::EnumIter, strum::Display, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PostsOrder {
+    #[default]
+    #[strum(serialize = "newest")]
+    Newest,
+    #[strum(serialize = "views")]
+    Views,
+}
+
+/// Path segments in source URLs that are never tags.
+const URL_STOPWORDS: [&str; 12] = [
+    "art", "artworks", "gallery", "images", "img", "index", "media", "post", "posts", "status",
+    "user", "users",
+];
+/// Tokens shorter than this are too ambiguous to suggest tags from.
+const MIN_TOKEN_LENGTH: usize = 3;
+
+/// Extracts tokens that may be tags from an uploaded file name or a source URL.
+///
+/// Underscored tokens such as `blue_sky` are kept whole, and artist handles such as `@artist` lose their `@`.
+/// For URLs, only the path is used.
+pub(crate) fn extract_tag_tokens(text: &str) -> Vec<String> {
+    let text = match text.split_once("://") {
+        Some((_, rest)) => {
+            let path = rest.split_once('/').map(|(_, path)| path).unwrap_or("");
+            path.split(['?', '#']).next().unwrap_or("")
+        }
+        None => text.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(text),
+    };
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .map(|token| token.trim_matches('_').to_lowercase())
+        .filter(|token| {
+            token.chars().count() >= MIN_TOKEN_LENGTH
+                && !token.chars().all(|c| c.is_ascii_digit())
+                && !URL_STOPWORDS.contains(&token.as_str())
+        })
+        .collect()
+}
+
+/// Computes the Levenshtein edit distance between two strings.
+pub(crate) fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
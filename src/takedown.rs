@@ -0,0 +1,111 @@
+use chrono::{NaiveDateTime, Utc};
+use samey_migration::{Expr, SimpleExpr};
+use sea_orm::{ActiveValue::Set, ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter};
+
+use crate::{
+    SameyError,
+    content::bump_post_version,
+    entities::{
+        prelude::{SameyPost, SameyTakedownRequest},
+        samey_post, samey_takedown_request,
+    },
+};
+
+/// Maximum length of the claimant's name and email address in a takedown request.
+pub(crate) const TAKEDOWN_CLAIMANT_MAX_LENGTH: u64 = 256;
+/// Maximum length of the reason given in a takedown request.
+pub(crate) const TAKEDOWN_REASON_MAX_LENGTH: u64 = 10_000;
+
+/// Where a takedown request is in its review.
+#[derive(strum::Display, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TakedownStatus {
+    #[strum(serialize = "pending")]
+    Pending,
+    #[strum(serialize = "taken_down")]
+    TakenDown,
+    #[strum(serialize = "rejected")]
+    Rejected,
+}
+
+/// Hides a post for everyone but admins, and locks it so that its uploader can't change or delete it.
+///
+/// Its files are kept, so that it can be restored if the claim is retracted, but only admins can still load them. Every
+/// pending request for the post is marked as taken down.
+pub(crate) async fn take_down_post(
+    db: &impl ConnectionTrait,
+    post_id: i32,
+    reviewer_id: i32,
+) -> Result<(), SameyError> {
+    let now = Utc::now().naive_utc();
+    SameyPost::update_many()
+        .set(samey_post::ActiveModel {
+            taken_down_at: Set(Some(now)),
+            is_locked: Set(true),
+            ..Default::default()
+        })
+        .filter(samey_post::Column::Id.eq(post_id))
+        .exec(db)
+        .await?;
+    bump_post_version(db, post_id).await?;
+    review_takedown_requests(
+        db,
+        samey_takedown_request::Column::PostId.eq(post_id),
+        TakedownStatus::TakenDown,
+        reviewer_id,
+    )
+    .await
+}
+
+/// Marks a pending takedown request as rejected, leaving its post as is.
+pub(crate) async fn reject_takedown_request(
+    db: &impl ConnectionTrait,
+    request_id: i32,
+    reviewer_id: i32,
+) -> Result<(), SameyError> {
+    review_takedown_requests(
+        db,
+        samey_takedown_request::Column::Id.eq(request_id),
+        TakedownStatus::Rejected,
+        reviewer_id,
+    )
+    .await
+}
+
+async fn review_takedown_requests(
+    db: &impl ConnectionTrait,
+    condition: SimpleExpr,
+    status: TakedownStatus,
+    reviewer_id: i32,
+) -> Result<(), SameyError> {
+    SameyTakedownRequest::update_many()
+        .set(samey_takedown_request::ActiveModel {
+            status: Set(status.to_string()),
+            reviewed_by_id: Set(Some(reviewer_id)),
+            reviewed_at: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        })
+        .filter(condition)
+        .filter(samey_takedown_request::Column::Status.eq(TakedownStatus::Pending.to_string()))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Shows a post that was taken down again.
+///
+/// It stays locked, since the reason for the takedown may still call for it; admins can unlock it from its details.
+/// The takedown requests keep their status, as a record of what happened.
+pub(crate) async fn restore_taken_down_post(
+    db: &impl ConnectionTrait,
+    post_id: i32,
+) -> Result<(), SameyError> {
+    SameyPost::update_many()
+        .col_expr(
+            samey_post::Column::TakenDownAt,
+            Expr::value(Option::<NaiveDateTime>::None),
+        )
+        .filter(samey_post::Column::Id.eq(post_id))
+        .exec(db)
+        .await?;
+    bump_post_version(db, post_id).await
+}
@@ -0,0 +1,145 @@
+//! Helpers for end-to-end tests against a Samey router.
+//!
+//! Only available with the `test-support` feature.
+//!
+//! ```
+//! use samey::test_support::TestApp;
+//!
+//! # async fn _main() {
+//! let app = TestApp::new().await.unwrap();
+//! let user_id = app.create_user("user", "password", false).await.unwrap();
+//! let post_id = app.create_post(user_id, &["tag"]).await.unwrap();
+//! // Send requests to `app.router`, e.g. with `tower::ServiceExt::oneshot`.
+//! # }
+//! ```
+
+use std::path::Path;
+
+use axum::Router;
+use chrono::Utc;
+use image::{ImageFormat, RgbImage};
+use samey_migration::{Migrator, MigratorTrait, OnConflict};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, Database, DatabaseConnection, EntityTrait,
+    PaginatorTrait, QueryFilter,
+};
+use tempfile::TempDir;
+
+use crate::{
+    SameyError, create_user,
+    entities::{
+        prelude::{SameyPost, SameyTag, SameyTagPost, SameyUser},
+        samey_post, samey_tag, samey_tag_post, samey_user,
+    },
+    get_router,
+};
+
+/// A Samey application backed by an in-memory database and a temporary files directory.
+///
+/// The files directory is deleted when this is dropped.
+pub struct TestApp {
+    /// Router for the application.
+    pub router: Router,
+    /// Connection to the in-memory database, with all migrations applied.
+    pub db: DatabaseConnection,
+    files_dir: TempDir,
+}
+
+impl TestApp {
+    /// Creates a new application with an empty database.
+    pub async fn new() -> Result<Self, SameyError> {
+        let db = Database::connect("sqlite::memory:").await?;
+        Migrator::up(&db, None).await?;
+        let files_dir = TempDir::new()?;
+        let router = get_router(db.clone(), files_dir.path()).await?;
+        Ok(Self {
+            router,
+            db,
+            files_dir,
+        })
+    }
+
+    /// Directory where uploaded media is stored.
+    pub fn files_dir(&self) -> &Path {
+        self.files_dir.path()
+    }
+
+    /// Creates a user, returning their ID.
+    pub async fn create_user(
+        &self,
+        username: &str,
+        password: &str,
+        is_admin: bool,
+    ) -> Result<i32, SameyError> {
+        create_user(self.db.clone(), username, password, is_admin).await?;
+        SameyUser::find()
+            .filter(samey_user::Column::Username.eq(username))
+            .one(&self.db)
+            .await?
+            .map(|user| user.id)
+            .ok_or(SameyError::NotFound)
+    }
+
+    /// Creates a public image post with the given tags, returning its ID.
+    pub async fn create_post(&self, uploader_id: i32, tags: &[&str]) -> Result<i32, SameyError> {
+        let post_count = SameyPost::find().count(&self.db).await?;
+        let media = format!("test-{}.png", post_count + 1);
+        let thumbnail = format!("thumb-{}", media);
+        let image = RgbImage::new(1, 1);
+        image.save_with_format(self.files_dir().join(&media), ImageFormat::Png)?;
+        image.save_with_format(self.files_dir().join(&thumbnail), ImageFormat::Png)?;
+
+        let post_id = samey_post::ActiveModel {
+            uploader_id: Set(uploader_id),
+            media: Set(media),
+            media_type: Set("image".into()),
+            width: Set(1),
+            height: Set(1),
+            thumbnail: Set(thumbnail),
+            thumbnail_width: Set(1),
+            thumbnail_height: Set(1),
+            title: Set(None),
+            description: Set(None),
+            is_public: Set(true),
+            rating: Set("u".into()),
+            uploaded_at: Set(Utc::now().naive_utc()),
+            parent_id: Set(None),
+            ..Default::default()
+        }
+        .insert(&self.db)
+        .await?
+        .id;
+
+        if !tags.is_empty() {
+            SameyTag::insert_many(tags.iter().map(|tag| samey_tag::ActiveModel {
+                name: Set((*tag).into()),
+                normalized_name: Set(tag.to_lowercase()),
+                updated_at: Set(Some(Utc::now().naive_utc())),
+                ..Default::default()
+            }))
+            .on_conflict(
+                OnConflict::column(samey_tag::Column::NormalizedName)
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec_without_returning(&self.db)
+            .await?;
+            let tags = SameyTag::find()
+                .filter(
+                    samey_tag::Column::NormalizedName
+                        .is_in(tags.iter().map(|tag| tag.to_lowercase())),
+                )
+                .all(&self.db)
+                .await?;
+            SameyTagPost::insert_many(tags.into_iter().map(|tag| samey_tag_post::ActiveModel {
+                post_id: Set(post_id),
+                tag_id: Set(tag.id),
+                ..Default::default()
+            }))
+            .exec(&self.db)
+            .await?;
+        }
+
+        Ok(post_id)
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use image::{GenericImageView, ImageReader, imageops::FilterType};
+use tokio::{sync::Semaphore, task::spawn_blocking};
+
+use crate::{
+    SameyError,
+    video::{generate_animation, generate_hls, generate_thumbnail, is_available, probe_video},
+};
+
+/// Dimensions of a media file and of its generated thumbnail.
+#[derive(Debug, Clone)]
+pub struct MediaDimensions {
+    pub width: u32,
+    pub height: u32,
+    /// Width of the thumbnail as written, not of the original media.
+    pub thumbnail_width: u32,
+    /// Height of the thumbnail as written, not of the original media.
+    pub thumbnail_height: u32,
+    /// Only set for videos.
+    pub video: Option<VideoMetadata>,
+}
+
+/// Metadata of a video file, as reported by `ffprobe`.
+#[derive(Debug, Clone, Default)]
+pub struct VideoMetadata {
+    /// Duration in seconds.
+    pub duration: Option<f64>,
+    pub codec: Option<String>,
+    /// Average frames per second.
+    pub frame_rate: Option<f64>,
+    /// Clockwise rotation in degrees, already applied to the video's dimensions.
+    pub rotation: i32,
+}
+
+/// How many FFmpeg processes [`DefaultThumbnailer`] runs at once by default.
+const MAX_CONCURRENT_FFMPEG_PROCESSES: usize = 2;
+
+/// Reads the width and height of an image file, without decoding all of it.
+pub(crate) async fn read_image_dimensions(path: &Path) -> Result<(u32, u32), SameyError> {
+    let path = path.to_owned();
+    Ok(spawn_blocking(move || ImageReader::open(path)?.into_dimensions()).await??)
+}
+
+/// Generates thumbnails for uploaded media.
+///
+/// Implementations should move any blocking work to a separate thread, e.g. with [`tokio::task::spawn_blocking`].
+#[async_trait]
+pub trait Thumbnailer: Send + Sync {
+    /// Writes a thumbnail for the image at `input` to `output`, fitting within `max_dimension`.
+    ///
+    /// The image format of both files is given by their extension.
+    async fn image(
+        &self,
+        input: &Path,
+        output: &Path,
+        max_dimension: u32,
+    ) -> Result<MediaDimensions, SameyError>;
+
+    /// Writes a PNG thumbnail for the video at `input` to `output`, fitting within `max_dimension`.
+    async fn video(
+        &self,
+        _input: &Path,
+        _output: &Path,
+        _max_dimension: u32,
+    ) -> Result<MediaDimensions, SameyError> {
+        Err(SameyError::BadRequest(
+            "Video uploads are not supported".into(),
+        ))
+    }
+
+    /// Whether video uploads should be accepted.
+    fn supports_video(&self) -> bool {
+        false
+    }
+
+    /// Writes an HLS stream for the video at `input` to the existing `output_dir`, as an `index.m3u8` playlist and
+    /// its segments.
+    async fn stream(&self, _input: &Path, _output_dir: &Path) -> Result<(), SameyError> {
+        Err(SameyError::BadRequest(
+            "Video streaming is not supported".into(),
+        ))
+    }
+
+    /// Whether long videos should get an HLS stream.
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+
+    /// Writes a WebM video to `output` from the frames listed in the FFmpeg concat file at `frames_list`.
+    ///
+    /// This is used to convert ugoira uploads, which are only accepted when [`Thumbnailer::supports_video`] is true.
+    async fn animation(&self, _frames_list: &Path, _output: &Path) -> Result<(), SameyError> {
+        Err(SameyError::BadRequest(
+            "Ugoira uploads are not supported".into(),
+        ))
+    }
+}
+
+/// Thumbnailer using the `image` crate for images, and `ffmpeg` for videos.
+///
+/// Video support is disabled if `ffmpeg` or `ffprobe` can't be executed.
+#[derive(Debug, Clone)]
+pub struct DefaultThumbnailer {
+    ffmpeg: PathBuf,
+    ffprobe: PathBuf,
+    video: bool,
+    ffmpeg_permits: Arc<Semaphore>,
+    /// Streams are generated one at a time, so that long videos don't hold up the thumbnails of new uploads.
+    stream_permits: Arc<Semaphore>,
+}
+
+impl DefaultThumbnailer {
+    /// Uses `ffmpeg` and `ffprobe` from the `PATH`.
+    pub fn new() -> Self {
+        Self::with_binaries("ffmpeg", "ffprobe")
+    }
+
+    /// Uses the given `ffmpeg` and `ffprobe` binaries.
+    pub fn with_binaries(ffmpeg: impl Into<PathBuf>, ffprobe: impl Into<PathBuf>) -> Self {
+        let ffmpeg = ffmpeg.into();
+        let ffprobe = ffprobe.into();
+        let video = is_available(&ffmpeg) && is_available(&ffprobe);
+        Self {
+            ffmpeg,
+            ffprobe,
+            video,
+            ffmpeg_permits: Arc::new(Semaphore::new(MAX_CONCURRENT_FFMPEG_PROCESSES)),
+            stream_permits: Arc::new(Semaphore::new(1)),
+        }
+    }
+
+    /// Only accepts images, for systems without `ffmpeg`.
+    pub fn images_only() -> Self {
+        Self {
+            ffmpeg: "ffmpeg".into(),
+            ffprobe: "ffprobe".into(),
+            video: false,
+            ffmpeg_permits: Arc::new(Semaphore::new(MAX_CONCURRENT_FFMPEG_PROCESSES)),
+            stream_permits: Arc::new(Semaphore::new(1)),
+        }
+    }
+
+    /// Limits how many videos are processed at once, to protect smaller servers.
+    pub fn with_max_concurrent_processes(mut self, max_processes: usize) -> Self {
+        self.ffmpeg_permits = Arc::new(Semaphore::new(max_processes));
+        self
+    }
+}
+
+impl Default for DefaultThumbnailer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Thumbnailer for DefaultThumbnailer {
+    async fn image(
+        &self,
+        input: &Path,
+        output: &Path,
+        max_dimension: u32,
+    ) -> Result<MediaDimensions, SameyError> {
+        let input = input.to_owned();
+        let output = output.to_owned();
+        spawn_blocking(move || {
+            let image = ImageReader::open(input)?.decode()?;
+            let (width, height) = image.dimensions();
+            let thumbnail = image.resize(max_dimension, max_dimension, FilterType::CatmullRom);
+            thumbnail.save(output)?;
+            let (thumbnail_width, thumbnail_height) = thumbnail.dimensions();
+            Ok(MediaDimensions {
+                width,
+                height,
+                thumbnail_width,
+                thumbnail_height,
+                video: None,
+            })
+        })
+        .await?
+    }
+
+    async fn video(
+        &self,
+        input: &Path,
+        output: &Path,
+        max_dimension: u32,
+    ) -> Result<MediaDimensions, SameyError> {
+        if !self.video {
+            return Err(SameyError::BadRequest(
+                "Video uploads are disabled, since FFmpeg is not available".into(),
+            ));
+        }
+        let _permit = self
+            .ffmpeg_permits
+            .acquire()
+            .await
+            .map_err(|err| SameyError::Other(err.to_string()))?;
+        let input = input.to_string_lossy();
+        generate_thumbnail(
+            &self.ffmpeg,
+            &input,
+            &output.to_string_lossy(),
+            max_dimension,
+        )
+        .await?;
+        let (width, height, metadata) = probe_video(&self.ffprobe, &input).await?;
+        let (thumbnail_width, thumbnail_height) = read_image_dimensions(output).await?;
+        Ok(MediaDimensions {
+            width,
+            height,
+            thumbnail_width,
+            thumbnail_height,
+            video: Some(metadata),
+        })
+    }
+
+    fn supports_video(&self) -> bool {
+        self.video
+    }
+
+    async fn stream(&self, input: &Path, output_dir: &Path) -> Result<(), SameyError> {
+        if !self.video {
+            return Err(SameyError::BadRequest(
+                "Video streaming is disabled, since FFmpeg is not available".into(),
+            ));
+        }
+        let _permit = self
+            .stream_permits
+            .acquire()
+            .await
+            .map_err(|err| SameyError::Other(err.to_string()))?;
+        generate_hls(&self.ffmpeg, &input.to_string_lossy(), output_dir).await
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.video
+    }
+
+    async fn animation(&self, frames_list: &Path, output: &Path) -> Result<(), SameyError> {
+        if !self.video {
+            return Err(SameyError::BadRequest(
+                "Ugoira uploads are disabled, since FFmpeg is not available".into(),
+            ));
+        }
+        let _permit = self
+            .ffmpeg_permits
+            .acquire()
+            .await
+            .map_err(|err| SameyError::Other(err.to_string()))?;
+        generate_animation(&self.ffmpeg, frames_list, output).await
+    }
+}
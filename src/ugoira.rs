@@ -0,0 +1,131 @@
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use tokio::task::spawn_blocking;
+use zip::ZipArchive;
+
+use crate::{SameyError, thumbnailer::Thumbnailer};
+
+/// Most frames that an ugoira may have.
+const MAX_UGOIRA_FRAMES: usize = 2_000;
+/// Largest total size of an ugoira's frames once extracted, to guard against zip bombs.
+const MAX_UGOIRA_SIZE: u64 = 1_000_000_000;
+/// Name of the FFmpeg concat list written next to the extracted frames.
+const FRAMES_LIST_NAME: &str = "frames.txt";
+
+#[derive(Deserialize)]
+struct UgoiraFrame {
+    file: String,
+    /// Delay until the next frame, in milliseconds.
+    delay: u32,
+}
+
+/// Timing of an ugoira's frames, either as returned by pixiv's `ugoira_meta` API or as a bare list of frames.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum UgoiraMetadata {
+    Meta { frames: Vec<UgoiraFrame> },
+    Frames(Vec<UgoiraFrame>),
+}
+
+fn invalid_archive(err: impl ToString) -> SameyError {
+    SameyError::BadRequest(format!("Invalid ugoira archive: {}", err.to_string()))
+}
+
+/// Extracts the frames of an ugoira archive into `output_dir`, returning an FFmpeg concat list that plays them with
+/// their delays.
+///
+/// The archive must contain a JSON file with the name and delay of each frame, such as `animation.json`.
+fn extract_frames(archive: &Path, output_dir: &Path) -> Result<PathBuf, SameyError> {
+    let mut archive = ZipArchive::new(File::open(archive)?).map_err(invalid_archive)?;
+    let metadata_name = archive
+        .file_names()
+        .find(|name| name.to_lowercase().ends_with(".json"))
+        .map(String::from)
+        .ok_or_else(|| invalid_archive("Missing JSON file with frame delays"))?;
+    let mut metadata = String::new();
+    archive
+        .by_name(&metadata_name)
+        .map_err(invalid_archive)?
+        .take(MAX_UGOIRA_SIZE)
+        .read_to_string(&mut metadata)?;
+    let frames = match serde_json::from_str(&metadata).map_err(invalid_archive)? {
+        UgoiraMetadata::Meta { frames } | UgoiraMetadata::Frames(frames) => frames,
+    };
+    if frames.is_empty() {
+        return Err(invalid_archive("No frames"));
+    }
+    if frames.len() > MAX_UGOIRA_FRAMES {
+        return Err(invalid_archive(format!(
+            "More than {} frames",
+            MAX_UGOIRA_FRAMES
+        )));
+    }
+
+    let mut remaining_size = MAX_UGOIRA_SIZE;
+    let mut frames_list = String::from("ffconcat version 1.0\n");
+    let mut last_frame_name = String::new();
+    for (index, frame) in frames.iter().enumerate() {
+        // Frames are written under our own names, so that names in the archive can't escape the output directory
+        let extension = frame
+            .file
+            .rsplit_once('.')
+            .map(|(_, extension)| extension.to_lowercase())
+            .filter(|extension| {
+                !extension.is_empty() && extension.chars().all(|c| c.is_ascii_alphanumeric())
+            })
+            .ok_or_else(|| invalid_archive(format!("Unknown frame format: {}", frame.file)))?;
+        let frame_name = format!("frame-{:05}.{}", index, extension);
+        let mut entry = archive.by_name(&frame.file).map_err(invalid_archive)?;
+        let size = entry.size();
+        if size > remaining_size {
+            return Err(invalid_archive("Frames are too large"));
+        }
+        remaining_size -= size;
+        let mut output = File::create(output_dir.join(&frame_name))?;
+        io::copy(&mut (&mut entry).take(size), &mut output)?;
+        frames_list.push_str(&format!(
+            "file '{}'\nduration {}\n",
+            frame_name,
+            f64::from(frame.delay.max(1)) / 1000.0
+        ));
+        last_frame_name = frame_name;
+    }
+    // The last frame has to be listed again for its duration to be used
+    frames_list.push_str(&format!("file '{}'\n", last_frame_name));
+
+    let frames_list_path = output_dir.join(FRAMES_LIST_NAME);
+    File::create(&frames_list_path)?.write_all(frames_list.as_bytes())?;
+    Ok(frames_list_path)
+}
+
+/// Converts a pixiv ugoira archive (a zip of frames, with a JSON file of their delays) into an animated video.
+pub(crate) async fn convert_ugoira(
+    thumbnailer: &dyn Thumbnailer,
+    archive: &Path,
+    output: &Path,
+) -> Result<(), SameyError> {
+    let frames_dir = std::env::temp_dir().join(format!(
+        "samey-ugoira-{}",
+        output
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default()
+    ));
+    tokio::fs::create_dir_all(&frames_dir).await?;
+    let result = async {
+        let frames_list = {
+            let archive = archive.to_owned();
+            let frames_dir = frames_dir.clone();
+            spawn_blocking(move || extract_frames(&archive, &frames_dir)).await??
+        };
+        thumbnailer.animation(&frames_list, output).await
+    }
+    .await;
+    let _ = tokio::fs::remove_dir_all(&frames_dir).await;
+    result
+}
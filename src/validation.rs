@@ -0,0 +1,102 @@
+use std::{fmt::Display, ops::RangeInclusive};
+
+use crate::SameyError;
+
+/// Errors found in a submitted form, by the name of the field they belong to.
+///
+/// Forms are rendered again with these errors next to their fields, instead of replacing the page with an error.
+#[derive(Debug, Default)]
+pub(crate) struct FieldErrors {
+    errors: Vec<(&'static str, String)>,
+}
+
+impl FieldErrors {
+    /// Adds an error to a field. Only the first error of each field is kept.
+    pub(crate) fn add(&mut self, field: &'static str, message: impl Into<String>) {
+        if self.get(field).is_none() {
+            self.errors.push((field, message.into()));
+        }
+    }
+
+    /// Returns the error of a field, if it has one.
+    pub(crate) fn get(&self, field: &str) -> Option<&str> {
+        self.errors
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, message)| message.as_str())
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Adds the message of a bad request as an error to a field, returning the value if there was no error.
+    ///
+    /// Any other error is passed through, since it has nothing to do with what was submitted.
+    pub(crate) fn check<T>(
+        &mut self,
+        field: &'static str,
+        result: Result<T, SameyError>,
+    ) -> Result<Option<T>, SameyError> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(SameyError::BadRequest(message)) => {
+                self.add(field, message);
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Adds an error to a field if its number is outside of `range`.
+    pub(crate) fn check_range<T: PartialOrd + Display>(
+        &mut self,
+        field: &'static str,
+        value: T,
+        range: &RangeInclusive<T>,
+    ) {
+        if !range.contains(&value) {
+            self.add(
+                field,
+                format!("Must be between {} and {}", range.start(), range.end()),
+            );
+        }
+    }
+
+    /// Adds an error to a field if its text is longer than `max_length` characters.
+    pub(crate) fn check_max_length(
+        &mut self,
+        field: &'static str,
+        text: Option<&str>,
+        max_length: u64,
+    ) {
+        if text.is_some_and(|text| text.chars().count() as u64 > max_length) {
+            self.add(
+                field,
+                format!("Must be at most {} characters long", max_length),
+            );
+        }
+    }
+}
+
+/// Turns errors into a single bad request, for requests that can't show errors next to their fields.
+impl From<FieldErrors> for SameyError {
+    fn from(errors: FieldErrors) -> Self {
+        SameyError::BadRequest(
+            errors
+                .errors
+                .into_iter()
+                .map(|(field, message)| format!("{}: {}", field, message))
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+}
+
+/// Trims text from a form field, treating blank text as missing.
+pub(crate) fn optional_text(text: &str) -> Option<String> {
+    match text.trim() {
+        "" => None,
+        text => Some(text.to_owned()),
+    }
+}
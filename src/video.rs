@@ -1,14 +1,70 @@
-use std::process::{Command, Stdio};
+use std::{collections::HashMap, path::Path, process::Stdio, time::Duration};
 
-use crate::SameyError;
+use serde::Deserialize;
+use tokio::{process::Command, time::timeout};
 
-pub(crate) fn generate_thumbnail(
+use crate::{SameyError, thumbnailer::VideoMetadata};
+
+/// How long a single FFmpeg process may run before it gets killed.
+const FFMPEG_TIMEOUT: Duration = Duration::from_secs(60);
+/// How long FFmpeg may take to split a video into HLS segments, since long videos have to be transcoded.
+const HLS_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+/// How long FFmpeg may take to turn the frames of an animation into a video.
+const ANIMATION_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// Target length of each HLS segment, in seconds.
+const HLS_SEGMENT_DURATION: u32 = 6;
+/// File name of the HLS playlist in a stream's directory.
+pub(crate) const HLS_PLAYLIST_NAME: &str = "index.m3u8";
+
+/// Checks if an FFmpeg binary (such as `ffmpeg` or `ffprobe`) can be executed.
+pub(crate) fn is_available(binary: &Path) -> bool {
+    std::process::Command::new(binary)
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Runs a command to completion, returning its standard output.
+///
+/// On failure, the error includes the command's standard error.
+async fn run(
+    command: &mut Command,
+    error_message: &str,
+    time_limit: Duration,
+) -> Result<Vec<u8>, SameyError> {
+    let output = timeout(
+        time_limit,
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| SameyError::Other(format!("{}: Timed out", error_message)))??;
+
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(SameyError::Other(format!(
+            "{}: {}",
+            error_message,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+pub(crate) async fn generate_thumbnail(
+    ffmpeg: &Path,
     input_path: &str,
     output_path: &str,
     max_thumbnail_dimension: u32,
 ) -> Result<(), SameyError> {
-    let status = Command::new("ffmpeg")
-        .args([
+    run(
+        Command::new(ffmpeg).args([
             "-i",
             input_path,
             "-vf",
@@ -23,51 +79,186 @@ pub(crate) fn generate_thumbnail(
             "-q:v",
             "2", // Quality (2 is good)
             output_path,
-        ])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .status()?;
+        ]),
+        "FFmpeg failed to generate thumbnail",
+        FFMPEG_TIMEOUT,
+    )
+    .await?;
+    Ok(())
+}
 
-    if status.success() {
-        Ok(())
-    } else {
-        Err(SameyError::Other(
-            "FFmpeg failed to generate thumbnail".into(),
-        ))
-    }
+/// Encodes the frames listed in an FFmpeg concat file into a WebM video, keeping the duration of each frame.
+pub(crate) async fn generate_animation(
+    ffmpeg: &Path,
+    frames_list: &Path,
+    output: &Path,
+) -> Result<(), SameyError> {
+    run(
+        Command::new(ffmpeg)
+            .args(["-f", "concat", "-i"])
+            .arg(frames_list)
+            .args([
+                "-c:v",
+                "libvpx-vp9",
+                "-b:v",
+                "0",
+                "-crf",
+                "30",
+                "-pix_fmt",
+                "yuv420p",
+                "-an",
+            ])
+            .arg(output),
+        "FFmpeg failed to generate animation",
+        ANIMATION_TIMEOUT,
+    )
+    .await?;
+    Ok(())
 }
 
-pub(crate) fn get_dimensions_for_video(input_path: &str) -> Result<(u32, u32), SameyError> {
-    let output = Command::new("ffprobe")
-        .args([
+/// Transcodes a video into HLS segments in `output_dir`, along with a [`HLS_PLAYLIST_NAME`] playlist.
+pub(crate) async fn generate_hls(
+    ffmpeg: &Path,
+    input_path: &str,
+    output_dir: &Path,
+) -> Result<(), SameyError> {
+    let segment_path = output_dir.join("segment-%05d.ts");
+    let playlist_path = output_dir.join(HLS_PLAYLIST_NAME);
+    run(
+        Command::new(ffmpeg)
+            .args([
+                "-i",
+                input_path,
+                "-map",
+                "0:v:0",
+                "-map",
+                "0:a:0?",
+                "-c:v",
+                "libx264",
+                "-preset",
+                "veryfast",
+                "-crf",
+                "23",
+                "-pix_fmt",
+                "yuv420p",
+                "-c:a",
+                "aac",
+                "-f",
+                "hls",
+                "-hls_time",
+                &HLS_SEGMENT_DURATION.to_string(),
+                "-hls_playlist_type",
+                "vod",
+                "-hls_segment_filename",
+            ])
+            .arg(segment_path)
+            .arg(playlist_path),
+        "FFmpeg failed to generate HLS stream",
+        HLS_TIMEOUT,
+    )
+    .await?;
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct FfprobeOutput {
+    #[serde(default)]
+    streams: Vec<FfprobeStream>,
+    format: Option<FfprobeFormat>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeStream {
+    width: u32,
+    height: u32,
+    codec_name: Option<String>,
+    avg_frame_rate: Option<String>,
+    duration: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+    #[serde(default)]
+    side_data_list: Vec<FfprobeSideData>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeSideData {
+    rotation: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct FfprobeFormat {
+    duration: Option<String>,
+}
+
+/// Parses a frame rate like `30000/1001`.
+fn parse_frame_rate(frame_rate: &str) -> Option<f64> {
+    let (numerator, denominator) = frame_rate.split_once('/')?;
+    let numerator: f64 = numerator.parse().ok()?;
+    let denominator: f64 = denominator.parse().ok()?;
+    (numerator > 0.0 && denominator > 0.0).then(|| numerator / denominator)
+}
+
+/// Returns the display dimensions of a video, after applying its rotation, along with its metadata.
+pub(crate) async fn probe_video(
+    ffprobe: &Path,
+    input_path: &str,
+) -> Result<(u32, u32, VideoMetadata), SameyError> {
+    let output = run(
+        Command::new(ffprobe).args([
             "-v",
             "error",
             "-select_streams",
             "v:0",
             "-show_entries",
-            "stream=width,height",
-            "-of",
-            "default=nw=1:nk=1",
+            "stream=width,height,codec_name,avg_frame_rate,duration:stream_tags=rotate:stream_side_data=rotation:format=duration",
+            "-print_format",
+            "json",
             input_path,
-        ])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()?;
-
-    if !output.status.success() {
-        return Err(SameyError::Other(
-            "FFprobe failed to get dimensions for video".into(),
-        ));
-    }
+        ]),
+        "FFprobe failed to get dimensions for video",
+        FFMPEG_TIMEOUT,
+    )
+    .await?;
 
-    let output_str = String::from_utf8_lossy(&output.stdout);
+    let output: FfprobeOutput = serde_json::from_slice(&output)
+        .map_err(|err| SameyError::Other(format!("Failed to parse FFprobe output: {}", err)))?;
+    let stream = output
+        .streams
+        .into_iter()
+        .next()
+        .ok_or(SameyError::BadRequest("Media has no video stream".into()))?;
 
-    let mut dimensions = output_str
-        .lines()
-        .filter_map(|line| line.trim().parse().ok());
+    // Newer FFmpeg versions report rotation as counter-clockwise side data, older ones as a clockwise tag
+    let rotation = stream
+        .side_data_list
+        .iter()
+        .find_map(|side_data| side_data.rotation.map(|rotation| -rotation))
+        .or_else(|| {
+            stream
+                .tags
+                .get("rotate")
+                .and_then(|rotate| rotate.parse().ok())
+        })
+        .map(|rotation| (rotation.round() as i32).rem_euclid(360))
+        .unwrap_or(0);
+    let duration = stream
+        .duration
+        .or(output.format.and_then(|format| format.duration))
+        .and_then(|duration| duration.parse().ok());
+    let (width, height) = if rotation % 180 == 90 {
+        (stream.height, stream.width)
+    } else {
+        (stream.width, stream.height)
+    };
 
-    match (dimensions.next(), dimensions.next()) {
-        (Some(width), Some(height)) => Ok((width, height)),
-        _ => Err(SameyError::Other("Failed to parse FFprobe output".into())),
-    }
+    Ok((
+        width,
+        height,
+        VideoMetadata {
+            duration,
+            codec: stream.codec_name,
+            frame_rate: stream.avg_frame_rate.as_deref().and_then(parse_frame_rate),
+            rotation,
+        },
+    ))
 }
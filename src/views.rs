@@ -1,97 +1,284 @@
 use std::{
-    any::Any,
     collections::{HashMap, HashSet},
-    fs::OpenOptions,
-    io::{BufReader, Seek, Write},
+    hash::{DefaultHasher, Hash, Hasher},
+    io::Cursor,
     mem,
-    num::NonZero,
+    net::SocketAddr,
     str::FromStr,
+    sync::Arc,
+    time::Duration,
 };
 
 use askama::Template;
 use axum::{
-    extract::{Multipart, Path, Query, State},
-    response::{Html, IntoResponse, Redirect},
+    Json,
+    body::Body,
+    extract::{ConnectInfo, Multipart, Path, Query, Request, State, multipart::Field},
+    http::{
+        HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri,
+        header::{
+            ACCEPT, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE, ETAG, HOST, IF_NONE_MATCH,
+            REFERER, USER_AGENT, VARY,
+        },
+    },
+    middleware::Next,
+    response::{Html, IntoResponse, Redirect, Response},
 };
 use axum_extra::extract::{Form, Host};
-use chrono::Utc;
-use image::{GenericImageView, ImageFormat, ImageReader};
+use chrono::{Datelike, Locale, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Utc};
+use chrono_tz::Tz;
+use http_body_util::Limited;
+use image::{ImageFormat, ImageReader, imageops::FilterType};
 use itertools::Itertools;
+use password_auth::generate_hash;
+use percent_encoding::{NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
 use rand::Rng;
-use samey_migration::{OnConflict, Query as MigrationQuery};
+use samey_migration::{Expr, OnConflict, Query as MigrationQuery};
 use sea_orm::{
-    ActiveValue::Set, ColumnTrait, Condition, EntityTrait, FromQueryResult, IntoSimpleExpr,
-    ModelTrait, PaginatorTrait, QueryFilter, QuerySelect,
+    ActiveModelTrait,
+    ActiveValue::{NotSet, Set},
+    ColumnTrait, DatabaseConnection, EntityTrait, FromQueryResult, ModelTrait, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, TransactionTrait,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
-use tokio::{task::spawn_blocking, try_join};
+use tokio::{
+    fs::File,
+    io::{AsyncWriteExt, BufWriter},
+    task::spawn_blocking,
+    time::timeout,
+};
+use tokio_util::io::ReaderStream;
+use tower_sessions::Session;
 
 use crate::{
     AppState,
-    auth::{AuthSession, Credentials, User},
-    config::{AGE_CONFIRMATION_KEY, APPLICATION_NAME_KEY, BASE_URL_KEY},
+    activitypub::{Federation, federate_post, generate_private_key},
+    api::{self, get_post_response, get_posts_response},
+    attachments::{ATTACHMENTS_DIRECTORY, store_attachment},
+    audit_log::{AuditAction, record_audit_log},
+    auth::{
+        AuthSession, Credentials, USER_AGENT_SESSION_KEY, USER_ID_SESSION_KEY, User,
+        hash_rss_token, hash_user_agent, revoke_user_sessions,
+    },
+    auto_tagger::suggest_tags,
+    cache::ObjectCache,
+    captcha::{CaptchaForm, CaptchaProvider, CaptchaSettings, CaptchaWidget},
+    config::{
+        ACCENT_COLOR_KEY, ACTIVITYPUB_ENABLED_KEY, ACTIVITYPUB_PRIVATE_KEY_KEY,
+        ACTIVITYPUB_USERNAME_KEY, AGE_CONFIRMATION_EXPLICIT_ONLY_KEY, AGE_CONFIRMATION_KEY,
+        ALLOW_UNRATED_KEY, ANNOUNCEMENT_EXPIRES_AT_KEY, ANNOUNCEMENT_MESSAGE_KEY,
+        APPLICATION_NAME_KEY, ATTACHMENT_MAX_SIZE_KEY, ATTACHMENT_MAX_SIZE_RANGE,
+        AUTO_TAGGER_ENABLED_KEY, AUTO_TAGGER_THRESHOLD_KEY, AUTO_TAGGER_URL_KEY, AppConfig,
+        AuthConfig, BASE_URL_KEY, BIND_SESSIONS_TO_USER_AGENT_KEY, CAPTCHA_LOGIN_ATTEMPTS_KEY,
+        CAPTCHA_LOGIN_ATTEMPTS_RANGE, CAPTCHA_PROVIDER_KEY, CAPTCHA_SECRET_KEY_KEY,
+        CAPTCHA_SITE_KEY_KEY, CLAMAV_ADDRESS_KEY, CLAMAV_ENABLED_KEY, CUSTOM_CSS_KEY, ConfigUpdate,
+        DATETIME_INPUT_FORMAT, DEFAULT_PUBLIC_KEY, DEFAULT_RATING_KEY, DELETE_EXPIRED_POSTS_KEY,
+        DESCRIPTION_MAX_LENGTH_KEY, DESCRIPTION_MAX_LENGTH_RANGE, FEATURED_POST_IDS_KEY,
+        FEATURED_TAGS_KEY, HOTLINK_ALLOWED_DOMAINS_KEY, HOTLINK_PROTECTION_KEY,
+        INDEX_RECENT_POSTS_KEY, INDEX_RECENT_POSTS_RANGE, INDEX_TOP_TAGS_KEY, INDEX_TOP_TAGS_RANGE,
+        IQDB_ENABLED_KEY, LOGO_URL_KEY, MODERATION_DIGEST_EMAILS_KEY,
+        MODERATION_DIGEST_WEBHOOK_URL_KEY, NOINDEX_EXPLICIT_POSTS_KEY, NOINDEX_INSTANCE_KEY,
+        ProcessConfigHandle, RATINGS_KEY, READ_ONLY_KEY, REQUIRE_RATING_TO_PUBLISH_KEY,
+        ROBOTS_TXT_KEY, SAUCENAO_API_KEY_KEY, SMTP_FROM_KEY, SMTP_URL_KEY, STATS_ENABLED_KEY,
+        STREAMING_ENABLED_KEY, STREAMING_MIN_DURATION_KEY, STREAMING_MIN_DURATION_RANGE,
+        STREAMING_MIN_SIZE_KEY, STREAMING_MIN_SIZE_RANGE, THUMBNAIL_DIMENSION_KEY,
+        THUMBNAIL_DIMENSION_RANGE, TITLE_MAX_LENGTH_KEY, TITLE_MAX_LENGTH_RANGE,
+    },
+    content::{
+        UploadedMedia, bump_post_version, create_uploaded_post, post_file_condition,
+        regenerate_thumbnails, remove_post_files, replace_post_tags, save_upload_tags,
+        store_upload_media,
+    },
+    context::BaseContext,
+    crosspost::{
+        DEFAULT_MESSAGE_TEMPLATE, IntegrationKind, queue_crossposts, retry_failed_crossposts,
+    },
+    digest::send_moderation_digest,
+    disk_usage::{
+        TagDiskUsage, UploaderDiskUsage, get_disk_usage_by_tag, get_disk_usage_by_uploader,
+        get_total_disk_usage,
+    },
+    email::parse_mailbox,
     entities::{
         prelude::{
-            SameyConfig, SameyPool, SameyPoolPost, SameyPost, SameyPostSource, SameyTag,
-            SameyTagPost,
+            SameyChange, SameyIntegration, SameyNotification, SameyNotificationSetting, SameyPool,
+            SameyPoolPost, SameyPost, SameyPostAttachment, SameyPostReport, SameyPostSource,
+            SameyTag, SameyTagPost, SameyTakedownRequest, SameyUser,
         },
-        samey_config, samey_pool, samey_pool_post, samey_post, samey_post_source, samey_tag,
-        samey_tag_post,
+        samey_change, samey_integration, samey_notification, samey_notification_setting,
+        samey_pool, samey_pool_post, samey_post, samey_post_attachment, samey_post_report,
+        samey_post_source, samey_tag, samey_tag_post, samey_takedown_request, samey_user,
     },
     error::SameyError,
+    fsck::{FsckOptions, FsckReport, fsck},
+    notifications::{notify_mentions, notify_pool_add},
+    permissions::{Admin, FeedUser, Owned, RequireFeedRole, RequireOwnerOrAdmin, RequireRole},
+    popularity::{PopularPeriod, get_popular_posts},
+    preferences::{
+        AUTOPLAY_VIDEOS_SESSION_KEY, LOCALE_SESSION_KEY, POSTS_PER_PAGE_CHOICES,
+        POSTS_PER_PAGE_SESSION_KEY, Preferences, THUMBNAIL_DENSITY_SESSION_KEY,
+        TIMEZONE_SESSION_KEY, ThumbnailDensity,
+    },
     query::{
-        PoolPost, PostOverview, PostPoolData, clean_dangling_tags, filter_posts_by_user,
-        get_pool_data_for_post, get_posts_in_pool, get_tags_for_post, search_posts,
+        CrosspostError, CurationPost, DayCount, IntegrationOverview, NotificationOverview,
+        PendingPostReport, PendingTakedownRequest, PoolPost, PostOverview, PostPoolData,
+        PostsCursor, PostsKeysetPage, TagCount, UserOverview, autocomplete_tags,
+        clean_dangling_tags, filter_pools_by_user, filter_posts_by_user, get_crosspost_errors,
+        get_curation_post, get_drafts_for_user, get_integration_overviews,
+        get_notifications_for_user, get_pending_post_reports, get_pending_takedown_requests,
+        get_pool_data_for_post, get_posts_in_pool, get_posts_needing_curation, get_protected_tags,
+        get_search_neighbours, get_tags_for_post, get_upload_counts_by_day, get_user_overview,
+        get_user_overviews, is_post_published, search_posts, search_posts_keyset,
+        search_posts_query, sort_post_overview_tags, suggest_tags_from_text,
+    },
+    render::filters,
+    search::{SearchError, SearchQuery},
+    source_lookup::{SourceCandidate, lookup_sources},
+    sources::normalize_source_url,
+    stats::Stats,
+    streaming::STREAM_DIRECTORY_PREFIX,
+    tags::{
+        MEDIA_TYPE_PREFIX, MediaType, NEGATIVE_PREFIX, ORDER_PREFIX, PostsOrder, RATING_PREFIX,
+        Rating, UNRATED, format_ratings, is_sensitive_rating, parse_ratings, rating_label,
     },
-    tags::{MEDIA_TYPE_PREFIX, MediaType, NEGATIVE_PREFIX, RATING_PREFIX, Rating},
-    video::{generate_thumbnail, get_dimensions_for_video},
+    takedown::{
+        TAKEDOWN_CLAIMANT_MAX_LENGTH, TAKEDOWN_REASON_MAX_LENGTH, TakedownStatus,
+        reject_takedown_request, restore_taken_down_post, take_down_post,
+    },
+    validation::{FieldErrors, optional_text},
+    video::HLS_PLAYLIST_NAME,
 };
 
-const MAX_THUMBNAIL_DIMENSION: u32 = 192;
-
-// Filters
-
-mod filters {
-    pub(crate) fn markdown(
-        s: impl std::fmt::Display,
-    ) -> askama::Result<askama::filters::Safe<String>> {
-        let s = s.to_string();
-        let parser = pulldown_cmark::Parser::new(&s);
-        let mut output = String::new();
-        pulldown_cmark::html::push_html(&mut output, parser);
-        Ok(askama::filters::Safe(output))
-    }
-}
-
 // Index view
 
 #[derive(Template)]
 #[template(path = "pages/index.html")]
 struct IndexTemplate {
-    application_name: String,
-    age_confirmation: bool,
-    user: Option<User>,
+    base: BaseContext,
+    unread_notifications: u64,
+    featured_posts: Vec<PostOverview>,
+    recent_posts: Arc<Vec<PostOverview>>,
+    top_tags: Arc<Vec<TagCount>>,
+    stats_enabled: bool,
+    /// Theme of unsaved appearance settings, when previewing them.
+    preview_css: Option<String>,
+}
+
+/// Maximum number of posts shown from the featured tags query.
+const FEATURED_TAGS_POSTS_LIMIT: u64 = 10;
+
+/// Settings that decide what is shown on the index page.
+struct IndexSettings {
+    featured_post_ids: Vec<i32>,
+    featured_tags: String,
+    index_recent_posts: u64,
+    index_top_tags: u64,
+    stats_enabled: bool,
+}
+
+impl IndexSettings {
+    fn from_config(app_config: &AppConfig) -> Self {
+        Self {
+            featured_post_ids: app_config.featured_post_ids.clone(),
+            featured_tags: app_config.featured_tags.clone(),
+            index_recent_posts: app_config.index_recent_posts,
+            index_top_tags: app_config.index_top_tags,
+            stats_enabled: app_config.stats_enabled,
+        }
+    }
 }
 
 pub(crate) async fn index(
-    State(AppState { app_config, .. }): State<AppState>,
-    auth_session: AuthSession,
+    State(AppState {
+        db,
+        app_config,
+        object_cache,
+        ..
+    }): State<AppState>,
+    base: BaseContext,
 ) -> Result<impl IntoResponse, SameyError> {
-    let app_config = app_config.read().await;
-    let application_name = app_config.application_name.clone();
-    let age_confirmation = app_config.age_confirmation;
-    drop(app_config);
+    let settings = IndexSettings::from_config(&*app_config.read().await);
+
     Ok(Html(
-        IndexTemplate {
-            application_name,
-            age_confirmation,
-            user: auth_session.user,
-        }
-        .render()?,
+        index_template(&db, &object_cache, base, settings)
+            .await?
+            .render()?,
     ))
 }
 
+async fn index_template(
+    db: &DatabaseConnection,
+    object_cache: &ObjectCache,
+    base: BaseContext,
+    IndexSettings {
+        featured_post_ids,
+        featured_tags,
+        index_recent_posts,
+        index_top_tags,
+        stats_enabled,
+    }: IndexSettings,
+) -> Result<IndexTemplate, SameyError> {
+    let user = base.user.as_ref();
+    let mut featured_posts = if featured_post_ids.is_empty() {
+        vec![]
+    } else {
+        let mut posts = search_posts_query(&SearchQuery::default(), user)
+            .filter(samey_post::Column::Id.is_in(featured_post_ids.iter().copied()))
+            .into_model::<PostOverview>()
+            .all(db)
+            .await?;
+        posts.sort_by_key(|post| featured_post_ids.iter().position(|id| *id == post.id));
+        posts
+    };
+    if !featured_tags.trim().is_empty() {
+        let PostsKeysetPage { posts, .. } = search_posts_keyset(
+            db,
+            &SearchQuery::parse(&featured_tags),
+            user,
+            PostsCursor::Page(0),
+            FEATURED_TAGS_POSTS_LIMIT,
+        )
+        .await?;
+        featured_posts.extend(
+            posts
+                .into_iter()
+                .filter(|post| !featured_post_ids.contains(&post.id)),
+        );
+    }
+    let featured_posts = sort_post_overview_tags(featured_posts);
+
+    let recent_posts = if index_recent_posts > 0 {
+        object_cache.recent_posts(db, index_recent_posts).await?
+    } else {
+        Default::default()
+    };
+    let top_tags = if index_top_tags > 0 {
+        object_cache.top_tags(db, index_top_tags).await?
+    } else {
+        Default::default()
+    };
+
+    let unread_notifications = match base.user.as_ref() {
+        Some(user) => {
+            get_notifications_for_user(user)
+                .filter(samey_notification::Column::IsRead.eq(false))
+                .count(db)
+                .await?
+        }
+        None => 0,
+    };
+    Ok(IndexTemplate {
+        base,
+        unread_notifications,
+        featured_posts,
+        recent_posts,
+        top_tags,
+        stats_enabled,
+        preview_css: None,
+    })
+}
+
 // RSS view
 
 #[derive(Template)]
@@ -101,22 +288,23 @@ struct RssEntryTemplate<'a> {
     base_url: &'a str,
 }
 
-#[axum::debug_handler]
+#[derive(Debug, Deserialize)]
+pub(crate) struct RssQuery {
+    tags: Option<String>,
+}
+
 pub(crate) async fn rss_page(
     State(AppState { app_config, db, .. }): State<AppState>,
-    Query(query): Query<PostsQuery>,
+    FeedUser(user): FeedUser,
+    Query(query): Query<RssQuery>,
 ) -> Result<impl IntoResponse, SameyError> {
     let app_config = app_config.read().await;
     let application_name = app_config.application_name.clone();
     let base_url = app_config.base_url.clone();
     drop(app_config);
 
-    let tags = query
-        .tags
-        .as_ref()
-        .map(|tags| tags.split_whitespace().collect::<Vec<_>>());
-
-    let posts = search_posts(tags.as_ref(), None)
+    let search = SearchQuery::parse(query.tags.as_deref().unwrap_or_default());
+    let posts = search_posts(&search, user.as_ref())
         .paginate(&db, 20)
         .fetch_page(0)
         .await?;
@@ -149,49 +337,407 @@ pub(crate) async fn rss_page(
     Ok(channel.to_string())
 }
 
+// Activity feed views
+
+/// Maximum number of entries of each kind in the activity feed.
+const ACTIVITY_FEED_LIMIT: u64 = 20;
+
+#[derive(Debug, Serialize)]
+struct ActivityEntry {
+    /// What the entry is about, either `pool` or `tag`.
+    kind: &'static str,
+    title: String,
+    link: String,
+    created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize)]
+struct ActivityResponse {
+    /// Entries from newest to oldest.
+    entries: Vec<ActivityEntry>,
+}
+
+/// Lists new pools and changes to tags, newest first, for moderators to follow the instance.
+async fn get_activity_entries(
+    db: &DatabaseConnection,
+    base_url: &str,
+) -> Result<Vec<ActivityEntry>, SameyError> {
+    // Pools from before creation dates were recorded are left out
+    let pools = SameyPool::find()
+        .filter(samey_pool::Column::CreatedAt.is_not_null())
+        .order_by_desc(samey_pool::Column::CreatedAt)
+        .limit(ACTIVITY_FEED_LIMIT)
+        .all(db)
+        .await?;
+    let tag_changes = SameyChange::find()
+        .filter(samey_change::Column::Entity.eq("tag"))
+        .order_by_desc(samey_change::Column::Id)
+        .limit(ACTIVITY_FEED_LIMIT)
+        .all(db)
+        .await?;
+    let tag_names: HashMap<i32, String> = SameyTag::find()
+        .filter(samey_tag::Column::Id.is_in(tag_changes.iter().map(|change| change.entity_id)))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|tag| (tag.id, tag.name))
+        .collect();
+
+    let mut entries = pools
+        .into_iter()
+        .filter_map(|pool| {
+            Some(ActivityEntry {
+                kind: "pool",
+                created_at: pool.created_at?,
+                title: format!("New pool: {}", pool.name),
+                link: format!("{}/pool/{}", base_url, pool.id),
+            })
+        })
+        .chain(tag_changes.into_iter().map(|change| {
+            let action = match change.action.as_str() {
+                "create" => "created",
+                "update" => "updated",
+                "delete" => "deleted",
+                action => action,
+            };
+            let (title, link) = match tag_names.get(&change.entity_id) {
+                Some(name) => (
+                    format!("Tag {}: {}", action, name),
+                    format!(
+                        "{}/posts?tags={}",
+                        base_url,
+                        utf8_percent_encode(name, NON_ALPHANUMERIC)
+                    ),
+                ),
+                None => (
+                    format!("Tag {}: #{}", action, change.entity_id),
+                    format!("{}/", base_url),
+                ),
+            };
+            ActivityEntry {
+                kind: "tag",
+                title,
+                link,
+                created_at: change.created_at,
+            }
+        }))
+        .collect_vec();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.created_at));
+    Ok(entries)
+}
+
+pub(crate) async fn activity_xml(
+    State(AppState { app_config, db, .. }): State<AppState>,
+    _: RequireFeedRole<Admin>,
+) -> Result<impl IntoResponse, SameyError> {
+    let app_config = app_config.read().await;
+    let application_name = app_config.application_name.clone();
+    let base_url = app_config.base_url.clone();
+    drop(app_config);
+
+    let entries = get_activity_entries(&db, &base_url).await?;
+
+    let channel = rss::ChannelBuilder::default()
+        .title(format!("Activity - {}", application_name))
+        .link(&base_url)
+        .items(
+            entries
+                .into_iter()
+                .map(|entry| {
+                    rss::ItemBuilder::default()
+                        .title(entry.title)
+                        .pub_date(entry.created_at.and_utc().to_rfc2822())
+                        .link(entry.link)
+                        .build()
+                })
+                .collect_vec(),
+        )
+        .build();
+
+    Ok(channel.to_string())
+}
+
+pub(crate) async fn activity_json(
+    State(AppState { app_config, db, .. }): State<AppState>,
+    _: RequireFeedRole<Admin>,
+) -> Result<impl IntoResponse, SameyError> {
+    let base_url = app_config.read().await.base_url.clone();
+
+    Ok(Json(ActivityResponse {
+        entries: get_activity_entries(&db, &base_url).await?,
+    }))
+}
+
+// oEmbed view
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct OEmbedQuery {
+    url: String,
+    format: Option<String>,
+    maxwidth: Option<i32>,
+    maxheight: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+struct OEmbedResponse {
+    version: &'static str,
+    #[serde(rename = "type")]
+    oembed_type: &'static str,
+    title: String,
+    provider_name: String,
+    provider_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    html: Option<String>,
+    width: i32,
+    height: i32,
+    thumbnail_url: String,
+    thumbnail_width: i32,
+    thumbnail_height: i32,
+}
+
+pub(crate) async fn oembed(
+    State(AppState { db, app_config, .. }): State<AppState>,
+    auth_session: AuthSession,
+    Query(query): Query<OEmbedQuery>,
+    Host(host): Host,
+) -> Result<impl IntoResponse, SameyError> {
+    let app_config = app_config.read().await;
+    let application_name = app_config.application_name.clone();
+    let base_url = match app_config.base_url.as_str() {
+        "" => format!("https://{}", host),
+        base_url => base_url.to_owned(),
+    };
+    drop(app_config);
+
+    if query.format.is_some_and(|format| format != "json") {
+        return Err(SameyError::BadRequest(
+            "Only JSON format is supported".into(),
+        ));
+    }
+    let post_id = query
+        .url
+        .split_once("/post/")
+        .and_then(|(_, path)| {
+            path.split(['?', '#', '/'])
+                .next()
+                .and_then(|id| id.parse::<i32>().ok())
+        })
+        .ok_or(SameyError::NotFound)?;
+    let post = filter_posts_by_user(SameyPost::find_by_id(post_id), auth_session.user.as_ref())
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+    let title = match post.title {
+        Some(title) => title,
+        None => get_tags_for_post(post.id)
+            .all(&db)
+            .await?
+            .into_iter()
+            .map(|tag| tag.name)
+            .join(" "),
+    };
+
+    // Scale down to fit the consumer's requested bounds, keeping the aspect ratio
+    let scale = [
+        query
+            .maxwidth
+            .map(|maxwidth| maxwidth as f64 / post.width as f64),
+        query
+            .maxheight
+            .map(|maxheight| maxheight as f64 / post.height as f64),
+    ]
+    .into_iter()
+    .flatten()
+    .fold(1.0_f64, f64::min);
+    let width = (post.width as f64 * scale).round() as i32;
+    let height = (post.height as f64 * scale).round() as i32;
+    let media_url = format!("{}/files/{}", base_url, post.media);
+
+    let (oembed_type, url, html) = match post.media_type.as_ref() {
+        "video" => (
+            "video",
+            None,
+            Some(format!(
+                "<video src=\"{}\" width=\"{}\" height=\"{}\" controls></video>",
+                media_url, width, height
+            )),
+        ),
+        _ => ("photo", Some(media_url), None),
+    };
+
+    Ok(Json(OEmbedResponse {
+        version: "1.0",
+        oembed_type,
+        title,
+        provider_name: application_name,
+        provider_url: base_url.clone(),
+        url,
+        html,
+        width,
+        height,
+        thumbnail_url: format!("{}/files/{}", base_url, post.thumbnail),
+        thumbnail_width: post.thumbnail_width,
+        thumbnail_height: post.thumbnail_height,
+    }))
+}
+
 // Auth views
 
 #[derive(Template)]
 #[template(path = "pages/login.html")]
 struct LoginPageTemplate {
-    application_name: String,
-    age_confirmation: bool,
+    base: BaseContext,
+    username: String,
+    error: Option<String>,
+    captcha: Option<CaptchaWidget>,
 }
 
 pub(crate) async fn login_page(
-    State(AppState { app_config, .. }): State<AppState>,
+    State(AppState {
+        app_config,
+        captcha,
+        ..
+    }): State<AppState>,
+    base: BaseContext,
     auth_session: AuthSession,
 ) -> Result<impl IntoResponse, SameyError> {
     if auth_session.user.is_some() {
         return Ok(Redirect::to("/").into_response());
     }
 
-    let app_config = app_config.read().await;
-    let application_name = app_config.application_name.clone();
-    let age_confirmation = app_config.age_confirmation;
-    drop(app_config);
+    // Without a username, the CAPTCHA is only shown upfront when every login needs it
+    let captcha_settings = CaptchaSettings::from_config(&*app_config.read().await);
+    let captcha = match captcha_settings {
+        Some(settings) if settings.required_for_every_login() => {
+            Some(settings.widget(&captcha).await)
+        }
+        _ => None,
+    };
 
     Ok(Html(
         LoginPageTemplate {
-            application_name,
-            age_confirmation,
+            base,
+            username: String::new(),
+            error: None,
+            captcha,
         }
         .render()?,
     )
     .into_response())
 }
 
+#[derive(Deserialize)]
+pub(crate) struct LoginForm {
+    username: String,
+    password: String,
+    #[serde(flatten)]
+    captcha: CaptchaForm,
+}
+
 pub(crate) async fn login(
+    State(AppState {
+        app_config,
+        captcha,
+        ..
+    }): State<AppState>,
+    base: BaseContext,
     mut auth_session: AuthSession,
-    Form(credentials): Form<Credentials>,
-) -> Result<impl IntoResponse, SameyError> {
-    let user = match auth_session.authenticate(credentials).await? {
+    session: Session,
+    headers: HeaderMap,
+    Form(body): Form<LoginForm>,
+) -> Result<Response, SameyError> {
+    let captcha_settings = CaptchaSettings::from_config(&*app_config.read().await);
+    if let Some(settings) = &captcha_settings {
+        if settings.required_for_login(&captcha, &body.username).await
+            && !settings.verify(&captcha, &body.captcha).await?
+        {
+            return Ok((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Html(
+                    LoginPageTemplate {
+                        base,
+                        username: body.username,
+                        error: Some("Complete the CAPTCHA to log in".into()),
+                        captcha: Some(settings.widget(&captcha).await),
+                    }
+                    .render()?,
+                ),
+            )
+                .into_response());
+        }
+    }
+
+    let credentials = Credentials {
+        username: body.username,
+        password: body.password,
+    };
+    let user = match auth_session.authenticate(credentials.clone()).await? {
         Some(user) => user,
-        None => return Err(SameyError::Authentication("Invalid credentials".into())),
+        None => {
+            let Some(settings) = captcha_settings else {
+                return Err(SameyError::Authentication("Invalid credentials".into()));
+            };
+            captcha.record_failed_login(&credentials.username).await;
+            let widget = if settings
+                .required_for_login(&captcha, &credentials.username)
+                .await
+            {
+                Some(settings.widget(&captcha).await)
+            } else {
+                None
+            };
+            return Ok((
+                StatusCode::UNAUTHORIZED,
+                Html(
+                    LoginPageTemplate {
+                        base,
+                        username: credentials.username,
+                        error: Some("Invalid credentials".into()),
+                        captcha: widget,
+                    }
+                    .render()?,
+                ),
+            )
+                .into_response());
+        }
     };
+    captcha.clear_failed_logins(&credentials.username).await;
 
-    auth_session.login(&user).await?;
-    Ok(Redirect::to("/"))
+    start_session(&mut auth_session, &session, &headers, &user).await?;
+    Ok(Redirect::to("/").into_response())
+}
+
+/// Logs a user in, with the session keys that revoking and binding sessions rely on.
+async fn start_session(
+    auth_session: &mut AuthSession,
+    session: &Session,
+    headers: &HeaderMap,
+    user: &User,
+) -> Result<(), SameyError> {
+    auth_session.login(user).await?;
+    // Always use a new session ID, even when switching from another logged in user
+    session
+        .cycle_id()
+        .await
+        .map_err(|err| SameyError::Other(err.to_string()))?;
+    session
+        .insert(USER_ID_SESSION_KEY, user.id)
+        .await
+        .map_err(|err| SameyError::Other(err.to_string()))?;
+    session
+        .insert(
+            USER_AGENT_SESSION_KEY,
+            hash_user_agent(
+                headers
+                    .get(USER_AGENT)
+                    .map(|user_agent| user_agent.as_bytes())
+                    .unwrap_or_default(),
+            ),
+        )
+        .await
+        .map_err(|err| SameyError::Other(err.to_string()))
 }
 
 pub(crate) async fn logout(mut auth_session: AuthSession) -> Result<impl IntoResponse, SameyError> {
@@ -199,307 +745,549 @@ pub(crate) async fn logout(mut auth_session: AuthSession) -> Result<impl IntoRes
     Ok(Redirect::to("/"))
 }
 
-// Post upload views
-
-#[derive(Template)]
-#[template(path = "pages/upload.html")]
-struct UploadPageTemplate {
-    application_name: String,
-    age_confirmation: bool,
-}
-
-pub(crate) async fn upload_page(
+/// Logs out sessions used from a different user agent than the one that logged in, if enabled.
+pub(crate) async fn session_binding_guard(
     State(AppState { app_config, .. }): State<AppState>,
-    auth_session: AuthSession,
-) -> Result<impl IntoResponse, SameyError> {
-    if auth_session.user.is_none() {
-        return Err(SameyError::Forbidden);
+    mut auth_session: AuthSession,
+    session: Session,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, SameyError> {
+    if !app_config.read().await.bind_sessions_to_user_agent || auth_session.user.is_none() {
+        return Ok(next.run(request).await);
+    }
+    let user_agent_hash = hash_user_agent(
+        request
+            .headers()
+            .get(USER_AGENT)
+            .map(|user_agent| user_agent.as_bytes())
+            .unwrap_or_default(),
+    );
+    let session_hash = session
+        .get::<String>(USER_AGENT_SESSION_KEY)
+        .await
+        .map_err(|err| SameyError::Other(err.to_string()))?;
+    // Sessions from before binding was enabled are logged out too
+    if session_hash.is_none_or(|session_hash| session_hash != user_agent_hash) {
+        auth_session.logout().await?;
+        // Handlers for this request must not see the user either
+        request.extensions_mut().insert(auth_session);
     }
+    Ok(next.run(request).await)
+}
 
-    let app_config = app_config.read().await;
-    let application_name = app_config.application_name.clone();
-    let age_confirmation = app_config.age_confirmation;
-    drop(app_config);
+/// Logs in the user named by a reverse proxy's header, if header authentication is enabled.
+///
+/// Sessions are kept when the header is missing, so that pages which the proxy lets through without a login still
+/// show the user. A user is logged in again when their admin status changes.
+pub(crate) async fn header_authentication(
+    State(AppState { process_config, .. }): State<AppState>,
+    mut auth_session: AuthSession,
+    session: Session,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, SameyError> {
+    let AuthConfig::Header(config) = process_config.get().await.auth else {
+        return Ok(next.run(request).await);
+    };
+    // Without trusted proxies, nobody is trusted, since the headers could come from any client
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| address.ip().to_canonical());
+    if !peer.is_some_and(|peer| config.trusted_proxies.contains(&peer)) {
+        return Ok(next.run(request).await);
+    }
+    let headers = request.headers();
+    let Some(username) = headers
+        .get(&config.username_header)
+        .and_then(|username| username.to_str().ok())
+        .map(str::trim)
+        .filter(|username| !username.is_empty())
+        .map(str::to_owned)
+    else {
+        return Ok(next.run(request).await);
+    };
+    let is_admin = config
+        .admin_group
+        .as_ref()
+        .zip(config.groups_header.as_ref())
+        .map(|(admin_group, groups_header)| {
+            headers
+                .get(groups_header)
+                .and_then(|groups| groups.to_str().ok())
+                .unwrap_or_default()
+                .split(',')
+                .any(|group| group.trim() == admin_group)
+        });
+    if auth_session.user.as_ref().is_some_and(|user| {
+        user.username == username && is_admin.is_none_or(|is_admin| user.is_admin == is_admin)
+    }) {
+        return Ok(next.run(request).await);
+    }
 
-    Ok(Html(
-        UploadPageTemplate {
-            application_name,
-            age_confirmation,
+    match auth_session
+        .backend
+        .external_user(&username, is_admin)
+        .await?
+    {
+        Some(user) => start_session(&mut auth_session, &session, headers, &user).await?,
+        // Disabled users can't keep a session from before
+        None => {
+            auth_session.logout().await?;
         }
-        .render()?,
-    )
-    .into_response())
+    }
+    // Handlers for this request must see the new user
+    request.extensions_mut().insert(auth_session);
+    Ok(next.run(request).await)
 }
 
-enum Format {
-    Video(&'static str),
-    Image(ImageFormat),
-}
+// Age confirmation views
+
+/// Session key set once a visitor has confirmed their age.
+pub(crate) const AGE_CONFIRMED_SESSION_KEY: &str = "age_confirmed";
 
-impl Format {
-    fn media_type(&self) -> &'static str {
-        match self {
-            Format::Video(_) => "video",
-            Format::Image(_) => "image",
+/// Only allows redirecting to paths on this site.
+fn local_redirect(redirect: Option<String>) -> String {
+    match redirect {
+        Some(redirect)
+            if redirect.starts_with('/')
+                && !redirect.starts_with("//")
+                && !redirect.starts_with("/\\") =>
+        {
+            redirect
         }
+        _ => "/".into(),
     }
 }
 
-impl FromStr for Format {
-    type Err = SameyError;
+/// Redirects visitors who haven't confirmed their age away from post pages and media files.
+///
+/// Thumbnails are not gated, so that listings still load behind the age check dialog.
+pub(crate) async fn age_confirmation_gate(
+    State(AppState { db, app_config, .. }): State<AppState>,
+    session: Session,
+    request: Request,
+    next: Next,
+) -> Result<Response, SameyError> {
+    let app_config = app_config.read().await;
+    let age_confirmation = app_config.age_confirmation;
+    let explicit_only = app_config.age_confirmation_explicit_only;
+    let ratings = app_config.ratings.clone();
+    drop(app_config);
 
-    fn from_str(content_type: &str) -> Result<Self, Self::Err> {
-        match content_type {
-            "video/mp4" => Ok(Self::Video(".mp4")),
-            "video/webm" => Ok(Self::Video(".webm")),
-            "application/x-matroska" | "video/mastroska" => Ok(Self::Video(".mkv")),
-            "video/quicktime" => Ok(Self::Video(".mov")),
-            _ => Ok(Self::Image(
-                ImageFormat::from_mime_type(content_type).ok_or(SameyError::BadRequest(
-                    format!("Unknown content type: {}", content_type),
-                ))?,
-            )),
-        }
+    if !age_confirmation || request.method() != Method::GET {
+        return Ok(next.run(request).await);
+    }
+    let path = request.uri().path();
+    let post_filter = if let Some(post_id) = path.strip_prefix("/post/") {
+        // Also covers subpaths such as `/post/{post_id}/download`
+        post_id
+            .split('/')
+            .next()
+            .and_then(|post_id| post_id.parse::<i32>().ok())
+            .map(|post_id| samey_post::Column::Id.eq(post_id))
+    } else if let Some(file) = path.strip_prefix("/files/") {
+        // The path is decoded the same way that the file server does, so that encoded names can't get around this
+        let file = percent_decode_str(file).decode_utf8_lossy();
+        Some(match file.split_once('/') {
+            // Playlists and segments of HLS streams are gated like the video of their post
+            Some((directory, _)) if directory.starts_with(STREAM_DIRECTORY_PREFIX) => {
+                samey_post::Column::StreamPlaylist
+                    .eq(format!("{}/{}", directory, HLS_PLAYLIST_NAME))
+            }
+            _ => samey_post::Column::Media.eq(file.into_owned()),
+        })
+    } else {
+        None
+    };
+    let Some(post_filter) = post_filter else {
+        return Ok(next.run(request).await);
+    };
+    let is_confirmed = session
+        .get::<bool>(AGE_CONFIRMED_SESSION_KEY)
+        .await
+        .map_err(|err| SameyError::Other(err.to_string()))?
+        .unwrap_or(false);
+    if is_confirmed {
+        return Ok(next.run(request).await);
     }
+
+    let is_gated = SameyPost::find()
+        .filter(post_filter)
+        .one(&db)
+        .await?
+        .is_some_and(|post| !explicit_only || is_sensitive_rating(&ratings, &post.rating));
+    if !is_gated {
+        return Ok(next.run(request).await);
+    }
+
+    Ok(Redirect::to(&format!("/age_confirmation?redirect={}", path)).into_response())
 }
 
-pub(crate) async fn upload(
-    State(AppState { db, files_dir, .. }): State<AppState>,
-    auth_session: AuthSession,
-    mut multipart: Multipart,
+#[derive(Template)]
+#[template(path = "pages/age_confirmation.html")]
+struct AgeConfirmationTemplate {
+    base: BaseContext,
+    redirect: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AgeConfirmationQuery {
+    redirect: Option<String>,
+}
+
+pub(crate) async fn age_confirmation_page(
+    base: BaseContext,
+    Query(query): Query<AgeConfirmationQuery>,
+) -> Result<impl IntoResponse, SameyError> {
+    Ok(Html(
+        AgeConfirmationTemplate {
+            base,
+            redirect: local_redirect(query.redirect),
+        }
+        .render()?,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AgeConfirmationForm {
+    redirect: Option<String>,
+}
+
+pub(crate) async fn confirm_age(
+    session: Session,
+    Form(body): Form<AgeConfirmationForm>,
+) -> Result<impl IntoResponse, SameyError> {
+    session
+        .insert(AGE_CONFIRMED_SESSION_KEY, true)
+        .await
+        .map_err(|err| SameyError::Other(err.to_string()))?;
+
+    // The age check dialog confirms in the background, without a redirect
+    Ok(match body.redirect {
+        Some(redirect) => Redirect::to(&local_redirect(Some(redirect))).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    })
+}
+
+// Post upload views
+
+#[derive(Template)]
+#[template(path = "pages/upload.html")]
+struct UploadPageTemplate {
+    base: BaseContext,
+    supports_video: bool,
+    tags: String,
+    errors: FieldErrors,
+}
+
+pub(crate) async fn upload_page(
+    State(AppState { thumbnailer, .. }): State<AppState>,
+    base: BaseContext,
+    auth_session: AuthSession,
+) -> Result<impl IntoResponse, SameyError> {
+    if auth_session.user.is_none() {
+        return Err(SameyError::Forbidden);
+    }
+
+    Ok(Html(
+        UploadPageTemplate {
+            base,
+            supports_video: thumbnailer.supports_video(),
+            tags: String::new(),
+            errors: FieldErrors::default(),
+        }
+        .render()?,
+    )
+    .into_response())
+}
+
+/// Streams a multipart field into a new file, syncing it to disk once complete.
+async fn write_field_to_file(
+    field: &mut Field<'_>,
+    file_path: impl AsRef<std::path::Path>,
+) -> Result<(), SameyError> {
+    let mut file = BufWriter::new(File::create(file_path).await?);
+    while let Some(chunk) = field.chunk().await? {
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    file.into_inner().sync_all().await?;
+    Ok(())
+}
+
+/// Streams a multipart field into a new file like [`write_field_to_file`], failing once it grows past `max_size`
+/// bytes. Returns the size of the file.
+async fn write_field_to_file_limited(
+    field: &mut Field<'_>,
+    file_path: impl AsRef<std::path::Path>,
+    max_size: u64,
+) -> Result<u64, SameyError> {
+    let mut file = BufWriter::new(File::create(file_path).await?);
+    let mut size = 0;
+    while let Some(chunk) = field.chunk().await? {
+        size += chunk.len() as u64;
+        if size > max_size {
+            return Err(SameyError::BadRequest(format!(
+                "File is larger than {} bytes",
+                max_size
+            )));
+        }
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    file.into_inner().sync_all().await?;
+    Ok(size)
+}
+
+/// Keeps private files, such as post attachments, from being served under `/files`.
+pub(crate) async fn private_files_guard(
+    request: Request,
+    next: Next,
+) -> Result<Response, SameyError> {
+    // The path is decoded the same way that the file server does, so that encoded names can't get around this
+    let path = percent_decode_str(request.uri().path()).decode_utf8_lossy();
+    let is_private = path
+        .split(['/', '\\'])
+        .find(|segment| !segment.is_empty() && *segment != ".")
+        .is_some_and(|segment| segment.eq_ignore_ascii_case(ATTACHMENTS_DIRECTORY));
+    if is_private {
+        return Err(SameyError::NotFound);
+    }
+    Ok(next.run(request).await)
+}
+
+/// Hides the files of taken down posts from everyone except admins, so that they can't be shared by link.
+pub(crate) async fn taken_down_files_guard(
+    State(AppState { db, .. }): State<AppState>,
+    auth_session: AuthSession,
+    request: Request,
+    next: Next,
+) -> Result<Response, SameyError> {
+    if auth_session.user.as_ref().is_some_and(|user| user.is_admin) {
+        return Ok(next.run(request).await);
+    }
+    // The path is decoded and normalized the same way that the file server does, so that encoded names can't get around this
+    let path = percent_decode_str(request.uri().path()).decode_utf8_lossy();
+    let file = path
+        .split(['/', '\\'])
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .collect::<Vec<_>>()
+        .join("/");
+    let is_taken_down = SameyPost::find()
+        .filter(samey_post::Column::TakenDownAt.is_not_null())
+        .filter(post_file_condition(&file))
+        .count(&db)
+        .await?
+        > 0;
+    if is_taken_down {
+        return Err(SameyError::NotFound);
+    }
+    Ok(next.run(request).await)
+}
+
+/// Limits upload requests to the current maximum size, which can change when the config is reloaded.
+pub(crate) async fn upload_size_limit(
+    State(AppState { process_config, .. }): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let max_upload_size = process_config.get().await.max_upload_size;
+    next.run(request.map(|body| Body::new(Limited::new(body, max_upload_size))))
+        .await
+}
+
+pub(crate) async fn upload(
+    State(state): State<AppState>,
+    base: BaseContext,
+    auth_session: AuthSession,
+    multipart: Multipart,
 ) -> Result<impl IntoResponse, SameyError> {
     let user = match auth_session.user {
         Some(user) => user,
         None => return Err(SameyError::Forbidden),
     };
 
+    match create_post_from_multipart(&state, &user, multipart).await? {
+        Ok(uploaded_post) => Ok(Redirect::to(&format!("/post/{}", uploaded_post)).into_response()),
+        Err(InvalidUpload { tags, errors }) => Ok((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Html(
+                UploadPageTemplate {
+                    base,
+                    supports_video: state.thumbnailer.supports_video(),
+                    tags,
+                    errors,
+                }
+                .render()?,
+            ),
+        )
+            .into_response()),
+    }
+}
+
+/// Upload that was rejected because of what was submitted, along with the tags to fill the form with again.
+pub(crate) struct InvalidUpload {
+    tags: String,
+    pub(crate) errors: FieldErrors,
+}
+
+/// Creates a post from a multipart form with `tags` and `media-file` fields, returning its ID.
+///
+/// The post is saved as a draft if the form has a `draft` field.
+///
+/// Problems with the submitted fields are returned as an [`InvalidUpload`], so that the form can show them.
+pub(crate) async fn create_post_from_multipart(
+    state: &AppState,
+    user: &User,
+    mut multipart: Multipart,
+) -> Result<Result<i32, InvalidUpload>, SameyError> {
+    let mut errors = FieldErrors::default();
+    let mut tags_text = String::new();
     let mut upload_tags: Option<Vec<samey_tag::Model>> = None;
-    let mut source_file: Option<String> = None;
-    let mut media_type: Option<&'static str> = None;
-    let mut width: Option<NonZero<i32>> = None;
-    let mut height: Option<NonZero<i32>> = None;
-    let mut thumbnail_file: Option<String> = None;
-    let mut thumbnail_width: Option<NonZero<i32>> = None;
-    let mut thumbnail_height: Option<NonZero<i32>> = None;
-    let base_path = files_dir.as_ref();
+    let mut uploaded_media: Option<UploadedMedia> = None;
+    let mut is_draft = false;
 
     // Read multipart form data
-    while let Some(mut field) = multipart.next_field().await.unwrap() {
-        match field.name().unwrap() {
-            "tags" => {
-                if let Ok(tags) = field.text().await {
-                    let tags: HashSet<String> = tags
-                        .split_whitespace()
-                        .filter_map(|tag| {
-                            if tag.starts_with(NEGATIVE_PREFIX) || tag.starts_with(RATING_PREFIX) {
-                                None
-                            } else {
-                                Some(String::from(tag))
-                            }
-                        })
-                        .collect();
-                    let normalized_tags: HashSet<String> =
-                        tags.iter().map(|tag| tag.to_lowercase()).collect();
-                    if tags.is_empty() {
-                        upload_tags = Some(vec![]);
-                    } else {
-                        SameyTag::insert_many(tags.into_iter().map(|tag| samey_tag::ActiveModel {
-                            normalized_name: Set(tag.to_lowercase()),
-                            name: Set(tag),
-                            ..Default::default()
-                        }))
-                        .on_conflict(
-                            OnConflict::column(samey_tag::Column::NormalizedName)
-                                .do_nothing()
-                                .to_owned(),
-                        )
-                        .exec_without_returning(&db)
-                        .await?;
-                        upload_tags = Some(
-                            SameyTag::find()
-                                .filter(samey_tag::Column::NormalizedName.is_in(normalized_tags))
-                                .all(&db)
-                                .await?,
-                        );
+    let result = async {
+        while let Some(mut field) = multipart.next_field().await? {
+            match field.name() {
+                Some("tags") => {
+                    if let Ok(tags) = field.text().await {
+                        upload_tags =
+                            errors.check("tags", save_upload_tags(&state.db, user, &tags).await)?;
+                        tags_text = tags;
                     }
                 }
-            }
-
-            "media-file" => {
-                let content_type = field
-                    .content_type()
-                    .ok_or(SameyError::BadRequest("Missing content type".into()))?;
-                match Format::from_str(content_type)? {
-                    format @ Format::Video(video_format) => {
-                        media_type = Some(format.media_type());
-                        let thumbnail_format = ImageFormat::Png;
-                        let (file_name, thumbnail_file_name) = {
-                            let mut rng = rand::rng();
-                            let mut file_name: String = (0..8)
-                                .map(|_| rng.sample(rand::distr::Alphanumeric) as char)
-                                .collect();
-                            let thumbnail_file_name = format!(
-                                "thumb-{}.{}",
-                                file_name,
-                                thumbnail_format.extensions_str()[0]
-                            );
-                            file_name.push_str(video_format);
-                            (file_name, thumbnail_file_name)
-                        };
-                        let file_path = base_path.join(&file_name);
-                        let mut file = OpenOptions::new()
-                            .read(true)
-                            .write(true)
-                            .create(true)
-                            .truncate(true)
-                            .open(&file_path)?;
-                        while let Some(chunk) = field.chunk().await? {
-                            file.write_all(&chunk)?;
-                        }
-                        let file_path_2 = file_path.to_string_lossy().into_owned();
-                        let thumbnail_path = base_path.join(&thumbnail_file_name);
-                        let jh_thumbnail = spawn_blocking(move || {
-                            generate_thumbnail(
-                                &file_path_2,
-                                &thumbnail_path.to_string_lossy(),
-                                MAX_THUMBNAIL_DIMENSION,
-                            )?;
-                            let mut image = ImageReader::new(BufReader::new(
-                                OpenOptions::new().read(true).open(thumbnail_path)?,
-                            ));
-                            image.set_format(thumbnail_format);
-                            Ok(image.into_dimensions()?)
-                        });
-                        let file_path_2 = file_path.to_string_lossy().into_owned();
-                        let jh_video =
-                            spawn_blocking(move || get_dimensions_for_video(&file_path_2));
-                        let (dim_thumbnail, dim_video) = match try_join!(jh_thumbnail, jh_video)? {
-                            (Ok(dim_thumbnail), Ok(dim_video)) => (dim_thumbnail, dim_video),
-                            (Err(err), _) | (_, Err(err)) => return Err(err),
-                        };
-                        width = NonZero::new(dim_video.0.try_into()?);
-                        height = NonZero::new(dim_video.1.try_into()?);
-                        thumbnail_width = NonZero::new(dim_thumbnail.0.try_into()?);
-                        thumbnail_height = NonZero::new(dim_thumbnail.1.try_into()?);
-                        source_file = Some(file_name);
-                        thumbnail_file = Some(thumbnail_file_name);
-                    }
-
-                    format @ Format::Image(image_format) => {
-                        media_type = Some(format.media_type());
-                        let file_name = {
-                            let mut rng = rand::rng();
-                            let mut file_name: String = (0..8)
-                                .map(|_| rng.sample(rand::distr::Alphanumeric) as char)
-                                .collect();
-                            file_name.push('.');
-                            file_name.push_str(image_format.extensions_str()[0]);
-                            file_name
-                        };
-                        let thumbnail_file_name = format!("thumb-{}", file_name);
-                        let file_path = base_path.join(&file_name);
-                        let mut file = OpenOptions::new()
-                            .read(true)
-                            .write(true)
-                            .create(true)
-                            .truncate(true)
-                            .open(&file_path)?;
-                        while let Some(chunk) = field.chunk().await? {
-                            file.write_all(&chunk)?;
-                        }
-                        let base_path_2 = base_path.to_owned();
-                        let thumbnail_path = base_path_2.join(&thumbnail_file_name);
-                        let (w, h, tw, th) = spawn_blocking(move || -> Result<_, SameyError> {
-                            file.seek(std::io::SeekFrom::Start(0))?;
-                            let mut image = ImageReader::new(BufReader::new(file));
-                            image.set_format(image_format);
-                            let image = image.decode()?;
-                            let (w, h) = image.dimensions();
-                            let width = NonZero::new(w.try_into()?);
-                            let height = NonZero::new(h.try_into()?);
-                            let thumbnail = image.resize(
-                                MAX_THUMBNAIL_DIMENSION,
-                                MAX_THUMBNAIL_DIMENSION,
-                                image::imageops::FilterType::CatmullRom,
-                            );
-                            thumbnail.save(thumbnail_path)?;
-                            let (tw, th) = image.dimensions();
-                            let thumbnail_width = NonZero::new(tw.try_into()?);
-                            let thumbnail_height = NonZero::new(th.try_into()?);
-                            Ok((width, height, thumbnail_width, thumbnail_height))
-                        })
-                        .await??;
-                        width = w;
-                        height = h;
-                        thumbnail_width = tw;
-                        thumbnail_height = th;
-                        source_file = Some(file_name);
-                        thumbnail_file = Some(thumbnail_file_name);
+                Some("draft") => is_draft = true,
+                // Browsers send an empty part when no file was chosen
+                Some("media-file") if field.file_name().is_some_and(str::is_empty) => (),
+                Some("media-file") => {
+                    let Some(content_type) = field.content_type().map(String::from) else {
+                        errors.add("media-file", "Missing content type");
+                        continue;
+                    };
+                    let original_filename = field.file_name().map(String::from);
+                    if let Some(media) = uploaded_media.take() {
+                        media.discard(&state.files_dir).await;
                     }
+                    uploaded_media = errors.check(
+                        "media-file",
+                        store_upload_media(
+                            state,
+                            user,
+                            &content_type,
+                            original_filename,
+                            async |file_path| write_field_to_file(&mut field, file_path).await,
+                        )
+                        .await,
+                    )?;
                 }
+                _ => (),
+            }
+        }
+        Ok::<_, SameyError>(())
+    }
+    .await;
+
+    match (result, upload_tags, uploaded_media) {
+        (Ok(()), Some(upload_tags), Some(media)) if errors.is_empty() => {
+            create_uploaded_post(state, user, upload_tags, media, is_draft)
+                .await
+                .map(Ok)
+        }
+        (result, upload_tags, media) => {
+            let is_missing_media = media.is_none();
+            if let Some(media) = media {
+                media.discard(&state.files_dir).await;
             }
-            _ => (),
-        }
-    }
-
-    if let (
-        Some(upload_tags),
-        Some(source_file),
-        Some(media_type),
-        Some(thumbnail_file),
-        Some(width),
-        Some(height),
-        Some(thumbnail_width),
-        Some(thumbnail_height),
-    ) = (
-        upload_tags,
-        source_file,
-        media_type,
-        thumbnail_file,
-        width.map(|w| w.get()),
-        height.map(|h| h.get()),
-        thumbnail_width.map(|w| w.get()),
-        thumbnail_height.map(|h| h.get()),
-    ) {
-        let uploaded_post = SameyPost::insert(samey_post::ActiveModel {
-            uploader_id: Set(user.id),
-            media: Set(source_file),
-            media_type: Set(media_type.into()),
-            width: Set(width),
-            height: Set(height),
-            thumbnail: Set(thumbnail_file),
-            thumbnail_width: Set(thumbnail_width),
-            thumbnail_height: Set(thumbnail_height),
-            title: Set(None),
-            description: Set(None),
-            rating: Set("u".to_owned()),
-            uploaded_at: Set(Utc::now().naive_utc()),
-            parent_id: Set(None),
+            result?;
+            if upload_tags.is_none() {
+                errors.add("tags", "Missing tags");
+            }
+            if is_missing_media {
+                errors.add("media-file", "Choose a file to upload");
+            }
+            Ok(Err(InvalidUpload {
+                tags: tags_text,
+                errors,
+            }))
+        }
+    }
+}
+
+// Draft views
+
+#[derive(Template)]
+#[template(path = "pages/drafts.html")]
+struct DraftsTemplate {
+    base: BaseContext,
+    posts: Vec<CurationPost>,
+}
+
+pub(crate) async fn drafts(
+    State(AppState { db, .. }): State<AppState>,
+    base: BaseContext,
+    auth_session: AuthSession,
+) -> Result<impl IntoResponse, SameyError> {
+    let user = auth_session.user.ok_or(SameyError::Forbidden)?;
+
+    let posts = get_drafts_for_user(user.id).all(&db).await?;
+
+    Ok(Html(DraftsTemplate { base, posts }.render()?))
+}
+
+/// Publishes a draft, once it has tags and a rating.
+pub(crate) async fn publish_draft(
+    State(AppState { db, app_config, .. }): State<AppState>,
+    RequireOwnerOrAdmin(_, post): RequireOwnerOrAdmin<SameyPost>,
+) -> Result<impl IntoResponse, SameyError> {
+    if !post.is_draft {
+        return Err(SameyError::BadRequest("Post is not a draft".into()));
+    }
+    let tag_count = SameyTagPost::find()
+        .filter(samey_tag_post::Column::PostId.eq(post.id))
+        .count(&db)
+        .await?;
+    if tag_count == 0 {
+        return Err(SameyError::BadRequest(
+            "Add tags to the draft before publishing it".into(),
+        ));
+    }
+    if post.rating == UNRATED {
+        return Err(SameyError::BadRequest(
+            "Choose a rating for the draft before publishing it".into(),
+        ));
+    }
+
+    SameyPost::update_many()
+        .set(samey_post::ActiveModel {
+            is_draft: Set(false),
+            is_public: Set(true),
+            updated_at: Set(Some(Utc::now().naive_utc())),
             ..Default::default()
         })
+        .col_expr(
+            samey_post::Column::Version,
+            Expr::col(samey_post::Column::Version).add(1),
+        )
+        .filter(samey_post::Column::Id.eq(post.id))
         .exec(&db)
+        .await?;
+    let post = SameyPost::find_by_id(post.id)
+        .one(&db)
         .await?
-        .last_insert_id;
-
-        // Add tags to post
-        if !upload_tags.is_empty() {
-            SameyTagPost::insert_many(upload_tags.into_iter().map(|tag| {
-                samey_tag_post::ActiveModel {
-                    post_id: Set(uploaded_post),
-                    tag_id: Set(tag.id),
-                    ..Default::default()
-                }
-            }))
-            .exec(&db)
-            .await?;
+        .ok_or(SameyError::NotFound)?;
+    // Scheduled drafts are federated and cross-posted once they are published instead
+    if is_post_published(&post) {
+        if let Some(federation) = Federation::from_config(&*app_config.read().await) {
+            federate_post(db.clone(), federation, post.id);
         }
-
-        Ok(Redirect::to(&format!("/post/{}", uploaded_post)))
-    } else {
-        Err(SameyError::BadRequest(
-            "Missing parameters for upload".into(),
-        ))
+        queue_crossposts(&db, post.id).await?;
     }
+
+    Ok(Redirect::to(&format!("/post/{}", post.id)))
 }
 
 // Search fields views
@@ -523,9 +1311,15 @@ pub(crate) struct SearchTagsForm {
 }
 
 pub(crate) async fn search_tags(
-    State(AppState { db, .. }): State<AppState>,
+    State(AppState { db, app_config, .. }): State<AppState>,
     Form(body): Form<SearchTagsForm>,
 ) -> Result<impl IntoResponse, SameyError> {
+    let rating_tags: Vec<String> = app_config
+        .read()
+        .await
+        .rating_codes()
+        .map(|rating| format!("{}{}", RATING_PREFIX, rating))
+        .collect();
     let tags = match body.tags[..body.selection_end].split(' ').next_back() {
         Some(mut tag) => {
             tag = tag.trim();
@@ -533,8 +1327,8 @@ pub(crate) async fn search_tags(
                 vec![]
             } else if let Some(stripped_tag) = tag.strip_prefix(NEGATIVE_PREFIX) {
                 if stripped_tag.starts_with(RATING_PREFIX) {
-                    Rating::iter()
-                        .map(|rating| format!("{}{}", RATING_PREFIX, rating))
+                    rating_tags
+                        .into_iter()
                         .filter(|t| t.starts_with(stripped_tag))
                         .map(|tag| SearchTag {
                             value: format!("-{}", &tag),
@@ -543,7 +1337,7 @@ pub(crate) async fn search_tags(
                         .collect()
                 } else if stripped_tag.starts_with(MEDIA_TYPE_PREFIX) {
                     MediaType::iter()
-                        .map(|rating| format!("{}{}", MEDIA_TYPE_PREFIX, rating))
+                        .map(|media_type| format!("{}{}", MEDIA_TYPE_PREFIX, media_type))
                         .filter(|t| t.starts_with(stripped_tag))
                         .map(|tag| SearchTag {
                             value: format!("-{}", &tag),
@@ -551,20 +1345,7 @@ pub(crate) async fn search_tags(
                         })
                         .collect()
                 } else {
-                    SameyTag::find()
-                        .filter(
-                            Condition::any()
-                                .add(
-                                    samey_tag::Column::NormalizedName
-                                        .starts_with(stripped_tag.to_lowercase()),
-                                )
-                                .add(
-                                    samey_tag::Column::NormalizedName
-                                        .contains(format!(":{}", stripped_tag.to_lowercase())),
-                                ),
-                        )
-                        .limit(10)
-                        .all(&db)
+                    autocomplete_tags(&db, stripped_tag)
                         .await?
                         .into_iter()
                         .map(|tag| SearchTag {
@@ -574,8 +1355,8 @@ pub(crate) async fn search_tags(
                         .collect()
                 }
             } else if tag.starts_with(RATING_PREFIX) {
-                Rating::iter()
-                    .map(|rating| format!("{}{}", RATING_PREFIX, rating))
+                rating_tags
+                    .into_iter()
                     .filter(|t| t.starts_with(tag))
                     .map(|tag| SearchTag {
                         value: tag.clone(),
@@ -584,7 +1365,16 @@ pub(crate) async fn search_tags(
                     .collect()
             } else if tag.starts_with(MEDIA_TYPE_PREFIX) {
                 MediaType::iter()
-                    .map(|rating| format!("{}{}", MEDIA_TYPE_PREFIX, rating))
+                    .map(|media_type| format!("{}{}", MEDIA_TYPE_PREFIX, media_type))
+                    .filter(|t| t.starts_with(tag))
+                    .map(|tag| SearchTag {
+                        value: tag.clone(),
+                        name: tag,
+                    })
+                    .collect()
+            } else if tag.starts_with(ORDER_PREFIX) {
+                PostsOrder::iter()
+                    .map(|order| format!("{}{}", ORDER_PREFIX, order))
                     .filter(|t| t.starts_with(tag))
                     .map(|tag| SearchTag {
                         value: tag.clone(),
@@ -592,17 +1382,7 @@ pub(crate) async fn search_tags(
                     })
                     .collect()
             } else {
-                SameyTag::find()
-                    .filter(
-                        Condition::any()
-                            .add(samey_tag::Column::NormalizedName.starts_with(tag.to_lowercase()))
-                            .add(
-                                samey_tag::Column::NormalizedName
-                                    .contains(format!(":{}", tag.to_lowercase())),
-                            ),
-                    )
-                    .limit(10)
-                    .all(&db)
+                autocomplete_tags(&db, tag)
                     .await?
                     .into_iter()
                     .map(|tag| SearchTag {
@@ -667,66 +1447,284 @@ pub(crate) async fn select_tag(
 #[derive(Template)]
 #[template(path = "pages/posts.html")]
 struct PostsTemplate<'a> {
-    application_name: String,
-    age_confirmation: bool,
+    base: BaseContext,
     tags: Option<Vec<&'a str>>,
     tags_text: Option<String>,
+    search_errors: Vec<SearchError>,
     posts: Vec<PostOverview>,
     page: u32,
-    page_count: u64,
+    page_count: Option<u64>,
+    previous_id: Option<i32>,
+    next_id: Option<i32>,
+    next_page: Option<u32>,
+    can_tag: bool,
 }
 
 #[derive(Debug, Deserialize)]
 pub(crate) struct PostsQuery {
     tags: Option<String>,
+    before_id: Option<i32>,
+    after_id: Option<i32>,
+}
+
+/// Above this many pages, the post list switches from numbered pages to previous/next links.
+const MAX_NUMBERED_POSTS_PAGES: u64 = 10;
+
+/// Checks if the client asked for JSON rather than an HTML page.
+fn accepts_json(headers: &HeaderMap) -> bool {
+    let Some(accept) = headers.get(ACCEPT).and_then(|accept| accept.to_str().ok()) else {
+        return false;
+    };
+    let media_types = accept
+        .split(',')
+        .map(|media_type| media_type.split(';').next().unwrap_or_default().trim())
+        .collect::<Vec<_>>();
+    media_types.contains(&"application/json") && !media_types.contains(&"text/html")
 }
 
 pub(crate) async fn posts(
     state: State<AppState>,
-    auth_session: AuthSession,
+    base: BaseContext,
+    headers: HeaderMap,
     query: Query<PostsQuery>,
+) -> Result<Response, SameyError> {
+    posts_page(state, base, headers, query, Path(1)).await
+}
+
+pub(crate) async fn posts_json(
+    State(AppState { db, .. }): State<AppState>,
+    base: BaseContext,
+    Query(query): Query<api::PostsQuery>,
 ) -> Result<impl IntoResponse, SameyError> {
-    posts_page(state, auth_session, query, Path(1)).await
+    Ok(Json(
+        get_posts_response(
+            &db,
+            base.user.as_ref(),
+            query,
+            base.preferences.posts_per_page,
+        )
+        .await?,
+    ))
 }
 
 pub(crate) async fn posts_page(
-    State(AppState { db, app_config, .. }): State<AppState>,
+    State(AppState { db, .. }): State<AppState>,
+    base: BaseContext,
+    headers: HeaderMap,
+    Query(query): Query<PostsQuery>,
+    Path(page): Path<u32>,
+) -> Result<Response, SameyError> {
+    if accepts_json(&headers) {
+        let query = api::PostsQuery {
+            tags: query.tags,
+            page: Some(page),
+            before_id: query.before_id,
+            after_id: query.after_id,
+        };
+        return Ok((
+            [(VARY, "Accept")],
+            Json(
+                get_posts_response(
+                    &db,
+                    base.user.as_ref(),
+                    query,
+                    base.preferences.posts_per_page,
+                )
+                .await?,
+            ),
+        )
+            .into_response());
+    }
+
+    let tags = query
+        .tags
+        .as_ref()
+        .map(|tags| tags.split_whitespace().collect::<Vec<_>>());
+    let search = SearchQuery::parse(query.tags.as_deref().unwrap_or_default());
+    let user = base.user.as_ref();
+    let posts_per_page = base.preferences.posts_per_page;
+    let cursor = match (query.before_id, query.after_id) {
+        (Some(before_id), _) => PostsCursor::Before(before_id),
+        (None, Some(after_id)) => PostsCursor::After(after_id),
+        (None, None) => PostsCursor::Page(page.saturating_sub(1) as u64),
+    };
+    let page_count = match cursor {
+        PostsCursor::Page(_) => {
+            let posts_count = search_posts_query(&search, user)
+                .select_only()
+                .column(samey_post::Column::Id)
+                .limit(posts_per_page * MAX_NUMBERED_POSTS_PAGES + 1)
+                .into_tuple::<i32>()
+                .all(&db)
+                .await?
+                .len() as u64;
+            (posts_count <= posts_per_page * MAX_NUMBERED_POSTS_PAGES)
+                .then(|| posts_count.div_ceil(posts_per_page))
+        }
+        _ => None,
+    };
+    let PostsKeysetPage {
+        posts,
+        previous_id,
+        next_id,
+    } = search_posts_keyset(&db, &search, user, cursor, posts_per_page).await?;
+    let next_page = match cursor {
+        PostsCursor::Page(_) => next_id.map(|_| page.max(1) + 1),
+        _ => None,
+    };
+    let posts = sort_post_overview_tags(posts);
+
+    Ok((
+        [(VARY, "Accept")],
+        Html(
+            PostsTemplate {
+                tags_text: tags.as_ref().map(|tags| tags.iter().join(" ")),
+                tags,
+                search_errors: search.errors,
+                posts,
+                page,
+                page_count,
+                previous_id,
+                next_id,
+                next_page,
+                can_tag: base.user.is_some(),
+                base,
+            }
+            .render()?,
+        ),
+    )
+        .into_response())
+}
+
+#[derive(Template)]
+#[template(path = "fragments/posts_list.html")]
+struct PostsFragmentTemplate {
+    tags_text: Option<String>,
+    posts: Vec<PostOverview>,
+    next_page: Option<u32>,
+    can_tag: bool,
+    thumbnail_density: ThumbnailDensity,
+}
+
+pub(crate) async fn posts_fragment(
+    State(AppState { db, .. }): State<AppState>,
     auth_session: AuthSession,
+    preferences: Preferences,
     Query(query): Query<PostsQuery>,
     Path(page): Path<u32>,
 ) -> Result<impl IntoResponse, SameyError> {
-    let app_config = app_config.read().await;
-    let application_name = app_config.application_name.clone();
-    let age_confirmation = app_config.age_confirmation;
-    drop(app_config);
+    let page = page.max(1);
     let tags = query
         .tags
         .as_ref()
         .map(|tags| tags.split_whitespace().collect::<Vec<_>>());
-    let pagination = search_posts(tags.as_ref(), auth_session.user.as_ref()).paginate(&db, 50);
-    let page_count = pagination.num_pages().await?;
-    let posts = pagination.fetch_page(page.saturating_sub(1) as u64).await?;
-    let posts = posts
+    let PostsKeysetPage { posts, next_id, .. } = search_posts_keyset(
+        &db,
+        &SearchQuery::parse(query.tags.as_deref().unwrap_or_default()),
+        auth_session.user.as_ref(),
+        PostsCursor::Page(page as u64 - 1),
+        preferences.posts_per_page,
+    )
+    .await?;
+
+    Ok(Html(
+        PostsFragmentTemplate {
+            tags_text: tags.as_ref().map(|tags| tags.iter().join(" ")),
+            posts: sort_post_overview_tags(posts),
+            next_page: next_id.map(|_| page + 1),
+            can_tag: auth_session.user.is_some(),
+            thumbnail_density: preferences.thumbnail_density,
+        }
+        .render()?,
+    ))
+}
+
+#[derive(Template)]
+#[template(path = "fragments/post_card.html")]
+struct PostCardTemplate {
+    tags_text: Option<String>,
+    post: PostOverview,
+    can_tag: bool,
+    thumbnail_density: ThumbnailDensity,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum PostCardTagAction {
+    Add,
+    Remove,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct PostCardTagForm {
+    tag: String,
+    action: PostCardTagAction,
+    tags_text: Option<String>,
+}
+
+pub(crate) async fn post_card_tag(
+    State(AppState { db, .. }): State<AppState>,
+    preferences: Preferences,
+    RequireOwnerOrAdmin(user, post): RequireOwnerOrAdmin<SameyPost>,
+    Form(body): Form<PostCardTagForm>,
+) -> Result<impl IntoResponse, SameyError> {
+    let tag = body
+        .tag
+        .split_whitespace()
+        .exactly_one()
+        .map_err(|_| SameyError::BadRequest("Enter a single tag".into()))?;
+    if tag.starts_with(NEGATIVE_PREFIX)
+        || tag.starts_with(RATING_PREFIX)
+        || tag.starts_with(MEDIA_TYPE_PREFIX)
+    {
+        return Err(SameyError::BadRequest(format!("Invalid tag {}", tag)));
+    }
+    let normalized_tag = tag.to_lowercase();
+
+    if !user.is_admin
+        && get_protected_tags()
+            .filter(samey_tag::Column::NormalizedName.eq(&normalized_tag))
+            .one(&db)
+            .await?
+            .is_some()
+    {
+        return Err(SameyError::BadRequest(format!("Tag {} is protected", tag)));
+    }
+
+    let txn = db.begin().await?;
+    let mut tags: Vec<String> = get_tags_for_post(post.id)
+        .all(&txn)
+        .await?
         .into_iter()
-        .map(|post| {
-            let tags: Option<String> = post.tags.map(|tags| {
-                let mut tags_vec = tags.split_ascii_whitespace().collect::<Vec<&str>>();
-                tags_vec.sort();
-                tags_vec.into_iter().join(" ")
-            });
-            PostOverview { tags, ..post }
-        })
+        .filter(|post_tag| post_tag.normalized_name != normalized_tag)
+        .map(|post_tag| post_tag.name)
         .collect();
+    if let PostCardTagAction::Add = body.action {
+        tags.push(tag.into());
+    }
+    replace_post_tags(&txn, post.id, tags).await?;
+    bump_post_version(&txn, post.id).await?;
+    txn.commit().await?;
+
+    let post = search_posts_query(&SearchQuery::default(), Some(&user))
+        .filter(samey_post::Column::Id.eq(post.id))
+        .into_model::<PostOverview>()
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+
+    tokio::spawn(async move {
+        if let Err(err) = clean_dangling_tags(&db).await {
+            println!("Error when cleaning dangling tags - {}", err);
+        }
+    });
 
     Ok(Html(
-        PostsTemplate {
-            application_name,
-            age_confirmation,
-            tags_text: tags.as_ref().map(|tags| tags.iter().join(" ")),
-            tags,
-            posts,
-            page,
-            page_count,
+        PostCardTemplate {
+            tags_text: body.tags_text.filter(|tags_text| !tags_text.is_empty()),
+            post: sort_post_overview_tags(vec![post]).remove(0),
+            can_tag: true,
+            thumbnail_density: preferences.thumbnail_density,
         }
         .render()?,
     ))
@@ -737,78 +1735,49 @@ pub(crate) async fn posts_page(
 #[derive(Template)]
 #[template(path = "pages/create_pool.html")]
 struct CreatePoolPageTemplate {
-    application_name: String,
-    age_confirmation: bool,
+    base: BaseContext,
 }
 
 pub(crate) async fn create_pool_page(
-    State(AppState { app_config, .. }): State<AppState>,
+    base: BaseContext,
     auth_session: AuthSession,
 ) -> Result<impl IntoResponse, SameyError> {
     if auth_session.user.is_none() {
         return Err(SameyError::Forbidden);
     }
 
-    let app_config = app_config.read().await;
-    let application_name = app_config.application_name.clone();
-    let age_confirmation = app_config.age_confirmation;
-    drop(app_config);
-
-    Ok(Html(
-        CreatePoolPageTemplate {
-            application_name,
-            age_confirmation,
-        }
-        .render()?,
-    )
-    .into_response())
+    Ok(Html(CreatePoolPageTemplate { base }.render()?).into_response())
 }
 
 pub(crate) async fn get_pools(
     state: State<AppState>,
-    auth_session: AuthSession,
+    base: BaseContext,
 ) -> Result<impl IntoResponse, SameyError> {
-    get_pools_page(state, auth_session, Path(1)).await
+    get_pools_page(state, base, Path(1)).await
 }
 
 #[derive(Template)]
 #[template(path = "pages/pools.html")]
 struct GetPoolsTemplate {
-    application_name: String,
-    age_confirmation: bool,
+    base: BaseContext,
     pools: Vec<samey_pool::Model>,
     page: u32,
     page_count: u64,
 }
 
 pub(crate) async fn get_pools_page(
-    State(AppState { db, app_config, .. }): State<AppState>,
-    auth_session: AuthSession,
+    State(AppState { db, .. }): State<AppState>,
+    base: BaseContext,
     Path(page): Path<u32>,
 ) -> Result<impl IntoResponse, SameyError> {
-    let app_config = app_config.read().await;
-    let application_name = app_config.application_name.clone();
-    let age_confirmation = app_config.age_confirmation;
-    drop(app_config);
-    let query = match auth_session.user {
-        None => SameyPool::find().filter(samey_pool::Column::IsPublic.into_simple_expr()),
-        Some(user) if user.is_admin => SameyPool::find(),
-        Some(user) => SameyPool::find().filter(
-            Condition::any()
-                .add(samey_pool::Column::IsPublic.into_simple_expr())
-                .add(samey_pool::Column::UploaderId.eq(user.id)),
-        ),
-    };
-
-    let pagination = query.paginate(&db, 25);
+    let pagination = filter_pools_by_user(SameyPool::find(), base.user.as_ref()).paginate(&db, 25);
     let page_count = pagination.num_pages().await?;
 
     let pools = pagination.fetch_page(page.saturating_sub(1) as u64).await?;
 
     Ok(Html(
         GetPoolsTemplate {
-            application_name,
-            age_confirmation,
+            base,
             pools,
             page,
             page_count,
@@ -832,14 +1801,14 @@ pub(crate) async fn create_pool(
         None => return Err(SameyError::Forbidden),
     };
 
-    let pool_id = SameyPool::insert(samey_pool::ActiveModel {
+    let pool_id = samey_pool::ActiveModel {
         name: Set(body.pool),
         uploader_id: Set(user.id),
         ..Default::default()
-    })
-    .exec(&db)
+    }
+    .insert(&db)
     .await?
-    .last_insert_id;
+    .id;
 
     Ok(Redirect::to(&format!("/pool/{}", pool_id)))
 }
@@ -847,8 +1816,7 @@ pub(crate) async fn create_pool(
 #[derive(Template)]
 #[template(path = "pages/pool.html")]
 struct ViewPoolTemplate {
-    application_name: String,
-    age_confirmation: bool,
+    base: BaseContext,
     pool: samey_pool::Model,
     posts: Vec<PoolPost>,
     can_edit: bool,
@@ -856,24 +1824,21 @@ struct ViewPoolTemplate {
 }
 
 pub(crate) async fn view_pool(
-    State(AppState { db, app_config, .. }): State<AppState>,
+    State(AppState { db, .. }): State<AppState>,
+    base: BaseContext,
     auth_session: AuthSession,
     Path(pool_id): Path<i32>,
     Host(host): Host,
 ) -> Result<impl IntoResponse, SameyError> {
-    let app_config = app_config.read().await;
-    let application_name = app_config.application_name.clone();
-    let age_confirmation = app_config.age_confirmation;
-    drop(app_config);
     let pool = SameyPool::find_by_id(pool_id)
         .one(&db)
         .await?
         .ok_or(SameyError::NotFound)?;
 
-    let can_edit = match auth_session.user.as_ref() {
-        None => false,
-        Some(user) => user.is_admin || pool.uploader_id == user.id,
-    };
+    let can_edit = auth_session
+        .user
+        .as_ref()
+        .is_some_and(|user| SameyPool::is_editable_by(&pool, user));
 
     if !pool.is_public && !can_edit {
         return Err(SameyError::NotFound);
@@ -885,8 +1850,7 @@ pub(crate) async fn view_pool(
 
     Ok(Html(
         ViewPoolTemplate {
-            application_name,
-            age_confirmation,
+            base,
             pool,
             can_edit,
             posts,
@@ -896,6 +1860,67 @@ pub(crate) async fn view_pool(
     ))
 }
 
+#[derive(Serialize)]
+struct SlideshowPost {
+    id: i32,
+    media: String,
+    media_type: String,
+    width: i32,
+    height: i32,
+    thumbnail: String,
+    /// HLS playlist for long videos, if one was generated.
+    stream_playlist: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SlideshowResponse {
+    id: i32,
+    name: String,
+    posts: Vec<SlideshowPost>,
+}
+
+/// Returns the media of a pool's posts in order, for playing them as a slideshow.
+pub(crate) async fn pool_slideshow(
+    State(AppState { db, .. }): State<AppState>,
+    auth_session: AuthSession,
+    Path(pool_id): Path<i32>,
+) -> Result<impl IntoResponse, SameyError> {
+    let user = auth_session.user.as_ref();
+    let pool = filter_pools_by_user(SameyPool::find_by_id(pool_id), user)
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+
+    let posts = filter_posts_by_user(
+        SameyPost::find()
+            .inner_join(SameyPoolPost)
+            .filter(samey_pool_post::Column::PoolId.eq(pool.id)),
+        user,
+    )
+    .order_by_asc(samey_pool_post::Column::Position)
+    .all(&db)
+    .await?;
+
+    Ok(Json(SlideshowResponse {
+        id: pool.id,
+        name: pool.name,
+        posts: posts
+            .into_iter()
+            .map(|post| SlideshowPost {
+                id: post.id,
+                media: format!("/files/{}", post.media),
+                media_type: post.media_type,
+                width: post.width,
+                height: post.height,
+                thumbnail: format!("/files/{}", post.thumbnail),
+                stream_playlist: post
+                    .stream_playlist
+                    .map(|playlist| format!("/files/{}", playlist)),
+            })
+            .collect(),
+    }))
+}
+
 #[derive(Debug, Deserialize)]
 pub(crate) struct ChangePoolNameForm {
     pool_name: String,
@@ -909,34 +1934,19 @@ struct ChangePoolNameTemplate {
 
 pub(crate) async fn change_pool_name(
     State(AppState { db, .. }): State<AppState>,
-    auth_session: AuthSession,
-    Path(pool_id): Path<i32>,
+    RequireOwnerOrAdmin(_, pool): RequireOwnerOrAdmin<SameyPool>,
     Form(body): Form<ChangePoolNameForm>,
 ) -> Result<impl IntoResponse, SameyError> {
-    let pool = SameyPool::find_by_id(pool_id)
-        .one(&db)
-        .await?
-        .ok_or(SameyError::NotFound)?;
-
-    let can_edit = match auth_session.user.as_ref() {
-        None => false,
-        Some(user) => user.is_admin || pool.uploader_id == user.id,
-    };
-
-    if !can_edit {
-        return Err(SameyError::Forbidden);
-    }
-
     if body.pool_name.trim().is_empty() {
         return Err(SameyError::BadRequest("Pool name cannot be empty".into()));
     }
 
-    SameyPool::update(samey_pool::ActiveModel {
+    samey_pool::ActiveModel {
         id: Set(pool.id),
         name: Set(body.pool_name.clone()),
         ..Default::default()
-    })
-    .exec(&db)
+    }
+    .update(&db)
     .await?;
 
     Ok(Html(
@@ -954,30 +1964,15 @@ pub(crate) struct ChangePoolVisibilityForm {
 
 pub(crate) async fn change_pool_visibility(
     State(AppState { db, .. }): State<AppState>,
-    auth_session: AuthSession,
-    Path(pool_id): Path<i32>,
+    RequireOwnerOrAdmin(_, pool): RequireOwnerOrAdmin<SameyPool>,
     Form(body): Form<ChangePoolVisibilityForm>,
 ) -> Result<impl IntoResponse, SameyError> {
-    let pool = SameyPool::find_by_id(pool_id)
-        .one(&db)
-        .await?
-        .ok_or(SameyError::NotFound)?;
-
-    let can_edit = match auth_session.user.as_ref() {
-        None => false,
-        Some(user) => user.is_admin || pool.uploader_id == user.id,
-    };
-
-    if !can_edit {
-        return Err(SameyError::Forbidden);
-    }
-
-    SameyPool::update(samey_pool::ActiveModel {
+    samey_pool::ActiveModel {
         id: Set(pool.id),
         is_public: Set(body.is_public.is_some()),
         ..Default::default()
-    })
-    .exec(&db)
+    }
+    .update(&db)
     .await?;
 
     Ok("")
@@ -1004,11 +1999,12 @@ struct AddPostToPoolTemplate {
 }
 
 pub(crate) async fn add_post_to_pool(
-    State(AppState { db, .. }): State<AppState>,
+    State(AppState { db, app_config, .. }): State<AppState>,
     auth_session: AuthSession,
     Path(pool_id): Path<i32>,
     Form(body): Form<AddPostToPoolForm>,
 ) -> Result<impl IntoResponse, SameyError> {
+    let txn = db.begin().await?;
     let pool = SameyPool::find_by_id(pool_id)
         .select_only()
         .column(samey_pool::Column::Id)
@@ -1017,7 +2013,7 @@ pub(crate) async fn add_post_to_pool(
         .left_join(SameyPoolPost)
         .group_by(samey_pool::Column::Id)
         .into_model::<PoolWithMaxPosition>()
-        .one(&db)
+        .one(&txn)
         .await?
         .ok_or(SameyError::NotFound)?;
 
@@ -1034,7 +2030,7 @@ pub(crate) async fn add_post_to_pool(
         SameyPost::find_by_id(body.post_id),
         auth_session.user.as_ref(),
     )
-    .one(&db)
+    .one(&txn)
     .await?
     .ok_or(SameyError::NotFound)?;
 
@@ -1044,8 +2040,12 @@ pub(crate) async fn add_post_to_pool(
         position: Set(pool.max_position.unwrap_or(0.0).floor() + 1.0),
         ..Default::default()
     })
-    .exec(&db)
+    .exec(&txn)
     .await?;
+    txn.commit().await?;
+    if let Some(user) = auth_session.user.as_ref() {
+        notify_pool_add(&db, &app_config, user.id, post.id, post.uploader_id).await?;
+    }
 
     let posts = get_posts_in_pool(pool.id, auth_session.user.as_ref())
         .all(&db)
@@ -1075,10 +2075,10 @@ pub(crate) async fn remove_pool_post(
         .await?
         .expect("Pool for samey_pool_post must exist");
 
-    let can_edit = match auth_session.user.as_ref() {
-        None => false,
-        Some(user) => user.is_admin || pool.uploader_id == user.id,
-    };
+    let can_edit = auth_session
+        .user
+        .as_ref()
+        .is_some_and(|user| SameyPool::is_editable_by(&pool, user));
 
     if !can_edit {
         return Err(SameyError::Forbidden);
@@ -1105,28 +2105,12 @@ struct PoolPostsTemplate {
 
 pub(crate) async fn sort_pool(
     State(AppState { db, .. }): State<AppState>,
-    auth_session: AuthSession,
-    Path(pool_id): Path<i32>,
+    RequireOwnerOrAdmin(user, pool): RequireOwnerOrAdmin<SameyPool>,
     Form(body): Form<SortPoolForm>,
 ) -> Result<impl IntoResponse, SameyError> {
-    let pool = SameyPool::find_by_id(pool_id)
-        .one(&db)
-        .await?
-        .ok_or(SameyError::NotFound)?;
-
-    let can_edit = match auth_session.user.as_ref() {
-        None => false,
-        Some(user) => user.is_admin || pool.uploader_id == user.id,
-    };
-
-    if !can_edit {
-        return Err(SameyError::Forbidden);
-    }
-
     if body.old_index != body.new_index {
-        let posts = get_posts_in_pool(pool_id, auth_session.user.as_ref())
-            .all(&db)
-            .await?;
+        let txn = db.begin().await?;
+        let posts = get_posts_in_pool(pool.id, Some(&user)).all(&txn).await?;
         let changed_post = posts.get(body.old_index).ok_or(SameyError::NotFound)?;
         let min_index = if body.new_index < body.old_index {
             body.new_index.checked_sub(1)
@@ -1149,13 +2133,12 @@ pub(crate) async fn sort_pool(
             position: Set((min + max) / 2.0),
             ..Default::default()
         })
-        .exec(&db)
+        .exec(&txn)
         .await?;
+        txn.commit().await?;
     }
 
-    let posts = get_posts_in_pool(pool_id, auth_session.user.as_ref())
-        .all(&db)
-        .await?;
+    let posts = get_posts_in_pool(pool.id, Some(&user)).all(&db).await?;
     Ok(Html(
         PoolPostsTemplate {
             pool,
@@ -1168,24 +2151,9 @@ pub(crate) async fn sort_pool(
 
 pub(crate) async fn delete_pool(
     State(AppState { db, .. }): State<AppState>,
-    auth_session: AuthSession,
-    Path(pool_id): Path<i32>,
+    RequireOwnerOrAdmin(_, pool): RequireOwnerOrAdmin<SameyPool>,
 ) -> Result<impl IntoResponse, SameyError> {
-    let pool = SameyPool::find_by_id(pool_id)
-        .one(&db)
-        .await?
-        .ok_or(SameyError::NotFound)?;
-
-    let can_edit = match auth_session.user.as_ref() {
-        None => false,
-        Some(user) => user.is_admin || pool.uploader_id == user.id,
-    };
-
-    if !can_edit {
-        return Err(SameyError::Forbidden);
-    }
-
-    SameyPool::delete_by_id(pool_id).exec(&db).await?;
+    SameyPool::delete_by_id(pool.id).exec(&db).await?;
 
     Ok(Redirect::to("/"))
 }
@@ -1201,29 +2169,25 @@ enum BulkEditTagMessage {
 #[derive(Template)]
 #[template(path = "pages/bulk_edit_tag.html")]
 struct BulkEditTagTemplate {
-    application_name: String,
-    age_confirmation: bool,
+    base: BaseContext,
     message: BulkEditTagMessage,
+    protected_tags: Vec<samey_tag::Model>,
+    protect_message: BulkEditTagMessage,
 }
 
 pub(crate) async fn bulk_edit_tag(
-    State(AppState { app_config, .. }): State<AppState>,
-    auth_session: AuthSession,
+    State(AppState { db, .. }): State<AppState>,
+    base: BaseContext,
+    _: RequireRole<Admin>,
 ) -> Result<impl IntoResponse, SameyError> {
-    if auth_session.user.is_none_or(|user| !user.is_admin) {
-        return Err(SameyError::Forbidden);
-    }
-
-    let app_config = app_config.read().await;
-    let application_name = app_config.application_name.clone();
-    let age_confirmation = app_config.age_confirmation;
-    drop(app_config);
+    let protected_tags = get_protected_tags().all(&db).await?;
 
     Ok(Html(
         BulkEditTagTemplate {
-            application_name,
-            age_confirmation,
+            base,
             message: BulkEditTagMessage::None,
+            protected_tags,
+            protect_message: BulkEditTagMessage::None,
         }
         .render()?,
     ))
@@ -1236,26 +2200,21 @@ pub(crate) struct EditTagForm {
 }
 
 pub(crate) async fn edit_tag(
-    State(AppState { db, app_config, .. }): State<AppState>,
-    auth_session: AuthSession,
+    State(AppState { db, .. }): State<AppState>,
+    base: BaseContext,
+    _: RequireRole<Admin>,
     Form(body): Form<EditTagForm>,
 ) -> Result<impl IntoResponse, SameyError> {
-    if auth_session.user.is_none_or(|user| !user.is_admin) {
-        return Err(SameyError::Forbidden);
-    }
-
-    let app_config = app_config.read().await;
-    let application_name = app_config.application_name.clone();
-    let age_confirmation = app_config.age_confirmation;
-    drop(app_config);
+    let protected_tags = get_protected_tags().all(&db).await?;
 
     let old_tag: Vec<_> = body.tags.split_whitespace().collect();
     if old_tag.len() != 1 {
         return Ok(Html(
             BulkEditTagTemplate {
-                application_name,
-                age_confirmation,
+                base,
                 message: BulkEditTagMessage::Failure("expected single tag to edit".into()),
+                protected_tags,
+                protect_message: BulkEditTagMessage::None,
             }
             .render()?,
         ));
@@ -1267,9 +2226,10 @@ pub(crate) async fn edit_tag(
     if new_tag.len() != 1 {
         return Ok(Html(
             BulkEditTagTemplate {
-                application_name,
-                age_confirmation,
+                base,
                 message: BulkEditTagMessage::Failure("expected single new tag".into()),
+                protected_tags,
+                protect_message: BulkEditTagMessage::None,
             }
             .render()?,
         ));
@@ -1277,15 +2237,16 @@ pub(crate) async fn edit_tag(
     let new_tag = new_tag.first().unwrap();
     let normalized_new_tag = new_tag.to_lowercase();
 
+    let txn = db.begin().await?;
     let old_tag_db = SameyTag::find()
         .filter(samey_tag::Column::NormalizedName.eq(&normalized_old_tag))
-        .one(&db)
+        .one(&txn)
         .await?
         .ok_or(SameyError::NotFound)?;
 
     if let Some(new_tag_db) = SameyTag::find()
         .filter(samey_tag::Column::NormalizedName.eq(&normalized_new_tag))
-        .one(&db)
+        .one(&txn)
         .await?
     {
         let subquery = MigrationQuery::select()
@@ -1300,168 +2261,2777 @@ pub(crate) async fn edit_tag(
                 tag_id: Set(new_tag_db.id),
                 ..Default::default()
             })
-            .exec(&db)
+            .exec(&txn)
             .await?;
-        SameyTag::delete_by_id(old_tag_db.id).exec(&db).await?;
+        SameyTag::delete_by_id(old_tag_db.id).exec(&txn).await?;
     } else {
-        SameyTag::update(samey_tag::ActiveModel {
+        samey_tag::ActiveModel {
             id: Set(old_tag_db.id),
             name: Set(new_tag.to_string()),
             normalized_name: Set(normalized_new_tag),
-        })
-        .exec(&db)
+            ..Default::default()
+        }
+        .update(&txn)
         .await?;
     }
+    txn.commit().await?;
 
     Ok(Html(
         BulkEditTagTemplate {
-            application_name,
-            age_confirmation,
+            base,
             message: BulkEditTagMessage::Success,
+            protected_tags,
+            protect_message: BulkEditTagMessage::None,
         }
         .render()?,
     ))
 }
 
-// Settings views
-
-#[derive(Template)]
-#[template(path = "pages/settings.html")]
-struct SettingsTemplate {
-    application_name: String,
-    base_url: String,
-    age_confirmation: bool,
+#[derive(Debug, Deserialize)]
+pub(crate) struct ProtectTagForm {
+    tag: String,
+    is_protected: Option<String>,
 }
 
-pub(crate) async fn settings(
-    State(AppState { db, app_config, .. }): State<AppState>,
-    auth_session: AuthSession,
+pub(crate) async fn protect_tag(
+    State(AppState { db, .. }): State<AppState>,
+    base: BaseContext,
+    _: RequireRole<Admin>,
+    Form(body): Form<ProtectTagForm>,
 ) -> Result<impl IntoResponse, SameyError> {
-    if auth_session.user.is_none_or(|user| !user.is_admin) {
-        return Err(SameyError::Forbidden);
-    }
-
-    let app_config = app_config.read().await;
-    let application_name = app_config.application_name.clone();
-    let base_url = app_config.base_url.clone();
-    let age_confirmation = app_config.age_confirmation;
-    drop(app_config);
-
-    let config = SameyConfig::find().all(&db).await?;
+    let tag = body
+        .tag
+        .split_whitespace()
+        .exactly_one()
+        .map(|tag| tag.to_lowercase());
+
+    let protect_message = match tag {
+        Ok(normalized_tag) => {
+            let result = SameyTag::update_many()
+                .filter(samey_tag::Column::NormalizedName.eq(normalized_tag))
+                .set(samey_tag::ActiveModel {
+                    is_protected: Set(body.is_protected.is_some()),
+                    updated_at: Set(Some(Utc::now().naive_utc())),
+                    ..Default::default()
+                })
+                .exec(&db)
+                .await?;
+            if result.rows_affected == 0 {
+                BulkEditTagMessage::Failure("tag not found".into())
+            } else {
+                BulkEditTagMessage::Success
+            }
+        }
+        Err(_) => BulkEditTagMessage::Failure("expected single tag".into()),
+    };
 
-    let values: HashMap<&str, Box<dyn Any>> = config
-        .iter()
-        .filter_map(|row| match row.key.as_str() {
-            key if key == APPLICATION_NAME_KEY => row
-                .data
-                .as_str()
-                .map::<(&str, Box<dyn Any>), _>(|data| (&row.key, Box::new(data.to_owned()))),
-            _ => None,
-        })
-        .collect();
+    let protected_tags = get_protected_tags().all(&db).await?;
 
     Ok(Html(
-        SettingsTemplate {
-            application_name,
-            base_url,
-            age_confirmation,
+        BulkEditTagTemplate {
+            base,
+            message: BulkEditTagMessage::None,
+            protected_tags,
+            protect_message,
         }
-        .render_with_values(&values)?,
+        .render()?,
     ))
 }
 
+// Moderation views
+
 #[derive(Debug, Deserialize)]
-pub(crate) struct UpdateSettingsForm {
-    application_name: String,
-    base_url: String,
-    favicon_post_id: String,
-    age_confirmation: Option<bool>,
+pub(crate) struct ReportPostForm {
+    reason: String,
 }
 
-pub(crate) async fn update_settings(
-    State(AppState {
-        db,
-        app_config,
-        files_dir,
-        ..
-    }): State<AppState>,
+#[derive(Template)]
+#[template(path = "fragments/report_post.html")]
+struct ReportPostTemplate;
+
+pub(crate) async fn report_post(
+    State(AppState { db, .. }): State<AppState>,
     auth_session: AuthSession,
-    Form(body): Form<UpdateSettingsForm>,
+    Path(post_id): Path<i32>,
+    Form(body): Form<ReportPostForm>,
 ) -> Result<impl IntoResponse, SameyError> {
-    if auth_session.user.is_none_or(|user| !user.is_admin) {
-        return Err(SameyError::Forbidden);
-    }
+    let user = match auth_session.user {
+        Some(user) => user,
+        None => return Err(SameyError::Forbidden),
+    };
 
-    let mut configs = vec![];
+    let post = filter_posts_by_user(SameyPost::find_by_id(post_id), Some(&user))
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
 
-    let application_name = body.application_name.trim();
-    if !application_name.is_empty() {
-        let _ = mem::replace(
-            &mut app_config.write().await.application_name,
-            application_name.into(),
-        );
-        configs.push(samey_config::ActiveModel {
-            key: Set(APPLICATION_NAME_KEY.into()),
-            data: Set(application_name.into()),
-            ..Default::default()
-        });
+    let reason = body.reason.trim();
+    if reason.is_empty() {
+        return Err(SameyError::BadRequest(
+            "Report reason cannot be empty".into(),
+        ));
     }
 
-    let base_url = body.base_url.trim_end_matches('/');
-    let _ = mem::replace(&mut app_config.write().await.base_url, base_url.into());
-    configs.push(samey_config::ActiveModel {
-        key: Set(BASE_URL_KEY.into()),
-        data: Set(base_url.into()),
+    SameyPostReport::insert(samey_post_report::ActiveModel {
+        post_id: Set(post.id),
+        reporter_id: Set(user.id),
+        reason: Set(reason.into()),
+        created_at: Set(Utc::now().naive_utc()),
         ..Default::default()
-    });
+    })
+    .exec(&db)
+    .await?;
 
-    let age_confirmation = body.age_confirmation.is_some();
-    let _ = mem::replace(
-        &mut app_config.write().await.age_confirmation,
-        age_confirmation,
-    );
-    configs.push(samey_config::ActiveModel {
-        key: Set(AGE_CONFIRMATION_KEY.into()),
-        data: Set(age_confirmation.into()),
-        ..Default::default()
-    });
+    Ok(Html(ReportPostTemplate.render()?))
+}
 
-    if !configs.is_empty() {
-        SameyConfig::insert_many(configs)
-            .on_conflict(
-                OnConflict::column(samey_config::Column::Key)
-                    .update_column(samey_config::Column::Data)
-                    .to_owned(),
-            )
-            .exec(&db)
-            .await?;
-    }
+#[derive(Template)]
+#[template(path = "pages/moderation.html")]
+struct ModerationTemplate {
+    base: BaseContext,
+    reports: Vec<PendingPostReport>,
+    takedown_requests: Vec<PendingTakedownRequest>,
+    digest_enabled: bool,
+}
 
-    if let Some(favicon_post_id) = body.favicon_post_id.split_whitespace().next() {
-        match favicon_post_id.parse::<i32>() {
-            Ok(favicon_post_id) => {
-                let post = SameyPost::find_by_id(favicon_post_id)
-                    .one(&db)
-                    .await?
-                    .ok_or(SameyError::NotFound)?;
-                ImageReader::open(files_dir.join(post.thumbnail))?
-                    .decode()?
-                    .save_with_format(files_dir.join("favicon.png"), ImageFormat::Png)?;
-            }
-            Err(err) => return Err(SameyError::IntParse(err)),
+pub(crate) async fn moderation(
+    State(AppState { db, app_config, .. }): State<AppState>,
+    base: BaseContext,
+    _: RequireRole<Admin>,
+) -> Result<impl IntoResponse, SameyError> {
+    let reports = get_pending_post_reports().all(&db).await?;
+    let takedown_requests = get_pending_takedown_requests().all(&db).await?;
+    let digest_enabled = {
+        let app_config = app_config.read().await;
+        !app_config.moderation_digest_emails.is_empty()
+            || !app_config.moderation_digest_webhook_url.is_empty()
+    };
+
+    Ok(Html(
+        ModerationTemplate {
+            base,
+            reports,
+            takedown_requests,
+            digest_enabled,
         }
-    }
+        .render()?,
+    ))
+}
 
-    Ok(Redirect::to("/"))
+#[derive(Debug, Deserialize)]
+pub(crate) struct ResolveReportsForm {
+    #[serde(rename = "report")]
+    reports: Option<Vec<i32>>,
+    hide_posts: Option<String>,
 }
 
-// Single post views
+pub(crate) async fn resolve_reports(
+    State(AppState { db, .. }): State<AppState>,
+    RequireRole(user, _): RequireRole<Admin>,
+    Form(body): Form<ResolveReportsForm>,
+) -> Result<impl IntoResponse, SameyError> {
+    let reports = body.reports.unwrap_or_default();
+    if reports.is_empty() {
+        return Ok(Redirect::to("/moderation"));
+    }
+
+    let txn = db.begin().await?;
+    if body.hide_posts.is_some() {
+        let subquery = MigrationQuery::select()
+            .column((SameyPostReport, samey_post_report::Column::PostId))
+            .from(SameyPostReport)
+            .and_where(samey_post_report::Column::Id.is_in(reports.iter().copied()))
+            .to_owned();
+        SameyPost::update_many()
+            .filter(samey_post::Column::Id.in_subquery(subquery))
+            .set(samey_post::ActiveModel {
+                is_public: Set(false),
+                updated_at: Set(Some(Utc::now().naive_utc())),
+                ..Default::default()
+            })
+            .exec(&txn)
+            .await?;
+    }
+
+    SameyPostReport::update_many()
+        .filter(samey_post_report::Column::Id.is_in(reports))
+        .filter(samey_post_report::Column::ResolvedAt.is_null())
+        .set(samey_post_report::ActiveModel {
+            resolved_by_id: Set(Some(user.id)),
+            resolved_at: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        })
+        .exec(&txn)
+        .await?;
+    txn.commit().await?;
+
+    Ok(Redirect::to("/moderation"))
+}
+
+/// Sends the moderation digest right away, such as to check the email and webhook settings.
+pub(crate) async fn send_digest(
+    State(AppState { db, app_config, .. }): State<AppState>,
+    _: RequireRole<Admin>,
+) -> Result<impl IntoResponse, SameyError> {
+    send_moderation_digest(&db, &app_config).await?;
+
+    Ok(Redirect::to("/moderation"))
+}
+
+#[derive(Template)]
+#[template(path = "pages/fsck.html")]
+struct FsckTemplate {
+    base: BaseContext,
+    report: FsckReport,
+}
+
+pub(crate) async fn fsck_page(
+    State(AppState {
+        db,
+        files_dir,
+        thumbnailer,
+        ..
+    }): State<AppState>,
+    base: BaseContext,
+    _: RequireRole<Admin>,
+) -> Result<impl IntoResponse, SameyError> {
+    let report = fsck(
+        db,
+        files_dir.as_ref(),
+        thumbnailer.as_ref(),
+        FsckOptions::default(),
+    )
+    .await?;
+
+    Ok(Html(FsckTemplate { base, report }.render()?))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct FsckForm {
+    remove_orphaned_files: Option<bool>,
+    regenerate_thumbnails: Option<bool>,
+}
+
+pub(crate) async fn run_fsck(
+    State(AppState {
+        db,
+        files_dir,
+        thumbnailer,
+        ..
+    }): State<AppState>,
+    base: BaseContext,
+    _: RequireRole<Admin>,
+    Form(body): Form<FsckForm>,
+) -> Result<impl IntoResponse, SameyError> {
+    let report = fsck(
+        db,
+        files_dir.as_ref(),
+        thumbnailer.as_ref(),
+        FsckOptions {
+            remove_orphaned_files: body.remove_orphaned_files.unwrap_or_default(),
+            regenerate_thumbnails: body.regenerate_thumbnails.unwrap_or_default(),
+        },
+    )
+    .await?;
+
+    Ok(Html(FsckTemplate { base, report }.render()?))
+}
+
+const DISK_USAGE_TOP_UPLOADERS: u64 = 20;
+const DISK_USAGE_TOP_TAGS: u64 = 20;
+
+#[derive(Template)]
+#[template(path = "pages/disk_usage.html")]
+struct DiskUsageTemplate {
+    base: BaseContext,
+    total: i64,
+    uploaders: Vec<UploaderDiskUsage>,
+    tags: Vec<TagDiskUsage>,
+}
+
+pub(crate) async fn disk_usage(
+    State(AppState { db, .. }): State<AppState>,
+    base: BaseContext,
+    _: RequireRole<Admin>,
+) -> Result<impl IntoResponse, SameyError> {
+    let total = get_total_disk_usage(&db).await?;
+    let uploaders = get_disk_usage_by_uploader(&db, DISK_USAGE_TOP_UPLOADERS).await?;
+    let tags = get_disk_usage_by_tag(&db, DISK_USAGE_TOP_TAGS).await?;
+
+    Ok(Html(
+        DiskUsageTemplate {
+            base,
+            total,
+            uploaders,
+            tags,
+        }
+        .render()?,
+    ))
+}
+
+// Takedown views
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TakedownQuery {
+    post_id: Option<i32>,
+}
+
+#[derive(Template)]
+#[template(path = "pages/takedown.html")]
+struct TakedownTemplate {
+    base: BaseContext,
+    post_id: String,
+    claimant_name: String,
+    claimant_email: String,
+    reason: String,
+    claimant_max_length: u64,
+    reason_max_length: u64,
+    captcha: Option<CaptchaWidget>,
+    errors: FieldErrors,
+}
+
+pub(crate) async fn takedown_page(
+    State(AppState {
+        app_config,
+        captcha,
+        ..
+    }): State<AppState>,
+    base: BaseContext,
+    Query(query): Query<TakedownQuery>,
+) -> Result<impl IntoResponse, SameyError> {
+    let captcha_settings = CaptchaSettings::from_config(&*app_config.read().await);
+    let captcha = match captcha_settings {
+        Some(settings) => Some(settings.widget(&captcha).await),
+        None => None,
+    };
+
+    Ok(Html(
+        TakedownTemplate {
+            base,
+            post_id: query
+                .post_id
+                .map(|post_id| post_id.to_string())
+                .unwrap_or_default(),
+            claimant_name: String::new(),
+            claimant_email: String::new(),
+            reason: String::new(),
+            claimant_max_length: TAKEDOWN_CLAIMANT_MAX_LENGTH,
+            reason_max_length: TAKEDOWN_REASON_MAX_LENGTH,
+            captcha,
+            errors: FieldErrors::default(),
+        }
+        .render()?,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct TakedownForm {
+    post_id: String,
+    claimant_name: String,
+    claimant_email: String,
+    reason: String,
+    statement: Option<String>,
+    #[serde(flatten)]
+    captcha: CaptchaForm,
+}
+
+#[derive(Template)]
+#[template(path = "pages/takedown_submitted.html")]
+struct TakedownSubmittedTemplate {
+    base: BaseContext,
+}
+
+/// Receives a takedown request for a post. Anyone can send one, since claimants rarely have an account, so it needs
+/// the CAPTCHA if one is enabled.
+pub(crate) async fn submit_takedown(
+    State(AppState {
+        db,
+        app_config,
+        captcha,
+        ..
+    }): State<AppState>,
+    base: BaseContext,
+    Form(body): Form<TakedownForm>,
+) -> Result<Response, SameyError> {
+    let mut errors = FieldErrors::default();
+
+    // Only posts that anyone can see may be claimed, so that the form doesn't reveal hidden posts
+    let post = match body.post_id.trim().trim_start_matches('#').parse::<i32>() {
+        Ok(post_id) => {
+            filter_posts_by_user(SameyPost::find_by_id(post_id), None)
+                .one(&db)
+                .await?
+        }
+        Err(_) => None,
+    };
+    if post.is_none() {
+        errors.add("post_id", "No post with this ID");
+    }
+
+    let claimant_name = body.claimant_name.trim();
+    if claimant_name.is_empty() {
+        errors.add("claimant_name", "Name cannot be empty");
+    }
+    errors.check_max_length(
+        "claimant_name",
+        Some(claimant_name),
+        TAKEDOWN_CLAIMANT_MAX_LENGTH,
+    );
+
+    let claimant_email = body.claimant_email.trim();
+    errors.check("claimant_email", parse_mailbox(claimant_email))?;
+    errors.check_max_length(
+        "claimant_email",
+        Some(claimant_email),
+        TAKEDOWN_CLAIMANT_MAX_LENGTH,
+    );
+
+    let reason = body.reason.trim();
+    if reason.is_empty() {
+        errors.add("reason", "Reason cannot be empty");
+    }
+    errors.check_max_length("reason", Some(reason), TAKEDOWN_REASON_MAX_LENGTH);
+
+    if body.statement.is_none() {
+        errors.add(
+            "statement",
+            "The statement must be confirmed to send a request",
+        );
+    }
+
+    let captcha_settings = CaptchaSettings::from_config(&*app_config.read().await);
+    if let Some(settings) = &captcha_settings {
+        if !settings.verify(&captcha, &body.captcha).await? {
+            errors.add("captcha", "Complete the CAPTCHA to send a request");
+        }
+    }
+
+    let Some(post) = post.filter(|_| errors.is_empty()) else {
+        let captcha = match captcha_settings {
+            Some(settings) => Some(settings.widget(&captcha).await),
+            None => None,
+        };
+        return Ok((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Html(
+                TakedownTemplate {
+                    base,
+                    post_id: body.post_id,
+                    claimant_name: body.claimant_name,
+                    claimant_email: body.claimant_email,
+                    reason: body.reason,
+                    claimant_max_length: TAKEDOWN_CLAIMANT_MAX_LENGTH,
+                    reason_max_length: TAKEDOWN_REASON_MAX_LENGTH,
+                    captcha,
+                    errors,
+                }
+                .render()?,
+            ),
+        )
+            .into_response());
+    };
+
+    SameyTakedownRequest::insert(samey_takedown_request::ActiveModel {
+        post_id: Set(Some(post.id)),
+        claimant_name: Set(claimant_name.into()),
+        claimant_email: Set(claimant_email.into()),
+        reason: Set(reason.into()),
+        status: Set(TakedownStatus::Pending.to_string()),
+        created_at: Set(Utc::now().naive_utc()),
+        ..Default::default()
+    })
+    .exec(&db)
+    .await?;
+
+    Ok(Html(TakedownSubmittedTemplate { base }.render()?).into_response())
+}
+
+#[derive(Template)]
+#[template(path = "pages/taken_down.html")]
+struct TakenDownTemplate {
+    base: BaseContext,
+    post_id: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ReviewTakedownForm {
+    take_down: Option<String>,
+}
+
+/// Takes down the post of a takedown request, or rejects the request.
+pub(crate) async fn review_takedown(
+    State(AppState { db, app_config, .. }): State<AppState>,
+    RequireRole(user, _): RequireRole<Admin>,
+    Path(request_id): Path<i32>,
+    Form(body): Form<ReviewTakedownForm>,
+) -> Result<impl IntoResponse, SameyError> {
+    let request = SameyTakedownRequest::find_by_id(request_id)
+        .filter(samey_takedown_request::Column::Status.eq(TakedownStatus::Pending.to_string()))
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+
+    // A post that was already deleted can't be taken down, so the request can only be rejected
+    let post = match body.take_down {
+        Some(_) => Some(
+            SameyPost::find_by_id(request.post_id.ok_or(SameyError::NotFound)?)
+                .one(&db)
+                .await?
+                .ok_or(SameyError::NotFound)?,
+        ),
+        None => None,
+    };
+
+    let txn = db.begin().await?;
+    match post.as_ref() {
+        Some(post) => {
+            take_down_post(&txn, post.id, user.id).await?;
+            record_audit_log(
+                &txn,
+                Some(user.id),
+                AuditAction::TakeDownPost,
+                format!(
+                    "Took down post {} for takedown request {} by {} <{}>",
+                    post.id, request.id, request.claimant_name, request.claimant_email
+                ),
+            )
+            .await?;
+        }
+        None => {
+            reject_takedown_request(&txn, request.id, user.id).await?;
+            record_audit_log(
+                &txn,
+                Some(user.id),
+                AuditAction::RejectTakedown,
+                format!(
+                    "Rejected takedown request {} by {} <{}>",
+                    request.id, request.claimant_name, request.claimant_email
+                ),
+            )
+            .await?;
+        }
+    }
+    txn.commit().await?;
+
+    // Federated posts are removed from other servers
+    if let Some(post) = post.filter(is_post_published) {
+        if let Some(federation) = Federation::from_config(&*app_config.read().await) {
+            federate_post(db.clone(), federation, post.id);
+        }
+    }
+
+    Ok(Redirect::to("/moderation"))
+}
+
+/// Shows a post that was taken down again, such as after a counter-notice.
+pub(crate) async fn restore_post(
+    State(AppState { db, app_config, .. }): State<AppState>,
+    RequireRole(user, _): RequireRole<Admin>,
+    Path(post_id): Path<i32>,
+) -> Result<impl IntoResponse, SameyError> {
+    let post = SameyPost::find_by_id(post_id)
+        .filter(samey_post::Column::TakenDownAt.is_not_null())
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+
+    let txn = db.begin().await?;
+    restore_taken_down_post(&txn, post.id).await?;
+    record_audit_log(
+        &txn,
+        Some(user.id),
+        AuditAction::RestorePost,
+        format!("Restored taken down post {}", post.id),
+    )
+    .await?;
+    txn.commit().await?;
+
+    let post = SameyPost::find_by_id(post_id)
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+    if is_post_published(&post) {
+        if let Some(federation) = Federation::from_config(&*app_config.read().await) {
+            federate_post(db.clone(), federation, post_id);
+        }
+    }
+
+    Ok(Redirect::to(&format!("/post/{}", post_id)))
+}
+
+// User management views
+
+const USERS_PER_PAGE: u64 = 50;
+/// Length of the passwords generated when an admin resets one.
+const RESET_PASSWORD_LENGTH: usize = 16;
+
+#[derive(Template)]
+#[template(path = "pages/admin_users.html")]
+struct AdminUsersTemplate {
+    base: BaseContext,
+    users: Vec<UserOverview>,
+    search: String,
+    inactive_days: Option<u32>,
+    page: u32,
+    page_count: u64,
+    current_user_id: i32,
+    new_password: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AdminUsersQuery {
+    search: Option<String>,
+    inactive_days: Option<String>,
+}
+
+pub(crate) async fn admin_users(
+    state: State<AppState>,
+    base: BaseContext,
+    admin: RequireRole<Admin>,
+    query: Query<AdminUsersQuery>,
+) -> Result<impl IntoResponse, SameyError> {
+    admin_users_page(state, base, admin, query, Path(1)).await
+}
+
+pub(crate) async fn admin_users_page(
+    State(AppState { db, .. }): State<AppState>,
+    base: BaseContext,
+    RequireRole(current_user, _): RequireRole<Admin>,
+    Query(query): Query<AdminUsersQuery>,
+    Path(page): Path<u32>,
+) -> Result<impl IntoResponse, SameyError> {
+    let search = query.search.unwrap_or_default();
+    let inactive_days = match query.inactive_days.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(days) => Some(
+            days.parse::<u32>()
+                .map_err(|_| SameyError::BadRequest("Invalid amount of days".into()))?,
+        ),
+    };
+    let inactive_since =
+        inactive_days.map(|days| Utc::now().naive_utc() - TimeDelta::days(days.into()));
+    let pagination =
+        get_user_overviews(Some(&search), inactive_since).paginate(&db, USERS_PER_PAGE);
+    let page_count = pagination.num_pages().await?;
+    let users = pagination.fetch_page(page.saturating_sub(1) as u64).await?;
+
+    Ok(Html(
+        AdminUsersTemplate {
+            base,
+            users,
+            search,
+            inactive_days,
+            page,
+            page_count,
+            current_user_id: current_user.id,
+            new_password: None,
+        }
+        .render()?,
+    ))
+}
+
+#[derive(Template)]
+#[template(path = "fragments/admin_user_row.html")]
+struct AdminUserRowTemplate {
+    base: BaseContext,
+    user: UserOverview,
+    current_user_id: i32,
+    /// Shown once after an admin resets the user's password.
+    new_password: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChangeUserRoleForm {
+    is_admin: bool,
+}
+
+pub(crate) async fn change_user_role(
+    State(AppState { db, .. }): State<AppState>,
+    base: BaseContext,
+    RequireRole(current_user, _): RequireRole<Admin>,
+    Path(user_id): Path<i32>,
+    Form(body): Form<ChangeUserRoleForm>,
+) -> Result<impl IntoResponse, SameyError> {
+    if user_id == current_user.id && !body.is_admin {
+        return Err(SameyError::BadRequest(
+            "You can't remove your own admin role".into(),
+        ));
+    }
+
+    SameyUser::update(samey_user::ActiveModel {
+        id: Set(user_id),
+        is_admin: Set(body.is_admin),
+        ..Default::default()
+    })
+    .exec(&db)
+    .await?;
+    if user_id != current_user.id {
+        // Privileges only apply on a fresh session
+        revoke_user_sessions(&db, user_id).await?;
+    }
+
+    let user = get_user_overview(user_id)
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+    Ok(Html(
+        AdminUserRowTemplate {
+            base,
+            user,
+            current_user_id: current_user.id,
+            new_password: None,
+        }
+        .render()?,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ChangeUserDisabledForm {
+    is_disabled: bool,
+}
+
+pub(crate) async fn change_user_disabled(
+    State(AppState { db, .. }): State<AppState>,
+    base: BaseContext,
+    RequireRole(current_user, _): RequireRole<Admin>,
+    Path(user_id): Path<i32>,
+    Form(body): Form<ChangeUserDisabledForm>,
+) -> Result<impl IntoResponse, SameyError> {
+    if user_id == current_user.id && body.is_disabled {
+        return Err(SameyError::BadRequest(
+            "You can't disable your own account".into(),
+        ));
+    }
+
+    SameyUser::update(samey_user::ActiveModel {
+        id: Set(user_id),
+        is_disabled: Set(body.is_disabled),
+        ..Default::default()
+    })
+    .exec(&db)
+    .await?;
+    if body.is_disabled {
+        revoke_user_sessions(&db, user_id).await?;
+    }
+
+    let user = get_user_overview(user_id)
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+    Ok(Html(
+        AdminUserRowTemplate {
+            base,
+            user,
+            current_user_id: current_user.id,
+            new_password: None,
+        }
+        .render()?,
+    ))
+}
+
+/// Replaces a user's password with a random one, which is shown to the admin only once.
+pub(crate) async fn reset_user_password(
+    State(AppState { db, .. }): State<AppState>,
+    base: BaseContext,
+    RequireRole(current_user, _): RequireRole<Admin>,
+    Path(user_id): Path<i32>,
+) -> Result<impl IntoResponse, SameyError> {
+    let user = get_user_overview(user_id)
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+    let new_password: String = {
+        let mut rng = rand::rng();
+        (0..RESET_PASSWORD_LENGTH)
+            .map(|_| rng.sample(rand::distr::Alphanumeric) as char)
+            .collect()
+    };
+    SameyUser::update(samey_user::ActiveModel {
+        id: Set(user_id),
+        password: Set(generate_hash(&new_password)),
+        ..Default::default()
+    })
+    .exec(&db)
+    .await?;
+    revoke_user_sessions(&db, user_id).await?;
+
+    Ok(Html(
+        AdminUserRowTemplate {
+            base,
+            user,
+            current_user_id: current_user.id,
+            new_password: Some(new_password),
+        }
+        .render()?,
+    ))
+}
+
+// Integration views
+
+/// How many of the latest crosspost errors are shown.
+const CROSSPOST_ERRORS_LIMIT: u64 = 20;
+
+#[derive(Template)]
+#[template(path = "pages/integrations.html")]
+struct IntegrationsTemplate {
+    base: BaseContext,
+    integrations: Vec<IntegrationOverview>,
+    errors: Vec<CrosspostError>,
+    kinds: Vec<String>,
+    default_message_template: &'static str,
+}
+
+pub(crate) async fn integrations(
+    State(AppState { db, .. }): State<AppState>,
+    base: BaseContext,
+    _: RequireRole<Admin>,
+) -> Result<impl IntoResponse, SameyError> {
+    let mut integrations = get_integration_overviews().all(&db).await?;
+    let now = Utc::now().naive_utc();
+    for integration in integrations.iter_mut() {
+        integration.rate_limited_until = integration
+            .rate_limited_until
+            .filter(|rate_limited_until| *rate_limited_until > now);
+    }
+    let errors = get_crosspost_errors(CROSSPOST_ERRORS_LIMIT)
+        .all(&db)
+        .await?;
+
+    Ok(Html(
+        IntegrationsTemplate {
+            base,
+            integrations,
+            errors,
+            kinds: IntegrationKind::iter()
+                .map(|kind| kind.to_string())
+                .collect(),
+            default_message_template: DEFAULT_MESSAGE_TEMPLATE,
+        }
+        .render()?,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AddIntegrationForm {
+    kind: String,
+    name: String,
+    url: String,
+    access_token: String,
+    tag_filter: String,
+    message_template: String,
+}
+
+pub(crate) async fn add_integration(
+    State(AppState { db, .. }): State<AppState>,
+    _: RequireRole<Admin>,
+    Form(body): Form<AddIntegrationForm>,
+) -> Result<impl IntoResponse, SameyError> {
+    let kind = IntegrationKind::parse(&body.kind)
+        .ok_or_else(|| SameyError::BadRequest(format!("Unknown integration {}", body.kind)))?;
+    let name = body.name.trim();
+    if name.is_empty() {
+        return Err(SameyError::BadRequest(
+            "Integration name cannot be empty".into(),
+        ));
+    }
+    let url = body.url.trim().trim_end_matches('/');
+    if !reqwest::Url::parse(url).is_ok_and(|url| ["http", "https"].contains(&url.scheme())) {
+        return Err(SameyError::BadRequest(format!("Invalid URL {}", url)));
+    }
+    let access_token = Some(body.access_token.trim())
+        .filter(|access_token| !access_token.is_empty())
+        .map(String::from);
+    if kind == IntegrationKind::Mastodon && access_token.is_none() {
+        return Err(SameyError::BadRequest(
+            "Mastodon integrations need an access token".into(),
+        ));
+    }
+    let message_template = match body.message_template.trim() {
+        "" => DEFAULT_MESSAGE_TEMPLATE,
+        message_template => message_template,
+    };
+
+    SameyIntegration::insert(samey_integration::ActiveModel {
+        kind: Set(kind.to_string()),
+        name: Set(name.into()),
+        url: Set(url.into()),
+        access_token: Set(access_token),
+        tag_filter: Set(body.tag_filter.split_whitespace().join(" ")),
+        message_template: Set(message_template.into()),
+        created_at: Set(Utc::now().naive_utc()),
+        ..Default::default()
+    })
+    .exec(&db)
+    .await?;
+
+    Ok(Redirect::to("/integrations"))
+}
+
+pub(crate) async fn delete_integration(
+    State(AppState { db, .. }): State<AppState>,
+    _: RequireRole<Admin>,
+    Path(integration_id): Path<i32>,
+) -> Result<impl IntoResponse, SameyError> {
+    SameyIntegration::delete_by_id(integration_id)
+        .exec(&db)
+        .await?;
+
+    Ok("")
+}
+
+pub(crate) async fn retry_integration(
+    State(AppState { db, .. }): State<AppState>,
+    _: RequireRole<Admin>,
+    Path(integration_id): Path<i32>,
+) -> Result<impl IntoResponse, SameyError> {
+    retry_failed_crossposts(&db, integration_id).await?;
+
+    Ok(Redirect::to("/integrations"))
+}
+
+// Curation views
+
+/// Posts with fewer tags than this are listed for curation by default.
+const DEFAULT_CURATION_MIN_TAGS: u32 = 5;
+const CURATION_PAGE_SIZE: u64 = 50;
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CurateQuery {
+    min_tags: Option<u32>,
+    page: Option<u32>,
+}
+
+#[derive(Template)]
+#[template(path = "pages/curate.html")]
+struct CurateTemplate {
+    base: BaseContext,
+    posts: Vec<CurationPost>,
+    ratings: Vec<Rating>,
+    min_tags: u32,
+    page: u32,
+    page_count: u64,
+}
+
+pub(crate) async fn curate(
+    State(AppState { db, app_config, .. }): State<AppState>,
+    base: BaseContext,
+    _: RequireRole<Admin>,
+    Query(query): Query<CurateQuery>,
+) -> Result<impl IntoResponse, SameyError> {
+    let min_tags = query.min_tags.unwrap_or(DEFAULT_CURATION_MIN_TAGS);
+    let page = query.page.unwrap_or(1).max(1);
+    let pagination = get_posts_needing_curation(min_tags).paginate(&db, CURATION_PAGE_SIZE);
+    let page_count = pagination.num_pages().await?;
+    let posts = pagination.fetch_page(page as u64 - 1).await?;
+
+    Ok(Html(
+        CurateTemplate {
+            base,
+            posts,
+            ratings: app_config.read().await.ratings.clone(),
+            min_tags,
+            page,
+            page_count,
+        }
+        .render()?,
+    ))
+}
+
+#[derive(Template)]
+#[template(path = "fragments/curate_post.html")]
+struct CuratePostTemplate {
+    post: CurationPost,
+    ratings: Vec<Rating>,
+    min_tags: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CurateRatingForm {
+    rating: String,
+    min_tags: u32,
+}
+
+pub(crate) async fn curate_rating(
+    State(AppState { db, app_config, .. }): State<AppState>,
+    _: RequireRole<Admin>,
+    Path(post_id): Path<i32>,
+    Form(body): Form<CurateRatingForm>,
+) -> Result<impl IntoResponse, SameyError> {
+    if !app_config.read().await.is_valid_rating(&body.rating) {
+        return Err(SameyError::BadRequest("Invalid rating".into()));
+    }
+
+    let txn = db.begin().await?;
+    samey_post::ActiveModel {
+        id: Set(post_id),
+        rating: Set(body.rating),
+        ..Default::default()
+    }
+    .update(&txn)
+    .await?;
+    bump_post_version(&txn, post_id).await?;
+    txn.commit().await?;
+
+    let post = get_curation_post(post_id)
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+
+    Ok(Html(
+        CuratePostTemplate {
+            post,
+            ratings: app_config.read().await.ratings.clone(),
+            min_tags: body.min_tags,
+        }
+        .render()?,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct CurateTagsForm {
+    tags: String,
+    min_tags: u32,
+}
+
+pub(crate) async fn curate_tags(
+    State(AppState { db, app_config, .. }): State<AppState>,
+    _: RequireRole<Admin>,
+    Path(post_id): Path<i32>,
+    Form(body): Form<CurateTagsForm>,
+) -> Result<impl IntoResponse, SameyError> {
+    SameyPost::find_by_id(post_id)
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+    let tags: HashSet<String> = body.tags.split_whitespace().map(String::from).collect();
+    let txn = db.begin().await?;
+    replace_post_tags(&txn, post_id, tags).await?;
+    bump_post_version(&txn, post_id).await?;
+    txn.commit().await?;
+
+    let post = get_curation_post(post_id)
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+
+    tokio::spawn(async move {
+        if let Err(err) = clean_dangling_tags(&db).await {
+            println!("Error when cleaning dangling tags - {}", err);
+        }
+    });
+
+    Ok(Html(
+        CuratePostTemplate {
+            post,
+            ratings: app_config.read().await.ratings.clone(),
+            min_tags: body.min_tags,
+        }
+        .render()?,
+    ))
+}
+
+// Notification views
+
+#[derive(Template)]
+#[template(path = "pages/notifications.html")]
+struct NotificationsTemplate {
+    base: BaseContext,
+    notifications: Vec<NotificationOverview>,
+}
+
+pub(crate) async fn notifications(
+    State(AppState { db, .. }): State<AppState>,
+    base: BaseContext,
+    auth_session: AuthSession,
+) -> Result<impl IntoResponse, SameyError> {
+    let user = match auth_session.user {
+        Some(user) => user,
+        None => return Err(SameyError::Forbidden),
+    };
+
+    let notifications = get_notifications_for_user(&user)
+        .limit(100)
+        .into_model::<NotificationOverview>()
+        .all(&db)
+        .await?;
+
+    Ok(Html(
+        NotificationsTemplate {
+            base,
+            notifications,
+        }
+        .render()?,
+    ))
+}
+
+pub(crate) async fn read_notifications(
+    State(AppState { db, .. }): State<AppState>,
+    auth_session: AuthSession,
+) -> Result<impl IntoResponse, SameyError> {
+    let user = match auth_session.user {
+        Some(user) => user,
+        None => return Err(SameyError::Forbidden),
+    };
+
+    SameyNotification::update_many()
+        .filter(samey_notification::Column::UserId.eq(user.id))
+        .set(samey_notification::ActiveModel {
+            is_read: Set(true),
+            ..Default::default()
+        })
+        .exec(&db)
+        .await?;
+
+    Ok(Redirect::to("/notifications"))
+}
+
+// Stats views
+
+#[derive(Template)]
+#[template(path = "pages/stats.html")]
+struct StatsTemplate {
+    base: BaseContext,
+    stats: Arc<Stats>,
+    ratings: Vec<Rating>,
+    max_month_count: i64,
+}
+
+impl StatsTemplate {
+    fn rating_label(&self, code: &str) -> String {
+        rating_label(&self.ratings, code)
+    }
+}
+
+pub(crate) async fn stats(
+    State(AppState {
+        db,
+        app_config,
+        stats_cache,
+        ..
+    }): State<AppState>,
+    base: BaseContext,
+) -> Result<impl IntoResponse, SameyError> {
+    let app_config = app_config.read().await;
+    let stats_enabled = app_config.stats_enabled;
+    let ratings = app_config.ratings.clone();
+    drop(app_config);
+    if !stats_enabled {
+        return Err(SameyError::NotFound);
+    }
+
+    let stats = stats_cache.get(&db).await?;
+    let max_month_count = stats
+        .uploads_per_month
+        .iter()
+        .map(|month| month.post_count)
+        .max()
+        .unwrap_or(0);
+
+    Ok(Html(
+        StatsTemplate {
+            base,
+            stats,
+            ratings,
+            max_month_count,
+        }
+        .render()?,
+    ))
+}
+
+// Popular views
+
+#[derive(Template)]
+#[template(path = "pages/popular.html")]
+struct PopularTemplate {
+    base: BaseContext,
+    period: PopularPeriod,
+    posts: Vec<PostOverview>,
+    tags_text: Option<String>,
+    can_tag: bool,
+}
+
+pub(crate) async fn popular() -> impl IntoResponse {
+    Redirect::to(&format!("/popular/{}", PopularPeriod::Week))
+}
+
+pub(crate) async fn popular_period(
+    State(AppState { db, .. }): State<AppState>,
+    base: BaseContext,
+    Path(period): Path<String>,
+) -> Result<impl IntoResponse, SameyError> {
+    let period = PopularPeriod::iter()
+        .find(|popular_period| popular_period.to_string() == period)
+        .ok_or(SameyError::NotFound)?;
+    let posts = get_popular_posts(&db, base.user.as_ref(), period).await?;
+
+    Ok(Html(
+        PopularTemplate {
+            period,
+            posts: sort_post_overview_tags(posts),
+            tags_text: None,
+            can_tag: base.user.is_some(),
+            base,
+        }
+        .render()?,
+    ))
+}
+
+// Calendar views
+
+#[derive(Debug, Clone)]
+struct CalendarDay {
+    day: u32,
+    date: String,
+    post_count: i64,
+}
+
+#[derive(Template)]
+#[template(path = "pages/calendar.html")]
+struct CalendarTemplate {
+    base: BaseContext,
+    title: String,
+    /// Weeks from Monday to Sunday, with `None` for days outside of the month.
+    weeks: Vec<Vec<Option<CalendarDay>>>,
+    total_posts: i64,
+    previous_month: NaiveDate,
+    next_month: NaiveDate,
+}
+
+pub(crate) async fn calendar() -> impl IntoResponse {
+    let today = Utc::now().date_naive();
+    Redirect::to(&format!("/calendar/{}/{}", today.year(), today.month()))
+}
+
+pub(crate) async fn calendar_month(
+    State(AppState { db, .. }): State<AppState>,
+    base: BaseContext,
+    Path((year, month)): Path<(i32, u32)>,
+) -> Result<impl IntoResponse, SameyError> {
+    let first_day = NaiveDate::from_ymd_opt(year, month, 1).ok_or(SameyError::NotFound)?;
+    let previous_month = first_day
+        .checked_sub_months(Months::new(1))
+        .ok_or(SameyError::NotFound)?;
+    let next_month = first_day
+        .checked_add_months(Months::new(1))
+        .ok_or(SameyError::NotFound)?;
+
+    let post_counts: HashMap<String, i64> = get_upload_counts_by_day(
+        first_day.and_time(NaiveTime::MIN),
+        next_month.and_time(NaiveTime::MIN),
+        base.user.as_ref(),
+    )
+    .all(&db)
+    .await?
+    .into_iter()
+    .map(|DayCount { day, post_count }| (day, post_count))
+    .collect();
+
+    let mut weeks = vec![];
+    let mut week = vec![None; first_day.weekday().num_days_from_monday() as usize];
+    for date in first_day.iter_days().take_while(|date| *date < next_month) {
+        let date_text = date.format("%Y-%m-%d").to_string();
+        week.push(Some(CalendarDay {
+            day: date.day(),
+            post_count: post_counts.get(&date_text).copied().unwrap_or(0),
+            date: date_text,
+        }));
+        if week.len() == 7 {
+            weeks.push(mem::take(&mut week));
+        }
+    }
+    if !week.is_empty() {
+        week.resize(7, None);
+        weeks.push(week);
+    }
+
+    Ok(Html(
+        CalendarTemplate {
+            title: first_day.format("%B %Y").to_string(),
+            weeks,
+            total_posts: post_counts.values().sum(),
+            previous_month,
+            next_month,
+            base,
+        }
+        .render()?,
+    ))
+}
+
+// Robots views
+
+const X_ROBOTS_TAG: HeaderName = HeaderName::from_static("x-robots-tag");
+
+pub(crate) async fn robots_txt(
+    State(AppState { app_config, .. }): State<AppState>,
+) -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, "text/plain; charset=utf-8")],
+        app_config.read().await.robots_txt.clone(),
+    )
+}
+
+/// Adds an `X-Robots-Tag: noindex` header to every response when the whole instance is marked as noindex.
+pub(crate) async fn robots_tag_header(
+    State(AppState { app_config, .. }): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let noindex_instance = app_config.read().await.noindex_instance;
+    let mut response = next.run(request).await;
+    if noindex_instance {
+        response
+            .headers_mut()
+            .insert(X_ROBOTS_TAG, HeaderValue::from_static("noindex"));
+    }
+    response
+}
+
+// Read-only views
+
+/// Routes that accept non-GET requests without changing any content.
+///
+/// The general settings are the exception, since they hold the toggle to leave read-only mode.
+const READ_ONLY_ALLOWED_PATHS: [&str; 7] = [
+    "/login",
+    "/age_confirmation",
+    "/search_tags",
+    "/select_tag",
+    "/graphql",
+    "/settings",
+    // Previews are only rendered, and never saved
+    "/settings/appearance/preview",
+];
+
+/// Rejects requests that could change content while the instance is in read-only mode.
+pub(crate) async fn read_only_guard(
+    State(AppState { app_config, .. }): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, SameyError> {
+    let read_only = app_config.read().await.read_only;
+    if read_only
+        && !matches!(
+            *request.method(),
+            Method::GET | Method::HEAD | Method::OPTIONS
+        )
+        && !READ_ONLY_ALLOWED_PATHS.contains(&request.uri().path().trim_end_matches('/'))
+    {
+        return Err(SameyError::ReadOnly);
+    }
+    Ok(next.run(request).await)
+}
+
+/// Routes that receive files when posted to, which get more time to finish.
+const UPLOAD_PATHS: [&str; 3] = ["/upload", "/settings/favicon", "/api/v1/posts"];
+const UPLOAD_REQUEST_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Cancels requests that take too long, with a longer limit for uploads.
+pub(crate) async fn request_timeout(request: Request, next: Next) -> Result<Response, SameyError> {
+    let duration = if request.method() == Method::POST
+        && UPLOAD_PATHS.contains(&request.uri().path().trim_end_matches('/'))
+    {
+        UPLOAD_REQUEST_TIMEOUT
+    } else {
+        REQUEST_TIMEOUT
+    };
+    timeout(duration, next.run(request))
+        .await
+        .map_err(|_| SameyError::RequestTimeout)
+}
+
+/// Drops cached posts and tags after any request that could have changed them.
+///
+/// This covers every way of editing content at once, at the cost of also dropping the cache for changes that don't
+/// affect it.
+pub(crate) async fn cache_invalidation(
+    State(AppState { object_cache, .. }): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let is_mutation = !matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    let response = next.run(request).await;
+    if is_mutation && !response.status().is_client_error() {
+        object_cache.invalidate_all();
+    }
+    response
+}
+
+// Hotlink protection views
+
+/// Checks if a host is a domain or one of its subdomains.
+fn matches_domain(host: &str, domain: &str) -> bool {
+    host.eq_ignore_ascii_case(domain)
+        || host
+            .len()
+            .checked_sub(domain.len() + 1)
+            .is_some_and(|start| {
+                host.as_bytes()[start] == b'.' && host[start + 1..].eq_ignore_ascii_case(domain)
+            })
+}
+
+/// Rejects requests for media that are referred by other sites, unless they are in the allowlist.
+///
+/// Requests without a `Referer` header are always allowed, so that media can still be opened directly.
+pub(crate) async fn hotlink_guard(
+    State(AppState { app_config, .. }): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, SameyError> {
+    if !request.uri().path().starts_with("/files/") {
+        return Ok(next.run(request).await);
+    }
+    let Some(referer) = request
+        .headers()
+        .get(REFERER)
+        .and_then(|referer| referer.to_str().ok())
+        .and_then(|referer| referer.parse::<Uri>().ok())
+    else {
+        return Ok(next.run(request).await);
+    };
+
+    let app_config = app_config.read().await;
+    if app_config.hotlink_protection {
+        let referer_host = referer.host().unwrap_or_default();
+        let host = request
+            .uri()
+            .host()
+            .or_else(|| {
+                request
+                    .headers()
+                    .get(HOST)
+                    .and_then(|host| host.to_str().ok())
+                    .and_then(|host| host.split(':').next())
+            })
+            .unwrap_or_default();
+        let own_hosts = [
+            host.to_owned(),
+            app_config
+                .base_url
+                .parse::<Uri>()
+                .ok()
+                .and_then(|base_url| base_url.host().map(String::from))
+                .unwrap_or_default(),
+        ];
+        let is_allowed = own_hosts
+            .iter()
+            .filter(|own_host| !own_host.is_empty())
+            .any(|own_host| referer_host.eq_ignore_ascii_case(own_host))
+            || app_config
+                .hotlink_allowed_domains
+                .iter()
+                .any(|domain| matches_domain(referer_host, domain));
+        if !is_allowed {
+            return Err(SameyError::Forbidden);
+        }
+    }
+    drop(app_config);
+
+    Ok(next.run(request).await)
+}
+
+// Theme views
+
+pub(crate) async fn custom_css(
+    State(AppState { app_config, .. }): State<AppState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let css = app_config.read().await.theme_css();
+
+    let mut hasher = DefaultHasher::new();
+    css.hash(&mut hasher);
+    let etag = format!("\"{:x}\"", hasher.finish());
+    let cache_headers = [
+        (CONTENT_TYPE, "text/css; charset=utf-8".to_owned()),
+        (CACHE_CONTROL, "public, max-age=300".to_owned()),
+        (ETAG, etag.clone()),
+    ];
+    if headers
+        .get(IF_NONE_MATCH)
+        .is_some_and(|if_none_match| if_none_match.as_bytes() == etag.as_bytes())
+    {
+        return (StatusCode::NOT_MODIFIED, cache_headers, String::new());
+    }
+    (StatusCode::OK, cache_headers, css)
+}
+
+// Web app manifest view
+
+/// Sizes of the web app icons generated from the uploaded favicon.
+const PWA_ICON_SIZES: [u32; 2] = [192, 512];
+const FAVICON_SIZE: u32 = 32;
+
+pub(crate) async fn manifest(
+    State(AppState {
+        app_config,
+        files_dir,
+        ..
+    }): State<AppState>,
+) -> Result<impl IntoResponse, SameyError> {
+    let application_name = app_config.read().await.application_name.clone();
+
+    let mut icons = vec![];
+    for size in PWA_ICON_SIZES {
+        let file_name = format!("icon-{}.png", size);
+        if tokio::fs::try_exists(files_dir.join(&file_name)).await? {
+            icons.push(serde_json::json!({
+                "src": format!("/files/{}", file_name),
+                "sizes": format!("{0}x{0}", size),
+                "type": "image/png",
+            }));
+        }
+    }
+
+    Ok((
+        [(CONTENT_TYPE, "application/manifest+json")],
+        Json(serde_json::json!({
+            "name": application_name,
+            "short_name": application_name,
+            "start_url": "/",
+            "scope": "/",
+            "display": "standalone",
+            "icons": icons,
+        })),
+    ))
+}
+
+// Preferences views
+
+#[derive(Template)]
+#[template(path = "pages/preferences.html")]
+struct PreferencesTemplate {
+    base: BaseContext,
+    timezones: Vec<&'static str>,
+    timezone: String,
+    locale: String,
+    posts_per_page_choices: [u64; 4],
+    thumbnail_densities: Vec<ThumbnailDensity>,
+    email_enabled: bool,
+    notification_setting: samey_notification_setting::Model,
+    /// Whether the user has a personal RSS token, which is only shown once when created.
+    has_rss_token: bool,
+}
+
+pub(crate) async fn preferences(
+    State(AppState { db, app_config, .. }): State<AppState>,
+    base: BaseContext,
+) -> Result<impl IntoResponse, SameyError> {
+    let timezone = base
+        .preferences
+        .timezone
+        .map(|timezone| timezone.name().to_owned())
+        .unwrap_or_default();
+    let locale = base
+        .preferences
+        .locale
+        .map(|locale| locale.to_string())
+        .unwrap_or_default();
+    let email_enabled = app_config.read().await.email_enabled();
+    let has_rss_token = match base.user.as_ref() {
+        Some(user) => SameyUser::find_by_id(user.id)
+            .one(&db)
+            .await?
+            .is_some_and(|user| user.rss_token_hash.is_some()),
+        None => false,
+    };
+    let notification_setting = match base.user.as_ref() {
+        Some(user) => {
+            SameyNotificationSetting::find_by_id(user.id)
+                .one(&db)
+                .await?
+        }
+        None => None,
+    }
+    .unwrap_or(samey_notification_setting::Model {
+        user_id: 0,
+        email: None,
+        email_on_mention: false,
+        email_on_pool_add: false,
+    });
+
+    Ok(Html(
+        PreferencesTemplate {
+            base,
+            timezones: chrono_tz::TZ_VARIANTS.into_iter().map(Tz::name).collect(),
+            timezone,
+            locale,
+            posts_per_page_choices: POSTS_PER_PAGE_CHOICES,
+            thumbnail_densities: ThumbnailDensity::iter().collect(),
+            email_enabled,
+            notification_setting,
+            has_rss_token,
+        }
+        .render()?,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct UpdatePreferencesForm {
+    timezone: String,
+    locale: String,
+    posts_per_page: u64,
+    thumbnail_density: String,
+    autoplay_videos: Option<String>,
+    notification_email: Option<String>,
+    email_on_mention: Option<String>,
+    email_on_pool_add: Option<String>,
+}
+
+pub(crate) async fn update_preferences(
+    State(AppState { db, .. }): State<AppState>,
+    auth_session: AuthSession,
+    session: Session,
+    Form(body): Form<UpdatePreferencesForm>,
+) -> Result<impl IntoResponse, SameyError> {
+    let timezone = match body.timezone.trim() {
+        "" => None,
+        timezone => Some(
+            Tz::from_str(timezone)
+                .map_err(|_| SameyError::BadRequest("Invalid timezone".into()))?
+                .name()
+                .to_owned(),
+        ),
+    };
+    let locale = match body.locale.trim() {
+        "" => None,
+        locale => Some(
+            Locale::from_str(locale)
+                .map_err(|_| SameyError::BadRequest("Invalid locale".into()))?
+                .to_string(),
+        ),
+    };
+    if !POSTS_PER_PAGE_CHOICES.contains(&body.posts_per_page) {
+        return Err(SameyError::BadRequest("Invalid posts per page".into()));
+    }
+    let thumbnail_density = ThumbnailDensity::from_str(&body.thumbnail_density)
+        .map_err(|_| SameyError::BadRequest("Invalid thumbnail density".into()))?;
+    let autoplay_videos = body.autoplay_videos.is_some();
+
+    match auth_session.user {
+        Some(user) => {
+            let email = match body.notification_email.as_deref().map(str::trim) {
+                None | Some("") => None,
+                Some(email) => {
+                    parse_mailbox(email)?;
+                    Some(email.to_owned())
+                }
+            };
+            let txn = db.begin().await?;
+            SameyUser::update(samey_user::ActiveModel {
+                id: Set(user.id),
+                timezone: Set(timezone),
+                locale: Set(locale),
+                posts_per_page: Set(Some(body.posts_per_page as i32)),
+                thumbnail_density: Set(Some(thumbnail_density.to_string())),
+                autoplay_videos: Set(autoplay_videos),
+                ..Default::default()
+            })
+            .exec(&txn)
+            .await?;
+            SameyNotificationSetting::insert(samey_notification_setting::ActiveModel {
+                user_id: Set(user.id),
+                email: Set(email),
+                email_on_mention: Set(body.email_on_mention.is_some()),
+                email_on_pool_add: Set(body.email_on_pool_add.is_some()),
+            })
+            .on_conflict(
+                OnConflict::column(samey_notification_setting::Column::UserId)
+                    .update_columns([
+                        samey_notification_setting::Column::Email,
+                        samey_notification_setting::Column::EmailOnMention,
+                        samey_notification_setting::Column::EmailOnPoolAdd,
+                    ])
+                    .to_owned(),
+            )
+            .exec(&txn)
+            .await?;
+            txn.commit().await?;
+        }
+        None => {
+            for (key, value) in [
+                (TIMEZONE_SESSION_KEY, timezone),
+                (LOCALE_SESSION_KEY, locale),
+            ] {
+                match value {
+                    Some(value) => session.insert(key, value).await,
+                    None => session.remove::<String>(key).await.map(|_| ()),
+                }
+                .map_err(|err| SameyError::Other(err.to_string()))?;
+            }
+            session
+                .insert(POSTS_PER_PAGE_SESSION_KEY, body.posts_per_page)
+                .await
+                .map_err(|err| SameyError::Other(err.to_string()))?;
+            session
+                .insert(THUMBNAIL_DENSITY_SESSION_KEY, thumbnail_density.to_string())
+                .await
+                .map_err(|err| SameyError::Other(err.to_string()))?;
+            session
+                .insert(AUTOPLAY_VIDEOS_SESSION_KEY, autoplay_videos)
+                .await
+                .map_err(|err| SameyError::Other(err.to_string()))?;
+        }
+    }
+
+    Ok(Redirect::to("/preferences"))
+}
+
+/// Directory in the files directory where avatars are stored.
+const AVATARS_DIRECTORY: &str = "avatars";
+/// Width and height of avatars, in pixels.
+const AVATAR_SIZE: u32 = 128;
+
+/// Replaces the avatar of the current user with a square crop of the uploaded image.
+pub(crate) async fn upload_avatar(
+    State(AppState { db, files_dir, .. }): State<AppState>,
+    auth_session: AuthSession,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, SameyError> {
+    let user = match auth_session.user {
+        Some(user) => user,
+        None => return Err(SameyError::Forbidden),
+    };
+
+    let mut avatar = None;
+    while let Some(field) = multipart.next_field().await? {
+        if field.name() == Some("avatar-file") {
+            avatar = Some(field.bytes().await?);
+        }
+    }
+    let avatar = avatar
+        .filter(|avatar| !avatar.is_empty())
+        .ok_or(SameyError::BadRequest("Missing avatar file".into()))?;
+
+    // A new name for every upload keeps browsers from showing a cached old avatar
+    let suffix: String = rand::rng()
+        .sample_iter(rand::distr::Alphanumeric)
+        .take(8)
+        .map(char::from)
+        .collect();
+    let file_name = format!("{}/{}-{}.png", AVATARS_DIRECTORY, user.id, suffix);
+    let file_path = files_dir.join(&file_name);
+    spawn_blocking(move || -> Result<(), SameyError> {
+        let image = ImageReader::new(Cursor::new(avatar))
+            .with_guessed_format()?
+            .decode()
+            .map_err(|_| SameyError::BadRequest("Avatar must be an image".into()))?;
+        if let Some(directory) = file_path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        image
+            .resize_to_fill(AVATAR_SIZE, AVATAR_SIZE, FilterType::Lanczos3)
+            .save_with_format(&file_path, ImageFormat::Png)?;
+        Ok(())
+    })
+    .await??;
+
+    SameyUser::update(samey_user::ActiveModel {
+        id: Set(user.id),
+        avatar: Set(Some(file_name)),
+        ..Default::default()
+    })
+    .exec(&db)
+    .await?;
+    if let Some(previous_avatar) = user.avatar {
+        let _ = tokio::fs::remove_file(files_dir.join(previous_avatar)).await;
+    }
+
+    Ok(Redirect::to("/preferences"))
+}
+
+/// Removes the avatar of the current user.
+pub(crate) async fn delete_avatar(
+    State(AppState { db, files_dir, .. }): State<AppState>,
+    auth_session: AuthSession,
+) -> Result<impl IntoResponse, SameyError> {
+    let user = match auth_session.user {
+        Some(user) => user,
+        None => return Err(SameyError::Forbidden),
+    };
+
+    SameyUser::update(samey_user::ActiveModel {
+        id: Set(user.id),
+        avatar: Set(None),
+        ..Default::default()
+    })
+    .exec(&db)
+    .await?;
+    if let Some(previous_avatar) = user.avatar {
+        let _ = tokio::fs::remove_file(files_dir.join(previous_avatar)).await;
+    }
+
+    Ok(Redirect::to("/preferences"))
+}
+
+/// Length of personal RSS tokens.
+const RSS_TOKEN_LENGTH: usize = 32;
+
+#[derive(Template)]
+#[template(path = "pages/rss_token.html")]
+struct RssTokenTemplate {
+    base: BaseContext,
+    rss_url: String,
+    /// Activity feed URL, for admins.
+    activity_url: Option<String>,
+}
+
+/// Mints a new personal RSS token for the current user, replacing any previous one.
+///
+/// Only a hash of the token is stored, so the feed URLs with it are shown this one time.
+pub(crate) async fn create_rss_token(
+    State(AppState { db, app_config, .. }): State<AppState>,
+    base: BaseContext,
+    auth_session: AuthSession,
+) -> Result<impl IntoResponse, SameyError> {
+    let user = match auth_session.user {
+        Some(user) => user,
+        None => return Err(SameyError::Forbidden),
+    };
+
+    let token: String = rand::rng()
+        .sample_iter(rand::distr::Alphanumeric)
+        .take(RSS_TOKEN_LENGTH)
+        .map(char::from)
+        .collect();
+    SameyUser::update(samey_user::ActiveModel {
+        id: Set(user.id),
+        rss_token_hash: Set(Some(hash_rss_token(&token))),
+        ..Default::default()
+    })
+    .exec(&db)
+    .await?;
+
+    let base_url = app_config.read().await.base_url.clone();
+    Ok(Html(
+        RssTokenTemplate {
+            base,
+            rss_url: format!("{}/posts.xml?token={}", base_url, token),
+            activity_url: user
+                .is_admin
+                .then(|| format!("{}/activity.xml?token={}", base_url, token)),
+        }
+        .render()?,
+    ))
+}
+
+/// Revokes the personal RSS token of the current user.
+pub(crate) async fn delete_rss_token(
+    State(AppState { db, .. }): State<AppState>,
+    auth_session: AuthSession,
+) -> Result<impl IntoResponse, SameyError> {
+    let user = match auth_session.user {
+        Some(user) => user,
+        None => return Err(SameyError::Forbidden),
+    };
+
+    SameyUser::update(samey_user::ActiveModel {
+        id: Set(user.id),
+        rss_token_hash: Set(None),
+        ..Default::default()
+    })
+    .exec(&db)
+    .await?;
+
+    Ok(Redirect::to("/preferences"))
+}
+
+// Settings views
+
+pub(crate) async fn favicon_ico(
+    State(AppState { files_dir, .. }): State<AppState>,
+) -> Result<impl IntoResponse, SameyError> {
+    let favicon = match tokio::fs::read(files_dir.join("favicon.ico")).await {
+        Ok(favicon) => favicon,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Err(SameyError::NotFound);
+        }
+        Err(err) => return Err(err.into()),
+    };
+    Ok(([(CONTENT_TYPE, "image/x-icon")], favicon))
+}
+
+pub(crate) async fn upload_favicon(
+    State(AppState { files_dir, .. }): State<AppState>,
+    _: RequireRole<Admin>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, SameyError> {
+    let mut favicon = None;
+    while let Some(field) = multipart.next_field().await? {
+        if field.name() == Some("favicon-file") {
+            favicon = Some(field.bytes().await?);
+        }
+    }
+    let favicon = favicon
+        .filter(|favicon| !favicon.is_empty())
+        .ok_or(SameyError::BadRequest("Missing favicon file".into()))?;
+
+    spawn_blocking(move || -> Result<(), SameyError> {
+        let image = ImageReader::new(Cursor::new(favicon))
+            .with_guessed_format()?
+            .decode()?;
+        let favicon = image.resize_to_fill(FAVICON_SIZE, FAVICON_SIZE, FilterType::Lanczos3);
+        favicon.save_with_format(files_dir.join("favicon.png"), ImageFormat::Png)?;
+        favicon.save_with_format(files_dir.join("favicon.ico"), ImageFormat::Ico)?;
+        for size in PWA_ICON_SIZES {
+            image
+                .resize_to_fill(size, size, FilterType::Lanczos3)
+                .save_with_format(
+                    files_dir.join(format!("icon-{}.png", size)),
+                    ImageFormat::Png,
+                )?;
+        }
+        Ok(())
+    })
+    .await??;
+
+    Ok(Redirect::to("/settings/appearance"))
+}
+
+/// Reloads process-level settings, such as limits from the config file, without restarting.
+pub(crate) async fn reload_config(
+    State(AppState { process_config, .. }): State<AppState>,
+    _: RequireRole<Admin>,
+) -> Result<impl IntoResponse, SameyError> {
+    if !process_config.can_reload() {
+        return Err(SameyError::BadRequest(
+            "There is no config file to reload".into(),
+        ));
+    }
+    process_config
+        .reload()
+        .await
+        .map_err(|err| SameyError::BadRequest(err.to_string()))?;
+
+    Ok(Redirect::to("/settings"))
+}
+
+/// Settings on the general page, which also shows the process-level settings.
+#[derive(Debug, Deserialize)]
+pub(crate) struct GeneralSettings {
+    application_name: String,
+    base_url: String,
+    #[serde(default)]
+    age_confirmation: bool,
+    #[serde(default)]
+    age_confirmation_explicit_only: bool,
+    #[serde(default)]
+    read_only: bool,
+    #[serde(default)]
+    hotlink_protection: bool,
+    hotlink_allowed_domains: String,
+    #[serde(default)]
+    bind_sessions_to_user_agent: bool,
+    captcha_provider: String,
+    captcha_site_key: String,
+    captcha_secret_key: String,
+    captcha_login_attempts: u64,
+    robots_txt: String,
+    #[serde(default)]
+    noindex_instance: bool,
+    #[serde(default)]
+    noindex_explicit_posts: bool,
+}
+
+impl GeneralSettings {
+    fn from_config(app_config: &AppConfig) -> Self {
+        Self {
+            application_name: app_config.application_name.clone(),
+            base_url: app_config.base_url.clone(),
+            age_confirmation: app_config.age_confirmation,
+            age_confirmation_explicit_only: app_config.age_confirmation_explicit_only,
+            read_only: app_config.read_only,
+            hotlink_protection: app_config.hotlink_protection,
+            hotlink_allowed_domains: app_config.hotlink_allowed_domains.join(" "),
+            bind_sessions_to_user_agent: app_config.bind_sessions_to_user_agent,
+            captcha_provider: app_config.captcha_provider.clone(),
+            captcha_site_key: app_config.captcha_site_key.clone(),
+            captcha_secret_key: app_config.captcha_secret_key.clone(),
+            captcha_login_attempts: app_config.captcha_login_attempts,
+            robots_txt: app_config.robots_txt.clone(),
+            noindex_instance: app_config.noindex_instance,
+            noindex_explicit_posts: app_config.noindex_explicit_posts,
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "pages/settings_general.html")]
+struct GeneralSettingsTemplate {
+    base: BaseContext,
+    form: GeneralSettings,
+    max_upload_size: usize,
+    auth_backend: &'static str,
+    can_reload_config: bool,
+    errors: FieldErrors,
+}
+
+impl GeneralSettingsTemplate {
+    async fn new(
+        base: BaseContext,
+        process_config: &ProcessConfigHandle,
+        form: GeneralSettings,
+        errors: FieldErrors,
+    ) -> Self {
+        let process_config_values = process_config.get().await;
+        Self {
+            base,
+            form,
+            max_upload_size: process_config_values.max_upload_size,
+            auth_backend: process_config_values.auth.name(),
+            can_reload_config: process_config.can_reload(),
+            errors,
+        }
+    }
+}
+
+pub(crate) async fn general_settings(
+    State(AppState {
+        app_config,
+        process_config,
+        ..
+    }): State<AppState>,
+    base: BaseContext,
+    _: RequireRole<Admin>,
+) -> Result<impl IntoResponse, SameyError> {
+    let form = GeneralSettings::from_config(&*app_config.read().await);
+
+    Ok(Html(
+        GeneralSettingsTemplate::new(base, &process_config, form, FieldErrors::default())
+            .await
+            .render()?,
+    ))
+}
+
+pub(crate) async fn update_general_settings(
+    State(AppState {
+        db,
+        app_config,
+        process_config,
+        ..
+    }): State<AppState>,
+    base: BaseContext,
+    _: RequireRole<Admin>,
+    Form(body): Form<GeneralSettings>,
+) -> Result<impl IntoResponse, SameyError> {
+    let mut errors = FieldErrors::default();
+
+    let base_url = body.base_url.trim().trim_end_matches('/');
+    if base_url.is_empty() {
+        if app_config.read().await.activitypub_enabled {
+            errors.add(
+                "base_url",
+                "Base URL is required while ActivityPub is enabled",
+            );
+        }
+    } else if !reqwest::Url::parse(base_url)
+        .is_ok_and(|url| ["http", "https"].contains(&url.scheme()))
+    {
+        errors.add(
+            "base_url",
+            "Base URL must be an absolute URL, such as https://example.com",
+        );
+    }
+    let captcha_provider = body.captcha_provider.trim();
+    if !captcha_provider.is_empty() {
+        match CaptchaProvider::parse(captcha_provider) {
+            Some(provider) => {
+                if provider.needs_keys()
+                    && (body.captcha_site_key.trim().is_empty()
+                        || body.captcha_secret_key.trim().is_empty())
+                {
+                    errors.add(
+                        "captcha_provider",
+                        "Site key and secret key are required for this CAPTCHA",
+                    );
+                }
+            }
+            None => errors.add("captcha_provider", "Unknown CAPTCHA provider"),
+        }
+    }
+    errors.check_range(
+        "captcha_login_attempts",
+        body.captcha_login_attempts,
+        &CAPTCHA_LOGIN_ATTEMPTS_RANGE,
+    );
+
+    if !errors.is_empty() {
+        // Nothing is saved, and the form is shown again with what was submitted
+        return Ok((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Html(
+                GeneralSettingsTemplate::new(base, &process_config, body, errors)
+                    .await
+                    .render()?,
+            ),
+        )
+            .into_response());
+    }
+
+    let mut update = ConfigUpdate::default();
+    let application_name = body.application_name.trim();
+    if !application_name.is_empty() {
+        update.set(APPLICATION_NAME_KEY, application_name)?;
+    }
+    update.set(BASE_URL_KEY, base_url)?;
+    update.set(AGE_CONFIRMATION_KEY, body.age_confirmation)?;
+    update.set(
+        AGE_CONFIRMATION_EXPLICIT_ONLY_KEY,
+        body.age_confirmation_explicit_only,
+    )?;
+    update.set(READ_ONLY_KEY, body.read_only)?;
+    update.set(HOTLINK_PROTECTION_KEY, body.hotlink_protection)?;
+    update.set(
+        HOTLINK_ALLOWED_DOMAINS_KEY,
+        body.hotlink_allowed_domains
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|domain| !domain.is_empty())
+            .map(|domain| domain.to_lowercase())
+            .collect::<Vec<_>>(),
+    )?;
+    update.set(
+        BIND_SESSIONS_TO_USER_AGENT_KEY,
+        body.bind_sessions_to_user_agent,
+    )?;
+    update.set(CAPTCHA_PROVIDER_KEY, captcha_provider)?;
+    update.set(CAPTCHA_SITE_KEY_KEY, body.captcha_site_key.trim())?;
+    update.set(CAPTCHA_SECRET_KEY_KEY, body.captcha_secret_key.trim())?;
+    update.set(CAPTCHA_LOGIN_ATTEMPTS_KEY, body.captcha_login_attempts)?;
+    update.set(ROBOTS_TXT_KEY, body.robots_txt.replace("\r\n", "\n"))?;
+    update.set(NOINDEX_INSTANCE_KEY, body.noindex_instance)?;
+    update.set(NOINDEX_EXPLICIT_POSTS_KEY, body.noindex_explicit_posts)?;
+    update.save(&db, &app_config).await?;
+
+    Ok(Redirect::to("/settings").into_response())
+}
+
+/// Settings of uploads and posts, such as their limits and ratings.
+#[derive(Debug, Deserialize)]
+pub(crate) struct UploadSettings {
+    #[serde(default)]
+    clamav_enabled: bool,
+    clamav_address: String,
+    thumbnail_dimension: u32,
+    #[serde(default)]
+    streaming_enabled: bool,
+    streaming_min_duration: u64,
+    streaming_min_size: u64,
+    attachment_max_size: u64,
+    title_max_length: u64,
+    description_max_length: u64,
+    ratings: String,
+    #[serde(default)]
+    allow_unrated: bool,
+    default_rating: String,
+    #[serde(default)]
+    default_public: bool,
+    #[serde(default)]
+    require_rating_to_publish: bool,
+    #[serde(default)]
+    delete_expired_posts: bool,
+}
+
+impl UploadSettings {
+    fn from_config(app_config: &AppConfig) -> Self {
+        Self {
+            clamav_enabled: app_config.clamav_enabled,
+            clamav_address: app_config.clamav_address.clone(),
+            thumbnail_dimension: app_config.thumbnail_dimension,
+            streaming_enabled: app_config.streaming_enabled,
+            streaming_min_duration: app_config.streaming_min_duration,
+            streaming_min_size: app_config.streaming_min_size,
+            attachment_max_size: app_config.attachment_max_size,
+            title_max_length: app_config.title_max_length,
+            description_max_length: app_config.description_max_length,
+            ratings: format_ratings(&app_config.ratings),
+            allow_unrated: app_config.allow_unrated,
+            default_rating: app_config.default_rating.clone(),
+            default_public: app_config.default_public,
+            require_rating_to_publish: app_config.require_rating_to_publish,
+            delete_expired_posts: app_config.delete_expired_posts,
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "pages/settings_uploads.html")]
+struct UploadSettingsTemplate {
+    base: BaseContext,
+    form: UploadSettings,
+    rating_choices: Vec<Rating>,
+    errors: FieldErrors,
+}
+
+pub(crate) async fn upload_settings(
+    State(AppState { app_config, .. }): State<AppState>,
+    base: BaseContext,
+    _: RequireRole<Admin>,
+) -> Result<impl IntoResponse, SameyError> {
+    let app_config = app_config.read().await;
+    let form = UploadSettings::from_config(&app_config);
+    let rating_choices = app_config.ratings.clone();
+    drop(app_config);
+
+    Ok(Html(
+        UploadSettingsTemplate {
+            base,
+            form,
+            rating_choices,
+            errors: FieldErrors::default(),
+        }
+        .render()?,
+    ))
+}
+
+pub(crate) async fn update_upload_settings(
+    State(AppState {
+        db,
+        app_config,
+        files_dir,
+        thumbnailer,
+        ..
+    }): State<AppState>,
+    base: BaseContext,
+    _: RequireRole<Admin>,
+    Form(body): Form<UploadSettings>,
+) -> Result<impl IntoResponse, SameyError> {
+    let mut errors = FieldErrors::default();
+
+    if body.clamav_enabled && body.clamav_address.trim().is_empty() {
+        errors.add(
+            "clamav_address",
+            "ClamAV address is required for virus scanning",
+        );
+    }
+    if !THUMBNAIL_DIMENSION_RANGE.contains(&body.thumbnail_dimension) {
+        errors.add(
+            "thumbnail_dimension",
+            format!(
+                "Thumbnail size must be between {} and {} pixels",
+                THUMBNAIL_DIMENSION_RANGE.start(),
+                THUMBNAIL_DIMENSION_RANGE.end()
+            ),
+        );
+    }
+    errors.check_range(
+        "streaming_min_duration",
+        body.streaming_min_duration,
+        &STREAMING_MIN_DURATION_RANGE,
+    );
+    errors.check_range(
+        "streaming_min_size",
+        body.streaming_min_size,
+        &STREAMING_MIN_SIZE_RANGE,
+    );
+    errors.check_range(
+        "attachment_max_size",
+        body.attachment_max_size,
+        &ATTACHMENT_MAX_SIZE_RANGE,
+    );
+    errors.check_range(
+        "title_max_length",
+        body.title_max_length,
+        &TITLE_MAX_LENGTH_RANGE,
+    );
+    errors.check_range(
+        "description_max_length",
+        body.description_max_length,
+        &DESCRIPTION_MAX_LENGTH_RANGE,
+    );
+
+    let ratings = errors.check("ratings", parse_ratings(&body.ratings))?;
+    let default_rating = body.default_rating.trim();
+    if let Some(ratings) = &ratings {
+        // Posts keep their rating code, so a rating can only be removed once no post uses it
+        if let Some(post) = SameyPost::find()
+            .filter(samey_post::Column::Rating.ne(UNRATED))
+            .filter(samey_post::Column::Rating.is_not_in(ratings.iter().map(|rating| &rating.code)))
+            .one(&db)
+            .await?
+        {
+            errors.add(
+                "ratings",
+                format!(
+                    "Rating \"{}\" can't be removed while posts use it, such as #{}",
+                    post.rating, post.id
+                ),
+            );
+        }
+        if default_rating != UNRATED && !ratings.iter().any(|rating| rating.code == default_rating)
+        {
+            errors.add(
+                "default_rating",
+                format!(
+                    "Default rating \"{}\" is not one of the ratings",
+                    default_rating
+                ),
+            );
+        }
+    }
+    if body.default_public && body.require_rating_to_publish && default_rating == UNRATED {
+        errors.add(
+            "default_public",
+            "New uploads can't be public when they are unrated and a rating is required to publish",
+        );
+    }
+
+    if !errors.is_empty() {
+        // Nothing is saved, and the form is shown again with what was submitted
+        let rating_choices = match ratings {
+            Some(ratings) => ratings,
+            None => app_config.read().await.ratings.clone(),
+        };
+        return Ok((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Html(
+                UploadSettingsTemplate {
+                    base,
+                    form: body,
+                    rating_choices,
+                    errors,
+                }
+                .render()?,
+            ),
+        )
+            .into_response());
+    }
+
+    let previous_thumbnail_dimension = app_config.read().await.thumbnail_dimension;
+    let thumbnail_dimension = body.thumbnail_dimension;
+
+    let mut update = ConfigUpdate::default();
+    update.set(CLAMAV_ENABLED_KEY, body.clamav_enabled)?;
+    update.set(CLAMAV_ADDRESS_KEY, body.clamav_address.trim())?;
+    update.set(THUMBNAIL_DIMENSION_KEY, thumbnail_dimension)?;
+    update.set(STREAMING_ENABLED_KEY, body.streaming_enabled)?;
+    update.set(STREAMING_MIN_DURATION_KEY, body.streaming_min_duration)?;
+    update.set(STREAMING_MIN_SIZE_KEY, body.streaming_min_size)?;
+    update.set(ATTACHMENT_MAX_SIZE_KEY, body.attachment_max_size)?;
+    update.set(TITLE_MAX_LENGTH_KEY, body.title_max_length)?;
+    update.set(DESCRIPTION_MAX_LENGTH_KEY, body.description_max_length)?;
+    update.set(RATINGS_KEY, ratings.unwrap_or_default())?;
+    update.set(ALLOW_UNRATED_KEY, body.allow_unrated)?;
+    update.set(DEFAULT_RATING_KEY, default_rating)?;
+    update.set(DEFAULT_PUBLIC_KEY, body.default_public)?;
+    update.set(
+        REQUIRE_RATING_TO_PUBLISH_KEY,
+        body.require_rating_to_publish,
+    )?;
+    update.set(DELETE_EXPIRED_POSTS_KEY, body.delete_expired_posts)?;
+    update.save(&db, &app_config).await?;
+
+    // Existing thumbnails would otherwise keep their old size next to new uploads
+    if thumbnail_dimension != previous_thumbnail_dimension {
+        tokio::spawn(async move {
+            if let Err(err) = regenerate_thumbnails(
+                &db,
+                &files_dir,
+                thumbnailer.as_ref(),
+                &app_config,
+                thumbnail_dimension,
+            )
+            .await
+            {
+                println!("Error when regenerating thumbnails - {}", err);
+            }
+        });
+    }
+
+    Ok(Redirect::to("/settings/uploads").into_response())
+}
+
+/// Settings of the external services that the instance talks to.
+#[derive(Debug, Deserialize)]
+pub(crate) struct IntegrationSettings {
+    #[serde(default)]
+    auto_tagger_enabled: bool,
+    auto_tagger_url: String,
+    auto_tagger_threshold: f64,
+    #[serde(default)]
+    activitypub_enabled: bool,
+    activitypub_username: String,
+    saucenao_api_key: String,
+    #[serde(default)]
+    iqdb_enabled: bool,
+    smtp_url: String,
+    smtp_from: String,
+    moderation_digest_emails: String,
+    moderation_digest_webhook_url: String,
+}
+
+impl IntegrationSettings {
+    fn from_config(app_config: &AppConfig) -> Self {
+        Self {
+            auto_tagger_enabled: app_config.auto_tagger_enabled,
+            auto_tagger_url: app_config.auto_tagger_url.clone(),
+            auto_tagger_threshold: app_config.auto_tagger_threshold,
+            activitypub_enabled: app_config.activitypub_enabled,
+            activitypub_username: app_config.activitypub_username.clone(),
+            saucenao_api_key: app_config.saucenao_api_key.clone(),
+            iqdb_enabled: app_config.iqdb_enabled,
+            smtp_url: app_config.smtp_url.clone(),
+            smtp_from: app_config.smtp_from.clone(),
+            moderation_digest_emails: app_config.moderation_digest_emails.join(" "),
+            moderation_digest_webhook_url: app_config.moderation_digest_webhook_url.clone(),
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "pages/settings_integrations.html")]
+struct IntegrationSettingsTemplate {
+    base: BaseContext,
+    form: IntegrationSettings,
+    errors: FieldErrors,
+}
+
+pub(crate) async fn integration_settings(
+    State(AppState { app_config, .. }): State<AppState>,
+    base: BaseContext,
+    _: RequireRole<Admin>,
+) -> Result<impl IntoResponse, SameyError> {
+    let form = IntegrationSettings::from_config(&*app_config.read().await);
+
+    Ok(Html(
+        IntegrationSettingsTemplate {
+            base,
+            form,
+            errors: FieldErrors::default(),
+        }
+        .render()?,
+    ))
+}
+
+pub(crate) async fn update_integration_settings(
+    State(AppState { db, app_config, .. }): State<AppState>,
+    base: BaseContext,
+    _: RequireRole<Admin>,
+    Form(body): Form<IntegrationSettings>,
+) -> Result<impl IntoResponse, SameyError> {
+    let mut errors = FieldErrors::default();
+
+    if body.auto_tagger_enabled && body.auto_tagger_url.trim().is_empty() {
+        errors.add(
+            "auto_tagger_url",
+            "Auto-tagger URL is required for tag suggestions",
+        );
+    }
+    if !(0.0..=1.0).contains(&body.auto_tagger_threshold) {
+        errors.add(
+            "auto_tagger_threshold",
+            "Auto-tagger threshold must be between 0 and 1",
+        );
+    }
+    let activitypub_username = body.activitypub_username.trim();
+    if activitypub_username.is_empty()
+        || !activitypub_username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        errors.add(
+            "activitypub_username",
+            "ActivityPub username may only contain letters, digits and underscores",
+        );
+    }
+    let (base_url_is_empty, has_private_key) = {
+        let app_config = app_config.read().await;
+        (
+            app_config.base_url.is_empty(),
+            !app_config.activitypub_private_key.is_empty(),
+        )
+    };
+    if body.activitypub_enabled && base_url_is_empty {
+        errors.add(
+            "activitypub_enabled",
+            "Base URL is required for ActivityPub",
+        );
+    }
+
+    let smtp_url = body.smtp_url.trim();
+    if !smtp_url.is_empty()
+        && !["smtp://", "smtps://"]
+            .iter()
+            .any(|scheme| smtp_url.starts_with(scheme))
+    {
+        errors.add("smtp_url", "SMTP URL must start with smtp:// or smtps://");
+    }
+    let smtp_from = body.smtp_from.trim();
+    if !smtp_from.is_empty() {
+        errors.check("smtp_from", parse_mailbox(smtp_from))?;
+    }
+    let moderation_digest_emails: Vec<&str> = body
+        .moderation_digest_emails
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|email| !email.is_empty())
+        .collect();
+    for email in &moderation_digest_emails {
+        errors.check("moderation_digest_emails", parse_mailbox(email))?;
+    }
+    if !moderation_digest_emails.is_empty() && (smtp_url.is_empty() || smtp_from.is_empty()) {
+        errors.add(
+            "moderation_digest_emails",
+            "SMTP URL and sender are required for digest emails",
+        );
+    }
+    let moderation_digest_webhook_url = body.moderation_digest_webhook_url.trim();
+    if !moderation_digest_webhook_url.is_empty()
+        && !["http://", "https://"]
+            .iter()
+            .any(|scheme| moderation_digest_webhook_url.starts_with(scheme))
+    {
+        errors.add(
+            "moderation_digest_webhook_url",
+            "Digest webhook URL must start with http:// or https://",
+        );
+    }
+
+    if !errors.is_empty() {
+        // Nothing is saved, and the form is shown again with what was submitted
+        return Ok((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Html(
+                IntegrationSettingsTemplate {
+                    base,
+                    form: body,
+                    errors,
+                }
+                .render()?,
+            ),
+        )
+            .into_response());
+    }
+
+    let mut update = ConfigUpdate::default();
+    update.set(AUTO_TAGGER_ENABLED_KEY, body.auto_tagger_enabled)?;
+    update.set(AUTO_TAGGER_URL_KEY, body.auto_tagger_url.trim())?;
+    update.set(AUTO_TAGGER_THRESHOLD_KEY, body.auto_tagger_threshold)?;
+    if body.activitypub_enabled && !has_private_key {
+        update.set(ACTIVITYPUB_PRIVATE_KEY_KEY, generate_private_key().await?)?;
+    }
+    update.set(ACTIVITYPUB_ENABLED_KEY, body.activitypub_enabled)?;
+    update.set(ACTIVITYPUB_USERNAME_KEY, activitypub_username)?;
+    update.set(SAUCENAO_API_KEY_KEY, body.saucenao_api_key.trim())?;
+    update.set(IQDB_ENABLED_KEY, body.iqdb_enabled)?;
+    update.set(SMTP_URL_KEY, smtp_url)?;
+    update.set(SMTP_FROM_KEY, smtp_from)?;
+    update.set(MODERATION_DIGEST_EMAILS_KEY, moderation_digest_emails)?;
+    update.set(
+        MODERATION_DIGEST_WEBHOOK_URL_KEY,
+        moderation_digest_webhook_url,
+    )?;
+    update.save(&db, &app_config).await?;
+
+    Ok(Redirect::to("/settings/integrations").into_response())
+}
+
+/// Settings of how the instance looks, including its index page.
+#[derive(Debug, Deserialize)]
+pub(crate) struct AppearanceSettings {
+    featured_post_ids: String,
+    featured_tags: String,
+    index_recent_posts: u64,
+    index_top_tags: u64,
+    #[serde(default)]
+    stats_enabled: bool,
+    accent_color: String,
+    logo_url: String,
+    custom_css: String,
+    announcement_message: String,
+    announcement_expires_at: String,
+}
+
+impl AppearanceSettings {
+    fn from_config(app_config: &AppConfig) -> Self {
+        Self {
+            featured_post_ids: app_config.featured_post_ids.iter().join(" "),
+            featured_tags: app_config.featured_tags.clone(),
+            index_recent_posts: app_config.index_recent_posts,
+            index_top_tags: app_config.index_top_tags,
+            stats_enabled: app_config.stats_enabled,
+            accent_color: app_config.accent_color.clone(),
+            logo_url: app_config.logo_url.clone(),
+            custom_css: app_config.custom_css.clone(),
+            announcement_message: app_config.announcement_message.clone(),
+            announcement_expires_at: app_config
+                .announcement_expires_at
+                .map(|expires_at| expires_at.format(DATETIME_INPUT_FORMAT).to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Validates the submitted values, returning a copy of the config with them applied.
+    fn apply(&self, app_config: &AppConfig, errors: &mut FieldErrors) -> AppConfig {
+        let mut config = app_config.clone();
+
+        config.featured_post_ids = self
+            .featured_post_ids
+            .split_whitespace()
+            .map(|id| id.parse::<i32>())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_or_else(|_| {
+                errors.add("featured_post_ids", "Invalid featured post ID");
+                vec![]
+            });
+        config.featured_tags = self.featured_tags.split_whitespace().join(" ");
+
+        errors.check_range(
+            "index_recent_posts",
+            self.index_recent_posts,
+            &INDEX_RECENT_POSTS_RANGE,
+        );
+        config.index_recent_posts = self.index_recent_posts;
+        errors.check_range("index_top_tags", self.index_top_tags, &INDEX_TOP_TAGS_RANGE);
+        config.index_top_tags = self.index_top_tags;
+        config.stats_enabled = self.stats_enabled;
+
+        let accent_color = self.accent_color.trim();
+        let is_valid_accent_color = accent_color.is_empty()
+            || accent_color
+                .strip_prefix('#')
+                .is_some_and(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()));
+        if !is_valid_accent_color {
+            errors.add("accent_color", "Accent color must be in #rrggbb format");
+        }
+        config.accent_color = accent_color.into();
+        config.logo_url = self.logo_url.trim().into();
+        config.custom_css = self.custom_css.replace("\r\n", "\n");
+        config.announcement_message = self.announcement_message.trim().replace("\r\n", "\n");
+        config.announcement_expires_at = match self.announcement_expires_at.trim() {
+            "" => None,
+            expires_at => NaiveDateTime::parse_from_str(expires_at, DATETIME_INPUT_FORMAT)
+                .inspect_err(|_| {
+                    errors.add("announcement_expires_at", "Invalid announcement expiry")
+                })
+                .ok(),
+        };
+
+        config
+    }
+}
+
+#[derive(Template)]
+#[template(path = "pages/settings_appearance.html")]
+struct AppearanceSettingsTemplate {
+    base: BaseContext,
+    form: AppearanceSettings,
+    errors: FieldErrors,
+}
+
+pub(crate) async fn appearance_settings(
+    State(AppState { app_config, .. }): State<AppState>,
+    base: BaseContext,
+    _: RequireRole<Admin>,
+) -> Result<impl IntoResponse, SameyError> {
+    let form = AppearanceSettings::from_config(&*app_config.read().await);
+
+    Ok(Html(
+        AppearanceSettingsTemplate {
+            base,
+            form,
+            errors: FieldErrors::default(),
+        }
+        .render()?,
+    ))
+}
+
+pub(crate) async fn update_appearance_settings(
+    State(AppState { db, app_config, .. }): State<AppState>,
+    base: BaseContext,
+    _: RequireRole<Admin>,
+    Form(body): Form<AppearanceSettings>,
+) -> Result<impl IntoResponse, SameyError> {
+    let mut errors = FieldErrors::default();
+    let config = body.apply(&*app_config.read().await, &mut errors);
+
+    if !errors.is_empty() {
+        // Nothing is saved, and the form is shown again with what was submitted
+        return Ok((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Html(
+                AppearanceSettingsTemplate {
+                    base,
+                    form: body,
+                    errors,
+                }
+                .render()?,
+            ),
+        )
+            .into_response());
+    }
+
+    let mut update = ConfigUpdate::default();
+    update.set(FEATURED_POST_IDS_KEY, config.featured_post_ids)?;
+    update.set(FEATURED_TAGS_KEY, config.featured_tags)?;
+    update.set(INDEX_RECENT_POSTS_KEY, config.index_recent_posts)?;
+    update.set(INDEX_TOP_TAGS_KEY, config.index_top_tags)?;
+    update.set(STATS_ENABLED_KEY, config.stats_enabled)?;
+    update.set(ACCENT_COLOR_KEY, config.accent_color)?;
+    update.set(LOGO_URL_KEY, config.logo_url)?;
+    update.set(CUSTOM_CSS_KEY, config.custom_css)?;
+    update.set(ANNOUNCEMENT_MESSAGE_KEY, config.announcement_message)?;
+    update.set(
+        ANNOUNCEMENT_EXPIRES_AT_KEY,
+        config
+            .announcement_expires_at
+            .map(|expires_at| expires_at.format(DATETIME_INPUT_FORMAT).to_string()),
+    )?;
+    update.save(&db, &app_config).await?;
+
+    Ok(Redirect::to("/settings/appearance").into_response())
+}
+
+/// Renders the index page with the submitted appearance settings, without saving them.
+pub(crate) async fn preview_appearance_settings(
+    State(AppState {
+        db,
+        app_config,
+        object_cache,
+        ..
+    }): State<AppState>,
+    mut base: BaseContext,
+    _: RequireRole<Admin>,
+    Form(body): Form<AppearanceSettings>,
+) -> Result<impl IntoResponse, SameyError> {
+    let mut errors = FieldErrors::default();
+    let preview = body.apply(&*app_config.read().await, &mut errors);
+
+    if !errors.is_empty() {
+        return Ok((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Html(
+                AppearanceSettingsTemplate {
+                    base,
+                    form: body,
+                    errors,
+                }
+                .render()?,
+            ),
+        )
+            .into_response());
+    }
+
+    base.logo_url = preview.logo_url.clone();
+    base.announcement = preview.announcement();
+    let mut template = index_template(
+        &db,
+        &object_cache,
+        base,
+        IndexSettings::from_config(&preview),
+    )
+    .await?;
+    template.preview_css = Some(preview.theme_css());
+
+    Ok(Html(template.render()?).into_response())
+}
+
+// Single post views
 
 #[derive(Template)]
 #[template(path = "pages/view_post.html")]
 struct ViewPostPageTemplate {
-    application_name: String,
-    age_confirmation: bool,
+    base: BaseContext,
     post: samey_post::Model,
     description_plaintext: Option<String>,
     pool_data: Vec<PostPoolData>,
@@ -1469,39 +5039,83 @@ struct ViewPostPageTemplate {
     tags_text: Option<String>,
     tags_post: String,
     sources: Vec<samey_post_source::Model>,
+    attachments: Vec<samey_post_attachment::Model>,
     can_edit: bool,
+    can_report: bool,
     parent_post: Option<PostOverview>,
     children_posts: Vec<PostOverview>,
+    previous_result_id: Option<i32>,
+    next_result_id: Option<i32>,
     host: String,
+    noindex: bool,
+    rating_label: String,
+    uploaded_at: String,
+    publish_at: Option<String>,
+    expires_at: Option<String>,
 }
 
 pub(crate) async fn view_post_page(
-    State(AppState { db, app_config, .. }): State<AppState>,
+    State(AppState {
+        db,
+        app_config,
+        view_counter,
+        object_cache,
+        ..
+    }): State<AppState>,
+    base: BaseContext,
     auth_session: AuthSession,
+    headers: HeaderMap,
     Query(query): Query<PostsQuery>,
-    Path(post_id): Path<i32>,
+    Path(post_id): Path<String>,
     Host(host): Host,
-) -> Result<impl IntoResponse, SameyError> {
-    let app_config = app_config.read().await;
-    let application_name = app_config.application_name.clone();
-    let age_confirmation = app_config.age_confirmation;
-    drop(app_config);
+) -> Result<Response, SameyError> {
+    let (post_id, is_json) = match post_id.strip_suffix(".json") {
+        Some(post_id) => (post_id, true),
+        None => (post_id.as_str(), accepts_json(&headers)),
+    };
+    let post_id: i32 = post_id.parse().map_err(|_| SameyError::NotFound)?;
+    if is_json {
+        return Ok((
+            [(VARY, "Accept")],
+            Json(get_post_response(&db, auth_session.user.as_ref(), post_id).await?),
+        )
+            .into_response());
+    }
 
     let post = SameyPost::find_by_id(post_id)
         .one(&db)
         .await?
         .ok_or(SameyError::NotFound)?;
 
+    // Links to posts that were taken down lead to a tombstone instead, for everyone but admins
+    if post.taken_down_at.is_some() && auth_session.user.as_ref().is_none_or(|user| !user.is_admin)
+    {
+        return Ok((
+            StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+            Html(TakenDownTemplate { base, post_id }.render()?),
+        )
+            .into_response());
+    }
+
+    let app_config = app_config.read().await;
+    let noindex = app_config.noindex_explicit_posts && app_config.is_sensitive_rating(&post.rating);
+    let rating_label = app_config.rating_label(&post.rating);
+    drop(app_config);
+
     let can_edit = match auth_session.user.as_ref() {
         None => false,
         Some(user) => user.is_admin || post.uploader_id == user.id,
     };
 
-    if !post.is_public && !can_edit {
+    if !is_post_published(&post) && !can_edit {
         return Err(SameyError::NotFound);
     }
+    view_counter.record(post_id);
 
-    let tags = get_tags_for_post(post_id).all(&db).await?;
+    let can_edit =
+        can_edit && (!post.is_locked || auth_session.user.as_ref().is_some_and(|u| u.is_admin));
+
+    let tags = object_cache.post_tags(&db, post_id).await?.to_vec();
     let tags_post = tags.iter().map(|tag| &tag.name).join(" ");
 
     let sources = SameyPostSource::find()
@@ -1509,6 +5123,11 @@ pub(crate) async fn view_post_page(
         .all(&db)
         .await?;
 
+    let attachments = SameyPostAttachment::find()
+        .filter(samey_post_attachment::Column::PostId.eq(post_id))
+        .all(&db)
+        .await?;
+
     let parent_post = if let Some(parent_id) = post.parent_id {
         match filter_posts_by_user(SameyPost::find_by_id(parent_id), auth_session.user.as_ref())
             .one(&db)
@@ -1517,6 +5136,7 @@ pub(crate) async fn view_post_page(
             Some(parent_post) => Some(PostOverview {
                 id: parent_id,
                 thumbnail: parent_post.thumbnail,
+                thumbnail_variants: parent_post.thumbnail_variants,
                 title: parent_post.title,
                 description: parent_post.description,
                 uploaded_at: parent_post.uploaded_at,
@@ -1550,6 +5170,7 @@ pub(crate) async fn view_post_page(
         children_posts.push(PostOverview {
             id: child_post.id,
             thumbnail: child_post.thumbnail,
+            thumbnail_variants: child_post.thumbnail_variants,
             title: child_post.title,
             description: child_post.description,
             uploaded_at: child_post.uploaded_at,
@@ -1569,6 +5190,20 @@ pub(crate) async fn view_post_page(
 
     let pool_data = get_pool_data_for_post(&db, post_id, auth_session.user.as_ref()).await?;
 
+    // Coming from a search, link to the neighbouring results to browse them one by one
+    let (previous_result_id, next_result_id) = match query.tags.as_deref() {
+        Some(tags) => {
+            get_search_neighbours(
+                &db,
+                &SearchQuery::parse(tags),
+                auth_session.user.as_ref(),
+                post_id,
+            )
+            .await?
+        }
+        None => (None, None),
+    };
+
     let description_plaintext = post.description.as_ref().map(|description| {
         use pulldown_cmark::{Event, Options, Parser, TagEnd, html::write_html_fmt};
 
@@ -1577,10 +5212,7 @@ pub(crate) async fn view_post_page(
                 Event::End(TagEnd::Paragraph) => Event::Text(" ".into()),
                 _ => event,
             })
-            .filter(|event| match event {
-                Event::Text(_) => true,
-                _ => false,
-            });
+            .filter(|event| matches!(event, Event::Text(_)));
         let mut buf = String::new();
         write_html_fmt(&mut buf, parser)
             .ok()
@@ -1588,24 +5220,40 @@ pub(crate) async fn view_post_page(
             .unwrap_or_else(|| description.clone())
     });
 
-    Ok(Html(
-        ViewPostPageTemplate {
-            application_name,
-            age_confirmation,
-            post,
-            description_plaintext,
-            pool_data,
-            tags,
-            tags_text: query.tags,
-            tags_post,
-            sources,
-            can_edit,
-            parent_post,
-            children_posts,
-            host,
-        }
-        .render()?,
-    ))
+    Ok((
+        [(VARY, "Accept")],
+        Html(
+            ViewPostPageTemplate {
+                uploaded_at: base.preferences.format_datetime(&post.uploaded_at),
+                publish_at: post
+                    .publish_at
+                    .map(|publish_at| base.preferences.format_datetime(&publish_at)),
+                expires_at: post
+                    .expires_at
+                    .map(|expires_at| base.preferences.format_datetime(&expires_at)),
+                base,
+                post,
+                description_plaintext,
+                pool_data,
+                tags,
+                tags_text: query.tags,
+                tags_post,
+                sources,
+                attachments,
+                can_edit,
+                can_report: auth_session.user.is_some(),
+                parent_post,
+                children_posts,
+                previous_result_id,
+                next_result_id,
+                host,
+                noindex,
+                rating_label,
+            }
+            .render()?,
+        ),
+    )
+        .into_response())
 }
 
 #[derive(Template)]
@@ -1614,11 +5262,16 @@ struct PostDetailsTemplate {
     post: samey_post::Model,
     sources: Vec<samey_post_source::Model>,
     can_edit: bool,
+    rating_label: String,
+    uploaded_at: String,
+    publish_at: Option<String>,
+    expires_at: Option<String>,
 }
 
 pub(crate) async fn post_details(
-    State(AppState { db, .. }): State<AppState>,
+    State(AppState { db, app_config, .. }): State<AppState>,
     auth_session: AuthSession,
+    preferences: Preferences,
     Path(post_id): Path<i32>,
 ) -> Result<impl IntoResponse, SameyError> {
     let sources = SameyPostSource::find()
@@ -1631,17 +5284,30 @@ pub(crate) async fn post_details(
         .await?
         .ok_or(SameyError::NotFound)?;
 
-    let can_edit = match auth_session.user {
+    let can_edit = match auth_session.user.as_ref() {
         None => false,
-        Some(user) => user.is_admin || post.uploader_id == user.id,
+        Some(user) => {
+            user.is_admin || (post.uploader_id == user.id && post.taken_down_at.is_none())
+        }
     };
 
-    if !post.is_public && !can_edit {
+    if !is_post_published(&post) && !can_edit {
         return Err(SameyError::NotFound);
     }
 
+    let can_edit =
+        can_edit && (!post.is_locked || auth_session.user.as_ref().is_some_and(|u| u.is_admin));
+
     Ok(Html(
         PostDetailsTemplate {
+            uploaded_at: preferences.format_datetime(&post.uploaded_at),
+            publish_at: post
+                .publish_at
+                .map(|publish_at| preferences.format_datetime(&publish_at)),
+            expires_at: post
+                .expires_at
+                .map(|expires_at| preferences.format_datetime(&expires_at)),
+            rating_label: app_config.read().await.rating_label(&post.rating),
             post,
             sources,
             can_edit,
@@ -1654,12 +5320,32 @@ pub(crate) async fn post_details(
 pub(crate) struct SubmitPostDetailsForm {
     title: String,
     description: String,
+    translation_language: String,
+    translated_title: String,
+    translated_description: String,
     is_public: Option<String>,
+    /// When the post becomes public, empty to publish it right away.
+    publish_at: String,
+    /// When the post stops being public, empty to keep it public.
+    expires_at: String,
+    is_locked: Option<String>,
     rating: String,
     #[serde(rename = "source")]
     sources: Option<Vec<String>>,
     tags: String,
     parent_post: String,
+    /// Version of the post when editing started.
+    version: i32,
+    /// Save even if the post was changed since editing started.
+    force: Option<bool>,
+}
+
+#[derive(Template)]
+#[template(path = "fragments/post_edit_conflict.html")]
+struct PostEditConflictTemplate {
+    post_id: i32,
+    version: i32,
+    form: SubmitPostDetailsForm,
 }
 
 #[derive(Template)]
@@ -1671,50 +5357,175 @@ struct SubmitPostDetailsTemplate {
     tags: Vec<samey_tag::Model>,
     tags_text: String,
     can_edit: bool,
+    rating_label: String,
+    uploaded_at: String,
+    publish_at: Option<String>,
+    expires_at: Option<String>,
 }
 
 pub(crate) async fn submit_post_details(
-    State(AppState { db, .. }): State<AppState>,
-    auth_session: AuthSession,
-    Path(post_id): Path<i32>,
+    State(AppState { db, app_config, .. }): State<AppState>,
+    preferences: Preferences,
+    RequireOwnerOrAdmin(user, post): RequireOwnerOrAdmin<SameyPost>,
     Form(body): Form<SubmitPostDetailsForm>,
 ) -> Result<impl IntoResponse, SameyError> {
-    let post = SameyPost::find_by_id(post_id)
-        .one(&db)
-        .await?
-        .ok_or(SameyError::NotFound)?;
+    let mut errors = FieldErrors::default();
+    let tags: HashSet<String> = body.tags.split_whitespace().map(String::from).collect();
+    let normalized_tags: HashSet<String> = tags.iter().map(|tag| tag.to_lowercase()).collect();
 
-    match auth_session.user.as_ref() {
-        None => return Err(SameyError::Forbidden),
-        Some(user) => {
-            if !user.is_admin && post.uploader_id != user.id {
-                return Err(SameyError::Forbidden);
-            }
+    if !user.is_admin {
+        let current_protected_tags: HashSet<String> = get_protected_tags()
+            .inner_join(SameyTagPost)
+            .filter(samey_tag_post::Column::PostId.eq(post.id))
+            .all(&db)
+            .await?
+            .into_iter()
+            .map(|tag| tag.name)
+            .collect();
+        let submitted_protected_tags: HashSet<String> = get_protected_tags()
+            .filter(
+                samey_tag::Column::NormalizedName.is_in(normalized_tags.iter().map(String::as_str)),
+            )
+            .all(&db)
+            .await?
+            .into_iter()
+            .map(|tag| tag.name)
+            .collect();
+        if let Some(tag) = current_protected_tags
+            .symmetric_difference(&submitted_protected_tags)
+            .next()
+        {
+            errors.add("tags", format!("Tag {} is protected", tag));
         }
     }
 
-    let title = match body.title.trim() {
+    let title = optional_text(&body.title);
+    let description = optional_text(&body.description);
+    let translated_title = optional_text(&body.translated_title);
+    let translated_description = optional_text(&body.translated_description);
+    let translation_language = optional_text(&body.translation_language);
+    let (title_max_length, description_max_length, is_valid_rating, require_rating_to_publish) = {
+        let app_config = app_config.read().await;
+        (
+            app_config.title_max_length,
+            app_config.description_max_length,
+            app_config.is_valid_rating(&body.rating),
+            app_config.require_rating_to_publish,
+        )
+    };
+    errors.check_max_length("title", title.as_deref(), title_max_length);
+    errors.check_max_length(
+        "description",
+        description.as_deref(),
+        description_max_length,
+    );
+    errors.check_max_length(
+        "translated_title",
+        translated_title.as_deref(),
+        title_max_length,
+    );
+    errors.check_max_length(
+        "translated_description",
+        translated_description.as_deref(),
+        description_max_length,
+    );
+    if (translated_title.is_some() || translated_description.is_some())
+        && translation_language.is_none()
+    {
+        errors.add("translation_language", "Translations need a language code");
+    }
+    if translation_language
+        .as_ref()
+        .is_some_and(|translation_language| {
+            translation_language.len() > 16
+                || !translation_language
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+    {
+        errors.add(
+            "translation_language",
+            "Language code may only contain letters, digits and hyphens, such as ja or pt-BR",
+        );
+    }
+    // Times that already passed publish the post right away
+    let publish_at = match body.publish_at.trim() {
         "" => None,
-        title => Some(title.to_owned()),
+        publish_at => NaiveDateTime::parse_from_str(publish_at, DATETIME_INPUT_FORMAT)
+            .inspect_err(|_| errors.add("publish_at", "Invalid publishing time"))
+            .ok()
+            .filter(|publish_at| *publish_at > Utc::now().naive_utc()),
     };
-    let description = match body.description.trim() {
+    let expires_at = match body.expires_at.trim() {
         "" => None,
-        description => Some(description.to_owned()),
+        expires_at => NaiveDateTime::parse_from_str(expires_at, DATETIME_INPUT_FORMAT)
+            .inspect_err(|_| errors.add("expires_at", "Invalid expiry"))
+            .ok(),
     };
+    if let (Some(publish_at), Some(expires_at)) = (publish_at, expires_at) {
+        if expires_at <= publish_at {
+            errors.add("expires_at", "The post must expire after it is published");
+        }
+    }
+    if !is_valid_rating {
+        errors.add("rating", "Invalid rating");
+    } else if require_rating_to_publish && body.is_public.is_some() && body.rating == UNRATED {
+        errors.add("rating", "Choose a rating before making the post public");
+    }
+    if !errors.is_empty() {
+        // Show the form again with what was submitted, so that nothing is lost while fixing the errors
+        let is_locked = if user.is_admin {
+            body.is_locked.is_some()
+        } else {
+            post.is_locked
+        };
+        let submitted_post = samey_post::Model {
+            title,
+            description,
+            translation_language,
+            translated_title,
+            translated_description,
+            is_public: body.is_public.is_some(),
+            publish_at,
+            expires_at,
+            is_locked,
+            rating: body.rating,
+            parent_id: body.parent_post.trim().parse().ok(),
+            version: body.version,
+            ..post
+        };
+        let sources = body
+            .sources
+            .unwrap_or_default()
+            .into_iter()
+            .map(|url| EditPostSource { url: Some(url) })
+            .collect();
+        let template = edit_details_template(
+            &*app_config.read().await,
+            submitted_post,
+            sources,
+            body.tags,
+            user.is_admin,
+            vec![],
+            errors,
+        );
+        return Ok((StatusCode::UNPROCESSABLE_ENTITY, Html(template.render()?)).into_response());
+    }
     let parent_post = if let Ok(parent_id) = body.parent_post.trim().parse() {
-        match filter_posts_by_user(SameyPost::find_by_id(parent_id), auth_session.user.as_ref())
+        match filter_posts_by_user(SameyPost::find_by_id(parent_id), Some(&user))
             .one(&db)
             .await?
         {
             Some(parent_post) => Some(PostOverview {
                 id: parent_id,
                 thumbnail: parent_post.thumbnail,
+                thumbnail_variants: parent_post.thumbnail_variants,
                 title: parent_post.title,
                 description: parent_post.description,
                 uploaded_at: parent_post.uploaded_at,
                 media: parent_post.media,
                 tags: Some(
-                    get_tags_for_post(post_id)
+                    get_tags_for_post(post.id)
                         .all(&db)
                         .await?
                         .iter()
@@ -1730,76 +5541,118 @@ pub(crate) async fn submit_post_details(
         None
     };
     let is_public = body.is_public.is_some();
-    let post = SameyPost::update(samey_post::ActiveModel {
-        id: Set(post_id),
-        title: Set(title),
-        description: Set(description),
-        is_public: Set(is_public),
-        rating: Set(body.rating),
-        parent_id: Set(parent_post.as_ref().map(|post| post.id)),
-        ..Default::default()
-    })
-    .exec(&db)
-    .await?;
-
-    // TODO: Improve this to not delete sources without necessity
-    SameyPostSource::delete_many()
-        .filter(samey_post_source::Column::PostId.eq(post_id))
-        .exec(&db)
-        .await?;
-    // TODO: Improve this to not recreate existing sources (see above)
-    if let Some(sources) = body.sources {
-        let sources: Vec<_> = sources
-            .into_iter()
-            .filter(|source| !source.is_empty())
-            .map(|source| samey_post_source::ActiveModel {
-                url: Set(source),
-                post_id: Set(post_id),
-                ..Default::default()
-            })
-            .collect();
-        if !sources.is_empty() {
-            SameyPostSource::insert_many(sources).exec(&db).await?;
-        }
-    };
-
-    let tags: HashSet<String> = body.tags.split_whitespace().map(String::from).collect();
-    let normalized_tags: HashSet<String> = tags.iter().map(|tag| tag.to_lowercase()).collect();
-    // TODO: Improve this to not delete tag-post entries without necessity
-    SameyTagPost::delete_many()
-        .filter(samey_tag_post::Column::PostId.eq(post_id))
-        .exec(&db)
-        .await?;
-    let tags = if tags.is_empty() {
-        vec![]
+    let is_locked = if user.is_admin {
+        Set(body.is_locked.is_some())
     } else {
-        // TODO: Improve this to not recreate existing tag-post entries (see above)
-        SameyTag::insert_many(tags.into_iter().map(|tag| samey_tag::ActiveModel {
-            normalized_name: Set(tag.to_lowercase()),
-            name: Set(tag),
+        NotSet
+    };
+    let txn = db.begin().await?;
+    let mut update = SameyPost::update_many()
+        .set(samey_post::ActiveModel {
+            title: Set(title),
+            description: Set(description),
+            translation_language: Set(translation_language),
+            translated_title: Set(translated_title),
+            translated_description: Set(translated_description),
+            is_public: Set(is_public),
+            publish_at: Set(publish_at),
+            expires_at: Set(expires_at),
+            is_locked,
+            rating: Set(body.rating.clone()),
+            parent_id: Set(parent_post.as_ref().map(|post| post.id)),
+            updated_at: Set(Some(Utc::now().naive_utc())),
             ..Default::default()
-        }))
-        .on_conflict(
-            OnConflict::column(samey_tag::Column::NormalizedName)
-                .do_nothing()
-                .to_owned(),
+        })
+        .col_expr(
+            samey_post::Column::Version,
+            Expr::col(samey_post::Column::Version).add(1),
+        )
+        .filter(samey_post::Column::Id.eq(post.id));
+    if !body.force.unwrap_or_default() {
+        update = update.filter(samey_post::Column::Version.eq(body.version));
+    }
+    if update.exec(&txn).await?.rows_affected == 0 {
+        // Someone else saved the post since this edit started
+        return Ok((
+            StatusCode::CONFLICT,
+            Html(
+                PostEditConflictTemplate {
+                    post_id: post.id,
+                    version: post.version,
+                    form: body,
+                }
+                .render()?,
+            ),
         )
-        .exec_without_returning(&db)
+            .into_response());
+    }
+    let was_published = is_post_published(&post);
+    let previous_description = post.description;
+    let post = SameyPost::find_by_id(post.id)
+        .one(&txn)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+
+    let current_sources = SameyPostSource::find()
+        .filter(samey_post_source::Column::PostId.eq(post.id))
+        .all(&txn)
         .await?;
-        let mut upload_tags = SameyTag::find()
-            .filter(samey_tag::Column::NormalizedName.is_in(normalized_tags))
-            .all(&db)
+    let submitted_sources: Vec<String> = body
+        .sources
+        .unwrap_or_default()
+        .into_iter()
+        .map(|source| normalize_source_url(&source))
+        .filter(|source| !source.is_empty())
+        .unique()
+        .collect();
+    let removed_source_ids: Vec<i32> = current_sources
+        .iter()
+        .filter(|source| !submitted_sources.contains(&source.url))
+        .map(|source| source.id)
+        .collect();
+    if !removed_source_ids.is_empty() {
+        SameyPostSource::delete_many()
+            .filter(samey_post_source::Column::Id.is_in(removed_source_ids))
+            .exec(&txn)
             .await?;
-        SameyTagPost::insert_many(upload_tags.iter().map(|tag| samey_tag_post::ActiveModel {
-            post_id: Set(post_id),
-            tag_id: Set(tag.id),
+    }
+    let added_sources: Vec<_> = submitted_sources
+        .into_iter()
+        .filter(|url| !current_sources.iter().any(|source| &source.url == url))
+        .map(|url| samey_post_source::ActiveModel {
+            url: Set(url),
+            post_id: Set(post.id),
             ..Default::default()
-        }))
-        .exec(&db)
-        .await?;
-        upload_tags.sort_by(|a, b| a.name.cmp(&b.name));
-        upload_tags
-    };
+        })
+        .collect();
+    if !added_sources.is_empty() {
+        SameyPostSource::insert_many(added_sources)
+            .exec(&txn)
+            .await?;
+    }
+
+    let tags = replace_post_tags(&txn, post.id, tags).await?;
+    txn.commit().await?;
+    // Scheduled posts are federated and cross-posted once they are published instead
+    let is_published = is_post_published(&post);
+    if was_published != is_published {
+        if let Some(federation) = Federation::from_config(&*app_config.read().await) {
+            federate_post(db.clone(), federation, post.id);
+        }
+        if is_published {
+            queue_crossposts(&db, post.id).await?;
+        }
+    }
+    notify_mentions(
+        &db,
+        &app_config,
+        user.id,
+        post.id,
+        previous_description.as_deref(),
+        post.description.as_deref(),
+    )
+    .await?;
+
     let mut tags_text = String::new();
     for tag in &tags {
         if !tags_text.is_empty() {
@@ -1809,7 +5662,7 @@ pub(crate) async fn submit_post_details(
     }
 
     let sources = SameyPostSource::find()
-        .filter(samey_post_source::Column::PostId.eq(post_id))
+        .filter(samey_post_source::Column::PostId.eq(post.id))
         .all(&db)
         .await?;
 
@@ -1821,6 +5674,14 @@ pub(crate) async fn submit_post_details(
 
     Ok(Html(
         SubmitPostDetailsTemplate {
+            uploaded_at: preferences.format_datetime(&post.uploaded_at),
+            publish_at: post
+                .publish_at
+                .map(|publish_at| preferences.format_datetime(&publish_at)),
+            expires_at: post
+                .expires_at
+                .map(|expires_at| preferences.format_datetime(&expires_at)),
+            rating_label: app_config.read().await.rating_label(&post.rating),
             post,
             sources,
             tags,
@@ -1829,7 +5690,8 @@ pub(crate) async fn submit_post_details(
             can_edit: true,
         }
         .render()?,
-    ))
+    )
+    .into_response())
 }
 
 struct EditPostSource {
@@ -1842,29 +5704,33 @@ struct EditDetailsTemplate {
     post: samey_post::Model,
     sources: Vec<EditPostSource>,
     tags: String,
+    is_admin: bool,
+    ratings: Vec<Rating>,
+    allow_unrated: bool,
+    auto_tagger_enabled: bool,
+    source_lookup_enabled: bool,
+    suggestions: Vec<String>,
+    title_max_length: u64,
+    description_max_length: u64,
+    errors: FieldErrors,
+}
+
+impl EditDetailsTemplate {
+    fn datetime_input(&self, value: &Option<NaiveDateTime>) -> String {
+        value
+            .map(|value| value.format(DATETIME_INPUT_FORMAT).to_string())
+            .unwrap_or_default()
+    }
 }
 
 pub(crate) async fn edit_post_details(
-    State(AppState { db, .. }): State<AppState>,
-    auth_session: AuthSession,
-    Path(post_id): Path<i32>,
+    State(AppState { db, app_config, .. }): State<AppState>,
+    RequireOwnerOrAdmin(user, post): RequireOwnerOrAdmin<SameyPost>,
 ) -> Result<impl IntoResponse, SameyError> {
-    let post = SameyPost::find_by_id(post_id)
-        .one(&db)
-        .await?
-        .ok_or(SameyError::NotFound)?;
-
-    match auth_session.user {
-        None => return Err(SameyError::Forbidden),
-        Some(user) => {
-            if !user.is_admin && post.uploader_id != user.id {
-                return Err(SameyError::Forbidden);
-            }
-        }
-    }
+    let is_admin = user.is_admin;
 
-    let sources = SameyPostSource::find()
-        .filter(samey_post_source::Column::PostId.eq(post_id))
+    let sources: Vec<EditPostSource> = SameyPostSource::find()
+        .filter(samey_post_source::Column::PostId.eq(post.id))
         .all(&db)
         .await?
         .into_iter()
@@ -1873,22 +5739,171 @@ pub(crate) async fn edit_post_details(
         })
         .collect();
 
-    let tags = get_tags_for_post(post_id)
+    let post_tags = get_tags_for_post(post.id).all(&db).await?;
+    let tags = post_tags.iter().map(|tag| &tag.name).join(" ");
+
+    let texts: Vec<&str> = post
+        .original_filename
+        .iter()
+        .chain(sources.iter().filter_map(|source| source.url.as_ref()))
+        .map(String::as_str)
+        .collect();
+    let suggestions = suggest_tags_from_text(
+        &db,
+        &texts,
+        &post_tags
+            .into_iter()
+            .map(|tag| tag.normalized_name)
+            .collect(),
+    )
+    .await?;
+
+    let template = edit_details_template(
+        &*app_config.read().await,
+        post,
+        sources,
+        tags,
+        is_admin,
+        suggestions,
+        FieldErrors::default(),
+    );
+
+    Ok(Html(template.render()?))
+}
+
+/// Builds the form for editing a post's details, with the current limits and ratings from the config.
+fn edit_details_template(
+    app_config: &AppConfig,
+    post: samey_post::Model,
+    sources: Vec<EditPostSource>,
+    tags: String,
+    is_admin: bool,
+    suggestions: Vec<String>,
+    errors: FieldErrors,
+) -> EditDetailsTemplate {
+    EditDetailsTemplate {
+        post,
+        sources,
+        tags,
+        is_admin,
+        ratings: app_config.ratings.clone(),
+        allow_unrated: app_config.allow_unrated,
+        auto_tagger_enabled: app_config.auto_tagger_enabled,
+        source_lookup_enabled: app_config.source_lookup_enabled(),
+        suggestions,
+        title_max_length: app_config.title_max_length,
+        description_max_length: app_config.description_max_length,
+        errors,
+    }
+}
+
+#[derive(Template)]
+#[template(path = "fragments/tag_suggestions.html")]
+struct TagSuggestionsTemplate {
+    suggestions: Vec<String>,
+}
+
+pub(crate) async fn tag_suggestions(
+    State(AppState {
+        db,
+        files_dir,
+        app_config,
+        ..
+    }): State<AppState>,
+    RequireOwnerOrAdmin(_, post): RequireOwnerOrAdmin<SameyPost>,
+) -> Result<impl IntoResponse, SameyError> {
+    let (auto_tagger_url, auto_tagger_threshold) = {
+        let app_config = app_config.read().await;
+        if !app_config.auto_tagger_enabled {
+            return Err(SameyError::BadRequest(
+                "Tag suggestions are disabled".into(),
+            ));
+        }
+        (
+            app_config.auto_tagger_url.clone(),
+            app_config.auto_tagger_threshold,
+        )
+    };
+
+    // Videos are tagged from their thumbnail
+    let image = match post.media_type.as_ref() {
+        "image" => &post.media,
+        _ => &post.thumbnail,
+    };
+    let existing_tags: HashSet<String> = get_tags_for_post(post.id)
         .select_only()
-        .column(samey_tag::Column::Name)
+        .column(samey_tag::Column::NormalizedName)
         .into_tuple::<String>()
         .all(&db)
         .await?
-        .join(" ");
+        .into_iter()
+        .collect();
+    let suggestions = suggest_tags(
+        &auto_tagger_url,
+        &files_dir.join(image),
+        auto_tagger_threshold,
+    )
+    .await?
+    .into_iter()
+    .filter(|tag| {
+        !tag.starts_with(NEGATIVE_PREFIX)
+            && !tag.starts_with(RATING_PREFIX)
+            && !tag.starts_with(MEDIA_TYPE_PREFIX)
+            && !existing_tags.contains(&tag.to_lowercase())
+    })
+    .collect();
 
-    Ok(Html(
-        EditDetailsTemplate {
-            post,
-            sources,
-            tags,
+    Ok(Html(TagSuggestionsTemplate { suggestions }.render()?))
+}
+
+#[derive(Template)]
+#[template(path = "fragments/source_candidates.html")]
+struct SourceCandidatesTemplate {
+    candidates: Vec<SourceCandidate>,
+}
+
+pub(crate) async fn source_lookup(
+    State(AppState {
+        db,
+        files_dir,
+        app_config,
+        ..
+    }): State<AppState>,
+    RequireOwnerOrAdmin(_, post): RequireOwnerOrAdmin<SameyPost>,
+) -> Result<impl IntoResponse, SameyError> {
+    let (saucenao_api_key, iqdb_enabled) = {
+        let app_config = app_config.read().await;
+        if !app_config.source_lookup_enabled() {
+            return Err(SameyError::BadRequest("Source lookup is disabled".into()));
         }
-        .render()?,
-    ))
+        (app_config.saucenao_api_key.clone(), app_config.iqdb_enabled)
+    };
+
+    let existing_sources: HashSet<String> = SameyPostSource::find()
+        .select_only()
+        .column(samey_post_source::Column::Url)
+        .filter(samey_post_source::Column::PostId.eq(post.id))
+        .into_tuple::<String>()
+        .all(&db)
+        .await?
+        .into_iter()
+        .collect();
+    let candidates = lookup_sources(
+        &saucenao_api_key,
+        iqdb_enabled,
+        &files_dir.join(&post.thumbnail),
+    )
+    .await?
+    .into_iter()
+    .map(|candidate| SourceCandidate {
+        url: normalize_source_url(&candidate.url),
+        ..candidate
+    })
+    .filter(|candidate| !existing_sources.contains(&candidate.url))
+    .unique_by(|candidate| candidate.url.clone())
+    .collect();
+
+    Ok(Html(SourceCandidatesTemplate { candidates }.render()?))
 }
 
 #[derive(Template)]
@@ -1897,10 +5912,17 @@ struct AddPostSourceTemplate {
     source: EditPostSource,
 }
 
-pub(crate) async fn add_post_source() -> Result<impl IntoResponse, SameyError> {
+#[derive(Debug, Deserialize)]
+pub(crate) struct AddPostSourceForm {
+    url: Option<String>,
+}
+
+pub(crate) async fn add_post_source(
+    Form(body): Form<AddPostSourceForm>,
+) -> Result<impl IntoResponse, SameyError> {
     Ok(Html(
         AddPostSourceTemplate {
-            source: EditPostSource { url: None },
+            source: EditPostSource { url: body.url },
         }
         .render()?,
     ))
@@ -1911,7 +5933,12 @@ pub(crate) async fn remove_field() -> impl IntoResponse {
 }
 
 pub(crate) async fn delete_post(
-    State(AppState { db, files_dir, .. }): State<AppState>,
+    State(AppState {
+        db,
+        files_dir,
+        app_config,
+        ..
+    }): State<AppState>,
     auth_session: AuthSession,
     Path(post_id): Path<i32>,
 ) -> Result<impl IntoResponse, SameyError> {
@@ -1930,10 +5957,14 @@ pub(crate) async fn delete_post(
     }
 
     SameyPost::delete_by_id(post.id).exec(&db).await?;
+    if is_post_published(&post) {
+        if let Some(federation) = Federation::from_config(&*app_config.read().await) {
+            federate_post(db.clone(), federation, post.id);
+        }
+    }
 
     tokio::spawn(async move {
-        let _ = std::fs::remove_file(files_dir.join(post.media));
-        let _ = std::fs::remove_file(files_dir.join(post.thumbnail));
+        remove_post_files(&files_dir, &post).await;
         if let Err(err) = clean_dangling_tags(&db).await {
             println!("Error when cleaning dangling tags - {}", err);
         }
@@ -1941,3 +5972,204 @@ pub(crate) async fn delete_post(
 
     Ok(Redirect::to("/"))
 }
+
+/// Maximum length of the tags part of a download's file name.
+const DOWNLOAD_TAGS_SLUG_MAX_LENGTH: usize = 100;
+
+/// Builds a file name like `{id}_{tags-slug}.{ext}` for downloading a post's media.
+fn download_file_name(post: &samey_post::Model, tags: &[samey_tag::Model], file: &str) -> String {
+    let mut file_name = post.id.to_string();
+    let slug = tags
+        .iter()
+        .map(|tag| {
+            tag.normalized_name
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+                .collect::<String>()
+        })
+        .filter(|tag| !tag.is_empty())
+        .fold(String::new(), |mut slug, tag| {
+            if slug.len() + tag.len() < DOWNLOAD_TAGS_SLUG_MAX_LENGTH {
+                if !slug.is_empty() {
+                    slug.push('-');
+                }
+                slug.push_str(&tag);
+            }
+            slug
+        });
+    if !slug.is_empty() {
+        file_name.push('_');
+        file_name.push_str(&slug);
+    }
+    if let Some((_, extension)) = file.rsplit_once('.') {
+        file_name.push('.');
+        file_name.push_str(extension);
+    }
+    file_name
+}
+
+pub(crate) async fn download_post(
+    State(AppState { db, files_dir, .. }): State<AppState>,
+    auth_session: AuthSession,
+    Path(post_id): Path<i32>,
+) -> Result<impl IntoResponse, SameyError> {
+    let post = filter_posts_by_user(SameyPost::find_by_id(post_id), auth_session.user.as_ref())
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+    let tags = get_tags_for_post(post_id).all(&db).await?;
+
+    let file = File::open(files_dir.join(&post.media)).await?;
+    let content_type = mime_guess::from_path(&post.media).first_or_octet_stream();
+    Ok((
+        [
+            (CONTENT_TYPE, content_type.to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{}\"",
+                    download_file_name(&post, &tags, &post.media)
+                ),
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(file)),
+    ))
+}
+
+/// Downloads the original upload that a post's media was converted from, such as an ugoira archive.
+///
+/// Attachments added to a post separately are downloaded with [`download_attachment`] instead.
+pub(crate) async fn download_post_attachment(
+    State(AppState { db, files_dir, .. }): State<AppState>,
+    auth_session: AuthSession,
+    Path(post_id): Path<i32>,
+) -> Result<impl IntoResponse, SameyError> {
+    let post = filter_posts_by_user(SameyPost::find_by_id(post_id), auth_session.user.as_ref())
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+    let attachment = post.attachment.as_deref().ok_or(SameyError::NotFound)?;
+    let tags = get_tags_for_post(post_id).all(&db).await?;
+
+    let file = File::open(files_dir.join(attachment)).await?;
+    let content_type = mime_guess::from_path(attachment).first_or_octet_stream();
+    Ok((
+        [
+            (CONTENT_TYPE, content_type.to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{}\"",
+                    download_file_name(&post, &tags, attachment)
+                ),
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(file)),
+    ))
+}
+
+/// Redirects to the thumbnail of a post, such as for post references in descriptions.
+pub(crate) async fn post_thumbnail(
+    State(AppState { db, .. }): State<AppState>,
+    auth_session: AuthSession,
+    Path(post_id): Path<i32>,
+) -> Result<impl IntoResponse, SameyError> {
+    let post = filter_posts_by_user(SameyPost::find_by_id(post_id), auth_session.user.as_ref())
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+
+    Ok(Redirect::to(&format!("/files/{}", post.thumbnail)))
+}
+
+pub(crate) async fn add_attachment(
+    State(state): State<AppState>,
+    RequireOwnerOrAdmin(user, post): RequireOwnerOrAdmin<SameyPost>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, SameyError> {
+    let max_size = state.app_config.read().await.attachment_max_size;
+    let mut is_uploaded = false;
+    while let Some(mut field) = multipart.next_field().await? {
+        if field.name() == Some("attachment-file") {
+            let original_filename = field.file_name().map(String::from).unwrap_or_default();
+            store_attachment(
+                &state,
+                &user,
+                post.id,
+                &original_filename,
+                async |file_path| {
+                    write_field_to_file_limited(&mut field, file_path, max_size).await
+                },
+            )
+            .await?;
+            is_uploaded = true;
+        }
+    }
+    if !is_uploaded {
+        return Err(SameyError::BadRequest("Missing attachment file".into()));
+    }
+
+    Ok(Redirect::to(&format!("/post/{}", post.id)))
+}
+
+pub(crate) async fn download_attachment(
+    State(AppState { db, files_dir, .. }): State<AppState>,
+    auth_session: AuthSession,
+    Path(attachment_id): Path<i32>,
+) -> Result<impl IntoResponse, SameyError> {
+    let attachment = SameyPostAttachment::find_by_id(attachment_id)
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+    // Attachments are only available to those who can see their post
+    filter_posts_by_user(
+        SameyPost::find_by_id(attachment.post_id),
+        auth_session.user.as_ref(),
+    )
+    .one(&db)
+    .await?
+    .ok_or(SameyError::NotFound)?;
+
+    let file = File::open(files_dir.join(&attachment.file_name)).await?;
+    let content_type = mime_guess::from_path(&attachment.original_filename).first_or_octet_stream();
+    Ok((
+        [
+            (CONTENT_TYPE, content_type.to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", attachment.original_filename),
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(file)),
+    ))
+}
+
+pub(crate) async fn delete_attachment(
+    State(AppState { db, files_dir, .. }): State<AppState>,
+    auth_session: AuthSession,
+    Path(attachment_id): Path<i32>,
+) -> Result<impl IntoResponse, SameyError> {
+    let attachment = SameyPostAttachment::find_by_id(attachment_id)
+        .one(&db)
+        .await?
+        .ok_or(SameyError::NotFound)?;
+    let post = SameyPost::find_by_id(attachment.post_id)
+        .one(&db)
+        .await?
+        .expect("Post for samey_post_attachment must exist");
+
+    let can_edit = auth_session
+        .user
+        .as_ref()
+        .is_some_and(|user| SameyPost::is_editable_by(&post, user));
+
+    if !can_edit {
+        return Err(SameyError::Forbidden);
+    }
+
+    let file_name = attachment.file_name.clone();
+    attachment.delete(&db).await?;
+    let _ = tokio::fs::remove_file(files_dir.join(file_name)).await;
+
+    Ok("")
+}
@@ -0,0 +1,5 @@
+// Minimal service worker, required for the app to be installable.
+// Requests are passed through to the network without caching, since post visibility depends on the session.
+self.addEventListener("fetch", (event) => {
+  event.respondWith(fetch(event.request));
+});